@@ -117,11 +117,40 @@ pub fn match_nodes_where(c: &mut Criterion) {
     );
 }
 
+pub fn match_nodes_where_cached(c: &mut Criterion) {
+    simple_bench(
+        c,
+        "match nodes where (cached)",
+        |graph| {
+            let mut txn = graph.mut_txn().unwrap();
+            let stmt = graph.prepare("CREATE (:TEST { number: $num })").unwrap();
+            for num in 0..1000 {
+                stmt.execute(&mut txn, ("num", num)).unwrap();
+            }
+            txn.commit().unwrap();
+        },
+        |graph| {
+            let stmt = graph
+                .prepare_cached("MATCH (n) WHERE n.number = $num RETURN ID(n)")
+                .unwrap();
+            let mut txn = graph.txn().unwrap();
+            let val = stmt
+                .query_map(&mut txn, ("num", black_box(42)), |m| m.get::<u64, _>(0))
+                .unwrap()
+                .last()
+                .unwrap()
+                .unwrap();
+            black_box(val);
+        },
+    );
+}
+
 criterion_group! {
     benches,
     create_nodes,
     create_edges,
     match_node_by_id,
     match_nodes_where,
+    match_nodes_where_cached,
 }
 criterion_main!(benches);
@@ -27,6 +27,16 @@ enum CQLiteStatus {
   CQLITE_OPEN_TRANSACTION = 116,
   CQLITE_OPEN_STATEMENT = 117,
   CQLITE_MISUSE = 118,
+  CQLITE_IDENTIFIER_IS_NOT_PATH = 119,
+  CQLITE_UNION_MISMATCH = 120,
+  CQLITE_UNKNOWN_PROCEDURE = 121,
+  CQLITE_UNKNOWN_FUNCTION = 122,
+  CQLITE_INVALID_ARGUMENTS = 123,
+  CQLITE_INVALID_TEMPORAL = 124,
+  CQLITE_OVERFLOW = 125,
+  CQLITE_CONSTRAINT_VIOLATION = 126,
+  CQLITE_VERSION_MISMATCH = 127,
+  CQLITE_SCHEMA_MISMATCH = 128,
 };
 typedef uint8_t CQLiteStatus;
 
@@ -38,6 +48,15 @@ enum CQLiteType {
   CQLITE_TEXT = 4,
   CQLITE_BLOB = 5,
   CQLITE_NULL = 6,
+  CQLITE_LIST = 7,
+  CQLITE_PATH = 8,
+  CQLITE_NODE = 9,
+  CQLITE_EDGE = 10,
+  CQLITE_DATE = 11,
+  CQLITE_LOCAL_DATE_TIME = 12,
+  CQLITE_DATE_TIME = 13,
+  CQLITE_DURATION = 14,
+  CQLITE_POINT = 15,
 };
 typedef uint8_t CQLiteType;
 
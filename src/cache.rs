@@ -0,0 +1,148 @@
+use crate::runtime::Program;
+use crate::store::{Index, IndexKind, Rule};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// The number of statements kept by [`prepare_cached`][crate::Graph::prepare_cached],
+/// unless configured otherwise.
+pub(crate) const DEFAULT_CAPACITY: usize = 64;
+
+/// The index and rule definitions which programs are planned with.
+/// Statistics are not part of the definitions, since they change
+/// with every commit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Definitions {
+    pub indexes: BTreeMap<Index, IndexKind>,
+    pub rules: BTreeSet<Rule>,
+}
+
+/// Compiled programs by their query text. Once more than `capacity`
+/// programs are kept, the least recently used program is dropped.
+/// All programs are dropped if the definitions change.
+#[derive(Debug)]
+pub(crate) struct StatementCache {
+    capacity: usize,
+    definitions: Definitions,
+    programs: HashMap<String, (Arc<Program>, u64)>,
+    clock: u64,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            definitions: Definitions::default(),
+            programs: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.programs.len() > capacity {
+            self.evict();
+        }
+    }
+
+    /// The program compiled for `query`, if it was
+    /// planned with the given `definitions`.
+    pub fn get(&mut self, query: &str, definitions: &Definitions) -> Option<Arc<Program>> {
+        if self.definitions != *definitions {
+            self.programs.clear();
+            self.definitions = definitions.clone();
+            return None;
+        }
+        self.clock += 1;
+        let clock = self.clock;
+        self.programs.get_mut(query).map(|(program, used)| {
+            *used = clock;
+            program.clone()
+        })
+    }
+
+    /// Keep the `program` compiled for `query`, which was
+    /// planned with the given `definitions`.
+    pub fn insert(&mut self, query: &str, program: Arc<Program>, definitions: &Definitions) {
+        if self.capacity == 0 || self.definitions != *definitions {
+            return;
+        }
+        if !self.programs.contains_key(query) && self.programs.len() >= self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        self.programs
+            .insert(query.to_string(), (program, self.clock));
+    }
+
+    pub fn clear(&mut self) {
+        self.programs.clear();
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .programs
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(query, _)| query.clone());
+        if let Some(query) = oldest {
+            self.programs.remove(&query);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::QueryPlan;
+
+    fn program() -> Arc<Program> {
+        let plan = QueryPlan {
+            steps: Vec::new(),
+            updates: Vec::new(),
+            returns: Vec::new(),
+        };
+        Arc::new(Program::new(&plan).unwrap())
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let definitions = Definitions::default();
+        let mut cache = StatementCache::new(2);
+        cache.insert("a", program(), &definitions);
+        cache.insert("b", program(), &definitions);
+        assert!(cache.get("a", &definitions).is_some());
+        cache.insert("c", program(), &definitions);
+        assert!(cache.get("a", &definitions).is_some());
+        assert!(cache.get("b", &definitions).is_none());
+        assert!(cache.get("c", &definitions).is_some());
+
+        cache.set_capacity(1);
+        assert_eq!(cache.programs.len(), 1);
+        assert!(cache.get("c", &definitions).is_some());
+
+        cache.set_capacity(0);
+        cache.insert("a", program(), &definitions);
+        assert!(cache.get("a", &definitions).is_none());
+    }
+
+    #[test]
+    fn drop_programs_with_other_definitions() {
+        let definitions = Definitions::default();
+        let mut cache = StatementCache::new(2);
+        cache.insert("a", program(), &definitions);
+
+        let mut indexed = definitions.clone();
+        indexed
+            .indexes
+            .insert(Index::new("PERSON", "name"), IndexKind::Plain);
+        assert!(cache.get("a", &indexed).is_none());
+        assert!(cache.get("a", &definitions).is_none());
+
+        // planned with outdated definitions
+        cache.insert("a", program(), &indexed);
+        assert!(cache.get("a", &definitions).is_none());
+        cache.insert("a", program(), &definitions);
+        assert!(cache.get("a", &definitions).is_some());
+    }
+}
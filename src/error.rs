@@ -2,7 +2,7 @@ use bincode::{Error as BincodeError, ErrorKind as BincodeErrorKind};
 use peg::{error::ParseError, str::LineCol};
 use sanakirja::Error as SanakirjaError;
 use std::convert::Infallible;
-use std::sync::TryLockError;
+use std::sync::{PoisonError, TryLockError};
 use thiserror::Error;
 
 /// Errors which can occur while interacting with a [`Graph`][crate::Graph].
@@ -45,12 +45,46 @@ pub enum Error {
     /// but does not refer to an edge.
     #[error("Identifier {0} does not refer to an edge")]
     IdentifierIsNotEdge(String),
+    /// The given identifier exists
+    /// but does not refer to a path.
+    #[error("Identifier {0} does not refer to a path")]
+    IdentifierIsNotPath(String),
     /// The given identifier already exists.
     #[error("Identifier {0} already exists")]
     IdentifierExists(String),
     /// The given identifier does not exists.
     #[error("Identifier {0} does not exists")]
     UnknownIdentifier(String),
+    /// Queries combined using `UNION` return different
+    /// columns, or the same columns in a different order.
+    #[error("All queries in a UNION must return the same columns")]
+    UnionMismatch,
+    /// The called procedure does not exist.
+    #[error("Procedure {0} does not exist")]
+    UnknownProcedure(String),
+    /// A stored statement was compiled by a
+    /// different version of the engine.
+    #[error("Statement was compiled by a different version")]
+    VersionMismatch,
+    /// A stored statement was compiled for different
+    /// indexes, constraints or procedures.
+    #[error("Statement was compiled for a different schema")]
+    SchemaMismatch,
+    /// The called function does not exist.
+    #[error("Function {0} does not exist")]
+    UnknownFunction(String),
+    /// The function was called with the wrong
+    /// number or kind of arguments.
+    #[error("Invalid arguments for function {0}")]
+    InvalidArguments(String),
+    /// The text is not a valid date,
+    /// time or duration.
+    #[error("Invalid temporal value {0}")]
+    InvalidTemporal(String),
+    /// The result of an arithmetic operation
+    /// is out of range.
+    #[error("Arithmetic overflow")]
+    Overflow,
 
     /// Attempted type conversion failed.
     #[error("Type mismatch")]
@@ -70,6 +104,57 @@ pub enum Error {
     /// graph.
     #[error("Attempt to delete connected node")]
     DeleteConnected,
+    /// A change violates a unique or node key
+    /// constraint on the label and keys.
+    #[error("Constraint violation on :{label}({key}) for value {value}")]
+    ConstraintViolation {
+        label: String,
+        key: String,
+        value: String,
+    },
+
+    /// A statement in a batch failed. The line
+    /// and column refer to the start of the
+    /// statement within the script.
+    #[error("Statement {index} at line {line}, column {column} failed: {source}")]
+    Batch {
+        index: usize,
+        line: usize,
+        column: usize,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Attribute the error to the statement at `index`
+    /// of a script, which starts at `start`. Syntax errors
+    /// are moved to their position within the script.
+    pub(crate) fn in_batch(self, index: usize, start: &LineCol) -> Self {
+        let source = match self {
+            Self::Syntax {
+                line,
+                column,
+                offset,
+                expected,
+            } => Self::Syntax {
+                line: start.line + line - 1,
+                column: if line == 1 {
+                    start.column + column - 1
+                } else {
+                    column
+                },
+                offset: start.offset + offset,
+                expected,
+            },
+            error => error,
+        };
+        Self::Batch {
+            index,
+            line: start.line,
+            column: start.column,
+            source: Box::new(source),
+        }
+    }
 }
 
 impl From<SanakirjaError> for Error {
@@ -117,3 +202,9 @@ impl<T> From<TryLockError<T>> for Error {
         }
     }
 }
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Self::Poison
+    }
+}
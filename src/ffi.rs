@@ -1,5 +1,5 @@
 use crate::parser;
-use crate::planner::QueryPlan;
+use crate::procedure::Procedures;
 use crate::runtime::{Program, Status, VirtualMachine};
 use crate::store::{PropOwned, Store, StoreTxn};
 use crate::Error;
@@ -38,6 +38,17 @@ pub enum CQLiteStatus {
     CQLITE_OPEN_TRANSACTION = 116,
     CQLITE_OPEN_STATEMENT = 117,
     CQLITE_MISUSE = 118,
+
+    CQLITE_IDENTIFIER_IS_NOT_PATH = 119,
+    CQLITE_UNION_MISMATCH = 120,
+    CQLITE_UNKNOWN_PROCEDURE = 121,
+    CQLITE_UNKNOWN_FUNCTION = 122,
+    CQLITE_INVALID_ARGUMENTS = 123,
+    CQLITE_INVALID_TEMPORAL = 124,
+    CQLITE_OVERFLOW = 125,
+    CQLITE_CONSTRAINT_VIOLATION = 126,
+    CQLITE_VERSION_MISMATCH = 127,
+    CQLITE_SCHEMA_MISMATCH = 128,
 }
 
 #[repr(u8)]
@@ -50,10 +61,20 @@ pub enum CQLiteType {
     CQLITE_TEXT = 4,
     CQLITE_BLOB = 5,
     CQLITE_NULL = 6,
+    CQLITE_LIST = 7,
+    CQLITE_PATH = 8,
+    CQLITE_NODE = 9,
+    CQLITE_EDGE = 10,
+    CQLITE_DATE = 11,
+    CQLITE_LOCAL_DATE_TIME = 12,
+    CQLITE_DATE_TIME = 13,
+    CQLITE_DURATION = 14,
+    CQLITE_POINT = 15,
 }
 
 pub struct CQLiteGraph {
     store: Store,
+    procedures: Procedures,
     txn_count: AtomicUsize,
     stmt_count: AtomicUsize,
 }
@@ -84,6 +105,7 @@ pub unsafe extern "C" fn cqlite_open(
             .map_err(|_| CQLiteStatus::CQLITE_INVALID_STRING)?;
         Ok(CQLiteGraph {
             store: Store::open(path)?,
+            procedures: Procedures::new(),
             txn_count: AtomicUsize::new(0),
             stmt_count: AtomicUsize::new(0),
         })
@@ -102,6 +124,7 @@ pub unsafe extern "C" fn cqlite_open_anon(graph: *mut *mut CQLiteGraph) -> CQLit
     let inner = || -> Result<CQLiteGraph, CQLiteStatus> {
         Ok(CQLiteGraph {
             store: Store::open_anon()?,
+            procedures: Procedures::new(),
             txn_count: AtomicUsize::new(0),
             stmt_count: AtomicUsize::new(0),
         })
@@ -199,8 +222,9 @@ pub unsafe extern "C" fn cqlite_prepare(
             .to_str()
             .map_err(|_| CQLiteStatus::CQLITE_INVALID_STRING)?;
         let ast = parser::parse(query).map_err(|_| CQLiteStatus::CQLITE_SYNTAX)?;
-        let plan = QueryPlan::new(&ast)?.optimize()?;
-        let program = Box::into_raw(Box::new(Program::new(&plan)?));
+        let schema = (*graph).store.txn()?.schema()?;
+        let program = Program::compile(&ast, &(*graph).procedures, &schema)?;
+        let program = Box::into_raw(Box::new(program));
         (*graph).stmt_count.fetch_add(1, Ordering::SeqCst);
         Ok(CQLiteStatement {
             graph,
@@ -223,15 +247,24 @@ pub unsafe extern "C" fn cqlite_start(
     stmt: *mut CQLiteStatement,
     txn: *mut CQLiteTxn,
 ) -> CQLiteStatus {
-    (*stmt).runtime = Some((
-        VirtualMachine::new(
-            &mut txn.as_mut().unwrap().txn,
-            (*stmt).program.as_mut().unwrap(),
-            (*stmt).parameters.clone(),
-        ),
-        (*(*stmt).program).returns.iter().map(|_| None).collect(),
-    ));
-    CQLiteStatus::CQLITE_OK
+    let vm = VirtualMachine::new(
+        &mut txn.as_mut().unwrap().txn,
+        (*stmt).program.as_mut().unwrap(),
+        (*stmt).parameters.clone(),
+    );
+    match vm {
+        Ok(vm) => {
+            (*stmt).runtime = Some((
+                vm,
+                (*(*stmt).program).returns.iter().map(|_| None).collect(),
+            ));
+            CQLiteStatus::CQLITE_OK
+        }
+        Err(err) => {
+            (*stmt).runtime = None;
+            err.into()
+        }
+    }
 }
 
 #[no_mangle]
@@ -438,6 +471,15 @@ pub unsafe extern "C" fn cqlite_return_type(stmt: *mut CQLiteStatement, idx: usi
         PropOwned::Text(_) => CQLiteType::CQLITE_TEXT,
         PropOwned::Blob(_) => CQLiteType::CQLITE_BLOB,
         PropOwned::Null => CQLiteType::CQLITE_NULL,
+        PropOwned::List(_) => CQLiteType::CQLITE_LIST,
+        PropOwned::Path(_) => CQLiteType::CQLITE_PATH,
+        PropOwned::Node(_) => CQLiteType::CQLITE_NODE,
+        PropOwned::Edge(_) => CQLiteType::CQLITE_EDGE,
+        PropOwned::Date(_) => CQLiteType::CQLITE_DATE,
+        PropOwned::LocalDateTime(_) => CQLiteType::CQLITE_LOCAL_DATE_TIME,
+        PropOwned::DateTime(_) => CQLiteType::CQLITE_DATE_TIME,
+        PropOwned::Duration(_) => CQLiteType::CQLITE_DURATION,
+        PropOwned::Point(_) => CQLiteType::CQLITE_POINT,
     }
 }
 
@@ -485,15 +527,23 @@ pub unsafe extern "C" fn cqlite_return_text(
     let (vm, buffers) = (*stmt).runtime.as_mut().unwrap();
     match &buffers[idx] {
         Some(buffer) => buffer.as_ptr() as *const c_char,
-        None => match vm.access_return(idx).unwrap() {
-            PropOwned::Text(string) => {
-                let mut buf = string.into_bytes();
-                buf.push(0);
-                buffers[idx] = Some(buf);
-                buffers[idx].as_ref().unwrap().as_ptr() as *const c_char
-            }
-            _ => panic!(),
-        },
+        None => {
+            // temporal values are returned as ISO 8601 text, and
+            // points like the query literal which creates them
+            let string = match vm.access_return(idx).unwrap() {
+                PropOwned::Text(string) => string,
+                PropOwned::Date(date) => date.to_string(),
+                PropOwned::LocalDateTime(date_time) => date_time.to_string(),
+                PropOwned::DateTime(date_time) => date_time.to_string(),
+                PropOwned::Duration(duration) => duration.to_string(),
+                PropOwned::Point(point) => point.to_string(),
+                _ => panic!(),
+            };
+            let mut buf = string.into_bytes();
+            buf.push(0);
+            buffers[idx] = Some(buf);
+            buffers[idx].as_ref().unwrap().as_ptr() as *const c_char
+        }
     }
 }
 
@@ -536,6 +586,7 @@ impl From<Error> for CQLiteStatus {
             Error::Syntax { .. } => CQLiteStatus::CQLITE_SYNTAX,
             Error::IdentifierIsNotNode(_) => CQLiteStatus::CQLITE_IDENTIFIER_IS_NOT_NODE,
             Error::IdentifierIsNotEdge(_) => CQLiteStatus::CQLITE_IDENTIFIER_IS_NOT_EDGE,
+            Error::IdentifierIsNotPath(_) => CQLiteStatus::CQLITE_IDENTIFIER_IS_NOT_PATH,
             Error::IdentifierExists(_) => CQLiteStatus::CQLITE_IDENTIGIER_EXISTS,
             Error::UnknownIdentifier(_) => CQLiteStatus::CQLITE_UNKNOWN_IDENTIFIER,
             Error::TypeMismatch => CQLiteStatus::CQLITE_TYPE_MISMATCH,
@@ -543,6 +594,16 @@ impl From<Error> for CQLiteStatus {
             Error::MissingNode => CQLiteStatus::CQLITE_MISSING_NODE,
             Error::MissingEdge => CQLiteStatus::CQLITE_MISSING_EDGE,
             Error::DeleteConnected => CQLiteStatus::CQLITE_DELETE_CONNECTED,
+            Error::UnionMismatch => CQLiteStatus::CQLITE_UNION_MISMATCH,
+            Error::UnknownProcedure(_) => CQLiteStatus::CQLITE_UNKNOWN_PROCEDURE,
+            Error::UnknownFunction(_) => CQLiteStatus::CQLITE_UNKNOWN_FUNCTION,
+            Error::InvalidArguments(_) => CQLiteStatus::CQLITE_INVALID_ARGUMENTS,
+            Error::InvalidTemporal(_) => CQLiteStatus::CQLITE_INVALID_TEMPORAL,
+            Error::Overflow => CQLiteStatus::CQLITE_OVERFLOW,
+            Error::ConstraintViolation { .. } => CQLiteStatus::CQLITE_CONSTRAINT_VIOLATION,
+            Error::VersionMismatch => CQLiteStatus::CQLITE_VERSION_MISMATCH,
+            Error::SchemaMismatch => CQLiteStatus::CQLITE_SCHEMA_MISMATCH,
+            Error::Batch { source, .. } => (*source).into(),
         }
     }
 }
@@ -0,0 +1,359 @@
+use crate::store::spatial::{Crs, Point};
+use crate::store::temporal::{Date, DateTime, Duration, LocalDateTime};
+use crate::store::PropRef;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// A built-in function, which can be called from
+/// queries, e.g. `date('2024-01-01')`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Function {
+    Date,
+    LocalDateTime,
+    DateTime,
+    Duration,
+    /// Takes the keys and values of a map, e.g.
+    /// `point({x: 1.0, y: 2.0})`.
+    Point,
+    Distance,
+    WithinBBox,
+    /// `lhs + rhs`
+    Add,
+    /// `lhs - rhs`
+    Subtract,
+    /// A component of a value, e.g. `d.year`. Takes
+    /// the value and the name of the component.
+    Component,
+}
+
+impl Function {
+    /// The keys which can be used in the map passed to `point`.
+    pub const POINT_KEYS: [&'static str; 8] =
+        ["x", "y", "z", "longitude", "latitude", "height", "crs", "srid"];
+
+    /// The function which can be called as `name`,
+    /// ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ("date", Self::Date),
+            ("localdatetime", Self::LocalDateTime),
+            ("datetime", Self::DateTime),
+            ("duration", Self::Duration),
+            ("point", Self::Point),
+            ("distance", Self::Distance),
+            ("point.distance", Self::Distance),
+            ("point.withinbbox", Self::WithinBBox),
+        ]
+        .iter()
+        .find(|(function, _)| function.eq_ignore_ascii_case(name))
+        .map(|(_, function)| *function)
+    }
+
+    /// Check if the function can be called
+    /// with `count` arguments.
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Self::Date | Self::LocalDateTime | Self::DateTime => count <= 1,
+            Self::Duration | Self::Point => count == 1,
+            Self::Distance => count == 2,
+            Self::WithinBBox => count == 3,
+            Self::Add | Self::Subtract | Self::Component => count == 2,
+        }
+    }
+
+    pub fn call(&self, arguments: &[PropRef]) -> Result<PropRef<'static>, Error> {
+        match (self, arguments) {
+            (Self::Date, []) => Ok(PropRef::Date(Date::today())),
+            (Self::LocalDateTime, []) => Ok(PropRef::LocalDateTime(LocalDateTime::now())),
+            (Self::DateTime, []) => Ok(PropRef::DateTime(DateTime::now())),
+            (_, [PropRef::Null]) => Ok(PropRef::Null),
+            (Self::Date, [value]) => to_date(value),
+            (Self::LocalDateTime, [value]) => to_local_date_time(value),
+            (Self::DateTime, [value]) => to_date_time(value),
+            (Self::Duration, [value]) => to_duration(value),
+            (Self::Point, [PropRef::Point(point)]) => Ok(PropRef::Point(*point)),
+            (Self::Point, entries) => to_point(entries),
+            (Self::Distance, [lhs, rhs]) => distance(lhs, rhs),
+            (Self::WithinBBox, [point, lower_left, upper_right]) => {
+                within_bbox(point, lower_left, upper_right)
+            }
+            (Self::Add, [lhs, rhs]) => add(lhs, rhs),
+            (Self::Subtract, [lhs, rhs]) => subtract(lhs, rhs),
+            (Self::Component, [value, PropRef::Text(key)]) => component(value, key),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+}
+
+fn to_date(value: &PropRef) -> Result<PropRef<'static>, Error> {
+    match value {
+        PropRef::Text(text) => Ok(PropRef::Date(text.parse()?)),
+        PropRef::Date(date) => Ok(PropRef::Date(*date)),
+        PropRef::LocalDateTime(date_time) => Ok(PropRef::Date(date_time.date())),
+        PropRef::DateTime(date_time) => Ok(PropRef::Date(date_time.local().date())),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn to_local_date_time(value: &PropRef) -> Result<PropRef<'static>, Error> {
+    match value {
+        PropRef::Text(text) => Ok(PropRef::LocalDateTime(text.parse()?)),
+        PropRef::Date(date) => Ok(PropRef::LocalDateTime(midnight(*date)?)),
+        PropRef::LocalDateTime(date_time) => Ok(PropRef::LocalDateTime(*date_time)),
+        PropRef::DateTime(date_time) => Ok(PropRef::LocalDateTime(date_time.local())),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn to_date_time(value: &PropRef) -> Result<PropRef<'static>, Error> {
+    let utc = |local| DateTime::new(local, 0).ok_or(Error::Overflow);
+    match value {
+        PropRef::Text(text) => Ok(PropRef::DateTime(text.parse()?)),
+        PropRef::Date(date) => Ok(PropRef::DateTime(utc(midnight(*date)?)?)),
+        PropRef::LocalDateTime(date_time) => Ok(PropRef::DateTime(utc(*date_time)?)),
+        PropRef::DateTime(date_time) => Ok(PropRef::DateTime(*date_time)),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn to_duration(value: &PropRef) -> Result<PropRef<'static>, Error> {
+    match value {
+        PropRef::Text(text) => Ok(PropRef::Duration(text.parse()?)),
+        PropRef::Duration(duration) => Ok(PropRef::Duration(*duration)),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn to_point(entries: &[PropRef]) -> Result<PropRef<'static>, Error> {
+    let invalid = || Error::InvalidArguments("point".to_string());
+    let (mut x, mut y, mut z) = (None, None, None);
+    let (mut cartesian, mut geographic) = (false, false);
+    let mut crs = None;
+    for entry in entries.chunks(2) {
+        let (key, value) = match entry {
+            [_, PropRef::Null] => return Ok(PropRef::Null),
+            [PropRef::Text(key), value] => (*key, value),
+            _ => return Err(Error::TypeMismatch),
+        };
+        let coordinate = match value {
+            PropRef::Integer(num) => Some(*num as f64),
+            PropRef::Real(num) => Some(*num),
+            _ => None,
+        };
+        match (key, coordinate, value) {
+            ("x" | "y" | "z", Some(_), _) => cartesian = true,
+            ("longitude" | "latitude" | "height", Some(_), _) => geographic = true,
+            ("crs", _, PropRef::Text(name)) => {
+                crs = Some(Crs::from_name(name).ok_or_else(invalid)?);
+                continue;
+            }
+            ("srid", _, PropRef::Integer(srid)) => {
+                let srid = Crs::from_srid(*srid).ok_or_else(invalid)?;
+                if crs.map(|crs| crs != srid).unwrap_or(false) {
+                    return Err(invalid());
+                }
+                crs = Some(srid);
+                continue;
+            }
+            _ => return Err(Error::TypeMismatch),
+        }
+        match key {
+            "x" | "longitude" => x = coordinate,
+            "y" | "latitude" => y = coordinate,
+            _ => z = coordinate,
+        }
+    }
+
+    if cartesian && geographic {
+        return Err(invalid());
+    }
+    let crs = crs.unwrap_or(match (geographic, z.is_some()) {
+        (false, false) => Crs::Cartesian,
+        (false, true) => Crs::Cartesian3D,
+        (true, false) => Crs::Wgs84,
+        (true, true) => Crs::Wgs843D,
+    });
+    match (x, y) {
+        (Some(x), Some(y)) => Point::new(crs, x, y, z)
+            .map(PropRef::Point)
+            .ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// Distances between points of different
+/// systems are `NULL`.
+fn distance(lhs: &PropRef, rhs: &PropRef) -> Result<PropRef<'static>, Error> {
+    match (lhs, rhs) {
+        (PropRef::Null, _) | (_, PropRef::Null) => Ok(PropRef::Null),
+        (PropRef::Point(lhs), PropRef::Point(rhs)) => {
+            Ok(lhs.distance(rhs).map(PropRef::Real).unwrap_or(PropRef::Null))
+        }
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn within_bbox(
+    point: &PropRef,
+    lower_left: &PropRef,
+    upper_right: &PropRef,
+) -> Result<PropRef<'static>, Error> {
+    match (point, lower_left, upper_right) {
+        (PropRef::Null, _, _) | (_, PropRef::Null, _) | (_, _, PropRef::Null) => Ok(PropRef::Null),
+        (PropRef::Point(point), PropRef::Point(lower_left), PropRef::Point(upper_right)) => Ok(
+            point
+                .within_bbox(lower_left, upper_right)
+                .map(PropRef::Boolean)
+                .unwrap_or(PropRef::Null),
+        ),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn midnight(date: Date) -> Result<LocalDateTime, Error> {
+    LocalDateTime::new(date, 0, 0, 0, 0).ok_or(Error::Overflow)
+}
+
+fn add(lhs: &PropRef, rhs: &PropRef) -> Result<PropRef<'static>, Error> {
+    match (lhs, rhs) {
+        (PropRef::Null, _) | (_, PropRef::Null) => Ok(PropRef::Null),
+        (PropRef::Integer(lhs), PropRef::Integer(rhs)) => Ok(PropRef::Integer(
+            lhs.checked_add(*rhs).ok_or(Error::Overflow)?,
+        )),
+        (PropRef::Real(lhs), PropRef::Real(rhs)) => Ok(PropRef::Real(lhs + rhs)),
+        (PropRef::Integer(lhs), PropRef::Real(rhs)) => Ok(PropRef::Real(*lhs as f64 + rhs)),
+        (PropRef::Real(lhs), PropRef::Integer(rhs)) => Ok(PropRef::Real(lhs + *rhs as f64)),
+        (PropRef::Duration(lhs), PropRef::Duration(rhs)) => Ok(PropRef::Duration(
+            lhs.checked_add(rhs).ok_or(Error::Overflow)?,
+        )),
+        (PropRef::Duration(duration), temporal) | (temporal, PropRef::Duration(duration)) => {
+            add_duration(temporal, duration)
+        }
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn subtract(lhs: &PropRef, rhs: &PropRef) -> Result<PropRef<'static>, Error> {
+    match (lhs, rhs) {
+        (PropRef::Null, _) | (_, PropRef::Null) => Ok(PropRef::Null),
+        (PropRef::Integer(lhs), PropRef::Integer(rhs)) => Ok(PropRef::Integer(
+            lhs.checked_sub(*rhs).ok_or(Error::Overflow)?,
+        )),
+        (PropRef::Real(lhs), PropRef::Real(rhs)) => Ok(PropRef::Real(lhs - rhs)),
+        (PropRef::Integer(lhs), PropRef::Real(rhs)) => Ok(PropRef::Real(*lhs as f64 - rhs)),
+        (PropRef::Real(lhs), PropRef::Integer(rhs)) => Ok(PropRef::Real(lhs - *rhs as f64)),
+        (lhs, PropRef::Duration(rhs)) => add(
+            lhs,
+            &PropRef::Duration(rhs.checked_neg().ok_or(Error::Overflow)?),
+        ),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn add_duration(temporal: &PropRef, duration: &Duration) -> Result<PropRef<'static>, Error> {
+    match temporal {
+        PropRef::Date(date) => date
+            .checked_add(duration)
+            .map(PropRef::Date)
+            .ok_or(Error::Overflow),
+        PropRef::LocalDateTime(date_time) => date_time
+            .checked_add(duration)
+            .map(PropRef::LocalDateTime)
+            .ok_or(Error::Overflow),
+        PropRef::DateTime(date_time) => date_time
+            .checked_add(duration)
+            .map(PropRef::DateTime)
+            .ok_or(Error::Overflow),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+/// Components which are not known are `NULL`,
+/// like missing properties.
+fn component(value: &PropRef, key: &str) -> Result<PropRef<'static>, Error> {
+    let component = match value {
+        PropRef::Null => None,
+        PropRef::Date(date) => date_component(date, key),
+        PropRef::LocalDateTime(date_time) => local_date_time_component(date_time, key),
+        PropRef::DateTime(date_time) => match key {
+            "offsetSeconds" => Some(date_time.offset() as i64),
+            "epochSeconds" => Some(date_time.timestamp()),
+            "epochMillis" => date_time.timestamp().checked_mul(1000).and_then(|millis| {
+                millis.checked_add(date_time.local().nanosecond() as i64 / 1_000_000)
+            }),
+            key => local_date_time_component(&date_time.local(), key),
+        },
+        PropRef::Duration(duration) => duration_component(duration, key)?,
+        PropRef::Point(point) => return Ok(point_component(point, key)),
+        _ => return Err(Error::TypeMismatch),
+    };
+    Ok(component.map(PropRef::Integer).unwrap_or(PropRef::Null))
+}
+
+fn date_component(date: &Date, key: &str) -> Option<i64> {
+    match key {
+        "year" => Some(date.year()),
+        "quarter" => Some((date.month() as i64 - 1) / 3 + 1),
+        "month" => Some(date.month() as i64),
+        "day" => Some(date.day() as i64),
+        "ordinalDay" => Some(date.ordinal() as i64),
+        "dayOfWeek" => Some(date.weekday() as i64),
+        _ => None,
+    }
+}
+
+fn local_date_time_component(date_time: &LocalDateTime, key: &str) -> Option<i64> {
+    match key {
+        "hour" => Some(date_time.hour() as i64),
+        "minute" => Some(date_time.minute() as i64),
+        "second" => Some(date_time.second() as i64),
+        "millisecond" => Some(date_time.nanosecond() as i64 / 1_000_000),
+        "microsecond" => Some(date_time.nanosecond() as i64 / 1_000),
+        "nanosecond" => Some(date_time.nanosecond() as i64),
+        key => date_component(&date_time.date(), key),
+    }
+}
+
+/// Geographic coordinates can also be accessed as `x`,
+/// `y` and `z`, but not the other way around.
+fn point_component(point: &Point, key: &str) -> PropRef<'static> {
+    let geographic = point.crs().is_geographic();
+    match key {
+        "x" => PropRef::Real(point.x()),
+        "y" => PropRef::Real(point.y()),
+        "z" => point.z().map(PropRef::Real).unwrap_or(PropRef::Null),
+        "longitude" if geographic => PropRef::Real(point.x()),
+        "latitude" if geographic => PropRef::Real(point.y()),
+        "height" if geographic => point.z().map(PropRef::Real).unwrap_or(PropRef::Null),
+        "crs" => PropRef::Text(point.crs().name()),
+        "srid" => PropRef::Integer(point.crs().srid()),
+        _ => PropRef::Null,
+    }
+}
+
+/// Components of the time part are totals, e.g. `PT1H30M`
+/// has `90` minutes.
+fn duration_component(duration: &Duration, key: &str) -> Result<Option<i64>, Error> {
+    let nanos = duration.seconds() as i128 * 1_000_000_000 + duration.nanoseconds() as i128;
+    let total = |unit: i128| -> Result<Option<i64>, Error> {
+        Ok(Some(
+            i64::try_from(nanos / unit).map_err(|_| Error::Overflow)?,
+        ))
+    };
+    match key {
+        "years" => Ok(Some(duration.months() / 12)),
+        "months" => Ok(Some(duration.months())),
+        "monthsOfYear" => Ok(Some(duration.months() % 12)),
+        "weeks" => Ok(Some(duration.days() / 7)),
+        "days" => Ok(Some(duration.days())),
+        "hours" => total(3_600_000_000_000),
+        "minutes" => total(60_000_000_000),
+        "seconds" => total(1_000_000_000),
+        "milliseconds" => total(1_000_000),
+        "microseconds" => total(1_000),
+        "nanoseconds" => total(1),
+        "nanosecondsOfSecond" => Ok(Some((nanos % 1_000_000_000) as i64)),
+        _ => Ok(None),
+    }
+}
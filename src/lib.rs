@@ -45,19 +45,25 @@
 //! # test().unwrap();
 //! ```
 
-use planner::QueryPlan;
+use cache::{Definitions, StatementCache};
 use runtime::{Program, Status, VirtualMachine};
-use std::{convert::TryInto, path::Path};
-use std::sync::Arc;
+use std::convert::TryInto;
 
+use procedure::Procedures;
+use std::sync::{Arc, Mutex};
 use store::{Store, StoreTxn};
 pub use store::vault::Vault;
-pub use store::types::{Node,Edge};
+pub use store::types::{Node,Edge,Path};
+pub use store::spatial::{Crs, Point};
+pub use store::temporal::{Date, DateTime, Duration, LocalDateTime};
 
+pub(crate) mod cache;
 pub(crate) mod error;
+pub(crate) mod function;
 pub(crate) mod params;
 pub(crate) mod parser;
 pub(crate) mod planner;
+pub(crate) mod procedure;
 pub(crate) mod property;
 pub(crate) mod runtime;
 pub(crate) mod store;
@@ -76,6 +82,8 @@ pub use property::Property;
 /// file and support ACID queries over the graph.
 pub struct Graph {
     pub (crate) store: Store,
+    procedures: Procedures,
+    statements: Mutex<StatementCache>,
 }
 
 /// An ongoing transaction.
@@ -85,12 +93,14 @@ pub struct Graph {
 ///
 /// Once a transaction has started, it will not observe any later
 /// modifications to the graph which occur inside other transactions.
+#[repr(transparent)]
 pub struct Txn<'graph>(StoreTxn<'graph>);
 
 /// A prepared statement.
 pub struct Statement<'graph> {
     _graph: &'graph Graph,
-    program: Program,
+    program: Arc<Program>,
+    definitions: Definitions,
 }
 
 /// RAII guard which represents an ongoing query.
@@ -131,9 +141,13 @@ impl Graph {
     /// # }
     /// # test().unwrap();
     /// ```
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
         let store = Store::open(path)?;
-        Ok(Self { store })
+        Ok(Self {
+            store,
+            procedures: Procedures::new(),
+            statements: Mutex::new(StatementCache::new(cache::DEFAULT_CAPACITY)),
+        })
     }
 
     /// Open an anonymous graph which is held in-memory.
@@ -151,7 +165,11 @@ impl Graph {
     /// ```
     pub fn open_anon() -> Result<Self, Error> {
         let store = Store::open_anon()?;
-        Ok(Self { store })
+        Ok(Self {
+            store,
+            procedures: Procedures::new(),
+            statements: Mutex::new(StatementCache::new(cache::DEFAULT_CAPACITY)),
+        })
     }
 
     pub fn with_vault(mut self, vault: Arc<dyn Vault<Error=crate::error::Error>>) -> Self {
@@ -194,19 +212,222 @@ impl Graph {
     ///     CREATE (a) -[:KNOWS { since: 'today' }]-> (b)
     ///     "
     /// )?;
+    /// let stmt = graph.prepare("CREATE INDEX ON :PERSON(name)")?;
     /// # Ok(())
     /// # }
     /// # test().unwrap();
     /// ```
     pub fn prepare<'graph>(&'graph self, query: &str) -> Result<Statement<'graph>, Error> {
         let ast = parser::parse(query)?;
-        let plan = QueryPlan::new(&ast)?.optimize()?;
+        let schema = self.store.txn()?.schema()?;
+        Ok(Statement {
+            _graph: self,
+            program: Arc::new(Program::compile(&ast, &self.procedures, &schema)?),
+            definitions: Definitions {
+                indexes: schema.indexes,
+                rules: schema.rules,
+            },
+        })
+    }
+
+    /// Prepare a statement like [`prepare`][Graph::prepare], but reuse
+    /// the statement if the same query was prepared recently. The graph
+    /// keeps the most recently used statements, see
+    /// [`with_statement_cache`][Graph::with_statement_cache].
+    ///
+    /// Kept statements are prepared again once indexes or constraints
+    /// change, since they may change the best plan. Statistics are not
+    /// checked, use [`clear_statement_cache`][Graph::clear_statement_cache]
+    /// to plan the queries for the current statistics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn test() -> Result<(), cqlite::Error> {
+    /// use cqlite::Graph;
+    ///
+    /// let graph = Graph::open_anon()?;
+    /// let mut txn = graph.mut_txn()?;
+    /// for name in &["Peter Parker", "Clark Kent"] {
+    ///     graph
+    ///         .prepare_cached("CREATE (:PERSON { name: $name })")?
+    ///         .execute(&mut txn, ("name", *name))?;
+    /// }
+    /// txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn prepare_cached<'graph>(&'graph self, query: &str) -> Result<Statement<'graph>, Error> {
+        let txn = self.store.txn()?;
+        let definitions = txn.definitions()?;
+        if let Some(program) = self.statements.lock()?.get(query, &definitions) {
+            return Ok(Statement {
+                _graph: self,
+                program,
+                definitions,
+            });
+        }
+        let ast = parser::parse(query)?;
+        let schema = txn.schema()?;
+        let program = Arc::new(Program::compile(&ast, &self.procedures, &schema)?);
+        self.statements
+            .lock()?
+            .insert(query, program.clone(), &definitions);
         Ok(Statement {
             _graph: self,
-            program: Program::new(&plan)?,
+            program,
+            definitions,
         })
     }
 
+    /// Load a statement stored using [`to_bytes`][Statement::to_bytes],
+    /// without parsing or planning the query again.
+    ///
+    /// The statement must have been stored by the same version of
+    /// this crate, and prepared while the graph had the same indexes
+    /// and constraints as it has now. Called procedures must still be
+    /// registered with the same columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn test() -> Result<(), cqlite::Error> {
+    /// use cqlite::Graph;
+    ///
+    /// let graph = Graph::open_anon()?;
+    /// let bytes = graph
+    ///     .prepare("MATCH (a:PERSON) RETURN a.name")?
+    ///     .to_bytes()?;
+    ///
+    /// let stmt = graph.load_statement(&bytes)?;
+    /// let names: Vec<String> = stmt
+    ///     .query_map(&mut graph.txn()?, (), |m| m.get(0))?
+    ///     .collect::<Result<_, _>>()?;
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn load_statement<'graph>(&'graph self, bytes: &[u8]) -> Result<Statement<'graph>, Error> {
+        let definitions = self.store.txn()?.definitions()?;
+        let program = Program::from_bytes(bytes, &definitions, &self.procedures)?;
+        Ok(Statement {
+            _graph: self,
+            program: Arc::new(program),
+            definitions,
+        })
+    }
+
+    /// Keep at most `capacity` statements prepared using
+    /// [`prepare_cached`][Graph::prepare_cached]. A capacity
+    /// of zero disables reusing statements. The default
+    /// capacity is 64 statements.
+    pub fn with_statement_cache(mut self, capacity: usize) -> Self {
+        self.statements
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner())
+            .set_capacity(capacity);
+        self
+    }
+
+    /// Drop all statements kept by [`prepare_cached`][Graph::prepare_cached],
+    /// such that they are prepared again when next used.
+    pub fn clear_statement_cache(&self) -> Result<(), Error> {
+        self.statements.lock()?.clear();
+        Ok(())
+    }
+
+    /// Prepare and execute each statement of a script of `;`
+    /// separated statements in order, inside the given transaction.
+    /// Any values returned by the statements are discarded.
+    ///
+    /// If a statement fails, the remaining statements are not run and
+    /// an [`Error::Batch`] is returned, which contains the index of the
+    /// failing statement and the position where it starts. Changes made
+    /// by earlier statements remain in the transaction, which can be
+    /// dropped to discard them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn test() -> Result<(), cqlite::Error> {
+    /// use cqlite::Graph;
+    ///
+    /// let graph = Graph::open_anon()?;
+    /// let mut txn = graph.mut_txn()?;
+    /// graph.execute_batch(
+    ///     &mut txn,
+    ///     "
+    ///     CREATE (:PERSON { name: 'Peter Parker' });
+    ///     CREATE (:PERSON { name: 'Clark Kent' });
+    ///     MATCH (a:PERSON { name: 'Peter Parker' })
+    ///     MATCH (b:PERSON { name: 'Clark Kent' })
+    ///     CREATE (a) -[:KNOWS]-> (b);
+    ///     ",
+    /// )?;
+    /// txn.commit()?;
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn execute_batch<'graph>(
+        &'graph self,
+        txn: &mut Txn<'graph>,
+        script: &str,
+    ) -> Result<(), Error> {
+        for (index, (start, statement)) in parser::parse_script(script)?.into_iter().enumerate() {
+            self.prepare(statement)
+                .and_then(|stmt| stmt.execute(txn, ()))
+                .map_err(|error| error.in_batch(index, &start))?;
+        }
+        Ok(())
+    }
+
+    /// Register a procedure which can be invoked from queries using
+    /// `CALL name(arguments) YIELD columns`. The procedure receives the
+    /// current transaction and the call arguments and returns a list of
+    /// rows, where each row has one value for each of the given `columns`.
+    ///
+    /// Registering a procedure with the same name as an existing one
+    /// replaces it. Statements which were prepared earlier keep using the
+    /// previous procedure, kept statements are prepared again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn test() -> Result<(), cqlite::Error> {
+    /// use cqlite::{Graph, Property};
+    ///
+    /// let mut graph = Graph::open_anon()?;
+    /// graph.register_procedure("my.range", &["value"], |_txn, args| {
+    ///     let end = match args.get(0) {
+    ///         Some(Property::Integer(end)) => *end,
+    ///         _ => 0,
+    ///     };
+    ///     Ok((0..end).map(|i| vec![Property::Integer(i)]).collect())
+    /// });
+    ///
+    /// let values: Vec<i64> = graph
+    ///     .prepare("CALL my.range(3) YIELD value RETURN value")?
+    ///     .query_map(&mut graph.txn()?, (), |m| m.get(0))?
+    ///     .collect::<Result<_, _>>()?;
+    /// assert_eq!(values, vec![0, 1, 2]);
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn register_procedure<F>(&mut self, name: &str, columns: &[&str], procedure: F)
+    where
+        F: Fn(&Txn, &[Property]) -> Result<Vec<Vec<Property>>, Error> + Send + Sync + 'static,
+    {
+        self.procedures.register(name, columns, Arc::new(procedure));
+        // statements call the procedures they were prepared with
+        self.statements
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+    }
+
     /// Start a new read-only transaction. There may be many simultaneous
     /// read-only transactions.
     pub fn txn(&self) -> Result<Txn, Error> {
@@ -218,6 +439,36 @@ impl Graph {
     pub fn mut_txn(&self) -> Result<Txn, Error> {
         Ok(Txn(self.store.mut_txn()?))
     }
+
+    /// Check all nodes and edges against the constraints of the graph.
+    /// Rules like `exists(n.name)` are only checked for nodes and edges
+    /// which change after the rule was created, use this to check the
+    /// existing data. Returns the first violation found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn test() -> Result<(), cqlite::Error> {
+    /// use cqlite::{Error, Graph};
+    ///
+    /// let graph = Graph::open_anon()?;
+    /// let mut txn = graph.mut_txn()?;
+    /// graph.execute_batch(
+    ///     &mut txn,
+    ///     "
+    ///     CREATE (:PERSON);
+    ///     CREATE CONSTRAINT ON (p:PERSON) ASSERT exists(p.name);
+    ///     ",
+    /// )?;
+    /// txn.commit()?;
+    /// assert!(matches!(graph.validate(), Err(Error::ConstraintViolation { .. })));
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        self.store.txn()?.validate()
+    }
 }
 
 impl<'graph> Txn<'graph> {
@@ -229,6 +480,21 @@ impl<'graph> Txn<'graph> {
     pub fn commit(self) -> Result<(), Error> {
         self.0.commit()
     }
+
+    /// Load the node with the given `id`, if it exists.
+    pub fn load_node(&self, id: u64) -> Result<Option<Node>, Error> {
+        self.0.load_node(id)
+    }
+
+    /// Load the edge with the given `id`, if it exists.
+    pub fn load_edge(&self, id: u64) -> Result<Option<Edge>, Error> {
+        self.0.load_edge(id)
+    }
+
+    pub(crate) fn from_store<'txn>(txn: &'txn StoreTxn<'graph>) -> &'txn Self {
+        // SAFETY: `Txn` is a transparent wrapper around `StoreTxn`
+        unsafe { &*(txn as *const StoreTxn<'graph> as *const Self) }
+    }
 }
 
 impl<'graph> Statement<'graph> {
@@ -289,7 +555,7 @@ impl<'graph> Statement<'graph> {
                     .into_iter()
                     .map(|(k, v)| (k, v.to_internal()))
                     .collect(),
-            ),
+            )?,
         })
     }
 
@@ -375,6 +641,13 @@ impl<'graph> Statement<'graph> {
         txn.0.flush()?;
         Ok(())
     }
+
+    /// Encode the compiled statement, such that it can be loaded
+    /// using [`load_statement`][Graph::load_statement] without
+    /// preparing the query again.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.program.to_bytes(&self.definitions)
+    }
 }
 
 impl<'stmt, 'txn> Query<'stmt, 'txn> {
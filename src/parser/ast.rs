@@ -1,36 +1,165 @@
+use crate::store::PropertyType;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement<'src> {
+    Query(Query<'src>),
+    Union {
+        queries: Vec<Query<'src>>,
+        all: bool,
+    },
+    Explain(Box<Statement<'src>>),
+    Profile(Box<Statement<'src>>),
+    CreateIndex {
+        label: &'src str,
+        key: &'src str,
+    },
+    DropIndex {
+        label: &'src str,
+        key: &'src str,
+    },
+    CreateConstraint(Constraint<'src>),
+    DropConstraint(Constraint<'src>),
+}
+
+/// A constraint on the properties of all nodes with `label`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint<'src> {
+    /// No two nodes have the same values for `keys`.
+    Unique {
+        label: &'src str,
+        keys: Vec<&'src str>,
+    },
+    /// All nodes have `keys` and no two nodes
+    /// have the same values for them.
+    NodeKey {
+        label: &'src str,
+        keys: Vec<&'src str>,
+    },
+    /// All nodes, or edges if `edge` is set,
+    /// have the property `key`.
+    Exists {
+        edge: bool,
+        label: &'src str,
+        key: &'src str,
+    },
+    /// The property `key` of all nodes, or edges if
+    /// `edge` is set, is missing or of type `kind`.
+    Type {
+        edge: bool,
+        label: &'src str,
+        key: &'src str,
+        kind: PropertyType,
+    },
+    /// All nodes, or edges if `edge` is set, have
+    /// no properties other than `keys`.
+    Keys {
+        edge: bool,
+        label: &'src str,
+        keys: Vec<&'src str>,
+    },
+    /// All edges with `label` go from a node with
+    /// label `origin` to one with label `target`.
+    Endpoints {
+        label: &'src str,
+        origin: &'src str,
+        target: &'src str,
+    },
+}
+
+impl<'src> Statement<'src> {
+    /// The queries which make up this statement.
+    pub fn queries(&self) -> &[Query<'src>] {
+        match self {
+            Self::Query(query) => std::slice::from_ref(query),
+            Self::Union { queries, .. } => queries,
+            Self::Explain(inner) | Self::Profile(inner) => inner.queries(),
+            Self::CreateIndex { .. }
+            | Self::DropIndex { .. }
+            | Self::CreateConstraint(_)
+            | Self::DropConstraint(_) => &[],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Query<'src> {
     pub match_clauses: Vec<MatchClause<'src>>,
+    pub call_clauses: Vec<CallClause<'src>>,
+    pub subquery_clauses: Vec<SubqueryClause<'src>>,
     pub where_clauses: Vec<Condition<'src>>,
     pub create_clauses: Vec<CreateClause<'src>>,
     pub set_clauses: Vec<SetClause<'src>>,
     pub delete_clauses: Vec<&'src str>,
+    pub foreach_clauses: Vec<ForeachClause<'src>>,
     pub return_clause: Vec<Expression<'src>>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallClause<'src> {
+    pub procedure: &'src str,
+    pub arguments: Vec<Expression<'src>>,
+    pub yields: Option<Vec<&'src str>>,
+}
+
+/// A correlated subquery, `CALL { WITH imports ... }`,
+/// which runs once for every outer match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubqueryClause<'src> {
+    pub imports: Vec<&'src str>,
+    pub query: Query<'src>,
+}
+
+/// `FOREACH (variable IN list | updates)`, where the
+/// body only contains update clauses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeachClause<'src> {
+    pub variable: &'src str,
+    pub list: Expression<'src>,
+    pub body: Query<'src>,
+}
+
+/// Whether a single edge may be used more than once
+/// when matching a pattern, e.g. `MATCH REPEATABLE ELEMENTS`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    DifferentEdges,
+    RepeatableElements,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchClause<'src> {
+    pub mode: MatchMode,
+    pub path: Option<&'src str>,
     pub start: Node<'src>,
     pub edges: Vec<(Edge<'src>, Node<'src>)>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SetClause<'src> {
     pub name: &'src str,
-    pub key: &'src str,
+    pub key: Symbol<'src>,
     pub value: Expression<'src>,
 }
 
+/// A label or property key, which is either written in the
+/// query or passed as a parameter, e.g. `$($label)` or `n[$key]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbol<'src> {
+    Static(&'src str),
+    Parameter(&'src str),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CreateClause<'src> {
     CreateNode {
         name: Option<&'src str>,
-        label: &'src str,
+        label: Symbol<'src>,
         properties: Vec<(&'src str, Expression<'src>)>,
     },
     CreateEdge {
         name: Option<&'src str>,
-        label: &'src str,
+        label: Symbol<'src>,
         origin: &'src str,
         target: &'src str,
         properties: Vec<(&'src str, Expression<'src>)>,
@@ -40,7 +169,7 @@ pub enum CreateClause<'src> {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Annotation<'src> {
     pub name: Option<&'src str>,
-    pub label: Option<&'src str>,
+    pub label: Option<Symbol<'src>>,
 }
 
 impl<'src> Annotation<'src> {
@@ -48,7 +177,7 @@ impl<'src> Annotation<'src> {
     pub fn new(name: &'src str, kind: &'src str) -> Self {
         Self {
             name: Some(name),
-            label: Some(kind),
+            label: Some(Symbol::Static(kind)),
         }
     }
 
@@ -64,7 +193,7 @@ impl<'src> Annotation<'src> {
     pub fn with_label(label: &'src str) -> Self {
         Self {
             name: None,
-            label: Some(label),
+            label: Some(Symbol::Static(label)),
         }
     }
 
@@ -161,22 +290,64 @@ impl<'src> Edge<'src> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal<'src> {
     Integer(i64),
     Real(f64),
     Boolean(bool),
-    Text(&'src str),
+    Text(Cow<'src, str>),
     Null,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression<'src> {
     Literal(Literal<'src>),
     IdOf { name: &'src str },
     LabelOf { name: &'src str },
-    Property { name: &'src str, key: &'src str },
+    Property { name: &'src str, key: Symbol<'src> },
     Parameter(&'src str),
+    Variable(&'src str),
+    LengthOf { name: &'src str },
+    NodesOf { name: &'src str },
+    RelationshipsOf { name: &'src str },
+    /// `*` in a `RETURN` clause, which
+    /// stands for every named variable.
+    All,
+    ListComprehension(Box<ListComprehension<'src>>),
+    PatternComprehension(Box<PatternComprehension<'src>>),
+    /// A call of a built-in function, e.g. `date('2024-01-01')`.
+    Function {
+        name: &'src str,
+        arguments: Vec<Expression<'src>>,
+    },
+    Add(Box<Expression<'src>>, Box<Expression<'src>>),
+    Subtract(Box<Expression<'src>>, Box<Expression<'src>>),
+    /// A component of a value, e.g. `d.year` or `n.born.year`.
+    Component {
+        value: Box<Expression<'src>>,
+        key: &'src str,
+    },
+    /// A map literal, e.g. `{x: 1.0, y: 2.0}`.
+    Map(Vec<(&'src str, Expression<'src>)>),
+}
+
+/// `[variable IN list WHERE filter | projection]`, where
+/// the filter and projection are optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListComprehension<'src> {
+    pub variable: &'src str,
+    pub list: Expression<'src>,
+    pub filter: Option<Condition<'src>>,
+    pub projection: Option<Expression<'src>>,
+}
+
+/// `[pattern WHERE filter | projection]`, which collects
+/// the projection for every match of the pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternComprehension<'src> {
+    pub pattern: MatchClause<'src>,
+    pub filter: Option<Condition<'src>>,
+    pub projection: Expression<'src>,
 }
 
 impl<'src> Expression<'src> {
@@ -188,8 +359,39 @@ impl<'src> Expression<'src> {
         Self::LabelOf { name }
     }
 
+    #[allow(dead_code)]
     pub fn property(name: &'src str, key: &'src str) -> Self {
-        Self::Property { name, key }
+        Self::Property {
+            name,
+            key: Symbol::Static(key),
+        }
+    }
+
+    pub fn length_of(name: &'src str) -> Self {
+        Self::LengthOf { name }
+    }
+
+    pub fn nodes_of(name: &'src str) -> Self {
+        Self::NodesOf { name }
+    }
+
+    pub fn relationships_of(name: &'src str) -> Self {
+        Self::RelationshipsOf { name }
+    }
+
+    pub fn add(lhs: Self, rhs: Self) -> Self {
+        Self::Add(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn subtract(lhs: Self, rhs: Self) -> Self {
+        Self::Subtract(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn component(value: Self, key: &'src str) -> Self {
+        Self::Component {
+            value: Box::new(value),
+            key,
+        }
     }
 }
 
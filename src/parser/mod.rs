@@ -1,6 +1,7 @@
 #![allow(clippy::redundant_closure_call)]
 
-use peg::{error::ParseError, str::LineCol};
+use peg::{error::ParseError, str::LineCol, Parse};
+use std::borrow::Cow;
 
 pub mod ast;
 #[cfg(test)]
@@ -9,27 +10,59 @@ mod tests;
 peg::parser! {
     grammar cypher() for str {
         use ast::*;
+        use crate::store::PropertyType;
 
         rule kw_match()     = ("MATCH" / "match")
+        rule kw_repeatable()= ("REPEATABLE" / "repeatable")
+        rule kw_elements()  = ("ELEMENTS" / "elements")
+        rule kw_different() = ("DIFFERENT" / "different")
         rule kw_create()    = ("CREATE" / "create")
         rule kw_set()       = ("SET" / "set")
         rule kw_delete()    = ("DELETE" / "delete")
+        rule kw_drop()      = ("DROP" / "drop")
+        rule kw_index()     = ("INDEX" / "index")
+        rule kw_on()        = ("ON" / "on")
+        rule kw_constraint()= ("CONSTRAINT" / "constraint")
+        rule kw_assert()    = ("ASSERT" / "assert")
+        rule kw_is()        = ("IS" / "is")
+        rule kw_unique()    = ("UNIQUE" / "unique")
+        rule kw_node()      = ("NODE" / "node")
+        rule kw_key()       = ("KEY" / "key")
+        rule kw_keys()      = ("KEYS" / "keys")
+        rule kw_exists()    = ("EXISTS" / "exists")
         rule kw_where()     = ("WHERE" / "where")
         rule kw_return()    = ("RETURN" / "return")
+        rule kw_call()      = ("CALL" / "call")
+        rule kw_yield()     = ("YIELD" / "yield")
+        rule kw_union()     = ("UNION" / "union")
+        rule kw_explain()   = ("EXPLAIN" / "explain")
+        rule kw_profile()   = ("PROFILE" / "profile")
+        rule kw_all()       = ("ALL" / "all")
+        rule kw_with()      = ("WITH" / "with")
+        rule kw_foreach()   = ("FOREACH" / "foreach")
+        rule kw_in()        = ("IN" / "in")
         rule kw_true()      = ("TRUE" / "true")
         rule kw_false()     = ("FALSE" / "false")
         rule kw_null()      = ("NULL" / "null")
         rule kw_and()       = ("AND" / "and")
         rule kw_or()        = ("OR" / "or")
         rule kw_not()       = ("NOT" / "not")
+        rule kw_between()   = ("BETWEEN" / "between")
         rule kw_id()        = ("ID" / "id")
         rule kw_label()     = ("LABEL" / "label")
+        rule kw_length()    = ("LENGTH" / "length")
+        rule kw_nodes()     = ("NODES" / "nodes")
+        rule kw_rels()      = ("RELATIONSHIPS" / "relationships")
 
         rule _()
             = [' ']
 
         rule __()
-            = [' ' | '\n' | '\t']
+            = [' ' | '\n' | '\t'] / comment()
+
+        // e.g. '// to the end of the line', '/* up to here */'
+        rule comment()
+            = "//" [^ '\n']* / "/*" (!"*/" [_])* "*/"
 
         rule alpha()
             = ['a'..='z' | 'A'..='Z']
@@ -53,9 +86,10 @@ peg::parser! {
         rule boolean() -> bool
             = kw_true() { true } / kw_false() { false }
 
-        // e.g. 'hello world'
-        rule text() -> &'input str
-            = "'" text:$([^ '\'' | '\n' | '\r']*) "'" { text }
+        // e.g. 'hello world', "hello world", 'it\'s'
+        rule text() -> Cow<'input, str>
+            = "'" text:$( ("\\" [_] / [^ '\'' | '\\' | '\n' | '\r'])* ) "'" { unescape(text) }
+            / "\"" text:$( ("\\" [_] / [^ '"' | '\\' | '\n' | '\r'])* ) "\"" { unescape(text) }
 
         // e.g. 'TRUE', '42', 'hello world'
         rule literal() -> Literal<'input>
@@ -65,21 +99,62 @@ peg::parser! {
             / t:text() { Literal::Text(t) }
             / kw_null() { Literal::Null }
 
-        rule expression() -> Expression<'input>
+        // e.g. 'a.age + 1', 'date($day) - duration('P1D')', 'n.born.year'
+        rule expression() -> Expression<'input> = precedence!{
+            a:(@) _* "+" _* b:@ { Expression::add(a, b) }
+            a:(@) _* "-" _* b:@ { Expression::subtract(a, b) }
+            --
+            e:(@) "." key:ident() { Expression::component(e, key) }
+            --
+            e:atom() { e }
+        }
+
+        rule atom() -> Expression<'input>
             = "$" name:ident() { Expression::Parameter(name) }
             / l:literal() { Expression::Literal(l) }
             / kw_id() _* "(" _* n:ident() _* ")" { Expression::id_of(n) }
             / kw_label() _* "(" _* n:ident() _* ")" { Expression::label_of(n) }
-            / p:property() { Expression::property(p.0, p.1) }
+            / kw_length() _* "(" _* n:ident() _* ")" { Expression::length_of(n) }
+            / kw_nodes() _* "(" _* n:ident() _* ")" { Expression::nodes_of(n) }
+            / kw_rels() _* "(" _* n:ident() _* ")" { Expression::relationships_of(n) }
+            / name:$(ident() ++ ".") _* "(" __* arguments:( expression() ** (__* "," __*) ) __* ")" {
+                Expression::Function { name, arguments }
+            }
+            / p:property() { Expression::Property { name: p.0, key: p.1 } }
+            / n:ident() { Expression::Variable(n) }
+            / c:list_comprehension() { Expression::ListComprehension(Box::new(c)) }
+            / c:pattern_comprehension() { Expression::PatternComprehension(Box::new(c)) }
+            / m:property_map() { Expression::Map(m) }
+
+        // e.g. '[x IN $list WHERE x > 2]', '[n IN nodes(p) | n.name]'
+        rule list_comprehension() -> ListComprehension<'input>
+            = "[" __* variable:ident() __+ kw_in() __+ list:expression()
+              filter:( __+ kw_where() __+ c:condition() { c } )?
+              projection:( __* "|" __* e:expression() { e } )? __* "]" {
+                ListComprehension { variable, list, filter, projection }
+            }
+
+        // e.g. '[(a) -[:KNOWS]-> (b) | b.name]', '[(a) -> (b) WHERE b.age > 42 | b]'
+        rule pattern_comprehension() -> PatternComprehension<'input>
+            = "[" __* pattern:pattern()
+              filter:( __+ kw_where() __+ c:condition() { c } )?
+              __* "|" __* projection:expression() __* "]" {
+                PatternComprehension { pattern, filter, projection }
+            }
 
         // e.g. 'hello_world', 'Rust', 'HAS_PROPERTY'
         rule ident() -> &'input str
             = ident:$(alpha()alpha_num()*) { ident }
 
 
-        // e.g. 'a', 'a : PERSON', ': KNOWS'
+        // e.g. 'PERSON', '$($label)'
+        rule symbol() -> Symbol<'input>
+            = "$(" _* "$" p:ident() _* ")" { Symbol::Parameter(p) }
+            / s:ident() { Symbol::Static(s) }
+
+        // e.g. 'a', 'a : PERSON', ': KNOWS', 'a:$($label)'
         rule annotation() -> Annotation<'input>
-            = name:ident()? label:( _* ":" _* k:ident() { k } )? { Annotation { name, label } }
+            = name:ident()? label:( _* ":" _* k:symbol() { k } )? { Annotation { name, label } }
 
         // e.g. '{answer: 42, book: 'Hitchhikers Guide'}'
         rule property_map() -> Vec<(&'input str, Expression<'input>)>
@@ -107,8 +182,16 @@ peg::parser! {
             / "-" { Edge::either(Annotation::empty(), Vec::new()) }
 
 
-        rule property() -> (&'input str, &'input str)
-            = name:ident() "." key:ident() { (name, key) }
+        // e.g. 'a.name', 'a[$key]', 'a['name']'
+        rule property() -> (&'input str, Symbol<'input>)
+            = name:ident() "." key:ident() { (name, Symbol::Static(key)) }
+            / name:ident() "[" _* "$" key:ident() _* "]" { (name, Symbol::Parameter(key)) }
+            / name:ident() "[" _* key:text() _* "]" {?
+                match key {
+                    Cow::Borrowed(key) => Ok((name, Symbol::Static(key))),
+                    Cow::Owned(_) => Err("property keys can not contain escape sequences"),
+                }
+            }
 
         rule condition() -> Condition<'input>= precedence!{
             a:(@) __* kw_and() __* b:@ { Condition::and(a, b) }
@@ -125,17 +208,54 @@ peg::parser! {
             a:expression() _* "<=" _* b:expression() { Condition::Le(a, b) }
             a:expression() _* ">"  _* b:expression() { Condition::Gt(a, b) }
             a:expression() _* ">=" _* b:expression() { Condition::Ge(a, b) }
+            a:expression() __+ kw_between() __+ l:expression() __+ kw_and() __+ u:expression() {
+                Condition::and(Condition::Ge(a.clone(), l), Condition::Le(a, u))
+            }
             --
             e:expression() { Condition::Expression(e) }
             "(" __* c:condition() __* ")" { c }
         }
 
 
-        // e.g. 'MATCH (a)', 'MATCH (a) -> (b) <- (c)', ...
-        rule match_clause() -> MatchClause<'input>
-            = kw_match() __+ start:node()
+        // e.g. '(a)', '(a) -> (b) <- (c)', 'p = (a) -> (b)', ...
+        rule pattern() -> MatchClause<'input>
+            = path:( p:ident() __* "=" __* { p } )? start:node()
               edges:( (__* e:edge() __* n:node() { (e, n) }) ** "" ) {
-                MatchClause { start, edges }
+                MatchClause { mode: MatchMode::DifferentEdges, path, start, edges }
+            }
+
+        // e.g. 'REPEATABLE ELEMENTS', 'DIFFERENT RELATIONSHIPS'
+        rule match_mode() -> MatchMode
+            = kw_repeatable() __+ kw_elements() { MatchMode::RepeatableElements }
+            / kw_different() __+ kw_rels() { MatchMode::DifferentEdges }
+
+        // e.g. 'MATCH (a)', 'MATCH (a) -> (b) <- (c)', 'MATCH p = (a) -> (b)', ...
+        rule match_clause() -> MatchClause<'input>
+            = kw_match() __+ mode:( m:match_mode() __+ { m } )? m:pattern() {
+                MatchClause { mode: mode.unwrap_or(MatchMode::DifferentEdges), ..m }
+            }
+
+        // e.g. 'CALL db.labels()', 'CALL my.procedure(a.name, 42) YIELD value, score'
+        rule call_clause() -> CallClause<'input>
+            = kw_call() __+ procedure:$(ident() ++ ".") _* "("
+              __* arguments:( expression() ** (__* "," __*) ) __* ")"
+              yields:( __+ kw_yield() __+ y:( ident() ++ (__* "," __*) ) { y } )? {
+                CallClause { procedure, arguments, yields }
+            }
+
+        // e.g. 'CALL { WITH a MATCH (a) -> (b) RETURN b }'
+        rule subquery_clause() -> SubqueryClause<'input>
+            = kw_call() __* "{" __*
+              imports:( kw_with() __+ i:( ident() ++ (__* "," __*) ) { i } )?
+              query:query() "}" {
+                SubqueryClause { imports: imports.unwrap_or_else(Vec::new), query }
+            }
+
+        // e.g. 'FOREACH (n IN nodes(p) | SET n.seen = TRUE)'
+        rule foreach_clause() -> ForeachClause<'input>
+            = kw_foreach() __* "(" __* variable:ident() __+ kw_in() __+ list:expression() __* "|"
+              body:update_body() ")" {
+                ForeachClause { variable, list, body }
             }
 
         // e.g. 'WHERE a.name <> b.name', 'WHERE a.age > b.age AND a.age <= 42'
@@ -176,31 +296,233 @@ peg::parser! {
         rule delete_clause() -> &'input str
             = kw_delete() __+ name:ident() { name }
 
-        // e.g. 'RETURN a, b'
+        // e.g. 'RETURN a, b', 'RETURN *'
         rule return_clause() -> Vec<Expression<'input>>
-            = kw_return() __+ items:( expression() ++ (__* "," __*) ) { items }
+            = kw_return() __+ items:( ("*" { Expression::All } / expression()) ++ (__* "," __*) ) {
+                items
+            }
 
         pub rule query() -> Query<'input>
             = __*
               match_clauses:( match_clause() ** (__+) )
+              call_clauses:( __* c:( call_clause() ** (__+) )? { c.unwrap_or_else(Vec::new) } )
+              subquery_clauses:( __* s:( subquery_clause() ** (__+) )? { s.unwrap_or_else(Vec::new) } )
               where_clauses:( __* w:( where_clause() ** (__+) )? { w.unwrap_or_else(Vec::new) } )
               create_clauses:( __* c:(create_clause() ** (__+) )? { c.unwrap_or_else(Vec::new) } )
               set_clauses:( __* s:(set_clause() ** (__+) )? { s.unwrap_or_else(Vec::new) } )
               delete_clauses:( __* d:(delete_clause() ** (__+) )? { d.unwrap_or_else(Vec::new) } )
+              foreach_clauses:( __* f:(foreach_clause() ** (__+) )? { f.unwrap_or_else(Vec::new) } )
               return_clause:( __* r:return_clause()? { r.unwrap_or_else(Vec::new) })
               __* {
                 Query {
                     match_clauses,
+                    call_clauses,
+                    subquery_clauses,
                     where_clauses,
                     create_clauses,
                     set_clauses,
                     delete_clauses,
+                    foreach_clauses,
                     return_clause,
                 }
             }
+
+        // the updates inside of a 'FOREACH'
+        rule update_body() -> Query<'input>
+            = __*
+              create_clauses:( c:(create_clause() ** (__+) )? { c.unwrap_or_else(Vec::new) } )
+              set_clauses:( __* s:(set_clause() ** (__+) )? { s.unwrap_or_else(Vec::new) } )
+              delete_clauses:( __* d:(delete_clause() ** (__+) )? { d.unwrap_or_else(Vec::new) } )
+              foreach_clauses:( __* f:(foreach_clause() ** (__+) )? { f.unwrap_or_else(Vec::new) } )
+              __* {
+                Query {
+                    match_clauses: Vec::new(),
+                    call_clauses: Vec::new(),
+                    subquery_clauses: Vec::new(),
+                    where_clauses: Vec::new(),
+                    create_clauses,
+                    set_clauses,
+                    delete_clauses,
+                    foreach_clauses,
+                    return_clause: Vec::new(),
+                }
+            }
+
+        // e.g. 'MATCH (a:A) RETURN a.name UNION MATCH (a:B) RETURN a.name'
+        rule union_statement() -> Statement<'input>
+            = first:query()
+              rest:( kw_union() all:( __+ kw_all() )? __+ q:query() { (all.is_some(), q) } )* {?
+                if rest.is_empty() {
+                    return Ok(Statement::Query(first));
+                }
+                let all = rest[0].0;
+                if rest.iter().any(|(a, _)| *a != all) {
+                    return Err("UNION and UNION ALL can not be mixed");
+                }
+                let mut queries = vec![first];
+                queries.extend(rest.into_iter().map(|(_, q)| q));
+                Ok(Statement::Union { queries, all })
+            }
+
+        // e.g. 'CREATE INDEX ON :PERSON(name)', 'DROP INDEX ON :PERSON(name)'
+        rule index_statement() -> Statement<'input>
+            = __* kw_create() __+ kw_index() __+ kw_on() __* ":" _* label:ident() _*
+              "(" _* key:ident() _* ")" __* { Statement::CreateIndex { label, key } }
+            / __* kw_drop() __+ kw_index() __+ kw_on() __* ":" _* label:ident() _*
+              "(" _* key:ident() _* ")" __* { Statement::DropIndex { label, key } }
+
+        // e.g. 'u.email', '(u.first, u.last)'
+        rule constraint_keys() -> Vec<(&'input str, &'input str)>
+            = "(" __* keys:( (n:ident() _* "." _* k:ident() { (n, k) }) ++ (__* "," __*) ) __*
+              ")" { keys }
+            / n:ident() _* "." _* k:ident() { vec![(n, k)] }
+
+        // e.g. 'INTEGER', 'LOCAL DATETIME'
+        rule property_type() -> PropertyType
+            = ("BOOLEAN" / "boolean") { PropertyType::Boolean }
+            / ("STRING" / "string") { PropertyType::String }
+            / ("INTEGER" / "integer") { PropertyType::Integer }
+            / ("FLOAT" / "float") { PropertyType::Float }
+            / ("LOCAL" / "local") __+ ("DATETIME" / "datetime") { PropertyType::LocalDateTime }
+            / ("ZONED" / "zoned") __+ ("DATETIME" / "datetime") { PropertyType::DateTime }
+            / ("DATE" / "date") { PropertyType::Date }
+            / ("DURATION" / "duration") { PropertyType::Duration }
+            / ("POINT" / "point") { PropertyType::Point }
+            / ("BLOB" / "blob") { PropertyType::Blob }
+            / ("LIST" / "list") { PropertyType::List }
+
+        // e.g. '(u:USER)' or '() -[e:KNOWS]- ()', as (is edge, name, label)
+        rule constraint_element() -> (bool, &'input str, &'input str)
+            = "(" _* name:ident() _* ":" _* label:ident() _* ")" { (false, name, label) }
+            / "(" _* ")" __* "-" _* "[" _* name:ident() _* ":" _* label:ident() _* "]" _* "-" ">"?
+              __* "(" _* ")" { (true, name, label) }
+
+        // e.g. 'ON (u:USER) ASSERT u.email IS UNIQUE',
+        // 'ON (u:USER) ASSERT (u.first, u.last) IS NODE KEY',
+        // 'ON () -[e:KNOWS]- () ASSERT exists(e.since)',
+        // 'ON (u:USER) ASSERT u.age IS :: INTEGER',
+        // 'ON (u:USER) ASSERT keys(u) IN ['email', 'age']',
+        // 'ON (:PERSON) -[:WORKS_AT]-> (:COMPANY)'
+        rule constraint() -> Constraint<'input>
+            = kw_on() __* "(" _* ":" _* origin:ident() _* ")" __* "-" _* "[" _* ":" _* label:ident()
+              _* "]" _* "->" __* "(" _* ":" _* target:ident() _* ")" {
+                Constraint::Endpoints { label, origin, target }
+            }
+            / kw_on() __* element:constraint_element() __+
+              kw_assert() __+ keys:constraint_keys() __+ kw_is() __+
+              unique:( kw_unique() { true } / kw_node() __+ kw_key() { false } ) {?
+                let (edge, name, label) = element;
+                if edge {
+                    return Err("only node properties can be unique");
+                }
+                if keys.iter().any(|(node, _)| *node != name) {
+                    return Err("constraint keys must belong to the constrained node");
+                }
+                let keys: Vec<_> = keys.into_iter().map(|(_, key)| key).collect();
+                if keys.iter().enumerate().any(|(idx, key)| keys[..idx].contains(key)) {
+                    return Err("constraint keys must be distinct");
+                }
+                if unique {
+                    Ok(Constraint::Unique { label, keys })
+                } else {
+                    Ok(Constraint::NodeKey { label, keys })
+                }
+            }
+            / kw_on() __* element:constraint_element() __+ kw_assert() __+
+              kw_exists() _* "(" _* n:ident() _* "." _* key:ident() _* ")" {?
+                let (edge, name, label) = element;
+                if n != name {
+                    return Err("constraint keys must belong to the constrained element");
+                }
+                Ok(Constraint::Exists { edge, label, key })
+            }
+            / kw_on() __* element:constraint_element() __+ kw_assert() __+
+              n:ident() _* "." _* key:ident() __+ kw_is() __* "::" __* kind:property_type() {?
+                let (edge, name, label) = element;
+                if n != name {
+                    return Err("constraint keys must belong to the constrained element");
+                }
+                Ok(Constraint::Type { edge, label, key, kind })
+            }
+            / kw_on() __* element:constraint_element() __+ kw_assert() __+
+              kw_keys() _* "(" _* n:ident() _* ")" __+ kw_in() __* "[" __*
+              keys:( ("'" k:ident() "'" { k }) ** (__* "," __*) ) __* "]" {?
+                let (edge, name, label) = element;
+                if n != name {
+                    return Err("constraint keys must belong to the constrained element");
+                }
+                Ok(Constraint::Keys { edge, label, keys })
+            }
+
+        // e.g. 'CREATE CONSTRAINT ON (u:USER) ASSERT u.email IS UNIQUE'
+        rule constraint_statement() -> Statement<'input>
+            = __* kw_create() __+ kw_constraint() __+ c:constraint() __* {
+                Statement::CreateConstraint(c)
+            }
+            / __* kw_drop() __+ kw_constraint() __+ c:constraint() __* {
+                Statement::DropConstraint(c)
+            }
+
+        // e.g. 'EXPLAIN MATCH (a) RETURN a.name', 'PROFILE MATCH (a) RETURN a.name'
+        pub rule statement() -> Statement<'input>
+            = __* kw_explain() __+ s:union_statement() { Statement::Explain(Box::new(s)) }
+            / __* kw_profile() __+ s:union_statement() { Statement::Profile(Box::new(s)) }
+            / index_statement()
+            / constraint_statement()
+            / union_statement()
+
+        // e.g. 'hello', 'a; b' (quoted text and comments may contain ';')
+        rule statement_text() -> &'input str
+            = $( ( text() {} / comment() / [^ ';' | '\'' | '"'] )+ )
+
+        // e.g. 'CREATE (a:PERSON); MATCH (a:PERSON) RETURN a.name;' (empty
+        // statements are skipped)
+        pub rule script() -> Vec<(usize, &'input str)>
+            = statements:(
+                __* s:( start:position!() s:statement_text() { (start, s) } )? { s }
+              ) ** ";" {
+                statements.into_iter().flatten().collect()
+            }
+    }
+}
+
+/// Replace the escape sequences in quoted `text`. A backslash
+/// before any other character is kept as it is.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('\\' | '\'' | '"')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
     }
+    Cow::Owned(unescaped)
+}
+
+pub fn parse(input: &str) -> Result<ast::Statement<'_>, ParseError<LineCol>> {
+    cypher::statement(input)
 }
 
-pub fn parse(input: &str) -> Result<ast::Query<'_>, ParseError<LineCol>> {
-    cypher::query(input)
+/// Split a script of `;` separated statements, returning
+/// each statement with the position where it starts.
+pub fn parse_script(input: &str) -> Result<Vec<(LineCol, &str)>, ParseError<LineCol>> {
+    Ok(cypher::script(input)?
+        .into_iter()
+        .map(|(start, statement)| (input.position_repr(start), statement))
+        .collect())
 }
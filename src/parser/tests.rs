@@ -1,5 +1,6 @@
 use super::*;
 use ast::*;
+use crate::store::PropertyType;
 
 #[test]
 fn match_clauses_work() {
@@ -7,16 +8,21 @@ fn match_clauses_work() {
         cypher::query("MATCH (a) - (b) RETURN a.name, LABEL(a) "),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::either(Annotation::empty(), vec![]),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::property("a", "name"), Expression::label_of("a")],
         })
     );
@@ -24,16 +30,21 @@ fn match_clauses_work() {
         cypher::query("MATCH (a:LABEL) <- ( )\nRETURN ID(a)"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::new("a", "LABEL")),
                 edges: vec![(
                     Edge::left(Annotation::empty(), vec![]),
                     Node::with_annotation(Annotation::empty())
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("a")],
         })
     );
@@ -41,16 +52,21 @@ fn match_clauses_work() {
         cypher::query(" MATCH () -> (:LABEL_ONLY) RETURN a.test"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::empty()),
                 edges: vec![(
                     Edge::right(Annotation::empty(), vec![]),
                     Node::with_annotation(Annotation::with_label("LABEL_ONLY"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::property("a", "test")],
         })
     );
@@ -59,16 +75,21 @@ fn match_clauses_work() {
         cypher::query("MATCH \n (a)  -[edge]->  (b) RETURN ID(edge)"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::right(Annotation::with_name("edge"), vec![]),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("edge")],
         })
     );
@@ -76,16 +97,21 @@ fn match_clauses_work() {
         cypher::query("MATCH (a) <-[e:KNOWS]- (b) RETURN e.since, b.name"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::left(Annotation::new("e", "KNOWS"), vec![]),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![
                 Expression::property("e", "since"),
                 Expression::property("b", "name"),
@@ -96,16 +122,21 @@ fn match_clauses_work() {
         cypher::query("MATCH (a) -[]- (b) RETURN ID(a), $test"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::either(Annotation::empty(), vec![]),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("a"), Expression::Parameter("test")],
         })
     );
@@ -114,6 +145,8 @@ fn match_clauses_work() {
         cypher::query("MATCH (a) -> (b) - (c) RETURN a.a , b.b, c.c"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![
                     (
@@ -126,10 +159,13 @@ fn match_clauses_work() {
                     )
                 ],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![
                 Expression::property("a", "a"),
                 Expression::property("b", "b"),
@@ -142,6 +178,8 @@ fn match_clauses_work() {
         Ok(Query {
             match_clauses: vec![
                 MatchClause {
+                    mode: MatchMode::DifferentEdges,
+                    path: None,
                     start: Node::with_annotation(Annotation::with_name("a")),
                     edges: vec![(
                         Edge::right(Annotation::empty(), vec![]),
@@ -149,6 +187,8 @@ fn match_clauses_work() {
                     )],
                 },
                 MatchClause {
+                    mode: MatchMode::DifferentEdges,
+                    path: None,
                     start: Node::with_annotation(Annotation::with_name("b")),
                     edges: vec![(
                         Edge::right(Annotation::empty(), vec![]),
@@ -156,10 +196,13 @@ fn match_clauses_work() {
                     )],
                 }
             ],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![
                 Expression::property("a", "a"),
                 Expression::property("b", "b"),
@@ -175,6 +218,8 @@ fn property_maps_work() {
         cypher::query("MATCH (a { answer: 42, book: $book}) - (b) RETURN ID(a)"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::new(
                     Annotation::with_name("a"),
                     vec![
@@ -187,10 +232,13 @@ fn property_maps_work() {
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("a")],
         })
     );
@@ -199,19 +247,24 @@ fn property_maps_work() {
         cypher::query("MATCH (a) -[:KNOWS{since: 'February' } ]- (b)"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a"),),
                 edges: vec![(
                     Edge::either(
                         Annotation::with_label("KNOWS"),
-                        vec![("since", Expression::Literal(Literal::Text("February"))),]
+                        vec![("since", Expression::Literal(Literal::Text("February".into()))),]
                     ),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![],
         })
     );
@@ -223,9 +276,13 @@ fn where_clauses_work() {
         cypher::query("MATCH (a) WHERE ID(a) = 42 RETURN a.name"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![Condition::IdEq(
                 "a",
                 Expression::Literal(Literal::Integer(42))
@@ -233,6 +290,7 @@ fn where_clauses_work() {
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::property("a", "name")],
         })
     );
@@ -241,19 +299,24 @@ fn where_clauses_work() {
         cypher::query("MATCH (a) WHERE a.age >= $min_age RETURN a.age"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![Condition::Ge(
                 Expression::Property {
                     name: "a",
-                    key: "age"
+                    key: Symbol::Static("age")
                 },
                 Expression::Parameter("min_age"),
             )],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::property("a", "age")],
         })
     );
@@ -268,40 +331,72 @@ fn where_clauses_work() {
         ),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::right(Annotation::new("e", "KNOWS"), vec![]),
                     Node::with_annotation(Annotation::with_name("b"))
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![Condition::or(
                 Condition::and(
                     Condition::Gt(
                         Expression::Property {
                             name: "a",
-                            key: "age",
+                            key: Symbol::Static("age"),
                         },
                         Expression::Literal(Literal::Integer(42))
                     ),
                     Condition::Eq(
                         Expression::Property {
                             name: "b",
-                            key: "name",
+                            key: Symbol::Static("name"),
                         },
-                        Expression::Literal(Literal::Text("Peter Parker"))
+                        Expression::Literal(Literal::Text("Peter Parker".into()))
                     )
                 ),
                 Condition::not(Condition::Expression(Expression::Property {
                     name: "e",
-                    key: "fake",
+                    key: Symbol::Static("fake"),
                 })),
             )],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::property("e", "since")],
         })
     );
+
+    let age = || Expression::property("a", "age");
+    assert_eq!(
+        cypher::query("MATCH (a) WHERE a.age BETWEEN 18 AND $max AND a.fake RETURN a.age"),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![],
+            }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
+            where_clauses: vec![Condition::and(
+                Condition::and(
+                    Condition::Ge(age(), Expression::Literal(Literal::Integer(18))),
+                    Condition::Le(age(), Expression::Parameter("max")),
+                ),
+                Condition::Expression(Expression::property("a", "fake")),
+            )],
+            create_clauses: vec![],
+            set_clauses: vec![],
+            delete_clauses: vec![],
+            foreach_clauses: vec![],
+            return_clause: vec![age()],
+        })
+    );
 }
 
 #[test]
@@ -310,17 +405,20 @@ fn create_clauses_work() {
         cypher::query("CREATE (node:PERSON { name: 'Peter Parker', answer: 42 }) RETURN ID(node)"),
         Ok(Query {
             match_clauses: vec![],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![CreateClause::CreateNode {
                 name: Some("node"),
-                label: "PERSON",
+                label: Symbol::Static("PERSON"),
                 properties: vec![
-                    ("name", Expression::Literal(Literal::Text("Peter Parker"))),
+                    ("name", Expression::Literal(Literal::Text("Peter Parker".into()))),
                     ("answer", Expression::Literal(Literal::Integer(42))),
                 ],
             }],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("node")],
         })
     );
@@ -330,24 +428,31 @@ fn create_clauses_work() {
         Ok(Query {
             match_clauses: vec![
                 MatchClause {
+                    mode: MatchMode::DifferentEdges,
+                    path: None,
                     start: Node::with_annotation(Annotation::with_name("a")),
                     edges: vec![],
                 },
                 MatchClause {
+                    mode: MatchMode::DifferentEdges,
+                    path: None,
                     start: Node::with_annotation(Annotation::with_name("b")),
                     edges: vec![],
                 }
             ],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![Condition::IdEq("a", Expression::Parameter("id"))],
             create_clauses: vec![CreateClause::CreateEdge {
                 name: None,
-                label: "KNOWS",
+                label: Symbol::Static("KNOWS"),
                 origin: "a",
                 target: "b",
                 properties: vec![],
             }],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![],
         })
     );
@@ -359,17 +464,22 @@ fn set_clauses_work() {
         cypher::query("MATCH (a) SET a.answer = 42"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![SetClause {
                 name: "a",
-                key: "answer",
+                key: Symbol::Static("answer"),
                 value: Expression::Literal(Literal::Integer(42)),
             }],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![],
         })
     );
@@ -378,24 +488,29 @@ fn set_clauses_work() {
         cypher::query("MATCH (a:PERSON) SET a.first = 'Peter' SET a.last = $last_name"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::new("a", "PERSON")),
                 edges: vec![],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![
                 SetClause {
                     name: "a",
-                    key: "first",
-                    value: Expression::Literal(Literal::Text("Peter")),
+                    key: Symbol::Static("first"),
+                    value: Expression::Literal(Literal::Text("Peter".into())),
                 },
                 SetClause {
                     name: "a",
-                    key: "last",
+                    key: Symbol::Static("last"),
                     value: Expression::Parameter("last_name"),
                 }
             ],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![],
         })
     );
@@ -407,13 +522,18 @@ fn delete_clauses_work() {
         cypher::query("MATCH (a:DEATH_STAR) DELETE a RETURN ID(a)"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::new("a", "DEATH_STAR")),
                 edges: vec![],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec!["a"],
+            foreach_clauses: vec![],
             return_clause: vec![Expression::id_of("a")],
         })
     );
@@ -422,17 +542,732 @@ fn delete_clauses_work() {
         cypher::query("MATCH (a) -[e:KNOWS]-> (b) DELETE b DELETE e"),
         Ok(Query {
             match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
                 start: Node::with_annotation(Annotation::with_name("a")),
                 edges: vec![(
                     Edge::right(Annotation::new("e", "KNOWS"), vec![]),
                     Node::with_annotation(Annotation::with_name("b")),
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec!["b", "e"],
+            foreach_clauses: vec![],
             return_clause: vec![],
         })
     );
 }
+
+#[test]
+fn named_paths_work() {
+    assert_eq!(
+        cypher::query("MATCH p = (a) -[e:KNOWS]-> (b) RETURN p, length(p), nodes(p), relationships(p)"),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: Some("p"),
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![(
+                    Edge::right(Annotation::new("e", "KNOWS"), vec![]),
+                    Node::with_annotation(Annotation::with_name("b")),
+                )],
+            }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
+            where_clauses: vec![],
+            create_clauses: vec![],
+            set_clauses: vec![],
+            delete_clauses: vec![],
+            foreach_clauses: vec![],
+            return_clause: vec![
+                Expression::Variable("p"),
+                Expression::length_of("p"),
+                Expression::nodes_of("p"),
+                Expression::relationships_of("p"),
+            ],
+        })
+    );
+}
+
+#[test]
+fn union_statements_work() {
+    let query = |label| Query {
+        match_clauses: vec![MatchClause {
+            mode: MatchMode::DifferentEdges,
+            path: None,
+            start: Node::with_annotation(Annotation::new("a", label)),
+            edges: vec![],
+        }],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![Expression::id_of("a")],
+    };
+
+    assert_eq!(
+        cypher::statement("MATCH (a:A) RETURN ID(a)"),
+        Ok(Statement::Query(query("A")))
+    );
+    assert_eq!(
+        cypher::statement("MATCH (a:A) RETURN ID(a) UNION MATCH (a:B) RETURN ID(a)"),
+        Ok(Statement::Union {
+            queries: vec![query("A"), query("B")],
+            all: false,
+        })
+    );
+    assert_eq!(
+        cypher::statement(
+            "MATCH (a:A) RETURN ID(a)
+             UNION ALL MATCH (a:B) RETURN ID(a)
+             UNION ALL MATCH (a:C) RETURN ID(a)"
+        ),
+        Ok(Statement::Union {
+            queries: vec![query("A"), query("B"), query("C")],
+            all: true,
+        })
+    );
+    assert!(cypher::statement(
+        "MATCH (a:A) RETURN ID(a) UNION MATCH (a:B) RETURN ID(a) UNION ALL MATCH (a:C) RETURN ID(a)"
+    )
+    .is_err());
+}
+
+#[test]
+fn call_clauses_work() {
+    assert_eq!(
+        cypher::query("CALL db.labels()"),
+        Ok(Query {
+            match_clauses: vec![],
+            call_clauses: vec![CallClause {
+                procedure: "db.labels",
+                arguments: vec![],
+                yields: None,
+            }],
+            subquery_clauses: vec![],
+            where_clauses: vec![],
+            create_clauses: vec![],
+            set_clauses: vec![],
+            delete_clauses: vec![],
+            foreach_clauses: vec![],
+            return_clause: vec![],
+        })
+    );
+
+    assert_eq!(
+        cypher::query(
+            "MATCH (a) CALL my.proc.name(a.name, 42) YIELD value, score WHERE score > 2 RETURN value"
+        ),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![],
+            }],
+            call_clauses: vec![CallClause {
+                procedure: "my.proc.name",
+                arguments: vec![
+                    Expression::property("a", "name"),
+                    Expression::Literal(Literal::Integer(42)),
+                ],
+                yields: Some(vec!["value", "score"]),
+            }],
+            subquery_clauses: vec![],
+            where_clauses: vec![Condition::Gt(
+                Expression::Variable("score"),
+                Expression::Literal(Literal::Integer(2)),
+            )],
+            create_clauses: vec![],
+            set_clauses: vec![],
+            delete_clauses: vec![],
+            foreach_clauses: vec![],
+            return_clause: vec![Expression::Variable("value")],
+        })
+    );
+}
+
+#[test]
+fn explain_and_profile_work() {
+    let query = Query {
+        match_clauses: vec![MatchClause {
+            mode: MatchMode::DifferentEdges,
+            path: None,
+            start: Node::with_annotation(Annotation::new("a", "A")),
+            edges: vec![],
+        }],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![Expression::id_of("a")],
+    };
+
+    assert_eq!(
+        cypher::statement("EXPLAIN MATCH (a:A) RETURN ID(a)"),
+        Ok(Statement::Explain(Box::new(Statement::Query(query.clone()))))
+    );
+    assert_eq!(
+        cypher::statement("profile MATCH (a:A) RETURN ID(a) UNION MATCH (a:A) RETURN ID(a)"),
+        Ok(Statement::Profile(Box::new(Statement::Union {
+            queries: vec![query.clone(), query],
+            all: false,
+        })))
+    );
+    assert!(cypher::statement("EXPLAIN PROFILE MATCH (a:A) RETURN ID(a)").is_err());
+}
+
+#[test]
+fn index_statements_work() {
+    assert_eq!(
+        cypher::statement("CREATE INDEX ON :PERSON(name)"),
+        Ok(Statement::CreateIndex {
+            label: "PERSON",
+            key: "name"
+        })
+    );
+    assert_eq!(
+        cypher::statement(" drop index on : PERSON ( name ) "),
+        Ok(Statement::DropIndex {
+            label: "PERSON",
+            key: "name"
+        })
+    );
+    assert!(cypher::statement("CREATE INDEX ON PERSON(name)").is_err());
+    assert!(cypher::statement("CREATE INDEX ON :PERSON").is_err());
+    assert!(cypher::statement("EXPLAIN CREATE INDEX ON :PERSON(name)").is_err());
+}
+
+#[test]
+fn constraint_statements_work() {
+    assert_eq!(
+        cypher::statement("CREATE CONSTRAINT ON (u:USER) ASSERT u.email IS UNIQUE"),
+        Ok(Statement::CreateConstraint(Constraint::Unique {
+            label: "USER",
+            keys: vec!["email"],
+        }))
+    );
+    assert_eq!(
+        cypher::statement("drop constraint on (u : USER) assert (u.first , u.last) is node key"),
+        Ok(Statement::DropConstraint(Constraint::NodeKey {
+            label: "USER",
+            keys: vec!["first", "last"],
+        }))
+    );
+    assert!(cypher::statement("CREATE CONSTRAINT ON (u:USER) ASSERT v.email IS UNIQUE").is_err());
+    assert!(cypher::statement("CREATE CONSTRAINT ON (u:USER) ASSERT (u.a,u.a) IS NODE KEY").is_err());
+    assert!(cypher::statement("CREATE CONSTRAINT ON (u) ASSERT u.email IS UNIQUE").is_err());
+    assert!(cypher::statement("CREATE CONSTRAINT ON (u:USER) ASSERT u.email IS KEY").is_err());
+}
+
+#[test]
+fn schema_constraints_work() {
+    assert_eq!(
+        cypher::statement("CREATE CONSTRAINT ON (p:PERSON) ASSERT exists(p.name)"),
+        Ok(Statement::CreateConstraint(Constraint::Exists {
+            edge: false,
+            label: "PERSON",
+            key: "name",
+        }))
+    );
+    assert_eq!(
+        cypher::statement("CREATE CONSTRAINT ON () -[e:KNOWS]-> () ASSERT e.since IS :: DATE"),
+        Ok(Statement::CreateConstraint(Constraint::Type {
+            edge: true,
+            label: "KNOWS",
+            key: "since",
+            kind: PropertyType::Date,
+        }))
+    );
+    assert_eq!(
+        cypher::statement("CREATE CONSTRAINT ON (p:PERSON) ASSERT p.born IS :: local datetime"),
+        Ok(Statement::CreateConstraint(Constraint::Type {
+            edge: false,
+            label: "PERSON",
+            key: "born",
+            kind: PropertyType::LocalDateTime,
+        }))
+    );
+    assert_eq!(
+        cypher::statement("DROP CONSTRAINT ON (p:PERSON) ASSERT keys(p) IN ['name', 'age']"),
+        Ok(Statement::DropConstraint(Constraint::Keys {
+            edge: false,
+            label: "PERSON",
+            keys: vec!["name", "age"],
+        }))
+    );
+    assert_eq!(
+        cypher::statement("CREATE CONSTRAINT ON (:PERSON) -[:WORKS_AT]-> (:COMPANY)"),
+        Ok(Statement::CreateConstraint(Constraint::Endpoints {
+            label: "WORKS_AT",
+            origin: "PERSON",
+            target: "COMPANY",
+        }))
+    );
+    assert!(cypher::statement("CREATE CONSTRAINT ON (p:PERSON) ASSERT exists(q.name)").is_err());
+    let unknown_type = "CREATE CONSTRAINT ON (p:PERSON) ASSERT p.age IS :: NUMBER";
+    assert!(cypher::statement(unknown_type).is_err());
+    let unique_edges = "CREATE CONSTRAINT ON () -[e:KNOWS]- () ASSERT e.id IS UNIQUE";
+    assert!(cypher::statement(unique_edges).is_err());
+}
+
+#[test]
+fn scripts_work() {
+    assert_eq!(cypher::script(""), Ok(vec![]));
+    assert_eq!(
+        cypher::script("CREATE (a:A); MATCH (a) RETURN a.name;\n"),
+        Ok(vec![(0, "CREATE (a:A)"), (14, "MATCH (a) RETURN a.name")])
+    );
+    assert_eq!(
+        cypher::script("CREATE (a:A { name: 'x;y' })\n;\n  RETURN 1"),
+        Ok(vec![(0, "CREATE (a:A { name: 'x;y' })\n"), (33, "RETURN 1")])
+    );
+    assert_eq!(
+        cypher::script(r"CREATE (a:A { name: 'it\'s; here' }); RETURN 1"),
+        Ok(vec![(0, r"CREATE (a:A { name: 'it\'s; here' })"), (38, "RETURN 1")])
+    );
+    assert_eq!(
+        cypher::script(r#"CREATE (a:A { name: "x;'y" }); RETURN 1"#),
+        Ok(vec![(0, r#"CREATE (a:A { name: "x;'y" })"#), (31, "RETURN 1")])
+    );
+    assert_eq!(
+        cypher::script("// setup; start\nCREATE (a:A) /* ; */;\nRETURN 1 // done;"),
+        Ok(vec![(16, "CREATE (a:A) /* ; */"), (38, "RETURN 1 // done;")])
+    );
+    assert_eq!(
+        cypher::script(";CREATE (a:A);; ;\nRETURN 1;;"),
+        Ok(vec![(1, "CREATE (a:A)"), (18, "RETURN 1")])
+    );
+    assert!(cypher::script("CREATE (a:A { name: 'x })").is_err());
+    assert!(cypher::script("CREATE (a:A { name: 'x\" }); RETURN 1").is_err());
+}
+
+#[test]
+fn text_escapes_work() {
+    let text = |query| match cypher::statement(query) {
+        Ok(Statement::Query(Query { return_clause, .. })) => match return_clause.as_slice() {
+            [Expression::Literal(Literal::Text(text))] => text.to_string(),
+            _ => panic!("Unexpected return clause"),
+        },
+        _ => panic!("Unexpected statement"),
+    };
+    assert_eq!(text(r"RETURN 'it\'s'"), "it's");
+    assert_eq!(text(r#"RETURN "say \"hi\"""#), "say \"hi\"");
+    assert_eq!(text(r#"RETURN "it's""#), "it's");
+    assert_eq!(text(r"RETURN 'a\\b\tc\nd'"), "a\\b\tc\nd");
+    assert_eq!(text(r"RETURN 'C:\path'"), "C:\\path");
+    assert!(cypher::statement(r"RETURN 'unterminated\'").is_err());
+    assert!(cypher::statement(r"MATCH (a) RETURN a['it\'s']").is_err());
+}
+
+#[test]
+fn return_variables_work() {
+    assert_eq!(
+        cypher::query("MATCH (a) -[e]-> (b) RETURN a, *, e"),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![(
+                    Edge::right(Annotation::with_name("e"), vec![]),
+                    Node::with_annotation(Annotation::with_name("b")),
+                )],
+            }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
+            where_clauses: vec![],
+            create_clauses: vec![],
+            set_clauses: vec![],
+            delete_clauses: vec![],
+            foreach_clauses: vec![],
+            return_clause: vec![
+                Expression::Variable("a"),
+                Expression::All,
+                Expression::Variable("e"),
+            ],
+        })
+    );
+}
+
+#[test]
+fn foreach_and_subqueries_work() {
+    let empty = Query {
+        match_clauses: vec![],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![],
+    };
+
+    assert_eq!(
+        cypher::query("MATCH p = (a) -> (b) FOREACH (n IN nodes(p) | SET n.seen = TRUE)"),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: Some("p"),
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![(
+                    Edge::right(Annotation::empty(), vec![]),
+                    Node::with_annotation(Annotation::with_name("b")),
+                )],
+            }],
+            foreach_clauses: vec![ForeachClause {
+                variable: "n",
+                list: Expression::nodes_of("p"),
+                body: Query {
+                    set_clauses: vec![SetClause {
+                        name: "n",
+                        key: Symbol::Static("seen"),
+                        value: Expression::Literal(Literal::Boolean(true)),
+                    }],
+                    ..empty.clone()
+                },
+            }],
+            ..empty.clone()
+        })
+    );
+
+    assert_eq!(
+        cypher::query(
+            "MATCH (a)
+             CALL {
+                 WITH a
+                 MATCH (a) -> (b)
+                 RETURN b
+             }
+             RETURN a, b"
+        ),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![],
+            }],
+            subquery_clauses: vec![SubqueryClause {
+                imports: vec!["a"],
+                query: Query {
+                    match_clauses: vec![MatchClause {
+                        mode: MatchMode::DifferentEdges,
+                        path: None,
+                        start: Node::with_annotation(Annotation::with_name("a")),
+                        edges: vec![(
+                            Edge::right(Annotation::empty(), vec![]),
+                            Node::with_annotation(Annotation::with_name("b")),
+                        )],
+                    }],
+                    return_clause: vec![Expression::Variable("b")],
+                    ..empty.clone()
+                },
+            }],
+            return_clause: vec![Expression::Variable("a"), Expression::Variable("b")],
+            ..empty.clone()
+        })
+    );
+
+    assert_eq!(
+        cypher::query("CALL { CREATE (a:A) }"),
+        Ok(Query {
+            subquery_clauses: vec![SubqueryClause {
+                imports: vec![],
+                query: Query {
+                    create_clauses: vec![CreateClause::CreateNode {
+                        name: Some("a"),
+                        label: Symbol::Static("A"),
+                        properties: vec![],
+                    }],
+                    ..empty.clone()
+                },
+            }],
+            ..empty
+        })
+    );
+
+    assert!(cypher::query("FOREACH (n IN $list | MATCH (a) SET a.n = n)").is_err());
+}
+
+#[test]
+fn comprehensions_work() {
+    let returns = |expr| Query {
+        match_clauses: vec![],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![expr],
+    };
+
+    assert_eq!(
+        cypher::query("RETURN [x IN $list WHERE x > 2 | x.name]"),
+        Ok(returns(Expression::ListComprehension(Box::new(ListComprehension {
+            variable: "x",
+            list: Expression::Parameter("list"),
+            filter: Some(Condition::Gt(
+                Expression::Variable("x"),
+                Expression::Literal(Literal::Integer(2)),
+            )),
+            projection: Some(Expression::property("x", "name")),
+        }))))
+    );
+
+    assert_eq!(
+        cypher::query("RETURN [n IN nodes(p)]"),
+        Ok(returns(Expression::ListComprehension(Box::new(ListComprehension {
+            variable: "n",
+            list: Expression::nodes_of("p"),
+            filter: None,
+            projection: None,
+        }))))
+    );
+
+    assert_eq!(
+        cypher::query("RETURN [(a) -[:KNOWS]-> (b) WHERE b.age > 42 | b.name]"),
+        Ok(returns(Expression::PatternComprehension(Box::new(PatternComprehension {
+            pattern: MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![(
+                    Edge::right(Annotation::with_label("KNOWS"), vec![]),
+                    Node::with_annotation(Annotation::with_name("b")),
+                )],
+            },
+            filter: Some(Condition::Gt(
+                Expression::property("b", "age"),
+                Expression::Literal(Literal::Integer(42)),
+            )),
+            projection: Expression::property("b", "name"),
+        }))))
+    );
+
+    assert_eq!(
+        cypher::query("RETURN [p = (a) -> () | length(p)]"),
+        Ok(returns(Expression::PatternComprehension(Box::new(PatternComprehension {
+            pattern: MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: Some("p"),
+                start: Node::with_annotation(Annotation::with_name("a")),
+                edges: vec![(
+                    Edge::right(Annotation::empty(), vec![]),
+                    Node::with_annotation(Annotation::empty()),
+                )],
+            },
+            filter: None,
+            projection: Expression::length_of("p"),
+        }))))
+    );
+
+    // pattern comprehensions require a projection
+    assert!(cypher::query("RETURN [(a) -> (b)]").is_err());
+}
+
+#[test]
+fn dynamic_labels_and_keys_work() {
+    let empty = Query {
+        match_clauses: vec![],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![],
+    };
+
+    assert_eq!(
+        cypher::query("MATCH (n:$($label)) -[:$( $type )]-> (m) SET n[$key] = $value RETURN m['name']"),
+        Ok(Query {
+            match_clauses: vec![MatchClause {
+                mode: MatchMode::DifferentEdges,
+                path: None,
+                start: Node::with_annotation(Annotation {
+                    name: Some("n"),
+                    label: Some(Symbol::Parameter("label")),
+                }),
+                edges: vec![(
+                    Edge::right(
+                        Annotation {
+                            name: None,
+                            label: Some(Symbol::Parameter("type")),
+                        },
+                        vec![]
+                    ),
+                    Node::with_annotation(Annotation::with_name("m")),
+                )],
+            }],
+            set_clauses: vec![SetClause {
+                name: "n",
+                key: Symbol::Parameter("key"),
+                value: Expression::Parameter("value"),
+            }],
+            return_clause: vec![Expression::property("m", "name")],
+            ..empty.clone()
+        })
+    );
+
+    assert_eq!(
+        cypher::query("CREATE (n:$($label) { name: $name }) RETURN n[$key]"),
+        Ok(Query {
+            create_clauses: vec![CreateClause::CreateNode {
+                name: Some("n"),
+                label: Symbol::Parameter("label"),
+                properties: vec![("name", Expression::Parameter("name"))],
+            }],
+            return_clause: vec![Expression::Property {
+                name: "n",
+                key: Symbol::Parameter("key"),
+            }],
+            ..empty.clone()
+        })
+    );
+
+    // only parameters can be used as dynamic labels
+    assert!(cypher::query("MATCH (n:$(label)) RETURN n").is_err());
+}
+
+#[test]
+fn match_modes_work() {
+    let pattern = |mode| MatchClause {
+        mode,
+        path: None,
+        start: Node::with_annotation(Annotation::with_name("a")),
+        edges: vec![(
+            Edge::either(Annotation::empty(), vec![]),
+            Node::with_annotation(Annotation::with_name("b")),
+        )],
+    };
+    let query = |mode| Query {
+        match_clauses: vec![pattern(mode)],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause: vec![Expression::Variable("b")],
+    };
+
+    assert_eq!(
+        cypher::query("MATCH (a) - (b) RETURN b"),
+        Ok(query(MatchMode::DifferentEdges))
+    );
+    assert_eq!(
+        cypher::query("MATCH DIFFERENT RELATIONSHIPS (a) - (b) RETURN b"),
+        Ok(query(MatchMode::DifferentEdges))
+    );
+    assert_eq!(
+        cypher::query("match repeatable elements (a) - (b) RETURN b"),
+        Ok(query(MatchMode::RepeatableElements))
+    );
+    assert!(cypher::query("MATCH REPEATABLE (a) - (b) RETURN b").is_err());
+}
+
+#[test]
+fn functions_and_arithmetic_work() {
+    let query = |return_clause| Query {
+        match_clauses: vec![],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause,
+    };
+
+    assert_eq!(
+        cypher::query("RETURN date('2024-01-01'), datetime( ), a.age + 1 - $b"),
+        Ok(query(vec![
+            Expression::Function {
+                name: "date",
+                arguments: vec![Expression::Literal(Literal::Text("2024-01-01".into()))],
+            },
+            Expression::Function {
+                name: "datetime",
+                arguments: vec![],
+            },
+            Expression::subtract(
+                Expression::add(
+                    Expression::property("a", "age"),
+                    Expression::Literal(Literal::Integer(1)),
+                ),
+                Expression::Parameter("b"),
+            ),
+        ]))
+    );
+
+    assert_eq!(
+        cypher::query("RETURN d.year, n.born.month, date($d).day"),
+        Ok(query(vec![
+            Expression::property("d", "year"),
+            Expression::component(Expression::property("n", "born"), "month"),
+            Expression::component(
+                Expression::Function {
+                    name: "date",
+                    arguments: vec![Expression::Parameter("d")],
+                },
+                "day",
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn maps_and_dotted_functions_work() {
+    let query = |return_clause| Query {
+        match_clauses: vec![],
+        call_clauses: vec![],
+        subquery_clauses: vec![],
+        where_clauses: vec![],
+        create_clauses: vec![],
+        set_clauses: vec![],
+        delete_clauses: vec![],
+        foreach_clauses: vec![],
+        return_clause,
+    };
+
+    assert_eq!(
+        cypher::query("RETURN point({x: 1.5, y: $y}), point.withinBBox(a.at, $ll, $ur).x"),
+        Ok(query(vec![
+            Expression::Function {
+                name: "point",
+                arguments: vec![Expression::Map(vec![
+                    ("x", Expression::Literal(Literal::Real(1.5))),
+                    ("y", Expression::Parameter("y")),
+                ])],
+            },
+            Expression::component(
+                Expression::Function {
+                    name: "point.withinBBox",
+                    arguments: vec![
+                        Expression::property("a", "at"),
+                        Expression::Parameter("ll"),
+                        Expression::Parameter("ur"),
+                    ],
+                },
+                "x",
+            ),
+        ]))
+    );
+}
@@ -1,25 +1,44 @@
-use super::plan::{Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use super::plan::{Collect, Column, Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use crate::function::Function;
 use crate::parser::ast;
+use crate::procedure::Procedures;
 use crate::store::PropRef;
 use crate::Error;
 use std::collections::HashMap;
 
 pub(crate) struct BuildEnv<'src> {
     names: HashMap<&'src str, NamedEntity>,
+    paths: Vec<NamedPath>,
     next_name: usize,
+    /// Comprehensions which must be evaluated
+    /// before the expressions built last.
+    collects: Vec<Collect<'src>>,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum NamedEntity {
     Node(usize),
     Edge(usize),
+    Path(usize),
+    Column { row: usize, column: usize },
+    Argument(usize),
+}
+
+/// The nodes and edges which make up a named path, in
+/// the order in which they appear in the pattern.
+#[derive(Debug, Clone)]
+struct NamedPath {
+    nodes: Vec<usize>,
+    edges: Vec<usize>,
 }
 
 impl<'src> BuildEnv<'src> {
     fn new() -> Self {
         Self {
             names: HashMap::new(),
+            paths: Vec::new(),
             next_name: 0,
+            collects: Vec::new(),
         }
     }
 
@@ -31,24 +50,30 @@ impl<'src> BuildEnv<'src> {
     fn get_node(&self, name: &str) -> Result<Option<usize>, Error> {
         match self.names.get(&name) {
             Some(NamedEntity::Node(name)) => Ok(Some(*name)),
-            Some(NamedEntity::Edge(_)) => Err(Error::IdentifierIsNotNode(name.to_string())),
+            Some(_) => Err(Error::IdentifierIsNotNode(name.to_string())),
             None => Ok(None),
         }
     }
 
     fn get_edge(&self, name: &str) -> Result<Option<usize>, Error> {
         match self.names.get(&name) {
-            Some(NamedEntity::Node(_)) => Err(Error::IdentifierIsNotEdge(name.to_string())),
             Some(NamedEntity::Edge(name)) => Ok(Some(*name)),
+            Some(_) => Err(Error::IdentifierIsNotEdge(name.to_string())),
             None => Ok(None),
         }
     }
 
+    fn get_path(&self, name: &str) -> Result<&NamedPath, Error> {
+        match self.names.get(&name) {
+            Some(NamedEntity::Path(path)) => Ok(&self.paths[*path]),
+            Some(_) => Err(Error::IdentifierIsNotPath(name.to_string())),
+            None => Err(Error::UnknownIdentifier(name.to_string())),
+        }
+    }
+
     fn create_node(&mut self, name: &'src str) -> Result<usize, Error> {
         match self.names.get(&name) {
-            Some(NamedEntity::Node(_)) | Some(NamedEntity::Edge(_)) => {
-                Err(Error::IdentifierExists(name.to_string()))
-            }
+            Some(_) => Err(Error::IdentifierExists(name.to_string())),
             None => {
                 let next_name = self.next_name();
                 self.names.insert(name, NamedEntity::Node(next_name));
@@ -59,9 +84,7 @@ impl<'src> BuildEnv<'src> {
 
     fn create_edge(&mut self, name: &'src str) -> Result<usize, Error> {
         match self.names.get(&name) {
-            Some(NamedEntity::Node(_)) | Some(NamedEntity::Edge(_)) => {
-                Err(Error::IdentifierExists(name.to_string()))
-            }
+            Some(_) => Err(Error::IdentifierExists(name.to_string())),
             None => {
                 let next_name = self.next_name();
                 self.names.insert(name, NamedEntity::Edge(next_name));
@@ -70,6 +93,37 @@ impl<'src> BuildEnv<'src> {
         }
     }
 
+    fn create_argument(&mut self, name: &'src str, index: usize) -> Result<(), Error> {
+        match self.names.get(&name) {
+            Some(_) => Err(Error::IdentifierExists(name.to_string())),
+            None => {
+                self.names.insert(name, NamedEntity::Argument(index));
+                Ok(())
+            }
+        }
+    }
+
+    fn create_column(&mut self, name: &'src str, row: usize, column: usize) -> Result<(), Error> {
+        match self.names.get(&name) {
+            Some(_) => Err(Error::IdentifierExists(name.to_string())),
+            None => {
+                self.names.insert(name, NamedEntity::Column { row, column });
+                Ok(())
+            }
+        }
+    }
+
+    fn create_path(&mut self, name: &'src str, path: NamedPath) -> Result<(), Error> {
+        match self.names.get(&name) {
+            Some(_) => Err(Error::IdentifierExists(name.to_string())),
+            None => {
+                self.names.insert(name, NamedEntity::Path(self.paths.len()));
+                self.paths.push(path);
+                Ok(())
+            }
+        }
+    }
+
     fn build_load_property(
         &mut self,
         expr: &'src ast::Expression<'src>,
@@ -80,7 +134,7 @@ impl<'src> BuildEnv<'src> {
                 ast::Literal::Integer(i) => PropRef::Integer(*i),
                 ast::Literal::Real(r) => PropRef::Real(*r),
                 ast::Literal::Boolean(b) => PropRef::Boolean(*b),
-                ast::Literal::Text(t) => PropRef::Text(t),
+                ast::Literal::Text(t) => PropRef::Text(t.as_ref()),
                 ast::Literal::Null => PropRef::Null,
             }),
             ast::Expression::IdOf { name } => match self
@@ -90,6 +144,7 @@ impl<'src> BuildEnv<'src> {
             {
                 NamedEntity::Node(node) => LoadProperty::IdOfNode { node: *node },
                 NamedEntity::Edge(edge) => LoadProperty::IdOfEdge { edge: *edge },
+                _ => return Err(Error::IdentifierIsNotNode(name.to_string())),
             },
             ast::Expression::LabelOf { name } => match self
                 .names
@@ -98,6 +153,7 @@ impl<'src> BuildEnv<'src> {
             {
                 NamedEntity::Node(node) => LoadProperty::LabelOfNode { node: *node },
                 NamedEntity::Edge(edge) => LoadProperty::LabelOfEdge { edge: *edge },
+                _ => return Err(Error::IdentifierIsNotNode(name.to_string())),
             },
             ast::Expression::Property { name, key } => {
                 match self
@@ -105,14 +161,356 @@ impl<'src> BuildEnv<'src> {
                     .get(name)
                     .ok_or_else(|| Error::UnknownIdentifier(name.to_string()))?
                 {
-                    NamedEntity::Node(node) => LoadProperty::PropertyOfNode { node: *node, key },
-                    NamedEntity::Edge(edge) => LoadProperty::PropertyOfEdge { edge: *edge, key },
+                    NamedEntity::Node(node) => LoadProperty::PropertyOfNode {
+                        node: *node,
+                        key: Box::new(load_symbol(*key)),
+                    },
+                    NamedEntity::Edge(edge) => LoadProperty::PropertyOfEdge {
+                        edge: *edge,
+                        key: Box::new(load_symbol(*key)),
+                    },
+                    // other values can have components, e.g. dates
+                    entity @ (NamedEntity::Column { .. } | NamedEntity::Argument(_)) => {
+                        LoadProperty::Function {
+                            function: Function::Component,
+                            arguments: vec![self.load_entity(*entity), load_symbol(*key)],
+                        }
+                    }
+                    _ => return Err(Error::IdentifierIsNotNode(name.to_string())),
+                }
+            }
+            ast::Expression::Variable(name) => match self.names.get(name) {
+                Some(NamedEntity::Node(node)) => LoadProperty::Node { node: *node },
+                Some(NamedEntity::Edge(edge)) => LoadProperty::Edge { edge: *edge },
+                Some(NamedEntity::Column { row, column }) => LoadProperty::ColumnOfRow {
+                    row: *row,
+                    column: *column,
+                },
+                Some(NamedEntity::Argument(index)) => LoadProperty::Argument { index: *index },
+                _ => {
+                    let path = self.get_path(name)?;
+                    LoadProperty::Path {
+                        nodes: path.nodes.clone(),
+                        edges: path.edges.clone(),
+                    }
+                }
+            },
+            ast::Expression::LengthOf { name } => {
+                let path = self.get_path(name)?;
+                LoadProperty::Constant(PropRef::Integer(path.edges.len() as i64))
+            }
+            ast::Expression::NodesOf { name } => LoadProperty::NodesOfPath {
+                nodes: self.get_path(name)?.nodes.clone(),
+            },
+            ast::Expression::RelationshipsOf { name } => LoadProperty::EdgesOfPath {
+                edges: self.get_path(name)?.edges.clone(),
+            },
+            ast::Expression::All => return Err(Error::Internal),
+            ast::Expression::ListComprehension(comprehension) => {
+                self.build_list_comprehension(comprehension)?
+            }
+            ast::Expression::PatternComprehension(comprehension) => {
+                self.build_pattern_comprehension(comprehension)?
+            }
+            ast::Expression::Function { name, arguments } => {
+                let function = Function::from_name(name)
+                    .ok_or_else(|| Error::UnknownFunction(name.to_string()))?;
+                if !function.accepts(arguments.len()) {
+                    return Err(Error::InvalidArguments(name.to_string()));
+                }
+                let arguments = match (function, arguments.as_slice()) {
+                    // the entries of a map are passed as keys followed by values
+                    (Function::Point, [ast::Expression::Map(entries)]) => {
+                        let mut arguments = Vec::with_capacity(2 * entries.len());
+                        for (key, value) in entries {
+                            if !Function::POINT_KEYS.contains(key) {
+                                return Err(Error::InvalidArguments(name.to_string()));
+                            }
+                            arguments.push(LoadProperty::text(key));
+                            arguments.push(self.build_load_property(value)?);
+                        }
+                        arguments
+                    }
+                    _ => arguments
+                        .iter()
+                        .map(|arg| self.build_load_property(arg))
+                        .collect::<Result<_, _>>()?,
+                };
+                LoadProperty::Function {
+                    function,
+                    arguments,
                 }
             }
+            ast::Expression::Add(lhs, rhs) => LoadProperty::Function {
+                function: Function::Add,
+                arguments: vec![self.build_load_property(lhs)?, self.build_load_property(rhs)?],
+            },
+            ast::Expression::Subtract(lhs, rhs) => LoadProperty::Function {
+                function: Function::Subtract,
+                arguments: vec![self.build_load_property(lhs)?, self.build_load_property(rhs)?],
+            },
+            ast::Expression::Component { value, key } => LoadProperty::Function {
+                function: Function::Component,
+                arguments: vec![self.build_load_property(value)?, LoadProperty::text(key)],
+            },
+            // maps can not be stored, so they are only
+            // supported as the argument of `point`
+            ast::Expression::Map(_) => return Err(Error::TypeMismatch),
         };
         Ok(load)
     }
 
+    /// Take the comprehensions used by the expressions
+    /// which were built since the last call.
+    fn take_collects(&mut self) -> Vec<Collect<'src>> {
+        std::mem::take(&mut self.collects)
+    }
+
+    fn build_list_comprehension(
+        &mut self,
+        comprehension: &'src ast::ListComprehension<'src>,
+    ) -> Result<LoadProperty<'src>, Error> {
+        let list = self.build_load_property(&comprehension.list)?;
+
+        // the item is the first argument, followed by all outer variables
+        let (mut env, mut steps, arguments) = self.build_imports(&self.sorted_names(), 1)?;
+        env.bind_argument(comprehension.variable, 0, element_of(&list), &mut steps)?;
+        if let Some(filter) = &comprehension.filter {
+            env.build_nested_filter(filter, &mut steps)?;
+        }
+        let projection = match &comprehension.projection {
+            Some(expr) => env.build_load_property(expr)?,
+            None => env.load_entity(env.names[comprehension.variable]),
+        };
+        Ok(self.build_collect(Some(list), env, steps, projection, arguments))
+    }
+
+    fn build_pattern_comprehension(
+        &mut self,
+        comprehension: &'src ast::PatternComprehension<'src>,
+    ) -> Result<LoadProperty<'src>, Error> {
+        let (mut env, mut steps, arguments) = self.build_imports(&self.sorted_names(), 0)?;
+        steps.append(&mut env.build_match(&comprehension.pattern)?);
+        if let Some(filter) = &comprehension.filter {
+            env.build_nested_filter(filter, &mut steps)?;
+        }
+        let projection = env.build_load_property(&comprehension.projection)?;
+        Ok(self.build_collect(None, env, steps, projection, arguments))
+    }
+
+    fn build_nested_filter(
+        &mut self,
+        cond: &'src ast::Condition<'src>,
+        steps: &mut Vec<MatchStep<'src>>,
+    ) -> Result<(), Error> {
+        let filter = self.build_filter(cond)?;
+        steps.extend(self.take_collects().into_iter().map(MatchStep::Collect));
+        steps.push(MatchStep::Filter(filter));
+        Ok(())
+    }
+
+    /// Finish the nested plan of a comprehension, which returns
+    /// the `projection` of every match. The collected values
+    /// are bound to a new row of the outer plan.
+    fn build_collect(
+        &mut self,
+        list: Option<LoadProperty<'src>>,
+        mut env: BuildEnv<'src>,
+        mut steps: Vec<MatchStep<'src>>,
+        projection: LoadProperty<'src>,
+        arguments: Vec<LoadProperty<'src>>,
+    ) -> LoadProperty<'src> {
+        steps.extend(env.take_collects().into_iter().map(MatchStep::Collect));
+        let row = self.next_name();
+        self.collects.push(Collect {
+            name: row,
+            list,
+            plan: Box::new(QueryPlan {
+                steps,
+                updates: Vec::new(),
+                returns: vec![projection],
+            }),
+            arguments,
+        });
+        LoadProperty::ColumnOfRow { row, column: 0 }
+    }
+
+    /// Build a value which is stored as a property. Nodes
+    /// and edges can not be stored inside properties.
+    fn build_stored_property(
+        &mut self,
+        expr: &'src ast::Expression<'src>,
+    ) -> Result<LoadProperty<'src>, Error> {
+        match self.build_load_property(expr)? {
+            LoadProperty::Node { .. } | LoadProperty::Edge { .. } => Err(Error::TypeMismatch),
+            load => Ok(load),
+        }
+    }
+
+    /// Build the properties of a created node or edge. These are
+    /// built before the created name is bound, such that used
+    /// comprehensions do not refer to it.
+    fn build_stored_properties(
+        &mut self,
+        properties: &'src [(&'src str, ast::Expression<'src>)],
+    ) -> Result<Vec<(&'src str, LoadProperty<'src>)>, Error> {
+        properties
+            .iter()
+            .map(|(key, expr)| Ok((*key, self.build_stored_property(expr)?)))
+            .collect()
+    }
+
+    /// All named variables, ordered by name.
+    fn sorted_names(&self) -> Vec<&'src str> {
+        let mut names: Vec<&'src str> = self.names.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The value of a named variable.
+    fn load_entity(&self, entity: NamedEntity) -> LoadProperty<'src> {
+        match entity {
+            NamedEntity::Node(node) => LoadProperty::Node { node },
+            NamedEntity::Edge(edge) => LoadProperty::Edge { edge },
+            NamedEntity::Column { row, column } => LoadProperty::ColumnOfRow { row, column },
+            NamedEntity::Path(path) => LoadProperty::Path {
+                nodes: self.paths[path].nodes.clone(),
+                edges: self.paths[path].edges.clone(),
+            },
+            NamedEntity::Argument(index) => LoadProperty::Argument { index },
+        }
+    }
+
+    /// Build the values returned for `RETURN *`, which
+    /// are all named variables ordered by name.
+    fn build_return_all(&mut self) -> Result<Vec<LoadProperty<'src>>, Error> {
+        Ok(self
+            .sorted_names()
+            .into_iter()
+            .map(|name| self.load_entity(self.names[name]))
+            .collect())
+    }
+
+    /// The columns returned by `RETURN`.
+    fn columns(&self, returns: &'src [ast::Expression<'src>]) -> Vec<Column<'src>> {
+        returns
+            .iter()
+            .flat_map(|expr| match expr {
+                ast::Expression::Variable(name) => vec![Column::Name(name.to_string())],
+                ast::Expression::All => self
+                    .sorted_names()
+                    .into_iter()
+                    .map(|name| Column::Name(name.to_string()))
+                    .collect(),
+                expr => vec![Column::Expression(expr)],
+            })
+            .collect()
+    }
+
+    /// The names under which the values returned by
+    /// `RETURN` can be referred to, if any.
+    fn return_names(&self, returns: &[ast::Expression<'src>]) -> Vec<Option<&'src str>> {
+        returns
+            .iter()
+            .flat_map(|expr| match expr {
+                ast::Expression::Variable(name) => vec![Some(*name)],
+                ast::Expression::All => self.sorted_names().into_iter().map(Some).collect(),
+                _ => vec![None],
+            })
+            .collect()
+    }
+
+    /// Create the environment for a nested query, which can
+    /// refer to the given outer variables. The variables are
+    /// passed to the nested query as arguments, starting at
+    /// `first`. Returns the nested environment, the steps
+    /// binding the arguments and the argument values.
+    #[allow(clippy::type_complexity)]
+    fn build_imports(
+        &self,
+        names: &[&'src str],
+        first: usize,
+    ) -> Result<(BuildEnv<'src>, Vec<MatchStep<'src>>, Vec<LoadProperty<'src>>), Error> {
+        let mut env = BuildEnv::new();
+        let mut steps = Vec::with_capacity(names.len());
+        let mut arguments = Vec::with_capacity(names.len());
+        for (index, name) in (first..).zip(names) {
+            let entity = *self
+                .names
+                .get(name)
+                .ok_or_else(|| Error::UnknownIdentifier(name.to_string()))?;
+            arguments.push(self.load_entity(entity));
+            env.bind_argument(name, index, entity, &mut steps)?;
+        }
+        Ok((env, steps, arguments))
+    }
+
+    /// Bind argument `index` to `name`. Nodes and edges are loaded,
+    /// such that they can be used in patterns and updates.
+    fn bind_argument(
+        &mut self,
+        name: &'src str,
+        index: usize,
+        entity: NamedEntity,
+        steps: &mut Vec<MatchStep<'src>>,
+    ) -> Result<(), Error> {
+        let id = LoadProperty::Argument { index };
+        match entity {
+            NamedEntity::Node(_) => steps.push(MatchStep::LoadExactNode {
+                name: self.create_node(name)?,
+                id,
+            }),
+            NamedEntity::Edge(_) => steps.push(MatchStep::LoadExactEdge {
+                name: self.create_edge(name)?,
+                id,
+            }),
+            _ => self.create_argument(name, index)?,
+        }
+        Ok(())
+    }
+
+    fn build_subquery(
+        &mut self,
+        clause: &'src ast::SubqueryClause<'src>,
+        procedures: &Procedures,
+    ) -> Result<Vec<MatchStep<'src>>, Error> {
+        let (mut env, steps, arguments) = self.build_imports(&clause.imports, 0)?;
+        let plan = env.build_plan(steps, &clause.query, procedures)?;
+
+        // returned variables are visible after the subquery
+        let row = self.next_name();
+        let mut steps = vec![];
+        let names = env.return_names(&clause.query.return_clause);
+        for (column, (name, load)) in names.into_iter().zip(&plan.returns).enumerate() {
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            let id = LoadProperty::ColumnOfRow { row, column };
+            match load {
+                LoadProperty::Node { .. } => steps.push(MatchStep::LoadExactNode {
+                    name: self.create_node(name)?,
+                    id,
+                }),
+                LoadProperty::Edge { .. } => steps.push(MatchStep::LoadExactEdge {
+                    name: self.create_edge(name)?,
+                    id,
+                }),
+                _ => self.create_column(name, row, column)?,
+            }
+        }
+
+        steps.insert(
+            0,
+            MatchStep::Subquery {
+                name: row,
+                plan: Box::new(plan),
+                arguments,
+            },
+        );
+        Ok(steps)
+    }
+
     fn build_filter(&mut self, cond: &'src ast::Condition<'src>) -> Result<Filter<'src>, Error> {
         let filter = match cond {
             ast::Condition::And(a, b) => Filter::and(self.build_filter(a)?, self.build_filter(b)?),
@@ -132,18 +530,16 @@ impl<'src> BuildEnv<'src> {
             ast::Condition::Lt(a, b) => {
                 Filter::Lt(self.build_load_property(a)?, self.build_load_property(b)?)
             }
-            ast::Condition::Le(a, b) => Filter::or(
-                Filter::Lt(self.build_load_property(a)?, self.build_load_property(b)?),
-                Filter::Eq(self.build_load_property(a)?, self.build_load_property(b)?),
-            ),
+            ast::Condition::Le(a, b) => {
+                Filter::Le(self.build_load_property(a)?, self.build_load_property(b)?)
+            }
 
             ast::Condition::Gt(a, b) => {
                 Filter::Gt(self.build_load_property(a)?, self.build_load_property(b)?)
             }
-            ast::Condition::Ge(a, b) => Filter::or(
-                Filter::Gt(self.build_load_property(a)?, self.build_load_property(b)?),
-                Filter::Eq(self.build_load_property(a)?, self.build_load_property(b)?),
-            ),
+            ast::Condition::Ge(a, b) => {
+                Filter::Ge(self.build_load_property(a)?, self.build_load_property(b)?)
+            }
 
             ast::Condition::IdEq(name, value) => match self
                 .names
@@ -158,6 +554,7 @@ impl<'src> BuildEnv<'src> {
                     edge: *edge,
                     id: self.build_load_property(value)?,
                 },
+                _ => return Err(Error::IdentifierIsNotNode(name.to_string())),
             },
         };
         Ok(filter)
@@ -171,18 +568,30 @@ impl<'src> BuildEnv<'src> {
         property_map
             .iter()
             .map(|(key, value)| {
-                Ok(MatchStep::Filter(match edge_or_node {
+                let filter = match edge_or_node {
                     NamedEntity::Node(node) => Filter::Eq(
-                        LoadProperty::PropertyOfNode { node, key },
+                        LoadProperty::PropertyOfNode {
+                            node,
+                            key: Box::new(LoadProperty::text(key)),
+                        },
                         self.build_load_property(value)?,
                     ),
                     NamedEntity::Edge(edge) => Filter::Eq(
-                        LoadProperty::PropertyOfEdge { edge, key },
+                        LoadProperty::PropertyOfEdge {
+                            edge,
+                            key: Box::new(LoadProperty::text(key)),
+                        },
                         self.build_load_property(value)?,
                     ),
-                }))
+                    _ => return Err(Error::Internal),
+                };
+                let mut steps: Vec<_> =
+                    self.take_collects().into_iter().map(MatchStep::Collect).collect();
+                steps.push(MatchStep::Filter(filter));
+                Ok(steps)
             })
-            .collect()
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|steps| steps.into_iter().flatten().collect())
     }
 
     fn build_match(
@@ -209,7 +618,7 @@ impl<'src> BuildEnv<'src> {
         if let Some(label) = clause.start.annotation.label {
             steps.push(MatchStep::Filter(Filter::NodeHasLabel {
                 node: prev_node_name,
-                label,
+                label: load_symbol(label),
             }));
         }
 
@@ -218,6 +627,11 @@ impl<'src> BuildEnv<'src> {
             clause.start.properties.as_ref(),
         )?);
 
+        let mut path = NamedPath {
+            nodes: vec![prev_node_name],
+            edges: vec![],
+        };
+
         for (edge, node) in &clause.edges {
             let edge_name = if let Some(name) = edge.annotation.name {
                 if let Some(name) = self.get_edge(name)? {
@@ -282,7 +696,7 @@ impl<'src> BuildEnv<'src> {
             if let Some(label) = edge.annotation.label {
                 steps.push(MatchStep::Filter(Filter::EdgeHasLabel {
                     edge: edge_name,
-                    label,
+                    label: load_symbol(label),
                 }));
             }
 
@@ -368,7 +782,7 @@ impl<'src> BuildEnv<'src> {
             if let Some(label) = node.annotation.label {
                 steps.push(MatchStep::Filter(Filter::NodeHasLabel {
                     node: prev_node_name,
-                    label,
+                    label: load_symbol(label),
                 }));
             }
 
@@ -376,11 +790,58 @@ impl<'src> BuildEnv<'src> {
                 NamedEntity::Node(prev_node_name),
                 node.properties.as_ref(),
             )?);
+
+            path.edges.push(edge_name);
+            path.nodes.push(prev_node_name);
+        }
+
+        // a pattern never matches the same edge twice, unless
+        // repeated elements are explicitly allowed
+        if clause.mode == ast::MatchMode::DifferentEdges {
+            for (idx, &edge) in path.edges.iter().enumerate() {
+                for &other in &path.edges[idx + 1..] {
+                    steps.push(MatchStep::Filter(Filter::not(Filter::Eq(
+                        LoadProperty::IdOfEdge { edge },
+                        LoadProperty::IdOfEdge { edge: other },
+                    ))));
+                }
+            }
+        }
+
+        if let Some(name) = clause.path {
+            self.create_path(name, path)?;
         }
 
         Ok(steps)
     }
 
+    fn build_call(
+        &mut self,
+        clause: &'src ast::CallClause<'src>,
+        procedures: &Procedures,
+    ) -> Result<MatchStep<'src>, Error> {
+        let procedure = procedures.get(clause.procedure)?.clone();
+        let arguments = clause
+            .arguments
+            .iter()
+            .map(|expr| self.build_load_property(expr))
+            .collect::<Result<_, Error>>()?;
+
+        let row = self.next_name();
+        for name in clause.yields.iter().flatten() {
+            let column = procedure
+                .column(name)
+                .ok_or_else(|| Error::UnknownIdentifier(name.to_string()))?;
+            self.create_column(name, row, column)?;
+        }
+
+        Ok(MatchStep::Call {
+            name: row,
+            procedure,
+            arguments,
+        })
+    }
+
     fn build_create_update(
         &mut self,
         clause: &'src ast::CreateClause<'src>,
@@ -390,44 +851,40 @@ impl<'src> BuildEnv<'src> {
                 name,
                 label,
                 properties,
-            } => Ok(UpdateStep::CreateNode {
-                name: name
-                    .map(|n| self.create_node(n))
-                    .transpose()?
-                    .unwrap_or_else(|| self.next_name()),
-                label,
-                properties: properties
-                    .iter()
-                    .map(|(key, expr)| -> Result<_, Error> {
-                        Ok((*key, self.build_load_property(expr)?))
-                    })
-                    .collect::<Result<_, Error>>()?,
-            }),
+            } => {
+                let properties = self.build_stored_properties(properties)?;
+                Ok(UpdateStep::CreateNode {
+                    name: name
+                        .map(|n| self.create_node(n))
+                        .transpose()?
+                        .unwrap_or_else(|| self.next_name()),
+                    label: load_symbol(*label),
+                    properties,
+                })
+            }
             ast::CreateClause::CreateEdge {
                 name,
                 label,
                 origin,
                 target,
                 properties,
-            } => Ok(UpdateStep::CreateEdge {
-                name: name
-                    .map(|n| self.create_edge(n))
-                    .transpose()?
-                    .unwrap_or_else(|| self.next_name()),
-                label,
-                origin: self
-                    .get_node(origin)?
-                    .ok_or_else(|| Error::UnknownIdentifier(origin.to_string()))?,
-                target: self
-                    .get_node(target)?
-                    .ok_or_else(|| Error::UnknownIdentifier(target.to_string()))?,
-                properties: properties
-                    .iter()
-                    .map(|(key, expr)| -> Result<_, Error> {
-                        Ok((*key, self.build_load_property(expr)?))
-                    })
-                    .collect::<Result<_, Error>>()?,
-            }),
+            } => {
+                let properties = self.build_stored_properties(properties)?;
+                Ok(UpdateStep::CreateEdge {
+                    name: name
+                        .map(|n| self.create_edge(n))
+                        .transpose()?
+                        .unwrap_or_else(|| self.next_name()),
+                    label: load_symbol(*label),
+                    origin: self
+                        .get_node(origin)?
+                        .ok_or_else(|| Error::UnknownIdentifier(origin.to_string()))?,
+                    target: self
+                        .get_node(target)?
+                        .ok_or_else(|| Error::UnknownIdentifier(target.to_string()))?,
+                    properties,
+                })
+            }
         }
     }
 
@@ -438,55 +895,148 @@ impl<'src> BuildEnv<'src> {
         match self.names.get(clause.name) {
             Some(NamedEntity::Node(node)) => Ok(UpdateStep::SetNodeProperty {
                 node: *node,
-                key: clause.key,
-                value: self.build_load_property(&clause.value)?,
+                key: load_symbol(clause.key),
+                value: self.build_stored_property(&clause.value)?,
             }),
             Some(NamedEntity::Edge(edge)) => Ok(UpdateStep::SetEdgeProperty {
                 edge: *edge,
-                key: clause.key,
-                value: self.build_load_property(&clause.value)?,
+                key: load_symbol(clause.key),
+                value: self.build_stored_property(&clause.value)?,
             }),
+            Some(_) => Err(Error::IdentifierIsNotNode(clause.name.to_string())),
             None => Err(Error::UnknownIdentifier(clause.name.to_string())),
         }
     }
 
+    fn build_foreach_update(
+        &mut self,
+        clause: &'src ast::ForeachClause<'src>,
+        procedures: &Procedures,
+    ) -> Result<UpdateStep<'src>, Error> {
+        let list = self.build_load_property(&clause.list)?;
+
+        // the body can refer to all outer variables
+        let (mut env, mut steps, arguments) = self.build_imports(&self.sorted_names(), 1)?;
+        env.bind_argument(clause.variable, 0, element_of(&list), &mut steps)?;
+        let plan = env.build_plan(steps, &clause.body, procedures)?;
+
+        Ok(UpdateStep::Foreach {
+            list,
+            plan: Box::new(plan),
+            arguments,
+        })
+    }
+
     fn build_delete_update(&mut self, name: &'src str) -> Result<UpdateStep<'src>, Error> {
         match self.names.get(name) {
             Some(&NamedEntity::Node(node)) => Ok(UpdateStep::DeleteNode { node }),
             Some(&NamedEntity::Edge(edge)) => Ok(UpdateStep::DeleteEdge { edge }),
+            Some(_) => Err(Error::IdentifierIsNotNode(name.to_string())),
             None => Err(Error::UnknownIdentifier(name.to_string())),
         }
     }
 }
 
 impl<'src> QueryPlan<'src> {
-    pub fn new(query: &'src ast::Query<'src>) -> Result<Self, Error> {
+    pub fn new(query: &'src ast::Query<'src>, procedures: &Procedures) -> Result<Self, Error> {
+        BuildEnv::new().build_plan(Vec::new(), query, procedures)
+    }
+
+    /// Build the plan for `query`, together with the
+    /// columns it returns.
+    pub fn with_columns(
+        query: &'src ast::Query<'src>,
+        procedures: &Procedures,
+    ) -> Result<(Self, Vec<Column<'src>>), Error> {
         let mut env = BuildEnv::new();
-        let mut steps = vec![];
+        let plan = env.build_plan(Vec::new(), query, procedures)?;
+        let columns = match plan.steps.as_slice() {
+            [MatchStep::Call { procedure, .. }] if is_standalone_call(query) => procedure
+                .columns
+                .iter()
+                .map(|column| Column::Name(column.clone()))
+                .collect(),
+            _ => env.columns(&query.return_clause),
+        };
+        Ok((plan, columns))
+    }
+}
+
+impl<'src> BuildEnv<'src> {
+    /// Build the plan for `query`, starting with the given
+    /// `steps` (which bind the arguments of nested queries).
+    fn build_plan(
+        &mut self,
+        mut steps: Vec<MatchStep<'src>>,
+        query: &'src ast::Query<'src>,
+        procedures: &Procedures,
+    ) -> Result<QueryPlan<'src>, Error> {
+        // updates paired with the comprehensions they use
         let mut updates = vec![];
 
         for clause in &query.match_clauses {
-            steps.append(&mut env.build_match(clause)?);
+            steps.append(&mut self.build_match(clause)?);
+        }
+
+        for clause in &query.call_clauses {
+            let call = self.build_call(clause, procedures)?;
+            steps.extend(self.take_collects().into_iter().map(MatchStep::Collect));
+            steps.push(call);
+        }
+
+        for clause in &query.subquery_clauses {
+            steps.append(&mut self.build_subquery(clause, procedures)?);
         }
 
         for condition in &query.where_clauses {
-            steps.push(MatchStep::Filter(env.build_filter(condition)?));
+            self.build_nested_filter(condition, &mut steps)?;
         }
 
         for clause in &query.create_clauses {
-            updates.push(env.build_create_update(clause)?);
+            let update = self.build_create_update(clause)?;
+            updates.push((self.take_collects(), update));
         }
         for clause in &query.set_clauses {
-            updates.push(env.build_set_update(clause)?);
+            let update = self.build_set_update(clause)?;
+            updates.push((self.take_collects(), update));
         }
         for name in &query.delete_clauses {
-            updates.push(env.build_delete_update(name)?);
+            updates.push((Vec::new(), self.build_delete_update(name)?));
+        }
+        for clause in &query.foreach_clauses {
+            let update = self.build_foreach_update(clause, procedures)?;
+            updates.push((self.take_collects(), update));
         }
-        updates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        updates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let mut updates: Vec<_> = updates
+            .into_iter()
+            .flat_map(|(collects, update)| {
+                collects
+                    .into_iter()
+                    .map(UpdateStep::Collect)
+                    .chain(std::iter::once(update))
+            })
+            .collect();
 
         let mut returns = Vec::with_capacity(query.return_clause.len());
         for expr in &query.return_clause {
-            returns.push(env.build_load_property(expr)?);
+            match expr {
+                ast::Expression::All => returns.append(&mut self.build_return_all()?),
+                expr => returns.push(self.build_load_property(expr)?),
+            }
+        }
+
+        // comprehensions in returns can refer to created nodes and edges
+        updates.extend(self.take_collects().into_iter().map(UpdateStep::Collect));
+
+        // a query consisting of a single `CALL` without
+        // `YIELD` returns all columns of the procedure
+        if is_standalone_call(query) {
+            if let [MatchStep::Call { name, procedure, .. }] = steps.as_slice() {
+                returns = (0..procedure.columns.len())
+                    .map(|column| LoadProperty::ColumnOfRow { row: *name, column })
+                    .collect();
+            }
         }
 
         Ok(QueryPlan {
@@ -496,3 +1046,34 @@ impl<'src> QueryPlan<'src> {
         })
     }
 }
+
+/// Labels and property keys are text, which is either
+/// given in the query or passed as a parameter.
+fn load_symbol(symbol: ast::Symbol) -> LoadProperty {
+    match symbol {
+        ast::Symbol::Static(text) => LoadProperty::text(text),
+        ast::Symbol::Parameter(name) => LoadProperty::Parameter { name },
+    }
+}
+
+/// The variable bound to the items of `list`. Items of
+/// `nodes(p)` and `relationships(p)` are nodes and edges.
+fn element_of(list: &LoadProperty) -> NamedEntity {
+    match list {
+        LoadProperty::NodesOfPath { .. } => NamedEntity::Node(0),
+        LoadProperty::EdgesOfPath { .. } => NamedEntity::Edge(0),
+        _ => NamedEntity::Argument(0),
+    }
+}
+
+fn is_standalone_call(query: &ast::Query) -> bool {
+    matches!(query.call_clauses.as_slice(), [ast::CallClause { yields: None, .. }])
+        && query.match_clauses.is_empty()
+        && query.subquery_clauses.is_empty()
+        && query.where_clauses.is_empty()
+        && query.create_clauses.is_empty()
+        && query.set_clauses.is_empty()
+        && query.delete_clauses.is_empty()
+        && query.foreach_clauses.is_empty()
+        && query.return_clause.is_empty()
+}
@@ -2,12 +2,13 @@ mod build;
 mod optimize;
 mod plan;
 
-pub(crate) use plan::{Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+pub(crate) use plan::{Collect, Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::parser::ast;
+    use crate::procedure::Procedures;
     use plan::*;
 
     #[test]
@@ -15,16 +16,21 @@ mod tests {
         // (a) -> (b)
         let query = ast::Query {
             match_clauses: vec![ast::MatchClause {
+                mode: ast::MatchMode::DifferentEdges,
+                path: None,
                 start: ast::Node::with_annotation(ast::Annotation::with_name("a")),
                 edges: vec![(
                     ast::Edge::right(ast::Annotation::empty(), vec![]),
                     ast::Node::with_annotation(ast::Annotation::with_name("b")),
                 )],
             }],
+            call_clauses: vec![],
+            subquery_clauses: vec![],
             where_clauses: vec![],
             create_clauses: vec![],
             set_clauses: vec![],
             delete_clauses: vec![],
+            foreach_clauses: vec![],
             return_clause: vec![
                 ast::Expression::property("a", "name"),
                 ast::Expression::property("b", "name"),
@@ -41,15 +47,18 @@ mod tests {
             returns: vec![
                 LoadProperty::PropertyOfNode {
                     node: 0,
-                    key: "name",
+                    key: Box::new(LoadProperty::text("name")),
                 },
                 LoadProperty::PropertyOfNode {
                     node: 2,
-                    key: "name",
+                    key: Box::new(LoadProperty::text("name")),
                 },
             ],
         };
 
-        assert_eq!(plan, QueryPlan::new(&query).unwrap());
+        assert_eq!(
+            plan,
+            QueryPlan::new(&query, &Procedures::default()).unwrap()
+        );
     }
 }
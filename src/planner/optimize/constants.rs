@@ -0,0 +1,278 @@
+use super::Optimization;
+use crate::planner::{Collect, Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use crate::store::PropRef;
+use crate::Error;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The result of folding a filter, which is either known
+/// to always (not) hold, or needs to be checked.
+enum Folded<'src> {
+    Constant(bool),
+    Filter(Filter<'src>),
+}
+
+/// Fold comparisons of constants, drop filters which always
+/// hold, and replace the steps of plans which provably have
+/// no matches with a single filter that never holds.
+pub(crate) struct FoldConstants;
+
+impl Optimization for FoldConstants {
+    fn apply(plan: &mut QueryPlan) -> Result<bool, Error> {
+        if plan.is_empty() {
+            return Ok(false);
+        }
+        let mut changed = false;
+        let mut empty = false;
+        plan.steps = plan
+            .steps
+            .drain(..)
+            .filter_map(|step| match step {
+                MatchStep::Filter(filter) => {
+                    let before = filter.clone();
+                    match fold(filter) {
+                        Folded::Constant(holds) => {
+                            changed = true;
+                            empty |= !holds;
+                            None
+                        }
+                        Folded::Filter(filter) => {
+                            changed |= filter != before;
+                            Some(MatchStep::Filter(filter))
+                        }
+                    }
+                }
+                step => Some(step),
+            })
+            .collect();
+        if empty || contradicts(&plan.steps) {
+            plan.steps = vec![MatchStep::Filter(Filter::never())];
+            plan.updates.clear();
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+fn fold(filter: Filter) -> Folded {
+    match filter {
+        Filter::And(lhs, rhs) => match (fold(*lhs), fold(*rhs)) {
+            (Folded::Constant(false), _) | (_, Folded::Constant(false)) => Folded::Constant(false),
+            (Folded::Constant(true), other) | (other, Folded::Constant(true)) => other,
+            (Folded::Filter(lhs), Folded::Filter(rhs)) => Folded::Filter(Filter::and(lhs, rhs)),
+        },
+        Filter::Or(lhs, rhs) => match (fold(*lhs), fold(*rhs)) {
+            (Folded::Constant(true), _) | (_, Folded::Constant(true)) => Folded::Constant(true),
+            (Folded::Constant(false), other) | (other, Folded::Constant(false)) => other,
+            (Folded::Filter(lhs), Folded::Filter(rhs)) => Folded::Filter(Filter::or(lhs, rhs)),
+        },
+        Filter::Not(inner) => match fold(*inner) {
+            Folded::Constant(holds) => Folded::Constant(!holds),
+            Folded::Filter(inner) => Folded::Filter(Filter::not(inner)),
+        },
+
+        Filter::NodeHasId {
+            id: LoadProperty::Constant(ref id),
+            ..
+        }
+        | Filter::EdgeHasId {
+            id: LoadProperty::Constant(ref id),
+            ..
+        } if id.cast_to_id().is_err() => Folded::Constant(false),
+
+        Filter::IsTruthy(LoadProperty::Constant(value)) => Folded::Constant(value.is_truthy()),
+
+        Filter::Eq(LoadProperty::Constant(lhs), LoadProperty::Constant(rhs)) => {
+            Folded::Constant(lhs.loosely_equals(&rhs))
+        }
+        Filter::Lt(LoadProperty::Constant(lhs), LoadProperty::Constant(rhs)) => {
+            Folded::Constant(lhs.loosely_compare(&rhs) == Some(Ordering::Less))
+        }
+        Filter::Le(LoadProperty::Constant(lhs), LoadProperty::Constant(rhs)) => Folded::Constant(
+            lhs.loosely_equals(&rhs) || lhs.loosely_compare(&rhs) == Some(Ordering::Less),
+        ),
+        Filter::Gt(LoadProperty::Constant(lhs), LoadProperty::Constant(rhs)) => {
+            Folded::Constant(lhs.loosely_compare(&rhs) == Some(Ordering::Greater))
+        }
+        Filter::Ge(LoadProperty::Constant(lhs), LoadProperty::Constant(rhs)) => Folded::Constant(
+            lhs.loosely_equals(&rhs) || lhs.loosely_compare(&rhs) == Some(Ordering::Greater),
+        ),
+
+        // ids and labels always equal themselves
+        Filter::Eq(ref lhs, ref rhs) | Filter::Le(ref lhs, ref rhs) | Filter::Ge(ref lhs, ref rhs)
+            if lhs == rhs && is_id_or_label(lhs) =>
+        {
+            Folded::Constant(true)
+        }
+        Filter::Lt(ref lhs, ref rhs) | Filter::Gt(ref lhs, ref rhs)
+            if lhs == rhs && is_id_or_label(lhs) =>
+        {
+            Folded::Constant(false)
+        }
+
+        filter => Folded::Filter(filter),
+    }
+}
+
+fn is_id_or_label(property: &LoadProperty) -> bool {
+    matches!(
+        property,
+        LoadProperty::IdOfNode { .. }
+            | LoadProperty::IdOfEdge { .. }
+            | LoadProperty::LabelOfNode { .. }
+            | LoadProperty::LabelOfEdge { .. }
+    )
+}
+
+/// Whether the filters in `steps` require the same node or
+/// edge to have two different constant ids or labels.
+fn contradicts(steps: &[MatchStep]) -> bool {
+    let mut ids: HashMap<(bool, usize), u64> = HashMap::new();
+    let mut labels: HashMap<(bool, usize), &str> = HashMap::new();
+    steps.iter().any(|step| match step {
+        MatchStep::Filter(Filter::NodeHasId {
+            node: name,
+            id: LoadProperty::Constant(id),
+        })
+        | MatchStep::Filter(Filter::EdgeHasId {
+            edge: name,
+            id: LoadProperty::Constant(id),
+        }) => {
+            let is_node = matches!(step, MatchStep::Filter(Filter::NodeHasId { .. }));
+            match id.cast_to_id() {
+                Ok(id) => *ids.entry((is_node, *name)).or_insert(id) != id,
+                Err(_) => true,
+            }
+        }
+        MatchStep::Filter(Filter::NodeHasLabel {
+            node: name,
+            label: LoadProperty::Constant(PropRef::Text(label)),
+        })
+        | MatchStep::Filter(Filter::EdgeHasLabel {
+            edge: name,
+            label: LoadProperty::Constant(PropRef::Text(label)),
+        }) => {
+            let is_node = matches!(step, MatchStep::Filter(Filter::NodeHasLabel { .. }));
+            *labels.entry((is_node, *name)).or_insert(label) != *label
+        }
+        _ => false,
+    })
+}
+
+impl<'src> QueryPlan<'src> {
+    /// The distinct filters in the plan and its nested plans which
+    /// only depend on parameters. Their outcome is known once the
+    /// parameters are bound.
+    pub fn conditions(&self) -> Vec<Filter<'src>> {
+        let mut conditions = Vec::new();
+        self.visit_filters(&mut |filter| {
+            if is_condition(filter) && !conditions.contains(filter) {
+                conditions.push(filter.clone());
+            }
+        });
+        conditions
+    }
+
+    /// Replace each of `conditions` with its outcome, where bit
+    /// `i` of `outcomes` is the outcome of condition `i`.
+    pub fn assume(mut self, conditions: &[Filter<'src>], outcomes: usize) -> Self {
+        self.map_filters(&mut |filter| {
+            conditions
+                .iter()
+                .position(|condition| condition == filter)
+                .map(|idx| {
+                    let holds = outcomes & (1 << idx) != 0;
+                    Filter::IsTruthy(LoadProperty::Constant(PropRef::Boolean(holds)))
+                })
+        });
+        self
+    }
+
+    fn visit_filters(&self, visit: &mut impl FnMut(&Filter<'src>)) {
+        fn walk<'src>(filter: &Filter<'src>, visit: &mut impl FnMut(&Filter<'src>)) {
+            match filter {
+                Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => {
+                    walk(lhs, visit);
+                    walk(rhs, visit);
+                }
+                Filter::Not(inner) => walk(inner, visit),
+                filter => visit(filter),
+            }
+        }
+        for step in &self.steps {
+            match step {
+                MatchStep::Filter(filter) => walk(filter, visit),
+                MatchStep::Subquery { plan, .. }
+                | MatchStep::Collect(Collect { plan, .. })
+                | MatchStep::Join { plan, .. } => plan.visit_filters(visit),
+                _ => (),
+            }
+        }
+        for update in &self.updates {
+            if let UpdateStep::Foreach { plan, .. } | UpdateStep::Collect(Collect { plan, .. }) =
+                update
+            {
+                plan.visit_filters(visit);
+            }
+        }
+    }
+
+    fn map_filters(&mut self, map: &mut impl FnMut(&Filter<'src>) -> Option<Filter<'src>>) {
+        fn walk<'src>(
+            filter: &mut Filter<'src>,
+            map: &mut impl FnMut(&Filter<'src>) -> Option<Filter<'src>>,
+        ) {
+            match filter {
+                Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => {
+                    walk(lhs, map);
+                    walk(rhs, map);
+                }
+                Filter::Not(inner) => walk(inner, map),
+                filter => {
+                    if let Some(mapped) = map(filter) {
+                        *filter = mapped;
+                    }
+                }
+            }
+        }
+        for step in &mut self.steps {
+            match step {
+                MatchStep::Filter(filter) => walk(filter, map),
+                MatchStep::Subquery { plan, .. }
+                | MatchStep::Collect(Collect { plan, .. })
+                | MatchStep::Join { plan, .. } => plan.map_filters(map),
+                _ => (),
+            }
+        }
+        for update in &mut self.updates {
+            if let UpdateStep::Foreach { plan, .. } | UpdateStep::Collect(Collect { plan, .. }) =
+                update
+            {
+                plan.map_filters(map);
+            }
+        }
+    }
+}
+
+/// Whether `filter` compares parameters and constants only.
+fn is_condition(filter: &Filter) -> bool {
+    fn independent(property: &LoadProperty) -> bool {
+        matches!(
+            property,
+            LoadProperty::Constant(_) | LoadProperty::Parameter { .. }
+        )
+    }
+    let (lhs, rhs) = match filter {
+        Filter::Eq(lhs, rhs)
+        | Filter::Lt(lhs, rhs)
+        | Filter::Le(lhs, rhs)
+        | Filter::Gt(lhs, rhs)
+        | Filter::Ge(lhs, rhs) => (lhs, rhs),
+        Filter::IsTruthy(value) => (value, value),
+        _ => return false,
+    };
+    independent(lhs)
+        && independent(rhs)
+        && (matches!(lhs, LoadProperty::Parameter { .. })
+            || matches!(rhs, LoadProperty::Parameter { .. }))
+}
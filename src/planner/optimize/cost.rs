@@ -0,0 +1,649 @@
+use super::loads::LoadLabeledToLoadIndexed;
+use crate::planner::{Filter, LoadProperty, MatchStep, QueryPlan};
+use crate::store::{Index, PropRef, Schema};
+use crate::Error;
+use std::collections::{BTreeSet, HashMap};
+use std::iter::once;
+
+/// Estimated fraction of matches which pass an equality check.
+const EQ_SELECTIVITY: f64 = 0.1;
+/// Estimated fraction of matches which pass a range check.
+const RANGE_SELECTIVITY: f64 = 0.3;
+/// Estimated fraction of matches for which a value is truthy.
+const TRUTHY_SELECTIVITY: f64 = 0.5;
+/// Patterns with more edges keep their order, since the
+/// number of join orders grows exponentially.
+const MAX_TREE_EDGES: usize = 10;
+
+/// Reorder the loads of each pattern such that the estimated number
+/// of intermediate matches is smallest. The estimates are based on
+/// the statistics in the schema. The order is only changed if it is
+/// estimated to be cheaper, which keeps plans for empty graphs stable.
+///
+/// The loads at the start of the plan form trees, each starting at a
+/// node which does not depend on other loads. Each tree is loaded
+/// starting from any of its nodes, or from a scan of a directed edge
+/// with a constant label, expanding its edges in any order. Filters
+/// are checked as soon as all their dependencies are loaded.
+pub(crate) struct ReorderByCost<'a>(pub &'a Schema);
+
+/// How a node is connected to an edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum End {
+    Origin,
+    Target,
+    Either,
+}
+
+/// An edge loaded from the node `near`, together
+/// with the node `far` loaded from the edge, if any.
+#[derive(Debug, Clone, Copy)]
+struct PatternEdge {
+    name: usize,
+    near: (usize, End),
+    far: Option<(usize, End)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Load {
+    Root(usize),
+    /// Load the edge with the given index by its label,
+    /// and then the nodes at both of its ends.
+    EdgeRoot(usize),
+    /// Load the edge with the given index from its near or
+    /// far node, and then the node at the other end.
+    Edge {
+        edge: usize,
+        from_near: bool,
+    },
+}
+
+/// A node which does not depend on other loads, and
+/// all edges which are (transitively) loaded from it.
+#[derive(Debug)]
+struct Tree {
+    root: usize,
+    nodes: Vec<usize>,
+    edges: Vec<usize>,
+}
+
+/// The loads and filters at the start of a plan.
+struct Pattern<'p, 'src> {
+    /// Steps loading the roots which can not be changed.
+    fixed: HashMap<usize, &'p MatchStep<'src>>,
+    trees: Vec<Tree>,
+    edges: Vec<PatternEdge>,
+    filters: Vec<(&'p Filter<'src>, BTreeSet<usize>)>,
+    /// Constant labels of nodes and edges, with
+    /// the index of the filter checking them.
+    labels: HashMap<usize, (usize, &'src str)>,
+}
+
+impl<'p, 'src> Pattern<'p, 'src> {
+    /// Parse the pattern from `steps`, or return `None` if
+    /// the loads are not connected as built for patterns.
+    fn new(steps: &'p [MatchStep<'src>]) -> Option<Self> {
+        let mut pattern = Self {
+            fixed: HashMap::new(),
+            trees: Vec::new(),
+            edges: Vec::new(),
+            filters: Vec::new(),
+            labels: HashMap::new(),
+        };
+        let mut tree_of: HashMap<usize, usize> = HashMap::new();
+        let mut edge_of: HashMap<usize, usize> = HashMap::new();
+        for step in steps {
+            match *step {
+                MatchStep::LoadAnyNode { name } | MatchStep::LoadExactNode { name, .. } => {
+                    if let MatchStep::LoadExactNode { .. } = step {
+                        pattern.fixed.insert(name, step);
+                    }
+                    tree_of.insert(name, pattern.trees.len());
+                    pattern.trees.push(Tree {
+                        root: name,
+                        nodes: vec![name],
+                        edges: Vec::new(),
+                    });
+                }
+                MatchStep::LoadOriginEdge { name, node }
+                | MatchStep::LoadTargetEdge { name, node }
+                | MatchStep::LoadEitherEdge { name, node } => {
+                    let end = match step {
+                        MatchStep::LoadOriginEdge { .. } => End::Origin,
+                        MatchStep::LoadTargetEdge { .. } => End::Target,
+                        _ => End::Either,
+                    };
+                    let tree = *tree_of.get(&node)?;
+                    edge_of.insert(name, pattern.edges.len());
+                    pattern.trees[tree].edges.push(pattern.edges.len());
+                    pattern.edges.push(PatternEdge {
+                        name,
+                        near: (node, end),
+                        far: None,
+                    });
+                }
+                MatchStep::LoadOriginNode { name, edge }
+                | MatchStep::LoadTargetNode { name, edge }
+                | MatchStep::LoadOtherNode { name, edge, .. } => {
+                    let loaded = &mut pattern.edges[*edge_of.get(&edge)?];
+                    let end = match (step, loaded.near.1) {
+                        (MatchStep::LoadOriginNode { .. }, End::Target) => End::Origin,
+                        (MatchStep::LoadTargetNode { .. }, End::Origin) => End::Target,
+                        (MatchStep::LoadOtherNode { node, .. }, End::Either)
+                            if *node == loaded.near.0 =>
+                        {
+                            End::Either
+                        }
+                        _ => return None,
+                    };
+                    if loaded.far.is_some() {
+                        return None;
+                    }
+                    loaded.far = Some((name, end));
+                    let tree = tree_of[&loaded.near.0];
+                    tree_of.insert(name, tree);
+                    pattern.trees[tree].nodes.push(name);
+                }
+                MatchStep::Filter(ref filter) => {
+                    if let Filter::NodeHasLabel { node: name, label }
+                    | Filter::EdgeHasLabel { edge: name, label } = filter
+                    {
+                        if let LoadProperty::Constant(PropRef::Text(label)) = label {
+                            let idx = pattern.filters.len();
+                            pattern.labels.entry(*name).or_insert((idx, *label));
+                        }
+                    }
+                    pattern.filters.push((filter, filter.dependencies()))
+                }
+                _ => return None,
+            }
+        }
+        Some(pattern)
+    }
+
+    /// The names loaded by `load`.
+    fn names(&self, load: Load) -> Vec<usize> {
+        match load {
+            Load::Root(name) => vec![name],
+            Load::Edge { edge, .. } | Load::EdgeRoot(edge) => {
+                let edge = &self.edges[edge];
+                let mut names = vec![edge.name, edge.near.0];
+                names.extend(edge.far.map(|(node, _)| node));
+                names
+            }
+        }
+    }
+
+    /// The steps which perform `load`, with the name loaded by each step.
+    fn steps(&self, load: Load) -> Vec<(usize, MatchStep<'src>)> {
+        let edge = match load {
+            Load::Root(name) => {
+                let step = match self.fixed.get(&name) {
+                    Some(&step) => step.clone(),
+                    None => MatchStep::LoadAnyNode { name },
+                };
+                return vec![(name, step)];
+            }
+            Load::Edge { edge, .. } => &self.edges[edge],
+            Load::EdgeRoot(edge) => {
+                let edge = &self.edges[edge];
+                let name = edge.name;
+                let (_, label) = self.labels[&name];
+                let mut steps = vec![(
+                    name,
+                    MatchStep::LoadLabeledEdge {
+                        name,
+                        label: LoadProperty::text(label),
+                    },
+                )];
+                for (node, end) in once(edge.near).chain(edge.far) {
+                    steps.push((node, Self::endpoint(name, (node, end), node)));
+                }
+                return steps;
+            }
+        };
+        let (from, to) = match load {
+            Load::Edge {
+                from_near: true, ..
+            } => (edge.near, edge.far),
+            _ => (edge.far.unwrap(), Some(edge.near)),
+        };
+        let name = edge.name;
+        let mut steps = vec![(
+            name,
+            match from {
+                (node, End::Origin) => MatchStep::LoadOriginEdge { name, node },
+                (node, End::Target) => MatchStep::LoadTargetEdge { name, node },
+                (node, End::Either) => MatchStep::LoadEitherEdge { name, node },
+            },
+        )];
+        steps.extend(to.map(|to| (to.0, Self::endpoint(edge.name, to, from.0))));
+        steps
+    }
+
+    /// The step loading the node at `end` of `edge`, which is
+    /// connected to the node `from` if the edge is undirected.
+    fn endpoint(edge: usize, (name, end): (usize, End), from: usize) -> MatchStep<'src> {
+        match end {
+            End::Origin => MatchStep::LoadOriginNode { name, edge },
+            End::Target => MatchStep::LoadTargetNode { name, edge },
+            End::Either => MatchStep::LoadOtherNode {
+                name,
+                node: from,
+                edge,
+            },
+        }
+    }
+}
+
+/// Estimates the number of matches produced by loads.
+struct CostModel<'a, 'p, 'src> {
+    schema: &'a Schema,
+    pattern: &'a Pattern<'p, 'src>,
+}
+
+impl<'a, 'p, 'src> CostModel<'a, 'p, 'src> {
+    fn new(schema: &'a Schema, pattern: &'a Pattern<'p, 'src>) -> Self {
+        Self { schema, pattern }
+    }
+
+    fn label(&self, name: usize) -> Option<&'src str> {
+        self.pattern.labels.get(&name).map(|&(_, label)| label)
+    }
+
+    /// The estimated cost and number of matches of the first load
+    /// of a tree, and the filters which are already accounted for.
+    fn start(&self, load: Load) -> (f64, f64, BTreeSet<usize>) {
+        match load {
+            Load::Root(root) => {
+                let (rows, absorbed) = self.root(root);
+                (rows, rows, absorbed)
+            }
+            Load::EdgeRoot(edge) => self.edge_root(edge),
+            Load::Edge { .. } => unreachable!(),
+        }
+    }
+
+    /// Whether the edge with the given index can be loaded by its label.
+    fn is_edge_root(&self, edge: usize) -> bool {
+        let edge = &self.pattern.edges[edge];
+        let directed = |(_, end): (usize, End)| end != End::Either;
+        directed(edge.near)
+            && edge.far.map(directed).unwrap_or(false)
+            && self.label(edge.name).is_some()
+    }
+
+    /// The estimated cost of scanning the label of `edge` and loading
+    /// the nodes at its ends, and the number of edges where both nodes
+    /// match. Each scanned edge is counted together with its near node.
+    fn edge_root(&self, edge: usize) -> (f64, f64, BTreeSet<usize>) {
+        let statistics = &self.schema.statistics;
+        let edge = &self.pattern.edges[edge];
+        let (filter, label) = self.pattern.labels[&edge.name];
+        let edges = statistics.edges(Some(label));
+        let mut cost = 0.0;
+        let mut rows = edges;
+        for (node, end) in once(edge.near).chain(edge.far) {
+            cost += rows;
+            if let Some(node_label) = self.label(node) {
+                let count = match end {
+                    End::Origin => statistics.origins(Some(label), Some(node_label)),
+                    _ => statistics.targets(Some(label), Some(node_label)),
+                };
+                rows *= count / edges.max(1.0);
+            }
+        }
+        (cost, rows, once(filter).collect())
+    }
+
+    /// The estimated number of nodes loaded for `root`, and the
+    /// filters which are already accounted for by the estimate.
+    fn root(&self, root: usize) -> (f64, BTreeSet<usize>) {
+        let mut absorbed = BTreeSet::new();
+        if self.pattern.fixed.contains_key(&root) {
+            return (1.0, absorbed);
+        }
+        let label = self.label(root);
+        let mut rows = self.schema.statistics.nodes(label);
+        let mut indexed = false;
+        for (idx, (filter, names)) in self.pattern.filters.iter().enumerate() {
+            // only filters which do not depend on other loads can be
+            // checked when loading the root
+            if names.len() != 1 || !names.contains(&root) {
+                continue;
+            }
+            if let Filter::NodeHasId { .. } = filter {
+                rows = rows.min(1.0);
+                absorbed.insert(idx);
+            }
+            let lookup = match (label, indexed) {
+                (Some(label), false) => LoadLabeledToLoadIndexed::lookup(root, filter)
+                    .filter(|(key, ..)| self.schema.indexes.contains_key(&Index::new(label, key))),
+                _ => None,
+            };
+            if lookup.is_some() {
+                rows *= self.selectivity(filter, true);
+                absorbed.insert(idx);
+                indexed = true;
+            }
+        }
+        (rows, absorbed)
+    }
+
+    /// The estimated number of edges (and nodes at their
+    /// other end) loaded for each match.
+    fn fanout(&self, edge: usize, from_near: bool) -> f64 {
+        let statistics = &self.schema.statistics;
+        let edge = &self.pattern.edges[edge];
+        let (from, to) = match from_near {
+            true => (edge.near, edge.far),
+            false => (edge.far.unwrap(), Some(edge.near)),
+        };
+        let label = self.label(edge.name);
+        let from_label = self.label(from.0);
+        let degree = match from.1 {
+            End::Origin => statistics.out_degree(label, from_label),
+            End::Target => statistics.in_degree(label, from_label),
+            End::Either => {
+                statistics.out_degree(label, from_label) + statistics.in_degree(label, from_label)
+            }
+        };
+        let edges = statistics.edges(label).max(1.0);
+        let fraction = match to.and_then(|(node, end)| Some((self.label(node)?, end))) {
+            Some((to_label, End::Origin)) => statistics.origins(label, Some(to_label)) / edges,
+            Some((to_label, End::Target)) => statistics.targets(label, Some(to_label)) / edges,
+            Some((to_label, End::Either)) => {
+                (statistics.origins(label, Some(to_label))
+                    + statistics.targets(label, Some(to_label)))
+                    / (2.0 * edges)
+            }
+            None => 1.0,
+        };
+        degree * fraction
+    }
+
+    /// The estimated fraction of matches which pass `filter`. Constant
+    /// labels of top level filters are accounted for by the loads.
+    fn selectivity(&self, filter: &Filter, top: bool) -> f64 {
+        let statistics = &self.schema.statistics;
+        match filter {
+            Filter::And(lhs, rhs) => self.selectivity(lhs, false) * self.selectivity(rhs, false),
+            Filter::Or(lhs, rhs) => {
+                (self.selectivity(lhs, false) + self.selectivity(rhs, false)).min(1.0)
+            }
+            Filter::Not(filter) => 1.0 - self.selectivity(filter, false),
+            Filter::NodeHasLabel {
+                label: LoadProperty::Constant(PropRef::Text(label)),
+                ..
+            } if !top => statistics.nodes(Some(label)) / statistics.nodes(None).max(1.0),
+            Filter::EdgeHasLabel {
+                label: LoadProperty::Constant(PropRef::Text(label)),
+                ..
+            } if !top => statistics.edges(Some(label)) / statistics.edges(None).max(1.0),
+            Filter::NodeHasLabel { .. } | Filter::EdgeHasLabel { .. } => 1.0,
+            Filter::NodeHasId { .. } | Filter::IsOrigin { .. } | Filter::IsTarget { .. } => {
+                1.0 / statistics.nodes(None).max(1.0)
+            }
+            Filter::EdgeHasId { .. } => 1.0 / statistics.edges(None).max(1.0),
+            Filter::IsTruthy(_) => TRUTHY_SELECTIVITY,
+            Filter::Eq(..) => EQ_SELECTIVITY,
+            Filter::Lt(..) | Filter::Le(..) | Filter::Gt(..) | Filter::Ge(..) => {
+                RANGE_SELECTIVITY
+            }
+        }
+    }
+
+    /// The estimated fraction of matches which pass the filters that
+    /// can be checked with `bound`, but not yet with `previous`.
+    fn filtered(
+        &self,
+        previous: &BTreeSet<usize>,
+        bound: &BTreeSet<usize>,
+        absorbed: &BTreeSet<usize>,
+    ) -> f64 {
+        self.pattern
+            .filters
+            .iter()
+            .enumerate()
+            .filter(|(idx, (_, names))| {
+                !absorbed.contains(idx) && names.is_subset(bound) && !names.is_subset(previous)
+            })
+            .map(|(_, (filter, _))| self.selectivity(filter, true))
+            .product()
+    }
+
+    /// The estimated number of matches produced by all loads
+    /// of `order`, given the names loaded before the tree.
+    fn cost(&self, order: &[Load], before: &BTreeSet<usize>) -> f64 {
+        let start = match order.first() {
+            Some(&start) => start,
+            None => return 0.0,
+        };
+        let (mut cost, mut rows, absorbed) = self.start(start);
+        let mut bound = before.clone();
+        bound.extend(self.pattern.names(start));
+        rows *= self.filtered(before, &bound, &absorbed);
+        for &load in &order[1..] {
+            if let Load::Edge { edge, from_near } = load {
+                let fanout = self.fanout(edge, from_near);
+                let previous = bound.clone();
+                bound.extend(self.pattern.names(load));
+                cost += rows * fanout;
+                rows *= fanout * self.filtered(&previous, &bound, &absorbed);
+            }
+        }
+        cost
+    }
+
+    /// The cheapest order to load `tree`, and its cost.
+    fn best(&self, tree: &Tree, before: &BTreeSet<usize>) -> (f64, Vec<Load>) {
+        let starts: Vec<Load> = match self.pattern.fixed.contains_key(&tree.root) {
+            true => vec![Load::Root(tree.root)],
+            false => tree
+                .nodes
+                .iter()
+                .map(|&node| Load::Root(node))
+                .chain(
+                    tree.edges
+                        .iter()
+                        .filter(|&&edge| self.is_edge_root(edge))
+                        .map(|&edge| Load::EdgeRoot(edge)),
+                )
+                .collect(),
+        };
+        let mut best: Option<(f64, Vec<Load>)> = None;
+        for start in starts {
+            let (scanned, rows, absorbed) = self.start(start);
+            let mut bound = before.clone();
+            bound.extend(self.pattern.names(start));
+            let filtered = self.filtered(before, &bound, &absorbed);
+            let search = Search {
+                model: self,
+                tree,
+                start,
+                before,
+                absorbed,
+            };
+            let initial = search.initial();
+            let mut memo = HashMap::new();
+            let cost = scanned + rows * filtered * search.rest(initial, &mut memo);
+
+            if best.as_ref().map(|(best, _)| cost < *best).unwrap_or(true) {
+                let mut order = vec![start];
+                let mut loaded = initial;
+                while let Some(&(_, Some(load))) = memo.get(&loaded) {
+                    if let Load::Edge { edge, .. } = load {
+                        let idx = tree.edges.iter().position(|&e| e == edge).unwrap();
+                        loaded |= 1 << idx;
+                    }
+                    order.push(load);
+                }
+                best = Some((cost, order));
+            }
+        }
+        best.unwrap()
+    }
+}
+
+/// Finds the cheapest order to load the edges of a tree,
+/// starting from a given root.
+struct Search<'m, 'a, 'p, 'src> {
+    model: &'m CostModel<'a, 'p, 'src>,
+    tree: &'m Tree,
+    start: Load,
+    before: &'m BTreeSet<usize>,
+    absorbed: BTreeSet<usize>,
+}
+
+impl<'m, 'a, 'p, 'src> Search<'m, 'a, 'p, 'src> {
+    /// The edges of the tree loaded by the start.
+    fn initial(&self) -> u32 {
+        match self.start {
+            Load::EdgeRoot(edge) => 1 << self.tree.edges.iter().position(|&e| e == edge).unwrap(),
+            _ => 0,
+        }
+    }
+
+    fn bound(&self, loaded: u32) -> BTreeSet<usize> {
+        let mut bound = self.before.clone();
+        bound.extend(self.model.pattern.names(self.start));
+        for (idx, &edge) in self.tree.edges.iter().enumerate() {
+            if loaded & (1 << idx) != 0 {
+                bound.extend(self.model.pattern.names(Load::Edge {
+                    edge,
+                    from_near: true,
+                }));
+            }
+        }
+        bound
+    }
+
+    /// The estimated cost of loading the remaining edges for each
+    /// match of the edges in `loaded`. The cheapest next load is
+    /// remembered in `memo`.
+    fn rest(&self, loaded: u32, memo: &mut HashMap<u32, (f64, Option<Load>)>) -> f64 {
+        if let Some((cost, _)) = memo.get(&loaded) {
+            return *cost;
+        }
+        let bound = self.bound(loaded);
+        let mut best: (f64, Option<Load>) = (0.0, None);
+        for (idx, &edge) in self.tree.edges.iter().enumerate() {
+            if loaded & (1 << idx) != 0 {
+                continue;
+            }
+            let pattern_edge = &self.model.pattern.edges[edge];
+            let from_near = if bound.contains(&pattern_edge.near.0) {
+                true
+            } else if pattern_edge
+                .far
+                .map(|(node, _)| bound.contains(&node))
+                .unwrap_or(false)
+            {
+                false
+            } else {
+                continue;
+            };
+            let load = Load::Edge { edge, from_near };
+            let mut next = bound.clone();
+            next.extend(self.model.pattern.names(load));
+            let fanout = self.model.fanout(edge, from_near);
+            let filtered = self.model.filtered(&bound, &next, &self.absorbed);
+            let cost = fanout * (1.0 + filtered * self.rest(loaded | (1 << idx), memo));
+            if best.1.is_none() || cost < best.0 {
+                best = (cost, Some(load));
+            }
+        }
+        memo.insert(loaded, best);
+        best.0
+    }
+}
+
+impl<'a> ReorderByCost<'a> {
+    pub fn apply<'src>(&self, plan: &mut QueryPlan<'src>) -> Result<bool, Error> {
+        let len = plan
+            .steps
+            .iter()
+            .position(|step| {
+                !matches!(
+                    step,
+                    MatchStep::LoadAnyNode { .. }
+                        | MatchStep::LoadExactNode { .. }
+                        | MatchStep::LoadOriginNode { .. }
+                        | MatchStep::LoadTargetNode { .. }
+                        | MatchStep::LoadOtherNode { .. }
+                        | MatchStep::LoadOriginEdge { .. }
+                        | MatchStep::LoadTargetEdge { .. }
+                        | MatchStep::LoadEitherEdge { .. }
+                        | MatchStep::Filter(_)
+                )
+            })
+            .unwrap_or(plan.steps.len());
+        let pattern = match Pattern::new(&plan.steps[..len]) {
+            Some(pattern) => pattern,
+            None => return Ok(false),
+        };
+        let model = CostModel::new(self.0, &pattern);
+
+        let mut changed = false;
+        let mut orders = Vec::with_capacity(pattern.trees.len());
+        let mut before = BTreeSet::new();
+        for tree in &pattern.trees {
+            let mut order = vec![Load::Root(tree.root)];
+            order.extend(tree.edges.iter().map(|&edge| Load::Edge {
+                edge,
+                from_near: true,
+            }));
+            if tree.edges.len() <= MAX_TREE_EDGES {
+                let cost = model.cost(&order, &before);
+                let (best_cost, best_order) = model.best(tree, &before);
+                if best_cost < cost * (1.0 - 1e-9) {
+                    order = best_order;
+                    changed = true;
+                }
+            }
+            for &load in &order {
+                before.extend(pattern.names(load));
+            }
+            orders.push(order);
+        }
+        if !changed {
+            return Ok(false);
+        }
+
+        let mut steps = Vec::with_capacity(len);
+        let mut checked = vec![false; pattern.filters.len()];
+        // labels of edges which are scanned by label are not checked again
+        for order in &orders {
+            if let Some(&Load::EdgeRoot(edge)) = order.first() {
+                let (filter, _) = pattern.labels[&pattern.edges[edge].name];
+                checked[filter] = true;
+            }
+        }
+        let mut bound = BTreeSet::new();
+        let mut check = |steps: &mut Vec<MatchStep<'src>>, bound: &BTreeSet<usize>| {
+            for (idx, (filter, names)) in pattern.filters.iter().enumerate() {
+                if !checked[idx] && names.is_subset(bound) {
+                    checked[idx] = true;
+                    steps.push(MatchStep::Filter((*filter).clone()));
+                }
+            }
+        };
+        check(&mut steps, &bound);
+        for load in orders.into_iter().flatten() {
+            for (name, step) in pattern.steps(load) {
+                steps.push(step);
+                bound.insert(name);
+                check(&mut steps, &bound);
+            }
+        }
+        let all: BTreeSet<usize> = pattern
+            .filters
+            .iter()
+            .flat_map(|(_, names)| names)
+            .copied()
+            .collect();
+        check(&mut steps, &all);
+        plan.steps.splice(..len, steps);
+        Ok(true)
+    }
+}
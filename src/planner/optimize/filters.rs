@@ -0,0 +1,105 @@
+use super::Optimization;
+use crate::planner::{Filter, MatchStep, QueryPlan};
+use crate::Error;
+use std::collections::BTreeSet;
+
+/// Conditions of the form `(a AND b) OR c` are only distributed
+/// into `(a OR c) AND (b OR c)` if this creates at most this many
+/// filters, since each filter repeats parts of the condition.
+const MAX_DISTRIBUTED: usize = 8;
+
+/// Move each filter to right after the last step loading one of its
+/// dependencies, such that matches are discarded before loading any
+/// further nodes or edges. Filters are split into as many independent
+/// conditions as possible first, such that each condition is checked
+/// as early as possible.
+pub(crate) struct PushDownFilters;
+
+impl Optimization for PushDownFilters {
+    fn apply(plan: &mut QueryPlan) -> Result<bool, Error> {
+        let original = plan.steps.clone();
+        let mut steps = Vec::with_capacity(plan.steps.len());
+        let mut filters = Vec::new();
+        for step in plan.steps.drain(..) {
+            match step {
+                MatchStep::Filter(filter) => filters.extend(conjuncts(filter)),
+                step => steps.push(step),
+            }
+        }
+
+        let mut positions: Vec<Vec<Filter>> = vec![Vec::new(); steps.len() + 1];
+        for filter in filters {
+            let dependencies = filter.dependencies();
+            let position = steps
+                .iter()
+                .rposition(|step| {
+                    step.name()
+                        .map(|name| dependencies.contains(&name))
+                        .unwrap_or(false)
+                })
+                .map(|idx| idx + 1)
+                .unwrap_or(0);
+            positions[position].push(filter);
+        }
+
+        let mut positions = positions.into_iter();
+        plan.steps
+            .extend(positions.next().unwrap().into_iter().map(MatchStep::Filter));
+        for (step, filters) in steps.into_iter().zip(positions) {
+            plan.steps.push(step);
+            plan.steps
+                .extend(filters.into_iter().map(MatchStep::Filter));
+        }
+        Ok(plan.steps != original)
+    }
+}
+
+/// Split `filter` into conditions which all have to hold. Negations
+/// are pushed into disjunctions, and disjunctions of conjunctions
+/// are distributed if that yields conditions with fewer dependencies.
+fn conjuncts(filter: Filter) -> Vec<Filter> {
+    match filter {
+        Filter::And(lhs, rhs) => {
+            let mut filters = conjuncts(*lhs);
+            filters.extend(conjuncts(*rhs));
+            filters
+        }
+        Filter::Not(inner) => match *inner {
+            Filter::Not(inner) => conjuncts(*inner),
+            Filter::Or(lhs, rhs) => {
+                let mut filters = conjuncts(Filter::not(*lhs));
+                filters.extend(conjuncts(Filter::not(*rhs)));
+                filters
+            }
+            inner => vec![Filter::not(inner)],
+        },
+        Filter::Or(lhs, rhs) => {
+            let lhs = conjuncts(*lhs);
+            let rhs = conjuncts(*rhs);
+            let distributed: Vec<Filter> = lhs
+                .iter()
+                .flat_map(|lhs| {
+                    rhs.iter()
+                        .map(move |rhs| Filter::or(lhs.clone(), rhs.clone()))
+                })
+                .collect();
+            let all: BTreeSet<usize> = distributed
+                .iter()
+                .flat_map(|filter| filter.dependencies())
+                .collect();
+            let helps = distributed
+                .iter()
+                .any(|filter| filter.dependencies() != all);
+            if distributed.len() == 1 || (helps && distributed.len() <= MAX_DISTRIBUTED) {
+                distributed
+            } else {
+                vec![Filter::or(and_all(lhs), and_all(rhs))]
+            }
+        }
+        filter => vec![filter],
+    }
+}
+
+fn and_all(filters: Vec<Filter>) -> Filter {
+    filters.into_iter().reduce(Filter::and).unwrap()
+}
@@ -0,0 +1,253 @@
+use super::Optimization;
+use crate::planner::{Collect, Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use crate::Error;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// Replace the nested loop over a part of the pattern which is not
+/// connected to the earlier steps by a `Join`, if the parts are
+/// compared by at least one equality. The part is loaded once into
+/// a hash table keyed by the compared values, and its nodes and
+/// edges are restored from the joined rows. The comparisons are
+/// still checked after the join.
+pub(crate) struct HashJoinDisconnected;
+
+impl Optimization for HashJoinDisconnected {
+    fn apply(plan: &mut QueryPlan) -> Result<bool, Error> {
+        let len = plan
+            .steps
+            .iter()
+            .position(|step| dependencies(step).is_none())
+            .unwrap_or(plan.steps.len());
+        for start in 1..len {
+            if let Some(join) = Join::new(&plan.steps[..len], start) {
+                join.apply(plan);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// A part of the pattern loaded by a hash join, which starts
+/// at step `start` and consists of the steps at `steps`.
+struct Join<'src> {
+    start: usize,
+    steps: Vec<usize>,
+    loaded: Vec<(usize, bool)>,
+    keys: Vec<(LoadProperty<'src>, LoadProperty<'src>)>,
+}
+
+impl<'src> Join<'src> {
+    fn new(steps: &[MatchStep<'src>], start: usize) -> Option<Self> {
+        let root = steps[start].name()?;
+        let is_root = matches!(
+            steps[start],
+            MatchStep::LoadAnyNode { .. }
+                | MatchStep::LoadLabeledNode { .. }
+                | MatchStep::LoadIndexedNode { .. }
+                | MatchStep::LoadSpatialNode { .. }
+                | MatchStep::LoadLabeledEdge { .. }
+        );
+        if !is_root || !dependencies(&steps[start])?.is_empty() {
+            return None;
+        }
+        let before: BTreeSet<usize> = steps[..start].iter().filter_map(MatchStep::name).collect();
+        if before.is_empty() {
+            return None;
+        }
+
+        let mut part = BTreeSet::new();
+        part.insert(root);
+        let mut join = Self {
+            start,
+            steps: vec![start],
+            loaded: vec![(root, is_edge(&steps[start]))],
+            keys: Vec::new(),
+        };
+        for (idx, step) in steps.iter().enumerate().skip(start + 1) {
+            let deps = dependencies(step)?;
+            if !deps.is_empty() && deps.is_subset(&part) {
+                join.steps.push(idx);
+                if let Some(name) = step.name() {
+                    part.insert(name);
+                    join.loaded.push((name, is_edge(step)));
+                }
+            } else if !deps.is_disjoint(&part) {
+                // the parts are connected by an edge
+                match step {
+                    MatchStep::Filter(filter) if !is_structural(filter) => (),
+                    _ => return None,
+                }
+            }
+        }
+
+        for step in steps.iter().skip(start + 1) {
+            if let MatchStep::Filter(Filter::Eq(lhs, rhs)) = step {
+                let (lhs_deps, rhs_deps) = (lhs.dependencies(), rhs.dependencies());
+                let is_key = |probe: &BTreeSet<usize>, build: &BTreeSet<usize>| {
+                    !probe.is_empty()
+                        && probe.is_subset(&before)
+                        && !build.is_empty()
+                        && build.is_subset(&part)
+                };
+                if is_key(&lhs_deps, &rhs_deps) {
+                    join.keys.push((lhs.clone(), rhs.clone()));
+                } else if is_key(&rhs_deps, &lhs_deps) {
+                    join.keys.push((rhs.clone(), lhs.clone()));
+                }
+            }
+        }
+        if join.keys.is_empty() {
+            None
+        } else {
+            Some(join)
+        }
+    }
+
+    fn apply(self, plan: &mut QueryPlan<'src>) {
+        let name = fresh_name(plan);
+        let (probe, build): (Vec<_>, Vec<_>) = self.keys.into_iter().unzip();
+        let columns = build.len();
+        let mut returns = build;
+        returns.extend(self.loaded.iter().map(|&(loaded, edge)| {
+            if edge {
+                LoadProperty::Edge { edge: loaded }
+            } else {
+                LoadProperty::Node { node: loaded }
+            }
+        }));
+        let nested = QueryPlan {
+            steps: self
+                .steps
+                .iter()
+                .map(|&idx| plan.steps[idx].clone())
+                .collect(),
+            updates: Vec::new(),
+            returns,
+        };
+
+        let mut steps = Vec::with_capacity(plan.steps.len());
+        for (idx, step) in plan.steps.drain(..).enumerate() {
+            if idx == self.start {
+                steps.push(MatchStep::Join {
+                    name,
+                    plan: Box::new(nested.clone()),
+                    keys: probe.clone(),
+                });
+                for (column, &(loaded, edge)) in self.loaded.iter().enumerate() {
+                    let id = LoadProperty::ColumnOfRow {
+                        row: name,
+                        column: columns + column,
+                    };
+                    steps.push(if edge {
+                        MatchStep::LoadExactEdge { name: loaded, id }
+                    } else {
+                        MatchStep::LoadExactNode { name: loaded, id }
+                    });
+                }
+            } else if !self.steps.contains(&idx) {
+                steps.push(step);
+            }
+        }
+        plan.steps = steps;
+    }
+}
+
+/// The names a step of a pattern depends on, or `None`
+/// if the step is not part of a pattern.
+fn dependencies(step: &MatchStep) -> Option<BTreeSet<usize>> {
+    let single = |name: usize| Some(std::iter::once(name).collect());
+    match step {
+        MatchStep::LoadAnyNode { .. } => Some(BTreeSet::new()),
+        MatchStep::LoadLabeledNode { label, .. } | MatchStep::LoadLabeledEdge { label, .. } => {
+            Some(label.dependencies())
+        }
+        MatchStep::LoadIndexedNode {
+            label,
+            key,
+            lower,
+            upper,
+            ..
+        } => {
+            let mut deps = label.dependencies();
+            deps.extend(key.dependencies());
+            for bound in [lower, upper].iter() {
+                if let Bound::Included(value) | Bound::Excluded(value) = bound {
+                    deps.extend(value.dependencies());
+                }
+            }
+            Some(deps)
+        }
+        MatchStep::LoadSpatialNode {
+            label, key, region, ..
+        } => {
+            let mut deps = label.dependencies();
+            deps.extend(key.dependencies());
+            for value in region.values() {
+                deps.extend(value.dependencies());
+            }
+            Some(deps)
+        }
+        MatchStep::LoadExactNode { id, .. } | MatchStep::LoadExactEdge { id, .. } => {
+            Some(id.dependencies())
+        }
+        MatchStep::LoadOriginNode { edge, .. } | MatchStep::LoadTargetNode { edge, .. } => {
+            single(*edge)
+        }
+        MatchStep::LoadOtherNode { node, edge, .. } => {
+            Some([*node, *edge].iter().copied().collect())
+        }
+        MatchStep::LoadOriginEdge { node, .. }
+        | MatchStep::LoadTargetEdge { node, .. }
+        | MatchStep::LoadEitherEdge { node, .. } => single(*node),
+        MatchStep::LoadLabeledOriginEdge { node, label, .. }
+        | MatchStep::LoadLabeledTargetEdge { node, label, .. }
+        | MatchStep::LoadLabeledEitherEdge { node, label, .. } => {
+            let mut deps = label.dependencies();
+            deps.insert(*node);
+            Some(deps)
+        }
+        MatchStep::Join { keys, .. } => {
+            Some(keys.iter().flat_map(|key| key.dependencies()).collect())
+        }
+        MatchStep::Filter(filter) => Some(filter.dependencies()),
+        MatchStep::Call { .. } | MatchStep::Subquery { .. } | MatchStep::Collect(_) => None,
+    }
+}
+
+fn is_edge(step: &MatchStep) -> bool {
+    matches!(
+        step,
+        MatchStep::LoadExactEdge { .. }
+            | MatchStep::LoadLabeledEdge { .. }
+            | MatchStep::LoadOriginEdge { .. }
+            | MatchStep::LoadTargetEdge { .. }
+            | MatchStep::LoadEitherEdge { .. }
+            | MatchStep::LoadLabeledOriginEdge { .. }
+            | MatchStep::LoadLabeledTargetEdge { .. }
+            | MatchStep::LoadLabeledEitherEdge { .. }
+    )
+}
+
+/// Whether `filter` checks how nodes and edges are connected.
+fn is_structural(filter: &Filter) -> bool {
+    match filter {
+        Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => is_structural(lhs) || is_structural(rhs),
+        Filter::Not(inner) => is_structural(inner),
+        Filter::IsOrigin { .. } | Filter::IsTarget { .. } => true,
+        _ => false,
+    }
+}
+
+/// A name which is not used by any step or update of `plan`.
+fn fresh_name(plan: &QueryPlan) -> usize {
+    let steps = plan.steps.iter().filter_map(MatchStep::name);
+    let updates = plan.updates.iter().filter_map(|update| match update {
+        UpdateStep::CreateNode { name, .. }
+        | UpdateStep::CreateEdge { name, .. }
+        | UpdateStep::Collect(Collect { name, .. }) => Some(*name),
+        _ => None,
+    });
+    steps.chain(updates).max().map(|name| name + 1).unwrap_or(0)
+}
@@ -1,8 +1,12 @@
 use super::Optimization;
+use crate::function::Function;
 use crate::planner::{Filter, LoadProperty, MatchStep, QueryPlan};
+use crate::store::{Index, PropRef, Region, Schema};
 use crate::Error;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::iter::once;
+use std::ops::Bound;
 
 /// Reorder to loads such that the initial `LoadAnyNode` has an
 /// `WHERE ID(n) =` constraint if possible. This performs at most
@@ -212,11 +216,13 @@ pub(crate) struct LoadAnyToLoadLabeled;
 impl Optimization for LoadAnyToLoadLabeled {
     fn apply(plan: &mut QueryPlan) -> Result<bool, Error> {
         let mut changed = false;
-        let mut node_label_checks: HashMap<usize, &str> = plan
+        let mut node_label_checks: HashMap<usize, LoadProperty> = plan
             .steps
             .iter()
             .filter_map(|step| match step {
-                MatchStep::Filter(Filter::NodeHasLabel { node, label }) => Some((*node, *label)),
+                MatchStep::Filter(Filter::NodeHasLabel { node, label }) => {
+                    Some((*node, label.clone()))
+                }
                 _ => None,
             })
             .collect();
@@ -244,3 +250,287 @@ impl Optimization for LoadAnyToLoadLabeled {
         Ok(changed)
     }
 }
+
+type IndexBound<'f, 'src> = Bound<&'f LoadProperty<'src>>;
+
+/// Transform pairs of `LoadLabeledNode` and a comparison of one
+/// of the node's properties with a value that does not depend on
+/// the match into `LoadIndexedNode`, if the schema has an index for
+/// the label and property. A second comparison of the property which
+/// bounds it from the other side is absorbed into the same scan.
+pub(crate) struct LoadLabeledToLoadIndexed<'a>(pub &'a Schema);
+
+impl<'a> LoadLabeledToLoadIndexed<'a> {
+    pub fn apply(&self, plan: &mut QueryPlan) -> Result<bool, Error> {
+        let mut changed = false;
+        let mut idx = 0;
+        while idx < plan.steps.len() {
+            let (name, label) = match &plan.steps[idx] {
+                MatchStep::LoadLabeledNode {
+                    name,
+                    label: LoadProperty::Constant(PropRef::Text(label)),
+                } => (*name, *label),
+                _ => {
+                    idx += 1;
+                    continue;
+                }
+            };
+            let steps = plan.steps.iter().enumerate().skip(idx + 1);
+            let mut lookups = steps.filter_map(|(filter_idx, step)| match step {
+                MatchStep::Filter(filter) => {
+                    Self::lookup(name, filter).map(|lookup| (filter_idx, lookup))
+                }
+                _ => None,
+            });
+            let lookup = lookups
+                .find(|(_, (key, ..))| self.0.indexes.contains_key(&Index::new(label, key)));
+            if let Some((filter_idx, (key, mut lower, mut upper))) = lookup {
+                let mut absorbed = vec![filter_idx];
+                let one_sided = |bound: IndexBound, other: IndexBound| {
+                    matches!(bound, Bound::Unbounded) && !matches!(other, Bound::Unbounded)
+                };
+                let other = lookups.find(|&(_, (other, other_lower, other_upper))| {
+                    other == key
+                        && (one_sided(lower, upper) && one_sided(other_upper, other_lower)
+                            || one_sided(upper, lower) && one_sided(other_lower, other_upper))
+                });
+                if let Some((other_idx, (_, other_lower, other_upper))) = other {
+                    if let Bound::Unbounded = lower {
+                        lower = other_lower;
+                    } else {
+                        upper = other_upper;
+                    }
+                    absorbed.push(other_idx);
+                }
+                plan.steps[idx] = MatchStep::LoadIndexedNode {
+                    name,
+                    label: LoadProperty::text(label),
+                    key: LoadProperty::text(key),
+                    lower: lower.cloned(),
+                    upper: upper.cloned(),
+                };
+                for filter_idx in absorbed.into_iter().rev() {
+                    plan.steps.remove(filter_idx);
+                }
+                changed = true;
+            }
+            idx += 1;
+        }
+        Ok(changed)
+    }
+
+    /// The property key and the lower and upper bound of its values
+    /// if `filter` compares a property of `node` with an independent
+    /// value.
+    pub(super) fn lookup<'f, 'src>(
+        node: usize,
+        filter: &'f Filter<'src>,
+    ) -> Option<(&'src str, IndexBound<'f, 'src>, IndexBound<'f, 'src>)> {
+        let (lhs, rhs, ordering, inclusive) = match filter {
+            Filter::Eq(lhs, rhs) => (lhs, rhs, Ordering::Equal, true),
+            Filter::Lt(lhs, rhs) => (lhs, rhs, Ordering::Less, false),
+            Filter::Le(lhs, rhs) => (lhs, rhs, Ordering::Less, true),
+            Filter::Gt(lhs, rhs) => (lhs, rhs, Ordering::Greater, false),
+            Filter::Ge(lhs, rhs) => (lhs, rhs, Ordering::Greater, true),
+            _ => return None,
+        };
+        // the bounds if the property is on the left hand side
+        let bounds = |ordering: Ordering, value| {
+            let bound = match inclusive {
+                true => Bound::Included(value),
+                false => Bound::Excluded(value),
+            };
+            match ordering {
+                Ordering::Equal => (bound, bound),
+                Ordering::Less => (Bound::Unbounded, bound),
+                Ordering::Greater => (bound, Bound::Unbounded),
+            }
+        };
+        let key_of = |property: &LoadProperty<'src>| match property {
+            LoadProperty::PropertyOfNode { node: other, key } if *other == node => {
+                match **key {
+                    LoadProperty::Constant(PropRef::Text(key)) => Some(key),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let is_independent = |value: &LoadProperty| {
+            matches!(
+                value,
+                LoadProperty::Constant(_)
+                    | LoadProperty::Parameter { .. }
+                    | LoadProperty::Argument { .. }
+            )
+        };
+        let (key, ordering, value) = match (key_of(lhs), key_of(rhs)) {
+            (Some(key), None) if is_independent(rhs) => (key, ordering, rhs),
+            (None, Some(key)) if is_independent(lhs) => (key, ordering.reverse(), lhs),
+            _ => return None,
+        };
+        let (lower, upper) = bounds(ordering, value);
+        Some((key, lower, upper))
+    }
+}
+
+/// Transform pairs of `LoadLabeledNode` and a filter which bounds
+/// one of the node's properties to a region, given by values that
+/// do not depend on the match, into `LoadSpatialNode`, if the schema
+/// has an index for the label and property. The filter is kept, since
+/// the index also returns points close to the region. Regions are
+/// given by `distance(n.key, center) < radius`, or `<=`, and by
+/// `point.withinBBox(n.key, lower_left, upper_right)`.
+pub(crate) struct LoadLabeledToLoadSpatial<'a>(pub &'a Schema);
+
+impl<'a> LoadLabeledToLoadSpatial<'a> {
+    pub fn apply(&self, plan: &mut QueryPlan) -> Result<bool, Error> {
+        let mut changed = false;
+        for idx in 0..plan.steps.len() {
+            let (name, label) = match &plan.steps[idx] {
+                MatchStep::LoadLabeledNode {
+                    name,
+                    label: LoadProperty::Constant(PropRef::Text(label)),
+                } => (*name, *label),
+                _ => continue,
+            };
+            let lookup = plan.steps[idx + 1..]
+                .iter()
+                .filter_map(|step| match step {
+                    MatchStep::Filter(filter) => Self::lookup(name, filter),
+                    _ => None,
+                })
+                .find(|(key, _)| self.0.indexes.contains_key(&Index::new(label, key)));
+            if let Some((key, region)) = lookup {
+                plan.steps[idx] = MatchStep::LoadSpatialNode {
+                    name,
+                    label: LoadProperty::text(label),
+                    key: LoadProperty::text(key),
+                    region,
+                };
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The property key and the region its values are bounded
+    /// to if `filter` bounds a property of `node`.
+    fn lookup<'src>(
+        node: usize,
+        filter: &Filter<'src>,
+    ) -> Option<(&'src str, Region<LoadProperty<'src>>)> {
+        let key_of = |property: &LoadProperty<'src>| match property {
+            LoadProperty::PropertyOfNode { node: other, key } if *other == node => {
+                match **key {
+                    LoadProperty::Constant(PropRef::Text(key)) => Some(key),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let is_independent = |value: &LoadProperty| value.dependencies().is_empty();
+        let (function, arguments, radius) = match filter {
+            Filter::Lt(lhs, radius) | Filter::Le(lhs, radius) => match lhs {
+                LoadProperty::Function {
+                    function,
+                    arguments,
+                } => (function, arguments, Some(radius)),
+                _ => return None,
+            },
+            Filter::Gt(radius, rhs) | Filter::Ge(radius, rhs) => match rhs {
+                LoadProperty::Function {
+                    function,
+                    arguments,
+                } => (function, arguments, Some(radius)),
+                _ => return None,
+            },
+            Filter::IsTruthy(LoadProperty::Function {
+                function,
+                arguments,
+            }) => (function, arguments, None),
+            _ => return None,
+        };
+        match (function, arguments.as_slice(), radius) {
+            (Function::Distance, [lhs, rhs], Some(radius)) if is_independent(radius) => {
+                let (key, center) = match (key_of(lhs), key_of(rhs)) {
+                    (Some(key), None) => (key, rhs),
+                    (None, Some(key)) => (key, lhs),
+                    _ => return None,
+                };
+                let region = Region::Circle {
+                    center: center.clone(),
+                    radius: radius.clone(),
+                };
+                Some((key, region)).filter(|_| is_independent(center))
+            }
+            (Function::WithinBBox, [point, lower_left, upper_right], None) => {
+                let region = Region::Box {
+                    lower_left: lower_left.clone(),
+                    upper_right: upper_right.clone(),
+                };
+                let key = key_of(point)?;
+                Some((key, region))
+                    .filter(|_| is_independent(lower_left) && is_independent(upper_right))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Transform pairs of `LoadOriginEdge`, `LoadTargetEdge` or
+/// `LoadEitherEdge` and an `EdgeHasLabel` with a label that does
+/// not depend on the match into their labeled variants.
+pub(crate) struct LoadEdgeToLoadLabeledEdge;
+
+impl Optimization for LoadEdgeToLoadLabeledEdge {
+    fn apply(plan: &mut QueryPlan) -> Result<bool, Error> {
+        let mut changed = false;
+        let mut edge_label_checks: HashMap<usize, LoadProperty> = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                MatchStep::Filter(Filter::EdgeHasLabel {
+                    edge,
+                    label: label @ (LoadProperty::Constant(_) | LoadProperty::Parameter { .. }),
+                }) => Some((*edge, label.clone())),
+                _ => None,
+            })
+            .collect();
+        let mut absorbed = HashSet::new();
+        plan.steps = plan
+            .steps
+            .drain(..)
+            .filter_map(|step| {
+                let (name, node) = match step {
+                    MatchStep::LoadOriginEdge { name, node }
+                    | MatchStep::LoadTargetEdge { name, node }
+                    | MatchStep::LoadEitherEdge { name, node } => (name, node),
+                    MatchStep::Filter(Filter::EdgeHasLabel { edge, label })
+                        if absorbed.contains(&edge)
+                            && edge_label_checks.get(&edge) == Some(&label) =>
+                    {
+                        edge_label_checks.remove(&edge);
+                        return None;
+                    }
+                    step => return Some(step),
+                };
+                let label = match edge_label_checks.get(&name) {
+                    Some(label) => label.clone(),
+                    None => return Some(step),
+                };
+                changed = true;
+                absorbed.insert(name);
+                Some(match step {
+                    MatchStep::LoadOriginEdge { .. } => {
+                        MatchStep::LoadLabeledOriginEdge { name, node, label }
+                    }
+                    MatchStep::LoadTargetEdge { .. } => {
+                        MatchStep::LoadLabeledTargetEdge { name, node, label }
+                    }
+                    _ => MatchStep::LoadLabeledEitherEdge { name, node, label },
+                })
+            })
+            .collect();
+        Ok(changed)
+    }
+}
@@ -1,6 +1,11 @@
-use super::QueryPlan;
+use super::{Collect, MatchStep, QueryPlan, UpdateStep};
+use crate::store::Schema;
 use crate::Error;
 
+mod constants;
+mod cost;
+mod filters;
+mod joins;
 mod loads;
 mod normalize;
 #[cfg(test)]
@@ -26,13 +31,44 @@ pub(crate) trait Optimization {
 }
 
 impl<'src> QueryPlan<'src> {
-    pub fn optimize(mut self) -> Result<Self, Error> {
+    pub fn optimize(mut self, schema: &Schema) -> Result<Self, Error> {
+        for step in &mut self.steps {
+            if let MatchStep::Subquery { plan, .. } | MatchStep::Collect(Collect { plan, .. }) =
+                step
+            {
+                optimize_nested(plan, schema)?;
+            }
+        }
+        for update in &mut self.updates {
+            if let UpdateStep::Foreach { plan, .. } | UpdateStep::Collect(Collect { plan, .. }) =
+                update
+            {
+                optimize_nested(plan, schema)?;
+            }
+        }
         normalize::SplitTopLevelAnd::fix(&mut self)?;
         normalize::CanonicalizeCheckNodeLabel::apply(&mut self)?;
+        constants::FoldConstants::apply(&mut self)?;
         normalize::MergeDuplicateUpdates::apply(&mut self)?;
         loads::ReorderIdConstrainedFirst::fix(&mut self)?;
+        filters::PushDownFilters::apply(&mut self)?;
+        cost::ReorderByCost(schema).apply(&mut self)?;
         loads::LoadAnyToLoadExact::apply(&mut self)?;
         loads::LoadAnyToLoadLabeled::apply(&mut self)?;
+        loads::LoadLabeledToLoadIndexed(schema).apply(&mut self)?;
+        loads::LoadLabeledToLoadSpatial(schema).apply(&mut self)?;
+        loads::LoadEdgeToLoadLabeledEdge::apply(&mut self)?;
+        joins::HashJoinDisconnected::fix(&mut self)?;
         Ok(self)
     }
 }
+
+fn optimize_nested(plan: &mut QueryPlan, schema: &Schema) -> Result<(), Error> {
+    let empty = QueryPlan {
+        steps: Vec::new(),
+        updates: Vec::new(),
+        returns: Vec::new(),
+    };
+    *plan = std::mem::replace(plan, empty).optimize(schema)?;
+    Ok(())
+}
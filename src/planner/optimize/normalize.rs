@@ -50,7 +50,10 @@ impl Optimization for CanonicalizeCheckNodeLabel {
                         LoadProperty::LabelOfNode { node },
                     ) => {
                         changed = true;
-                        MatchStep::Filter(Filter::NodeHasLabel { node: *node, label })
+                        MatchStep::Filter(Filter::NodeHasLabel {
+                            node: *node,
+                            label: LoadProperty::text(label),
+                        })
                     }
                     _ => step,
                 },
@@ -63,6 +66,7 @@ impl Optimization for CanonicalizeCheckNodeLabel {
 
 /// Combine sets for the same node/ edge and property into a single
 /// set. Combine deletes for the same node/ edge into a single delete.
+/// Sets of keys which are only known at run time are not combined.
 pub(crate) struct MergeDuplicateUpdates;
 
 impl Optimization for MergeDuplicateUpdates {
@@ -92,10 +96,14 @@ impl Optimization for MergeDuplicateUpdates {
             .rev()
             .filter(|update| match update {
                 UpdateStep::SetNodeProperty {
-                    node: name, key, ..
+                    node: name,
+                    key: LoadProperty::Constant(PropRef::Text(key)),
+                    ..
                 }
                 | UpdateStep::SetEdgeProperty {
-                    edge: name, key, ..
+                    edge: name,
+                    key: LoadProperty::Constant(PropRef::Text(key)),
+                    ..
                 } => {
                     let pair = (*name, *key);
                     if seen_sets.contains(&pair) {
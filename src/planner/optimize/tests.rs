@@ -1,6 +1,8 @@
 use super::*;
+use crate::function::Function;
 use crate::planner::{Filter, LoadProperty, MatchStep, UpdateStep};
-use crate::store::PropRef;
+use crate::store::{Counter, Index, IndexKind, PropRef, Region, Schema};
+use std::ops::Bound;
 
 #[test]
 fn simplify_top_level_and() {
@@ -59,11 +61,11 @@ fn canonicalize_check_node_label() {
             MatchStep::LoadAnyNode { name: 0 },
             MatchStep::Filter(Filter::NodeHasLabel {
                 node: 0,
-                label: "LABEL",
+                label: LoadProperty::text("LABEL"),
             }),
             MatchStep::Filter(Filter::NodeHasLabel {
                 node: 0,
-                label: "LABEL",
+                label: LoadProperty::text("LABEL"),
             }),
         ],
         updates: vec![],
@@ -81,18 +83,18 @@ fn simplify_merge_sets() {
         updates: vec![
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "foo",
+                key: LoadProperty::text("foo"),
                 value: LoadProperty::Parameter { name: "foo" },
             },
             UpdateStep::DeleteEdge { edge: 1 },
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "foo",
+                key: LoadProperty::text("foo"),
                 value: LoadProperty::Parameter { name: "bar" },
             },
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "foo",
+                key: LoadProperty::text("foo"),
                 value: LoadProperty::Parameter { name: "baz" },
             },
             UpdateStep::DeleteEdge { edge: 1 },
@@ -105,7 +107,7 @@ fn simplify_merge_sets() {
             UpdateStep::DeleteEdge { edge: 1 },
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "foo",
+                key: LoadProperty::text("foo"),
                 value: LoadProperty::Parameter { name: "baz" },
             },
         ],
@@ -217,3 +219,601 @@ fn load_any_node_to_load_exact_node() {
     loads::LoadAnyToLoadExact::apply(&mut plan_before).unwrap();
     assert_eq!(plan_before, plan_after);
 }
+
+#[test]
+fn load_labeled_node_to_load_indexed_node() {
+    let property = |node| LoadProperty::PropertyOfNode {
+        node,
+        key: Box::new(LoadProperty::text("name")),
+    };
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledNode {
+                name: 0,
+                label: LoadProperty::text("PERSON"),
+            },
+            MatchStep::LoadLabeledNode {
+                name: 1,
+                label: LoadProperty::text("PERSON"),
+            },
+            MatchStep::LoadLabeledNode {
+                name: 2,
+                label: LoadProperty::text("ROBOT"),
+            },
+            MatchStep::Filter(Filter::Eq(property(0), property(1))),
+            MatchStep::Filter(Filter::Lt(
+                LoadProperty::Parameter { name: "name" },
+                property(1),
+            )),
+            MatchStep::Filter(Filter::Eq(
+                property(2),
+                LoadProperty::Parameter { name: "name" },
+            )),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledNode {
+                name: 0,
+                label: LoadProperty::text("PERSON"),
+            },
+            MatchStep::LoadIndexedNode {
+                name: 1,
+                label: LoadProperty::text("PERSON"),
+                key: LoadProperty::text("name"),
+                lower: Bound::Excluded(LoadProperty::Parameter { name: "name" }),
+                upper: Bound::Unbounded,
+            },
+            MatchStep::LoadLabeledNode {
+                name: 2,
+                label: LoadProperty::text("ROBOT"),
+            },
+            MatchStep::Filter(Filter::Eq(property(0), property(1))),
+            MatchStep::Filter(Filter::Eq(
+                property(2),
+                LoadProperty::Parameter { name: "name" },
+            )),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    let mut schema = Schema::default();
+    schema
+        .indexes
+        .insert(Index::new("PERSON", "name"), IndexKind::Plain);
+    loads::LoadLabeledToLoadIndexed(&schema)
+        .apply(&mut plan_before)
+        .unwrap();
+    assert_eq!(plan_before, plan_after);
+}
+
+#[test]
+fn load_indexed_range() {
+    let age = || LoadProperty::PropertyOfNode {
+        node: 0,
+        key: Box::new(LoadProperty::text("age")),
+    };
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledNode {
+                name: 0,
+                label: LoadProperty::text("PERSON"),
+            },
+            MatchStep::Filter(Filter::Ge(age(), LoadProperty::Parameter { name: "min" })),
+            MatchStep::Filter(Filter::Gt(age(), LoadProperty::Parameter { name: "other" })),
+            MatchStep::Filter(Filter::Lt(LoadProperty::Parameter { name: "max" }, age())),
+            MatchStep::Filter(Filter::Le(age(), LoadProperty::Parameter { name: "max" })),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadIndexedNode {
+                name: 0,
+                label: LoadProperty::text("PERSON"),
+                key: LoadProperty::text("age"),
+                lower: Bound::Included(LoadProperty::Parameter { name: "min" }),
+                upper: Bound::Included(LoadProperty::Parameter { name: "max" }),
+            },
+            MatchStep::Filter(Filter::Gt(age(), LoadProperty::Parameter { name: "other" })),
+            MatchStep::Filter(Filter::Lt(LoadProperty::Parameter { name: "max" }, age())),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    let mut schema = Schema::default();
+    schema
+        .indexes
+        .insert(Index::new("PERSON", "age"), IndexKind::Plain);
+    loads::LoadLabeledToLoadIndexed(&schema)
+        .apply(&mut plan_before)
+        .unwrap();
+    assert_eq!(plan_before, plan_after);
+}
+
+#[test]
+fn load_labeled_node_to_load_spatial_node() {
+    let at = |node: usize| LoadProperty::PropertyOfNode {
+        node,
+        key: Box::new(LoadProperty::text("at")),
+    };
+    let call = |function: Function, arguments: Vec<LoadProperty<'static>>| LoadProperty::Function {
+        function,
+        arguments,
+    };
+    let site = |name: usize| MatchStep::LoadLabeledNode {
+        name,
+        label: LoadProperty::text("SITE"),
+    };
+    let distance = Filter::Le(
+        call(
+            Function::Distance,
+            vec![LoadProperty::Parameter { name: "center" }, at(0)],
+        ),
+        LoadProperty::Parameter { name: "radius" },
+    );
+    let within = Filter::IsTruthy(call(
+        Function::WithinBBox,
+        vec![
+            at(1),
+            LoadProperty::Parameter { name: "ll" },
+            LoadProperty::Parameter { name: "ur" },
+        ],
+    ));
+    // the region must not depend on the match
+    let dependent = Filter::Lt(
+        call(Function::Distance, vec![at(2), at(0)]),
+        LoadProperty::Parameter { name: "radius" },
+    );
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            site(0),
+            MatchStep::Filter(distance.clone()),
+            site(1),
+            MatchStep::Filter(within.clone()),
+            site(2),
+            MatchStep::Filter(dependent.clone()),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadSpatialNode {
+                name: 0,
+                label: LoadProperty::text("SITE"),
+                key: LoadProperty::text("at"),
+                region: Region::Circle {
+                    center: LoadProperty::Parameter { name: "center" },
+                    radius: LoadProperty::Parameter { name: "radius" },
+                },
+            },
+            MatchStep::Filter(distance),
+            MatchStep::LoadSpatialNode {
+                name: 1,
+                label: LoadProperty::text("SITE"),
+                key: LoadProperty::text("at"),
+                region: Region::Box {
+                    lower_left: LoadProperty::Parameter { name: "ll" },
+                    upper_right: LoadProperty::Parameter { name: "ur" },
+                },
+            },
+            MatchStep::Filter(within),
+            site(2),
+            MatchStep::Filter(dependent),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    let mut schema = Schema::default();
+    schema
+        .indexes
+        .insert(Index::new("SITE", "at"), IndexKind::Plain);
+    loads::LoadLabeledToLoadSpatial(&schema)
+        .apply(&mut plan_before)
+        .unwrap();
+    assert_eq!(plan_before, plan_after);
+}
+
+#[test]
+fn reorder_by_cost() {
+    // (a:PERSON) -[:KNOWS]-> (b:ROBOT)
+    let plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 0,
+                label: LoadProperty::text("PERSON"),
+            }),
+            MatchStep::LoadOriginEdge { name: 1, node: 0 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 1,
+                label: LoadProperty::text("KNOWS"),
+            }),
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 2,
+                label: LoadProperty::text("ROBOT"),
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 2 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 2,
+                label: LoadProperty::text("ROBOT"),
+            }),
+            MatchStep::LoadTargetEdge { name: 1, node: 2 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 1,
+                label: LoadProperty::text("KNOWS"),
+            }),
+            MatchStep::LoadOriginNode { name: 0, edge: 1 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 0,
+                label: LoadProperty::text("PERSON"),
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    // without statistics the order is kept
+    let mut plan = plan_before.clone();
+    assert!(!cost::ReorderByCost(&Schema::default())
+        .apply(&mut plan)
+        .unwrap());
+    assert_eq!(plan, plan_before);
+
+    let mut schema = Schema::default();
+    let statistics = &mut schema.statistics;
+    statistics.insert(Counter::Nodes("PERSON".into()), 1000);
+    statistics.insert(Counter::Nodes("ROBOT".into()), 2);
+    statistics.insert(Counter::Edges("KNOWS".into()), 1000);
+    statistics.insert(Counter::Origins("KNOWS".into(), "PERSON".into()), 1000);
+    statistics.insert(Counter::Targets("KNOWS".into(), "PERSON".into()), 990);
+    statistics.insert(Counter::Targets("KNOWS".into(), "ROBOT".into()), 10);
+
+    let mut plan = plan_before;
+    assert!(cost::ReorderByCost(&schema).apply(&mut plan).unwrap());
+    assert_eq!(plan, plan_after);
+}
+
+#[test]
+fn push_down_filters() {
+    let property = |node| LoadProperty::PropertyOfNode {
+        node,
+        key: Box::new(LoadProperty::text("name")),
+    };
+    let name = |node, name| Filter::Eq(property(node), LoadProperty::text(name));
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::LoadOriginEdge { name: 1, node: 0 },
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::Filter(Filter::not(Filter::or(name(0, "a"), name(2, "b")))),
+            MatchStep::Filter(Filter::or(
+                Filter::and(name(0, "a"), name(2, "b")),
+                name(0, "c"),
+            )),
+            MatchStep::Filter(Filter::Eq(
+                LoadProperty::Parameter { name: "x" },
+                LoadProperty::Parameter { name: "y" },
+            )),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::Filter(Filter::Eq(
+                LoadProperty::Parameter { name: "x" },
+                LoadProperty::Parameter { name: "y" },
+            )),
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::not(name(0, "a"))),
+            MatchStep::Filter(Filter::or(name(0, "a"), name(0, "c"))),
+            MatchStep::LoadOriginEdge { name: 1, node: 0 },
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::Filter(Filter::not(name(2, "b"))),
+            MatchStep::Filter(Filter::or(name(2, "b"), name(0, "c"))),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    assert!(filters::PushDownFilters::apply(&mut plan_before).unwrap());
+    assert_eq!(plan_before, plan_after);
+    assert!(!filters::PushDownFilters::apply(&mut plan_before).unwrap());
+}
+
+#[test]
+fn fold_constants() {
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::Eq(
+                LoadProperty::Constant(PropRef::Integer(1)),
+                LoadProperty::Constant(PropRef::Real(1.0)),
+            )),
+            MatchStep::Filter(Filter::or(
+                Filter::Lt(
+                    LoadProperty::Constant(PropRef::Integer(2)),
+                    LoadProperty::Constant(PropRef::Integer(1)),
+                ),
+                Filter::IsTruthy(LoadProperty::PropertyOfNode {
+                    node: 0,
+                    key: Box::new(LoadProperty::text("done")),
+                }),
+            )),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::IsTruthy(LoadProperty::PropertyOfNode {
+                node: 0,
+                key: Box::new(LoadProperty::text("done")),
+            })),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    assert!(constants::FoldConstants::apply(&mut plan_before).unwrap());
+    assert_eq!(plan_before, plan_after);
+}
+
+#[test]
+fn fold_contradicting_ids() {
+    let mut plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::NodeHasId {
+                node: 0,
+                id: LoadProperty::Constant(PropRef::Integer(3)),
+            }),
+            MatchStep::Filter(Filter::NodeHasId {
+                node: 0,
+                id: LoadProperty::Constant(PropRef::Integer(4)),
+            }),
+        ],
+        updates: vec![UpdateStep::DeleteNode { node: 0 }],
+        returns: vec![LoadProperty::IdOfNode { node: 0 }],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![MatchStep::Filter(Filter::never())],
+        updates: vec![],
+        returns: vec![LoadProperty::IdOfNode { node: 0 }],
+    };
+
+    assert!(constants::FoldConstants::apply(&mut plan_before).unwrap());
+    assert_eq!(plan_before, plan_after);
+    assert!(plan_before.is_empty());
+    assert!(!constants::FoldConstants::apply(&mut plan_before).unwrap());
+}
+
+#[test]
+fn assume_parameter_conditions() {
+    let condition = Filter::Eq(
+        LoadProperty::Parameter { name: "p" },
+        LoadProperty::Constant(PropRef::Integer(1)),
+    );
+    let plan = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::or(
+                condition.clone(),
+                Filter::NodeHasId {
+                    node: 0,
+                    id: LoadProperty::Parameter { name: "id" },
+                },
+            )),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    assert_eq!(plan.conditions(), vec![condition.clone()]);
+
+    let mut holds = plan.clone().assume(std::slice::from_ref(&condition), 1);
+    constants::FoldConstants::apply(&mut holds).unwrap();
+    assert_eq!(holds.steps, vec![MatchStep::LoadAnyNode { name: 0 }]);
+
+    let mut fails = plan.assume(&[condition], 0);
+    constants::FoldConstants::apply(&mut fails).unwrap();
+    assert_eq!(
+        fails.steps,
+        vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::NodeHasId {
+                node: 0,
+                id: LoadProperty::Parameter { name: "id" },
+            }),
+        ]
+    );
+}
+
+#[test]
+fn reorder_by_cost_from_edge_label() {
+    // (a:PERSON) -[:PAYMENT]-> (b:COMPANY)
+    let plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 0,
+                label: LoadProperty::text("PERSON"),
+            }),
+            MatchStep::LoadOriginEdge { name: 1, node: 0 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 1,
+                label: LoadProperty::text("PAYMENT"),
+            }),
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 2,
+                label: LoadProperty::text("COMPANY"),
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledEdge {
+                name: 1,
+                label: LoadProperty::text("PAYMENT"),
+            },
+            MatchStep::LoadOriginNode { name: 0, edge: 1 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 0,
+                label: LoadProperty::text("PERSON"),
+            }),
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::Filter(Filter::NodeHasLabel {
+                node: 2,
+                label: LoadProperty::text("COMPANY"),
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    let mut schema = Schema::default();
+    let statistics = &mut schema.statistics;
+    statistics.insert(Counter::Nodes("PERSON".into()), 1000);
+    statistics.insert(Counter::Nodes("COMPANY".into()), 100);
+    statistics.insert(Counter::Edges("KNOWS".into()), 5000);
+    statistics.insert(Counter::Edges("PAYMENT".into()), 5);
+    statistics.insert(Counter::Origins("KNOWS".into(), "PERSON".into()), 5000);
+    statistics.insert(Counter::Targets("KNOWS".into(), "PERSON".into()), 5000);
+    statistics.insert(Counter::Origins("PAYMENT".into(), "PERSON".into()), 5);
+    statistics.insert(Counter::Targets("PAYMENT".into(), "COMPANY".into()), 5);
+
+    let mut plan = plan_before;
+    assert!(cost::ReorderByCost(&schema).apply(&mut plan).unwrap());
+    assert_eq!(plan, plan_after);
+}
+
+#[test]
+fn load_labeled_adjacent_edges() {
+    let plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::LoadOriginEdge { name: 1, node: 0 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 1,
+                label: LoadProperty::text("KNOWS"),
+            }),
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::LoadEitherEdge { name: 3, node: 2 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 3,
+                label: LoadProperty::LabelOfNode { node: 2 },
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadAnyNode { name: 0 },
+            MatchStep::LoadLabeledOriginEdge {
+                name: 1,
+                node: 0,
+                label: LoadProperty::text("KNOWS"),
+            },
+            MatchStep::LoadTargetNode { name: 2, edge: 1 },
+            MatchStep::LoadEitherEdge { name: 3, node: 2 },
+            MatchStep::Filter(Filter::EdgeHasLabel {
+                edge: 3,
+                label: LoadProperty::LabelOfNode { node: 2 },
+            }),
+        ],
+        updates: vec![],
+        returns: vec![],
+    };
+
+    let mut plan = plan_before;
+    assert!(loads::LoadEdgeToLoadLabeledEdge::apply(&mut plan).unwrap());
+    assert_eq!(plan, plan_after);
+    assert!(!loads::LoadEdgeToLoadLabeledEdge::apply(&mut plan).unwrap());
+}
+
+#[test]
+fn hash_join_disconnected() {
+    // MATCH (a:USER) MATCH (b:TICKET) WHERE b.owner = a.login
+    let key = |node, key| LoadProperty::PropertyOfNode {
+        node,
+        key: Box::new(LoadProperty::text(key)),
+    };
+    let owner = Filter::Eq(key(1, "owner"), key(0, "login"));
+    let plan_before = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledNode {
+                name: 0,
+                label: LoadProperty::text("USER"),
+            },
+            MatchStep::LoadLabeledNode {
+                name: 1,
+                label: LoadProperty::text("TICKET"),
+            },
+            MatchStep::Filter(Filter::IsTruthy(key(1, "open"))),
+            MatchStep::Filter(owner.clone()),
+        ],
+        updates: vec![],
+        returns: vec![LoadProperty::IdOfNode { node: 1 }],
+    };
+    let plan_after = QueryPlan {
+        steps: vec![
+            MatchStep::LoadLabeledNode {
+                name: 0,
+                label: LoadProperty::text("USER"),
+            },
+            MatchStep::Join {
+                name: 2,
+                plan: Box::new(QueryPlan {
+                    steps: vec![
+                        MatchStep::LoadLabeledNode {
+                            name: 1,
+                            label: LoadProperty::text("TICKET"),
+                        },
+                        MatchStep::Filter(Filter::IsTruthy(key(1, "open"))),
+                    ],
+                    updates: vec![],
+                    returns: vec![key(1, "owner"), LoadProperty::Node { node: 1 }],
+                }),
+                keys: vec![key(0, "login")],
+            },
+            MatchStep::LoadExactNode {
+                name: 1,
+                id: LoadProperty::ColumnOfRow { row: 2, column: 1 },
+            },
+            MatchStep::Filter(owner.clone()),
+        ],
+        updates: vec![],
+        returns: vec![LoadProperty::IdOfNode { node: 1 }],
+    };
+
+    let mut plan = plan_before.clone();
+    assert!(!joins::HashJoinDisconnected::fix(&mut plan).unwrap());
+    assert_eq!(plan, plan_after);
+
+    // connected by an edge
+    let mut plan = plan_before;
+    plan.steps.insert(1, MatchStep::LoadOriginEdge { name: 3, node: 0 });
+    plan.steps.push(MatchStep::Filter(Filter::IsTarget { node: 1, edge: 3 }));
+    let connected = plan.clone();
+    assert!(!joins::HashJoinDisconnected::apply(&mut plan).unwrap());
+    assert_eq!(plan, connected);
+}
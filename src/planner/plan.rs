@@ -1,5 +1,10 @@
-use crate::store::PropRef;
+use crate::function::Function;
+use crate::parser::ast;
+use crate::procedure::Procedure;
+use crate::store::{PropRef, Region};
 use std::cmp::{Ordering, PartialOrd};
+use std::collections::BTreeSet;
+use std::ops::Bound;
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct QueryPlan<'src> {
@@ -8,6 +13,22 @@ pub(crate) struct QueryPlan<'src> {
     pub returns: Vec<LoadProperty<'src>>,
 }
 
+/// A column returned by a query, which is named by the
+/// returned variable or else by the returned expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Column<'src> {
+    /// A variable, or a column of a called procedure.
+    Name(String),
+    Expression(&'src ast::Expression<'src>),
+}
+
+impl<'src> QueryPlan<'src> {
+    /// Whether the plan was shown to have no matches.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.steps.as_slice(), [MatchStep::Filter(filter)] if *filter == Filter::never())
+    }
+}
+
 /// A step in the logical query plan. The execution model
 /// is to conceptually instantiate every combination of
 /// possible nodes in order (think nested loops).
@@ -18,7 +39,30 @@ pub(crate) struct QueryPlan<'src> {
 pub(crate) enum MatchStep<'src> {
     LoadAnyNode { name: usize },
     LoadExactNode { name: usize, id: LoadProperty<'src> },
-    LoadLabeledNode { name: usize, label: &'src str },
+    LoadExactEdge { name: usize, id: LoadProperty<'src> },
+    LoadLabeledNode { name: usize, label: LoadProperty<'src> },
+    /// Load nodes with `label`, where the property `key` lies between
+    /// `lower` and `upper`, using an index if possible. Nodes loaded
+    /// through an index are ordered by the property.
+    LoadIndexedNode {
+        name: usize,
+        label: LoadProperty<'src>,
+        key: LoadProperty<'src>,
+        lower: Bound<LoadProperty<'src>>,
+        upper: Bound<LoadProperty<'src>>,
+    },
+    /// Load nodes with `label`, where the property `key` may be a point
+    /// within `region`, using a spatial index if possible. The region
+    /// only narrows down the nodes, which still need to be filtered.
+    LoadSpatialNode {
+        name: usize,
+        label: LoadProperty<'src>,
+        key: LoadProperty<'src>,
+        region: Region<LoadProperty<'src>>,
+    },
+    /// Load edges with `label` using the edge label index,
+    /// which starts a pattern from its edges.
+    LoadLabeledEdge { name: usize, label: LoadProperty<'src> },
     LoadOriginNode { name: usize, edge: usize },
     LoadTargetNode { name: usize, edge: usize },
     LoadOtherNode { name: usize, node: usize, edge: usize },
@@ -26,10 +70,65 @@ pub(crate) enum MatchStep<'src> {
     LoadOriginEdge { name: usize, node: usize },
     LoadTargetEdge { name: usize, node: usize },
     LoadEitherEdge { name: usize, node: usize },
+    /// Load the edges with `label` which are connected to `node`,
+    /// using a range of the adjacency index.
+    LoadLabeledOriginEdge { name: usize, node: usize, label: LoadProperty<'src> },
+    LoadLabeledTargetEdge { name: usize, node: usize, label: LoadProperty<'src> },
+    LoadLabeledEitherEdge { name: usize, node: usize, label: LoadProperty<'src> },
+
+    Call { name: usize, procedure: Procedure, arguments: Vec<LoadProperty<'src>> },
+    Subquery { name: usize, plan: Box<QueryPlan<'src>>, arguments: Vec<LoadProperty<'src>> },
+    Collect(Collect<'src>),
+    /// Bind row `name` to each row returned by `plan` where the
+    /// first columns equal `keys`. The plan does not depend on the
+    /// match, such that it runs once to build a hash table on these
+    /// columns, which is probed for every match.
+    Join { name: usize, plan: Box<QueryPlan<'src>>, keys: Vec<LoadProperty<'src>> },
 
     Filter(Filter<'src>),
 }
 
+impl<'src> MatchStep<'src> {
+    /// The name of the node, edge or row loaded by the step.
+    pub fn name(&self) -> Option<usize> {
+        match self {
+            Self::LoadAnyNode { name }
+            | Self::LoadExactNode { name, .. }
+            | Self::LoadExactEdge { name, .. }
+            | Self::LoadLabeledNode { name, .. }
+            | Self::LoadIndexedNode { name, .. }
+            | Self::LoadSpatialNode { name, .. }
+            | Self::LoadLabeledEdge { name, .. }
+            | Self::LoadOriginNode { name, .. }
+            | Self::LoadTargetNode { name, .. }
+            | Self::LoadOtherNode { name, .. }
+            | Self::LoadOriginEdge { name, .. }
+            | Self::LoadTargetEdge { name, .. }
+            | Self::LoadEitherEdge { name, .. }
+            | Self::LoadLabeledOriginEdge { name, .. }
+            | Self::LoadLabeledTargetEdge { name, .. }
+            | Self::LoadLabeledEitherEdge { name, .. }
+            | Self::Call { name, .. }
+            | Self::Subquery { name, .. }
+            | Self::Collect(Collect { name, .. })
+            | Self::Join { name, .. } => Some(*name),
+            Self::Filter(_) => None,
+        }
+    }
+}
+
+/// Bind row `name` to a single column, which holds the list
+/// of values returned by `plan`. The plan runs once for each
+/// item of `list`, which is passed as the first argument, or
+/// once if there is no list. Used for comprehensions.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Collect<'src> {
+    pub name: usize,
+    pub list: Option<LoadProperty<'src>>,
+    pub plan: Box<QueryPlan<'src>>,
+    pub arguments: Vec<LoadProperty<'src>>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Filter<'src> {
     And(Box<Filter<'src>>, Box<Filter<'src>>),
@@ -39,8 +138,8 @@ pub(crate) enum Filter<'src> {
     IsOrigin { node: usize, edge: usize },
     IsTarget { node: usize, edge: usize },
 
-    NodeHasLabel { node: usize, label: &'src str },
-    EdgeHasLabel { edge: usize, label: &'src str },
+    NodeHasLabel { node: usize, label: LoadProperty<'src> },
+    EdgeHasLabel { edge: usize, label: LoadProperty<'src> },
 
     NodeHasId { node: usize, id: LoadProperty<'src> },
     EdgeHasId { edge: usize, id: LoadProperty<'src> },
@@ -49,7 +148,9 @@ pub(crate) enum Filter<'src> {
 
     Eq(LoadProperty<'src>, LoadProperty<'src>),
     Lt(LoadProperty<'src>, LoadProperty<'src>),
+    Le(LoadProperty<'src>, LoadProperty<'src>),
     Gt(LoadProperty<'src>, LoadProperty<'src>),
+    Ge(LoadProperty<'src>, LoadProperty<'src>),
 }
 
 impl<'src> Filter<'src> {
@@ -64,6 +165,49 @@ impl<'src> Filter<'src> {
     pub fn not(filter: Self) -> Self {
         Self::Not(Box::new(filter))
     }
+
+    /// A filter which never holds.
+    pub fn never() -> Self {
+        Self::IsTruthy(LoadProperty::Constant(PropRef::Boolean(false)))
+    }
+
+    /// The names of the nodes, edges and rows which
+    /// must be loaded before the filter can be checked.
+    pub fn dependencies(&self) -> BTreeSet<usize> {
+        let mut names = BTreeSet::new();
+        self.collect_dependencies(&mut names);
+        names
+    }
+
+    fn collect_dependencies(&self, names: &mut BTreeSet<usize>) {
+        match self {
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+                lhs.collect_dependencies(names);
+                rhs.collect_dependencies(names);
+            }
+            Self::Not(filter) => filter.collect_dependencies(names),
+            Self::IsOrigin { node, edge } | Self::IsTarget { node, edge } => {
+                names.insert(*node);
+                names.insert(*edge);
+            }
+            Self::NodeHasLabel { node: name, label: value }
+            | Self::EdgeHasLabel { edge: name, label: value }
+            | Self::NodeHasId { node: name, id: value }
+            | Self::EdgeHasId { edge: name, id: value } => {
+                names.insert(*name);
+                value.collect_dependencies(names);
+            }
+            Self::IsTruthy(value) => value.collect_dependencies(names),
+            Self::Eq(lhs, rhs)
+            | Self::Lt(lhs, rhs)
+            | Self::Le(lhs, rhs)
+            | Self::Gt(lhs, rhs)
+            | Self::Ge(lhs, rhs) => {
+                lhs.collect_dependencies(names);
+                rhs.collect_dependencies(names);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -73,33 +217,84 @@ pub(crate) enum LoadProperty<'src> {
     IdOfEdge { edge: usize },
     LabelOfNode { node: usize },
     LabelOfEdge { edge: usize },
-    PropertyOfNode { node: usize, key: &'src str },
-    PropertyOfEdge { edge: usize, key: &'src str },
+    PropertyOfNode { node: usize, key: Box<LoadProperty<'src>> },
+    PropertyOfEdge { edge: usize, key: Box<LoadProperty<'src>> },
     Parameter { name: &'src str },
+    Path { nodes: Vec<usize>, edges: Vec<usize> },
+    NodesOfPath { nodes: Vec<usize> },
+    EdgesOfPath { edges: Vec<usize> },
+    ColumnOfRow { row: usize, column: usize },
+    Node { node: usize },
+    Edge { edge: usize },
+    Argument { index: usize },
+    Function { function: Function, arguments: Vec<LoadProperty<'src>> },
+}
+
+impl<'src> LoadProperty<'src> {
+    /// A constant label or property key.
+    pub fn text(text: &'src str) -> Self {
+        Self::Constant(PropRef::Text(text))
+    }
+
+    /// The names of the nodes, edges and rows which
+    /// must be loaded before the value is known.
+    pub fn dependencies(&self) -> BTreeSet<usize> {
+        let mut names = BTreeSet::new();
+        self.collect_dependencies(&mut names);
+        names
+    }
+
+    fn collect_dependencies(&self, names: &mut BTreeSet<usize>) {
+        match self {
+            Self::Constant(_) | Self::Parameter { .. } | Self::Argument { .. } => (),
+            Self::IdOfNode { node: name }
+            | Self::IdOfEdge { edge: name }
+            | Self::LabelOfNode { node: name }
+            | Self::LabelOfEdge { edge: name }
+            | Self::ColumnOfRow { row: name, .. }
+            | Self::Node { node: name }
+            | Self::Edge { edge: name } => {
+                names.insert(*name);
+            }
+            Self::PropertyOfNode { node: name, key } | Self::PropertyOfEdge { edge: name, key } => {
+                names.insert(*name);
+                key.collect_dependencies(names);
+            }
+            Self::Path { nodes, edges } => names.extend(nodes.iter().chain(edges).copied()),
+            Self::NodesOfPath { nodes: names_of } | Self::EdgesOfPath { edges: names_of } => {
+                names.extend(names_of.iter().copied())
+            }
+            Self::Function { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_dependencies(names);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum UpdateStep<'src> {
     CreateNode {
         name: usize,
-        label: &'src str,
+        label: LoadProperty<'src>,
         properties: Vec<(&'src str, LoadProperty<'src>)>,
     },
     CreateEdge {
         name: usize,
-        label: &'src str,
+        label: LoadProperty<'src>,
         origin: usize,
         target: usize,
         properties: Vec<(&'src str, LoadProperty<'src>)>,
     },
     SetNodeProperty {
         node: usize,
-        key: &'src str,
+        key: LoadProperty<'src>,
         value: LoadProperty<'src>,
     },
     SetEdgeProperty {
         edge: usize,
-        key: &'src str,
+        key: LoadProperty<'src>,
         value: LoadProperty<'src>,
     },
     DeleteNode {
@@ -108,6 +303,17 @@ pub(crate) enum UpdateStep<'src> {
     DeleteEdge {
         edge: usize,
     },
+    /// Run the nested `plan` once for each item of `list`,
+    /// which is passed as the first argument, followed by
+    /// the given `arguments`.
+    Foreach {
+        list: LoadProperty<'src>,
+        plan: Box<QueryPlan<'src>>,
+        arguments: Vec<LoadProperty<'src>>,
+    },
+    /// Comprehensions used by the following update
+    /// or by the returns (if there are no more updates).
+    Collect(Collect<'src>),
 }
 
 impl<'src> PartialOrd for UpdateStep<'src> {
@@ -122,6 +328,7 @@ impl<'src> PartialOrd for UpdateStep<'src> {
             ) => Some(Ordering::Equal),
             (DeleteNode { .. }, DeleteNode { .. }) => Some(Ordering::Equal),
             (DeleteEdge { .. }, DeleteEdge { .. }) => Some(Ordering::Equal),
+            (Foreach { .. }, Foreach { .. }) => Some(Ordering::Equal),
 
             (CreateNode { .. }, CreateEdge { .. }) => Some(Ordering::Less),
             (CreateEdge { .. }, CreateNode { .. }) => Some(Ordering::Greater),
@@ -152,6 +359,28 @@ impl<'src> PartialOrd for UpdateStep<'src> {
 
             (DeleteEdge { .. }, DeleteNode { .. }) => Some(Ordering::Less),
             (DeleteNode { .. }, DeleteEdge { .. }) => Some(Ordering::Greater),
+
+            (
+                CreateNode { .. }
+                | CreateEdge { .. }
+                | SetNodeProperty { .. }
+                | SetEdgeProperty { .. },
+                Foreach { .. },
+            ) => Some(Ordering::Less),
+            (
+                Foreach { .. },
+                CreateNode { .. }
+                | CreateEdge { .. }
+                | SetNodeProperty { .. }
+                | SetEdgeProperty { .. },
+            ) => Some(Ordering::Greater),
+
+            (Foreach { .. }, DeleteNode { .. } | DeleteEdge { .. }) => Some(Ordering::Less),
+            (DeleteNode { .. } | DeleteEdge { .. }, Foreach { .. }) => Some(Ordering::Greater),
+
+            // comprehensions are kept in front of the update
+            // which uses them, instead of being ordered
+            (Collect(_), _) | (_, Collect(_)) => None,
         }
     }
 }
@@ -166,12 +395,12 @@ mod tests {
             UpdateStep::DeleteNode { node: 1 },
             UpdateStep::SetEdgeProperty {
                 edge: 0,
-                key: "test",
+                key: LoadProperty::text("test"),
                 value: LoadProperty::Parameter { name: "test" },
             },
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "test",
+                key: LoadProperty::text("test"),
                 value: LoadProperty::Parameter { name: "test" },
             },
             UpdateStep::DeleteEdge { edge: 2 },
@@ -181,12 +410,12 @@ mod tests {
         let steps_ord = vec![
             UpdateStep::SetEdgeProperty {
                 edge: 0,
-                key: "test",
+                key: LoadProperty::text("test"),
                 value: LoadProperty::Parameter { name: "test" },
             },
             UpdateStep::SetNodeProperty {
                 node: 0,
-                key: "test",
+                key: LoadProperty::text("test"),
                 value: LoadProperty::Parameter { name: "test" },
             },
             UpdateStep::DeleteEdge { edge: 2 },
@@ -0,0 +1,199 @@
+use crate::store::{NodeIter, PropOwned, StoreTxn};
+use crate::{Error, Property, Txn};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+pub(crate) type ProcedureFn =
+    dyn Fn(&Txn, &[Property]) -> Result<Vec<Vec<Property>>, Error> + Send + Sync;
+
+/// A procedure which can be invoked using
+/// `CALL name(arguments) YIELD columns`.
+#[derive(Clone)]
+pub(crate) struct Procedure {
+    pub name: String,
+    pub columns: Vec<String>,
+    call: Arc<ProcedureFn>,
+    /// The rows returned by a [constant][Procedure::constant]
+    /// procedure, which are stored with the procedure.
+    rows: Option<Vec<Vec<PropOwned>>>,
+}
+
+/// The set of procedures which can be called
+/// from queries against a graph.
+#[derive(Debug, Clone)]
+pub(crate) struct Procedures(HashMap<String, Procedure>);
+
+impl Procedure {
+    /// Returns the position of the column called `name`.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    /// Create a procedure which always returns the given rows.
+    pub fn constant(name: &str, columns: &[&str], rows: Vec<Vec<Property>>) -> Self {
+        let rows = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(Property::to_internal).collect())
+            .collect();
+        Self::with_rows(
+            name.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+            rows,
+        )
+    }
+
+    fn with_rows(name: String, columns: Vec<String>, rows: Vec<Vec<PropOwned>>) -> Self {
+        let returned = rows.clone();
+        Self {
+            name,
+            columns,
+            call: Arc::new(move |_, _| {
+                Ok(returned
+                    .iter()
+                    .map(|row| row.iter().cloned().map(PropOwned::to_external).collect())
+                    .collect())
+            }),
+            rows: Some(rows),
+        }
+    }
+
+    pub fn call(&self, txn: &StoreTxn, arguments: &[Property]) -> Result<Vec<Vec<Property>>, Error> {
+        (self.call)(Txn::from_store(txn), arguments)
+    }
+}
+
+impl fmt::Debug for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Procedure")
+            .field("name", &self.name)
+            .field("columns", &self.columns)
+            .finish()
+    }
+}
+
+/// Procedures are stored by name and columns, since their
+/// code can not be stored. Constant procedures also store
+/// their rows.
+impl Serialize for Procedure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.name, &self.columns, &self.rows).serialize(serializer)
+    }
+}
+
+/// Loaded procedures fail when called, until they are
+/// [resolved][Procedures::resolve] to a registered procedure.
+/// Constant procedures return their stored rows.
+impl<'de> Deserialize<'de> for Procedure {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (name, columns, rows) =
+            <(String, Vec<String>, Option<Vec<Vec<PropOwned>>>)>::deserialize(deserializer)?;
+        if let Some(rows) = rows {
+            return Ok(Self::with_rows(name, columns, rows));
+        }
+        let missing = name.clone();
+        Ok(Self {
+            name,
+            columns,
+            call: Arc::new(move |_, _| Err(Error::UnknownProcedure(missing.clone()))),
+            rows: None,
+        })
+    }
+}
+
+impl PartialEq for Procedure {
+    fn eq(&self, other: &Self) -> bool {
+        let same_call = match (&self.rows, &other.rows) {
+            (Some(rows), Some(other_rows)) => rows == other_rows,
+            (None, None) => Arc::ptr_eq(&self.call, &other.call),
+            _ => false,
+        };
+        self.name == other.name && self.columns == other.columns && same_call
+    }
+}
+
+impl Procedures {
+    /// Create a registry which contains the
+    /// built-in `db.*` procedures.
+    pub fn new() -> Self {
+        let mut procedures = Self(HashMap::new());
+        procedures.register("db.labels", &["label"], Arc::new(labels));
+        procedures.register(
+            "db.relationshipTypes",
+            &["relationshipType"],
+            Arc::new(relationship_types),
+        );
+        procedures.register("db.propertyKeys", &["propertyKey"], Arc::new(property_keys));
+        procedures
+    }
+
+    pub fn register(&mut self, name: &str, columns: &[&str], call: Arc<ProcedureFn>) {
+        let procedure = Procedure {
+            name: name.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            call,
+            rows: None,
+        };
+        self.0.insert(procedure.name.clone(), procedure);
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Procedure, Error> {
+        self.0
+            .get(name)
+            .ok_or_else(|| Error::UnknownProcedure(name.to_string()))
+    }
+
+    /// The registered procedure with the name of `procedure`,
+    /// which must return the same columns. Constant procedures
+    /// are not registered and resolve to themselves.
+    pub fn resolve(&self, procedure: &Procedure) -> Result<Procedure, Error> {
+        if procedure.rows.is_some() {
+            return Ok(procedure.clone());
+        }
+        let registered = self.get(&procedure.name)?;
+        if registered.columns == procedure.columns {
+            Ok(registered.clone())
+        } else {
+            Err(Error::SchemaMismatch)
+        }
+    }
+}
+
+impl Default for Procedures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn rows(values: BTreeSet<String>) -> Vec<Vec<Property>> {
+    values
+        .into_iter()
+        .map(|value| vec![Property::Text(value)])
+        .collect()
+}
+
+fn labels(txn: &Txn, _: &[Property]) -> Result<Vec<Vec<Property>>, Error> {
+    Ok(rows(txn.0.node_labels()?))
+}
+
+fn relationship_types(txn: &Txn, _: &[Property]) -> Result<Vec<Vec<Property>>, Error> {
+    let mut types = BTreeSet::new();
+    for id in txn.0.edge_ids()? {
+        let edge = txn.0.load_edge(id)?.ok_or(Error::MissingEdge)?;
+        types.insert(edge.label);
+    }
+    Ok(rows(types))
+}
+
+fn property_keys(txn: &Txn, _: &[Property]) -> Result<Vec<Vec<Property>>, Error> {
+    let mut keys = BTreeSet::new();
+    for node in NodeIter::all(&txn.0)? {
+        keys.extend(node?.properties.into_keys());
+    }
+    for id in txn.0.edge_ids()? {
+        let edge = txn.0.load_edge(id)?.ok_or(Error::MissingEdge)?;
+        keys.extend(edge.properties.into_keys());
+    }
+    Ok(rows(keys))
+}
@@ -1,3 +1,6 @@
+use crate::store::spatial::Point;
+use crate::store::temporal::{Date, DateTime, Duration, LocalDateTime};
+use crate::store::types::{Edge, Node, Path};
 use crate::store::PropOwned;
 use crate::Error;
 use std::convert::{TryFrom, TryInto};
@@ -12,6 +15,15 @@ pub enum Property {
     Text(String),
     Blob(Vec<u8>),
     Null,
+    List(Vec<Property>),
+    Path(Path),
+    Node(Node),
+    Edge(Edge),
+    Date(Date),
+    LocalDateTime(LocalDateTime),
+    DateTime(DateTime),
+    Duration(Duration),
+    Point(Point),
 }
 
 impl PropOwned {
@@ -24,6 +36,15 @@ impl PropOwned {
             Self::Text(text) => Property::Text(text),
             Self::Blob(bytes) => Property::Blob(bytes),
             Self::Null => Property::Null,
+            Self::List(items) => Property::List(items.into_iter().map(Self::to_external).collect()),
+            Self::Path(path) => Property::Path(path),
+            Self::Node(node) => Property::Node(node),
+            Self::Edge(edge) => Property::Edge(edge),
+            Self::Date(date) => Property::Date(date),
+            Self::LocalDateTime(date_time) => Property::LocalDateTime(date_time),
+            Self::DateTime(date_time) => Property::DateTime(date_time),
+            Self::Duration(duration) => Property::Duration(duration),
+            Self::Point(point) => Property::Point(point),
         }
     }
 }
@@ -38,6 +59,15 @@ impl Property {
             Self::Text(text) => PropOwned::Text(text),
             Self::Blob(bytes) => PropOwned::Blob(bytes),
             Self::Null => PropOwned::Null,
+            Self::List(items) => PropOwned::List(items.into_iter().map(Self::to_internal).collect()),
+            Self::Path(path) => PropOwned::Path(path),
+            Self::Node(node) => PropOwned::Node(node),
+            Self::Edge(edge) => PropOwned::Edge(edge),
+            Self::Date(date) => PropOwned::Date(date),
+            Self::LocalDateTime(date_time) => PropOwned::LocalDateTime(date_time),
+            Self::DateTime(date_time) => PropOwned::DateTime(date_time),
+            Self::Duration(duration) => PropOwned::Duration(duration),
+            Self::Point(point) => PropOwned::Point(point),
         }
     }
 }
@@ -102,6 +132,15 @@ try_from!(f64, Real);
 try_from!(bool, Boolean);
 try_from!(String, Text);
 try_from!(Vec<u8>, Blob);
+try_from!(Vec<Property>, List);
+try_from!(Path, Path);
+try_from!(Node, Node);
+try_from!(Edge, Edge);
+try_from!(Date, Date);
+try_from!(LocalDateTime, LocalDateTime);
+try_from!(DateTime, DateTime);
+try_from!(Duration, Duration);
+try_from!(Point, Point);
 
 
 from!(i32, Integer);
@@ -1,4 +1,5 @@
 mod program;
+mod verify;
 mod vm;
 
 pub(crate) use program::Program;
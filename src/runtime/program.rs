@@ -1,25 +1,65 @@
-use crate::planner::{Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use crate::cache::Definitions;
+use crate::parser::ast::{Constraint, Statement};
+use crate::planner::{Collect, Filter, LoadProperty, MatchStep, QueryPlan, UpdateStep};
+use crate::procedure::{Procedure, Procedures};
 use crate::runtime::{Access, Instruction};
-use crate::Error;
+use crate::store::{Index, IndexKind, PropOwned, Rule, Schema};
+use crate::{Error, Property};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ops::Bound;
 
 const JUMP_PLACEHOLDER: usize = usize::MAX;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Programs with more conditions on parameters are not
+/// compiled in a variant for each of their outcomes.
+pub(super) const MAX_CONDITIONS: usize = 3;
+
+/// Stored programs start with these bytes, followed by the
+/// version of the format and of the engine which stored them.
+const MAGIC: &[u8; 4] = b"CQLP";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct Program {
     pub instructions: Vec<Instruction>,
     pub accesses: Vec<Access>,
     pub returns: Vec<Access>,
+    pub procedures: Vec<Procedure>,
+
+    /// Programs which run after this one finishes, for
+    /// queries combined using `UNION`.
+    pub unions: Vec<Program>,
+    /// Drop matches with the same return values as an
+    /// earlier match (`UNION` without `ALL`).
+    pub distinct: bool,
+    /// Program which is run to completion and profiled
+    /// before this program reports the results (`PROFILE`).
+    pub profile: Option<Box<Program>>,
+    /// Nested programs for `CALL { }` subqueries and
+    /// `FOREACH`, which run once for every match.
+    pub subqueries: Vec<Program>,
+    /// Programs which yield a single match if a condition that
+    /// only depends on parameters holds.
+    pub conditions: Vec<Program>,
+    /// The program re-optimized for each outcome of `conditions`,
+    /// where bit `i` of the index is the outcome of condition `i`.
+    /// Chosen in place of this program once the parameters are bound.
+    pub variants: Vec<Program>,
 }
 
 struct CompileEnv {
     names: HashMap<usize, usize>, // map names to stack position
     node_stack_len: usize,
     edge_stack_len: usize,
+    row_stack_len: usize,
 
     instructions: Vec<Instruction>,
     accesses: Vec<Access>,
     returns: Vec<Access>,
+    procedures: Vec<Procedure>,
+    subqueries: Vec<Program>,
 }
 
 impl CompileEnv {
@@ -28,10 +68,13 @@ impl CompileEnv {
             names: HashMap::new(),
             node_stack_len: 0,
             edge_stack_len: 0,
+            row_stack_len: 0,
 
             instructions: Vec::new(),
             accesses: Vec::new(),
             returns: Vec::new(),
+            procedures: Vec::new(),
+            subqueries: Vec::new(),
         }
     }
 
@@ -55,10 +98,24 @@ impl CompileEnv {
         self.edge_stack_len -= 1;
     }
 
+    fn push_row(&mut self, name: usize) {
+        self.names.insert(name, self.row_stack_len);
+        self.row_stack_len += 1;
+    }
+
+    fn pop_row(&mut self, name: usize) {
+        self.names.remove(&name);
+        self.row_stack_len -= 1;
+    }
+
     fn get_stack_idx(&self, name: usize) -> Result<usize, Error> {
         self.names.get(&name).copied().ok_or(Error::Internal)
     }
 
+    fn get_stack_indices(&self, names: &[usize]) -> Result<Vec<usize>, Error> {
+        names.iter().map(|name| self.get_stack_idx(*name)).collect()
+    }
+
     fn adjust_jumps(instructions: &mut [Instruction], from: usize, to: usize) {
         for inst in instructions {
             use Instruction::*;
@@ -66,7 +123,9 @@ impl CompileEnv {
                 Jump { jump }
                 | LoadNextNode { jump }
                 | LoadNextEdge { jump }
+                | LoadNextRow { jump }
                 | LoadExactNode { jump, .. }
+                | LoadExactEdge { jump, .. }
                 | CheckIsOrigin { jump, .. }
                 | CheckIsTarget { jump, .. }
                 | CheckNodeLabel { jump, .. }
@@ -76,7 +135,9 @@ impl CompileEnv {
                 | CheckTrue { jump, .. }
                 | CheckEq { jump, .. }
                 | CheckLt { jump, .. }
-                | CheckGt { jump, .. } => {
+                | CheckLe { jump, .. }
+                | CheckGt { jump, .. }
+                | CheckGe { jump, .. } => {
                     if *jump == from {
                         *jump = to;
                     }
@@ -86,20 +147,37 @@ impl CompileEnv {
                 | Halt
                 | IterNodes
                 | IterLabeledNodes { .. }
+                | IterIndexedNodes { .. }
+                | IterSpatialNodes { .. }
                 | IterOriginEdges { .. }
                 | IterTargetEdges { .. }
                 | IterBothEdges { .. }
+                | IterLabeledEdges { .. }
+                | IterLabeledOriginEdges { .. }
+                | IterLabeledTargetEdges { .. }
+                | IterLabeledBothEdges { .. }
+                | IterProcedure { .. }
+                | IterProfile
+                | IterSubquery { .. }
+                | IterJoin { .. }
+                | Collect { .. }
                 | LoadOriginNode { .. }
                 | LoadTargetNode { .. }
                 | LoadOtherNode { .. }
                 | PopNode
                 | PopEdge
+                | PopRow
                 | CreateNode { .. }
                 | CreateEdge { .. }
                 | SetNodeProperty { .. }
                 | SetEdgeProperty { .. }
                 | DeleteNode { .. }
-                | DeleteEdge { .. } => (),
+                | DeleteEdge { .. }
+                | CreateIndex { .. }
+                | DropIndex { .. }
+                | CreateRule { .. }
+                | DropRule { .. }
+                | Foreach { .. } => (),
             }
         }
     }
@@ -125,13 +203,44 @@ impl CompileEnv {
             }
             LoadProperty::PropertyOfNode { node, key } => {
                 let node = self.get_stack_idx(*node)?;
-                Access::NodeProperty(node, key.to_string())
+                Access::NodeProperty(node, Box::new(self.compile_access_raw(key)?))
             }
             LoadProperty::PropertyOfEdge { edge, key } => {
                 let edge = self.get_stack_idx(*edge)?;
-                Access::EdgeProperty(edge, key.to_string())
+                Access::EdgeProperty(edge, Box::new(self.compile_access_raw(key)?))
             }
             LoadProperty::Parameter { name } => Access::Parameter(name.to_string()),
+            LoadProperty::Path { nodes, edges } => Access::Path(
+                self.get_stack_indices(nodes)?,
+                self.get_stack_indices(edges)?,
+            ),
+            LoadProperty::NodesOfPath { nodes } => Access::NodeIds(self.get_stack_indices(nodes)?),
+            LoadProperty::EdgesOfPath { edges } => Access::EdgeIds(self.get_stack_indices(edges)?),
+            LoadProperty::ColumnOfRow { row, column } => {
+                let row = self.get_stack_idx(*row)?;
+                Access::Column(row, *column)
+            }
+            LoadProperty::Node { node } => Access::Node(self.get_stack_idx(*node)?),
+            LoadProperty::Edge { edge } => Access::Edge(self.get_stack_idx(*edge)?),
+            LoadProperty::Argument { index } => Access::Argument(*index),
+            LoadProperty::Function {
+                function,
+                arguments,
+            } => Access::Function(
+                *function,
+                arguments
+                    .iter()
+                    .map(|arg| self.compile_access_raw(arg))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn compile_bound(&mut self, bound: &Bound<LoadProperty>) -> Result<Bound<usize>, Error> {
+        Ok(match bound {
+            Bound::Included(load) => Bound::Included(self.compile_access(load)?),
+            Bound::Excluded(load) => Bound::Excluded(self.compile_access(load)?),
+            Bound::Unbounded => Bound::Unbounded,
         })
     }
 
@@ -150,6 +259,29 @@ impl CompileEnv {
         }
     }
 
+    /// Run the nested plan of a comprehension and push
+    /// the row with the collected list as `collect.name`.
+    fn compile_collect(&mut self, collect: &Collect) -> Result<(), Error> {
+        let list = collect
+            .list
+            .as_ref()
+            .map(|list| self.compile_access(list))
+            .transpose()?;
+        let arguments = collect
+            .arguments
+            .iter()
+            .map(|arg| self.compile_access(arg))
+            .collect::<Result<_, Error>>()?;
+        self.instructions.push(Instruction::Collect {
+            subquery: self.subqueries.len(),
+            list,
+            arguments,
+        });
+        self.subqueries.push(Program::new(&collect.plan)?);
+        self.push_row(collect.name);
+        Ok(())
+    }
+
     /// Uses `JUMP_PLACEHOLDER` as a place-holder for the failed condition jump to
     /// be replaced after the position is known.
     fn compile_filter(&mut self, plan: &QueryPlan, filter: &Filter) -> Result<(), Error> {
@@ -204,18 +336,20 @@ impl CompileEnv {
 
             Filter::NodeHasLabel { node, label } => {
                 let node = self.get_stack_idx(*node)?;
+                let label = self.compile_access(label)?;
                 self.instructions.push(Instruction::CheckNodeLabel {
                     jump: JUMP_PLACEHOLDER,
                     node,
-                    label: label.to_string(),
+                    label,
                 });
             }
             Filter::EdgeHasLabel { edge, label } => {
                 let edge = self.get_stack_idx(*edge)?;
+                let label = self.compile_access(label)?;
                 self.instructions.push(Instruction::CheckEdgeLabel {
                     jump: JUMP_PLACEHOLDER,
                     edge,
-                    label: label.to_string(),
+                    label,
                 });
             }
 
@@ -264,6 +398,15 @@ impl CompileEnv {
                     rhs,
                 });
             }
+            Filter::Le(lhs, rhs) => {
+                let lhs = self.compile_access(lhs)?;
+                let rhs = self.compile_access(rhs)?;
+                self.instructions.push(Instruction::CheckLe {
+                    jump: JUMP_PLACEHOLDER,
+                    lhs,
+                    rhs,
+                });
+            }
             Filter::Gt(lhs, rhs) => {
                 let lhs = self.compile_access(lhs)?;
                 let rhs = self.compile_access(rhs)?;
@@ -273,6 +416,15 @@ impl CompileEnv {
                     rhs,
                 });
             }
+            Filter::Ge(lhs, rhs) => {
+                let lhs = self.compile_access(lhs)?;
+                let rhs = self.compile_access(rhs)?;
+                self.instructions.push(Instruction::CheckGe {
+                    jump: JUMP_PLACEHOLDER,
+                    lhs,
+                    rhs,
+                });
+            }
         }
         Ok(())
     }
@@ -286,7 +438,7 @@ impl CompileEnv {
                     properties,
                 } => {
                     let create_node = Instruction::CreateNode {
-                        label: label.to_string(),
+                        label: self.compile_access(label)?,
                         properties: properties
                             .iter()
                             .map(|(key, load)| -> Result<_, Error> {
@@ -309,7 +461,7 @@ impl CompileEnv {
                     properties,
                 } => {
                     let create_edge = Instruction::CreateEdge {
-                        label: label.to_string(),
+                        label: self.compile_access(label)?,
                         origin: self.get_stack_idx(*origin)?,
                         target: self.get_stack_idx(*target)?,
                         properties: properties
@@ -328,22 +480,16 @@ impl CompileEnv {
                 }
                 UpdateStep::SetNodeProperty { node, key, value } => {
                     let node = self.get_stack_idx(*node)?;
+                    let key = self.compile_access(key)?;
                     let value = self.compile_access(value)?;
-                    self.instructions.push(Instruction::SetNodeProperty {
-                        node,
-                        key: key.to_string(),
-                        value,
-                    });
+                    self.instructions.push(Instruction::SetNodeProperty { node, key, value });
                     self.compile_update(plan, &updates[1..])
                 }
                 UpdateStep::SetEdgeProperty { edge, key, value } => {
                     let edge = self.get_stack_idx(*edge)?;
+                    let key = self.compile_access(key)?;
                     let value = self.compile_access(value)?;
-                    self.instructions.push(Instruction::SetEdgeProperty {
-                        edge,
-                        key: key.to_string(),
-                        value,
-                    });
+                    self.instructions.push(Instruction::SetEdgeProperty { edge, key, value });
                     self.compile_update(plan, &updates[1..])
                 }
                 UpdateStep::DeleteNode { node } => {
@@ -356,6 +502,31 @@ impl CompileEnv {
                     self.instructions.push(Instruction::DeleteEdge { edge });
                     self.compile_update(plan, &updates[1..])
                 }
+                UpdateStep::Foreach {
+                    list,
+                    plan: nested,
+                    arguments,
+                } => {
+                    let list = self.compile_access(list)?;
+                    let arguments = arguments
+                        .iter()
+                        .map(|arg| self.compile_access(arg))
+                        .collect::<Result<_, Error>>()?;
+                    self.instructions.push(Instruction::Foreach {
+                        subquery: self.subqueries.len(),
+                        list,
+                        arguments,
+                    });
+                    self.subqueries.push(Program::new(nested)?);
+                    self.compile_update(plan, &updates[1..])
+                }
+                UpdateStep::Collect(collect) => {
+                    self.compile_collect(collect)?;
+                    self.compile_update(plan, &updates[1..])?;
+                    self.pop_row(collect.name);
+                    self.instructions.push(Instruction::PopRow);
+                    Ok(())
+                }
             }
         } else {
             self.instructions.push(Instruction::Yield);
@@ -386,8 +557,36 @@ impl CompileEnv {
                     };
                 }
                 MatchStep::LoadLabeledNode { name, label } => {
-                    self.instructions.push(Instruction::IterLabeledNodes {
-                        label: label.to_string(),
+                    let label = self.compile_access(label)?;
+                    self.instructions
+                        .push(Instruction::IterLabeledNodes { label });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_node(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_node(*name);
+                    self.instructions.push(Instruction::PopNode);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextNode {
+                        jump: self.instructions.len(),
+                    };
+                }
+                MatchStep::LoadIndexedNode {
+                    name,
+                    label,
+                    key,
+                    lower,
+                    upper,
+                } => {
+                    let label = self.compile_access(label)?;
+                    let key = self.compile_access(key)?;
+                    let lower = self.compile_bound(lower)?;
+                    let upper = self.compile_bound(upper)?;
+                    self.instructions.push(Instruction::IterIndexedNodes {
+                        label,
+                        key,
+                        lower,
+                        upper,
                     });
                     self.instructions.push(Instruction::NoOp); // set after to calc jump
                     self.push_node(*name);
@@ -400,6 +599,43 @@ impl CompileEnv {
                         jump: self.instructions.len(),
                     };
                 }
+                MatchStep::LoadSpatialNode {
+                    name,
+                    label,
+                    key,
+                    region,
+                } => {
+                    let label = self.compile_access(label)?;
+                    let key = self.compile_access(key)?;
+                    let region = region.try_map(|value| self.compile_access(value))?;
+                    self.instructions
+                        .push(Instruction::IterSpatialNodes { label, key, region });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_node(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_node(*name);
+                    self.instructions.push(Instruction::PopNode);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextNode {
+                        jump: self.instructions.len(),
+                    };
+                }
+                MatchStep::LoadLabeledEdge { name, label } => {
+                    let label = self.compile_access(label)?;
+                    self.instructions
+                        .push(Instruction::IterLabeledEdges { label });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_edge(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_edge(*name);
+                    self.instructions.push(Instruction::PopEdge);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextEdge {
+                        jump: self.instructions.len(),
+                    };
+                }
                 MatchStep::LoadExactNode { name, id } => {
                     let id = self.compile_access(id)?;
                     self.instructions.push(Instruction::LoadExactNode {
@@ -417,6 +653,23 @@ impl CompileEnv {
                         end,
                     );
                 }
+                MatchStep::LoadExactEdge { name, id } => {
+                    let id = self.compile_access(id)?;
+                    self.instructions.push(Instruction::LoadExactEdge {
+                        jump: JUMP_PLACEHOLDER,
+                        id,
+                    });
+                    self.push_edge(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_edge(*name);
+                    self.instructions.push(Instruction::PopEdge);
+                    let end = self.instructions.len();
+                    Self::adjust_jumps(
+                        &mut self.instructions[start..=start],
+                        JUMP_PLACEHOLDER,
+                        end,
+                    );
+                }
                 MatchStep::LoadOriginNode { name, edge } => {
                     self.instructions.push(Instruction::LoadOriginNode {
                         edge: self.get_stack_idx(*edge)?,
@@ -461,6 +714,23 @@ impl CompileEnv {
                         jump: self.instructions.len(),
                     };
                 }
+                MatchStep::LoadLabeledOriginEdge { name, node, label } => {
+                    let label = self.compile_access(label)?;
+                    self.instructions.push(Instruction::IterLabeledOriginEdges {
+                        node: self.get_stack_idx(*node)?,
+                        label,
+                    });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_edge(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_edge(*name);
+                    self.instructions.push(Instruction::PopEdge);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextEdge {
+                        jump: self.instructions.len(),
+                    };
+                }
                 MatchStep::LoadTargetEdge { name, node } => {
                     self.instructions.push(Instruction::IterTargetEdges {
                         node: self.get_stack_idx(*node)?,
@@ -476,6 +746,23 @@ impl CompileEnv {
                         jump: self.instructions.len(),
                     };
                 }
+                MatchStep::LoadLabeledTargetEdge { name, node, label } => {
+                    let label = self.compile_access(label)?;
+                    self.instructions.push(Instruction::IterLabeledTargetEdges {
+                        node: self.get_stack_idx(*node)?,
+                        label,
+                    });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_edge(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_edge(*name);
+                    self.instructions.push(Instruction::PopEdge);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextEdge {
+                        jump: self.instructions.len(),
+                    };
+                }
                 MatchStep::LoadEitherEdge { name, node } => {
                     self.instructions.push(Instruction::IterBothEdges {
                         node: self.get_stack_idx(*node)?,
@@ -491,6 +778,105 @@ impl CompileEnv {
                         jump: self.instructions.len(),
                     };
                 }
+                MatchStep::LoadLabeledEitherEdge { name, node, label } => {
+                    let label = self.compile_access(label)?;
+                    self.instructions.push(Instruction::IterLabeledBothEdges {
+                        node: self.get_stack_idx(*node)?,
+                        label,
+                    });
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_edge(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_edge(*name);
+                    self.instructions.push(Instruction::PopEdge);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextEdge {
+                        jump: self.instructions.len(),
+                    };
+                }
+
+                MatchStep::Call {
+                    name,
+                    procedure,
+                    arguments,
+                } => {
+                    let arguments = arguments
+                        .iter()
+                        .map(|arg| self.compile_access(arg))
+                        .collect::<Result<_, Error>>()?;
+                    self.instructions.push(Instruction::IterProcedure {
+                        procedure: self.procedures.len(),
+                        arguments,
+                    });
+                    self.procedures.push(procedure.clone());
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_row(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_row(*name);
+                    self.instructions.push(Instruction::PopRow);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextRow {
+                        jump: self.instructions.len(),
+                    };
+                }
+                MatchStep::Subquery {
+                    name,
+                    plan: nested,
+                    arguments,
+                } => {
+                    let arguments = arguments
+                        .iter()
+                        .map(|arg| self.compile_access(arg))
+                        .collect::<Result<_, Error>>()?;
+                    self.instructions.push(Instruction::IterSubquery {
+                        subquery: self.subqueries.len(),
+                        arguments,
+                    });
+                    self.subqueries.push(Program::new(nested)?);
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_row(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_row(*name);
+                    self.instructions.push(Instruction::PopRow);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextRow {
+                        jump: self.instructions.len(),
+                    };
+                }
+                MatchStep::Join {
+                    name,
+                    plan: nested,
+                    keys,
+                } => {
+                    let keys = keys
+                        .iter()
+                        .map(|key| self.compile_access(key))
+                        .collect::<Result<_, Error>>()?;
+                    self.instructions.push(Instruction::IterJoin {
+                        subquery: self.subqueries.len(),
+                        keys,
+                    });
+                    self.subqueries.push(Program::new(nested)?);
+                    self.instructions.push(Instruction::NoOp); // set after to calc jump
+                    self.push_row(*name);
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_row(*name);
+                    self.instructions.push(Instruction::PopRow);
+                    self.instructions
+                        .push(Instruction::Jump { jump: start + 1 });
+                    self.instructions[start + 1] = Instruction::LoadNextRow {
+                        jump: self.instructions.len(),
+                    };
+                }
+                MatchStep::Collect(collect) => {
+                    self.compile_collect(collect)?;
+                    self.compile_step(plan, &steps[1..])?;
+                    self.pop_row(collect.name);
+                    self.instructions.push(Instruction::PopRow);
+                }
 
                 MatchStep::Filter(filter) => {
                     self.compile_filter(plan, filter)?;
@@ -515,12 +901,354 @@ impl Program {
     /// Compile a `QueryPlan` into a `Program`.
     pub fn new(plan: &QueryPlan) -> Result<Program, Error> {
         let mut env = CompileEnv::new();
-        env.compile_step(plan, &plan.steps)?;
+        if plan.is_empty() {
+            // none of the names are loaded, but
+            // the number of columns must match
+            env.returns = plan
+                .returns
+                .iter()
+                .map(|_| Access::Constant(PropOwned::Null))
+                .collect();
+        } else {
+            env.compile_step(plan, &plan.steps)?;
+        }
         env.instructions.push(Instruction::Halt);
         Ok(Program {
             instructions: env.instructions,
             accesses: env.accesses,
             returns: env.returns,
+            procedures: env.procedures,
+            unions: Vec::new(),
+            distinct: false,
+            profile: None,
+            subqueries: env.subqueries,
+            conditions: Vec::new(),
+            variants: Vec::new(),
         })
     }
+
+    /// Compile the optimized `plan`, together with a variant for
+    /// each outcome of the conditions in the plan which only
+    /// depend on parameters.
+    fn bound(plan: &QueryPlan, schema: &Schema) -> Result<Program, Error> {
+        Self::with_variants(plan, |plan| Self::new(&plan.optimize(schema)?))
+    }
+
+    /// Compile `plan` using `compile`, together with a variant compiled
+    /// from the plan assuming each outcome of its conditions. Plans with
+    /// more than `MAX_CONDITIONS` conditions only compile as they are.
+    fn with_variants<F>(plan: &QueryPlan, mut compile: F) -> Result<Program, Error>
+    where
+        F: FnMut(QueryPlan) -> Result<Program, Error>,
+    {
+        let mut program = compile(plan.clone())?;
+        let conditions = plan.conditions();
+        if !conditions.is_empty() && conditions.len() <= MAX_CONDITIONS {
+            for condition in &conditions {
+                program.conditions.push(Self::condition(condition)?);
+            }
+            for outcomes in 0..1 << conditions.len() {
+                program.variants.push(compile(plan.clone().assume(&conditions, outcomes))?);
+            }
+        }
+        Ok(program)
+    }
+
+    /// Compile a program which yields a single match
+    /// if `condition` holds.
+    fn condition(condition: &Filter) -> Result<Program, Error> {
+        Self::new(&QueryPlan {
+            steps: vec![MatchStep::Filter(condition.clone())],
+            updates: Vec::new(),
+            returns: Vec::new(),
+        })
+    }
+
+    /// Create a program which yields one match for each
+    /// row produced by `source`. The rows must have at
+    /// least `columns` values.
+    fn iter_rows(source: Instruction, columns: usize, procedures: Vec<Procedure>) -> Program {
+        Program {
+            instructions: vec![
+                source,
+                Instruction::LoadNextRow { jump: 5 },
+                Instruction::Yield,
+                Instruction::PopRow,
+                Instruction::Jump { jump: 1 },
+                Instruction::Halt,
+            ],
+            accesses: Vec::new(),
+            returns: (0..columns).map(|column| Access::Column(0, column)).collect(),
+            procedures,
+            unions: Vec::new(),
+            distinct: false,
+            profile: None,
+            subqueries: Vec::new(),
+            conditions: Vec::new(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// Compile a program which returns the optimized query plan
+    /// and the compiled instructions of `statement` as rows of
+    /// `(query, kind, index, detail)`. Like the statement, each
+    /// query is bound to the plan optimized for the outcomes of its
+    /// conditions on parameters.
+    fn explain(
+        statement: &Statement,
+        procedures: &Procedures,
+        schema: &Schema,
+    ) -> Result<Program, Error> {
+        // ensure the statement compiles as a whole
+        Self::compile(statement, procedures, schema)?;
+
+        let mut programs = statement
+            .queries()
+            .iter()
+            .enumerate()
+            .map(|(part, query)| {
+                let plan = QueryPlan::new(query, procedures)?;
+                Self::with_variants(&plan, |plan| Self::explain_plan(part, plan, schema))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut program = programs.remove(0);
+        program.unions = programs;
+        Ok(program)
+    }
+
+    /// Compile a program which returns the rows explaining `plan`,
+    /// which is the query with index `part` of the statement.
+    fn explain_plan(part: usize, plan: QueryPlan, schema: &Schema) -> Result<Program, Error> {
+        let mut rows = Vec::new();
+        let mut push = |kind: &str, items: Vec<String>| {
+            for (idx, detail) in items.into_iter().enumerate() {
+                rows.push(vec![
+                    Property::Integer(part as i64),
+                    Property::Text(kind.to_string()),
+                    Property::Integer(idx as i64),
+                    Property::Text(detail),
+                ]);
+            }
+        };
+        let conditions = plan.conditions().len();
+        if conditions > MAX_CONDITIONS {
+            push(
+                "note",
+                vec![format!(
+                    "not optimized for the outcomes of {} conditions on parameters, \
+                     at most {} are supported",
+                    conditions, MAX_CONDITIONS
+                )],
+            );
+        }
+        let plan = plan.optimize(schema)?;
+        let program = Self::new(&plan)?;
+        push("step", plan.steps.iter().map(|s| format!("{:?}", s)).collect());
+        push("update", plan.updates.iter().map(|u| format!("{:?}", u)).collect());
+        push("return", plan.returns.iter().map(|r| format!("{:?}", r)).collect());
+        push(
+            "instruction",
+            program.instructions.iter().map(|i| format!("{:?}", i)).collect(),
+        );
+
+        let columns = ["query", "kind", "index", "detail"];
+        let explain = Procedure::constant("explain", &columns, rows);
+        Ok(Self::iter_rows(
+            Instruction::IterProcedure {
+                procedure: 0,
+                arguments: Vec::new(),
+            },
+            columns.len(),
+            vec![explain],
+        ))
+    }
+
+    /// Compile a program which queues a single update
+    /// and returns no matches.
+    fn update(update: Instruction) -> Program {
+        Program {
+            instructions: vec![update, Instruction::Halt],
+            accesses: Vec::new(),
+            returns: Vec::new(),
+            procedures: Vec::new(),
+            unions: Vec::new(),
+            distinct: false,
+            profile: None,
+            subqueries: Vec::new(),
+            conditions: Vec::new(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// Compile a program which creates or drops `constraint`. Unique
+    /// and node key constraints are enforced by an index, the other
+    /// constraints are stored as rules.
+    fn constraint(constraint: &Constraint, create: bool) -> Program {
+        let owned = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        let rule = match constraint {
+            Constraint::Unique { label, keys } | Constraint::NodeKey { label, keys } => {
+                let index = Index::composite(label, keys);
+                let kind = match constraint {
+                    Constraint::Unique { .. } => IndexKind::Unique,
+                    _ => IndexKind::NodeKey,
+                };
+                return Self::update(if create {
+                    Instruction::CreateIndex { index, kind }
+                } else {
+                    Instruction::DropIndex { index, kind }
+                });
+            }
+            Constraint::Exists { edge, label, key } => Rule::Exists {
+                edge: *edge,
+                label: label.to_string(),
+                key: key.to_string(),
+            },
+            Constraint::Type {
+                edge,
+                label,
+                key,
+                kind,
+            } => Rule::Type {
+                edge: *edge,
+                label: label.to_string(),
+                key: key.to_string(),
+                kind: *kind,
+            },
+            Constraint::Keys { edge, label, keys } => Rule::Keys {
+                edge: *edge,
+                label: label.to_string(),
+                keys: owned(keys),
+            },
+            Constraint::Endpoints {
+                label,
+                origin,
+                target,
+            } => Rule::Endpoints {
+                label: label.to_string(),
+                origin: origin.to_string(),
+                target: target.to_string(),
+            },
+        };
+        Self::update(if create {
+            Instruction::CreateRule { rule }
+        } else {
+            Instruction::DropRule { rule }
+        })
+    }
+
+    /// Encode the program, which was compiled for `definitions`,
+    /// such that the same version of the engine can load it.
+    pub fn to_bytes(&self, definitions: &Definitions) -> Result<Vec<u8>, Error> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bincode::serialize_into(&mut bytes, env!("CARGO_PKG_VERSION"))?;
+        bincode::serialize_into(&mut bytes, definitions)?;
+        bincode::serialize_into(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Decode a program encoded by [`to_bytes`][Program::to_bytes], which
+    /// must have been compiled by this version of the engine for the
+    /// current `definitions`. Procedures are resolved by their name,
+    /// and programs which fail to [verify][Program::verify] are
+    /// rejected as corrupted.
+    pub fn from_bytes(
+        bytes: &[u8],
+        definitions: &Definitions,
+        procedures: &Procedures,
+    ) -> Result<Program, Error> {
+        let mut bytes = bytes.strip_prefix(&MAGIC[..]).ok_or(Error::Corruption)?;
+        let format = bytes.get(..4).ok_or(Error::Corruption)?;
+        if u32::from_be_bytes(format.try_into().unwrap()) != FORMAT_VERSION {
+            return Err(Error::VersionMismatch);
+        }
+        bytes = &bytes[4..];
+        // reading from a slice only fails if the bytes are invalid
+        fn decode<T: serde::de::DeserializeOwned>(bytes: &mut &[u8]) -> Result<T, Error> {
+            bincode::deserialize_from(bytes).map_err(|_| Error::Corruption)
+        }
+        let version: String = decode(&mut bytes)?;
+        if version != env!("CARGO_PKG_VERSION") {
+            return Err(Error::VersionMismatch);
+        }
+        let compiled_for: Definitions = decode(&mut bytes)?;
+        if compiled_for != *definitions {
+            return Err(Error::SchemaMismatch);
+        }
+        let mut program: Program = decode(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(Error::Corruption);
+        }
+        program.verify().map_err(|_| Error::Corruption)?;
+        program.resolve(procedures)?;
+        Ok(program)
+    }
+
+    /// Replace the procedures of this and all nested
+    /// programs with the registered procedures.
+    fn resolve(&mut self, procedures: &Procedures) -> Result<(), Error> {
+        for procedure in &mut self.procedures {
+            *procedure = procedures.resolve(procedure)?;
+        }
+        let nested = self
+            .unions
+            .iter_mut()
+            .chain(self.profile.as_deref_mut())
+            .chain(&mut self.subqueries)
+            .chain(&mut self.conditions)
+            .chain(&mut self.variants);
+        for program in nested {
+            program.resolve(procedures)?;
+        }
+        Ok(())
+    }
+
+    /// Compile `statement`, where `schema` determines
+    /// which indexes the optimized plan may use. The
+    /// program is [verified][Program::verify] before
+    /// it is returned.
+    pub fn compile(
+        statement: &Statement,
+        procedures: &Procedures,
+        schema: &Schema,
+    ) -> Result<Program, Error> {
+        let program = match statement {
+            Statement::Query(query) => Self::bound(&QueryPlan::new(query, procedures)?, schema)?,
+            Statement::Union { queries, all } => {
+                let parts = queries
+                    .iter()
+                    .map(|query| QueryPlan::with_columns(query, procedures))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                if parts.iter().any(|(_, columns)| *columns != parts[0].1) {
+                    return Err(Error::UnionMismatch);
+                }
+                let mut programs = parts
+                    .iter()
+                    .map(|(plan, _)| Self::bound(plan, schema))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let mut program = programs.remove(0);
+                program.unions = programs;
+                program.distinct = !all;
+                program
+            }
+            Statement::Explain(inner) => Self::explain(inner, procedures, schema)?,
+            Statement::Profile(inner) => {
+                let mut program = Self::iter_rows(Instruction::IterProfile, 6, Vec::new());
+                program.profile = Some(Box::new(Self::compile(inner, procedures, schema)?));
+                program
+            }
+            Statement::CreateIndex { label, key } => Self::update(Instruction::CreateIndex {
+                index: Index::new(label, key),
+                kind: IndexKind::Plain,
+            }),
+            Statement::DropIndex { label, key } => Self::update(Instruction::DropIndex {
+                index: Index::new(label, key),
+                kind: IndexKind::Plain,
+            }),
+            Statement::CreateConstraint(constraint) => Self::constraint(constraint, true),
+            Statement::DropConstraint(constraint) => Self::constraint(constraint, false),
+        };
+        program.verify()?;
+        Ok(program)
+    }
 }
@@ -0,0 +1,496 @@
+use super::program::MAX_CONDITIONS;
+use super::{Access, Instruction, Program};
+use crate::Error;
+use std::ops::Bound;
+
+/// The number of values on each stack of the virtual machine
+/// before an instruction runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Depths {
+    nodes: usize,
+    edges: usize,
+    rows: usize,
+    node_iters: usize,
+    edge_iters: usize,
+    row_iters: usize,
+}
+
+impl Program {
+    /// Check that running the program and its nested programs
+    /// does not index out of bounds. Every instruction must be
+    /// reached with the same stack depths on all paths, only refer
+    /// to values which are on the stacks at that point, and jump to
+    /// existing instructions. The stacks are empty when the program
+    /// halts, such that unions start from empty stacks. Programs
+    /// with variants have one for each outcome of their conditions.
+    ///
+    /// The virtual machine relies on these checks, instead of
+    /// checking indices while it runs.
+    pub fn verify(&self) -> Result<(), Error> {
+        let mut depths: Vec<Option<Depths>> = vec![None; self.instructions.len()];
+        let mut pending = vec![(0, Depths::default())];
+        while let Some((inst, before)) = pending.pop() {
+            match depths.get_mut(inst).ok_or(Error::Internal)? {
+                Some(known) if *known == before => continue,
+                Some(_) => return Err(Error::Internal),
+                known => *known = Some(before),
+            }
+            pending.extend(self.successors(inst, before)?);
+        }
+        check(self.conditions.len() <= MAX_CONDITIONS)?;
+        check(self.variants.is_empty() || self.variants.len() == 1 << self.conditions.len())?;
+
+        let nested = self
+            .unions
+            .iter()
+            .chain(self.profile.as_deref())
+            .chain(&self.subqueries)
+            .chain(&self.conditions)
+            .chain(&self.variants);
+        for program in nested {
+            program.verify()?;
+        }
+        Ok(())
+    }
+
+    /// The instructions which may run after `inst`,
+    /// with the stack depths they are reached with.
+    fn successors(&self, inst: usize, before: Depths) -> Result<Vec<(usize, Depths)>, Error> {
+        use Instruction::*;
+
+        let node = |node: usize| check(node < before.nodes);
+        let edge = |edge: usize| check(edge < before.edges);
+        let access = |access: usize| {
+            let access = self.accesses.get(access).ok_or(Error::Internal)?;
+            check_access(access, &before)
+        };
+        let accesses = |accesses: &[usize]| accesses.iter().try_for_each(|&idx| access(idx));
+        let bound = |bound: &Bound<usize>| match bound {
+            Bound::Included(idx) | Bound::Excluded(idx) => access(*idx),
+            Bound::Unbounded => Ok(()),
+        };
+        let subquery = |subquery: usize| self.subqueries.get(subquery).ok_or(Error::Internal);
+
+        let mut after = before;
+        let mut jumped = None;
+        match &self.instructions[inst] {
+            NoOp | CreateIndex { .. } | DropIndex { .. } | CreateRule { .. } | DropRule { .. } => {}
+            Jump { jump } => return Ok(vec![(*jump, before)]),
+            Yield => {
+                for access in &self.returns {
+                    check_access(access, &before)?;
+                }
+            }
+            Halt => {
+                check(before == Depths::default())?;
+                return Ok(Vec::new());
+            }
+
+            IterNodes => after.node_iters += 1,
+            IterLabeledNodes { label } => {
+                access(*label)?;
+                after.node_iters += 1;
+            }
+            IterIndexedNodes {
+                label,
+                key,
+                lower,
+                upper,
+            } => {
+                access(*label)?;
+                access(*key)?;
+                bound(lower)?;
+                bound(upper)?;
+                after.node_iters += 1;
+            }
+            IterSpatialNodes { label, key, region } => {
+                access(*label)?;
+                access(*key)?;
+                region.values().iter().try_for_each(|&&idx| access(idx))?;
+                after.node_iters += 1;
+            }
+            IterOriginEdges { node: idx }
+            | IterTargetEdges { node: idx }
+            | IterBothEdges { node: idx } => {
+                node(*idx)?;
+                after.edge_iters += 1;
+            }
+            IterLabeledEdges { label } => {
+                access(*label)?;
+                after.edge_iters += 1;
+            }
+            IterLabeledOriginEdges { node: idx, label }
+            | IterLabeledTargetEdges { node: idx, label }
+            | IterLabeledBothEdges { node: idx, label } => {
+                node(*idx)?;
+                access(*label)?;
+                after.edge_iters += 1;
+            }
+            IterProcedure {
+                procedure,
+                arguments,
+            } => {
+                check(*procedure < self.procedures.len())?;
+                accesses(arguments)?;
+                after.row_iters += 1;
+            }
+            IterProfile => after.row_iters += 1,
+            IterSubquery {
+                subquery: idx,
+                arguments,
+            } => {
+                subquery(*idx)?;
+                accesses(arguments)?;
+                after.row_iters += 1;
+            }
+            IterJoin {
+                subquery: idx,
+                keys,
+            } => {
+                // rows are hashed by their first columns
+                check(subquery(*idx)?.returns.len() >= keys.len())?;
+                accesses(keys)?;
+                after.row_iters += 1;
+            }
+            Collect {
+                subquery: idx,
+                list,
+                arguments,
+            } => {
+                subquery(*idx)?;
+                list.map(access).transpose()?;
+                accesses(arguments)?;
+                after.rows += 1;
+            }
+
+            LoadNextNode { jump } => {
+                let mut done = before;
+                done.node_iters = pop(before.node_iters)?;
+                jumped = Some((*jump, done));
+                after.nodes += 1;
+            }
+            LoadNextEdge { jump } => {
+                let mut done = before;
+                done.edge_iters = pop(before.edge_iters)?;
+                jumped = Some((*jump, done));
+                after.edges += 1;
+            }
+            LoadNextRow { jump } => {
+                let mut done = before;
+                done.row_iters = pop(before.row_iters)?;
+                jumped = Some((*jump, done));
+                after.rows += 1;
+            }
+            LoadExactNode { jump, id } => {
+                access(*id)?;
+                jumped = Some((*jump, before));
+                after.nodes += 1;
+            }
+            LoadExactEdge { jump, id } => {
+                access(*id)?;
+                jumped = Some((*jump, before));
+                after.edges += 1;
+            }
+            LoadOriginNode { edge: idx } | LoadTargetNode { edge: idx } => {
+                edge(*idx)?;
+                after.nodes += 1;
+            }
+            LoadOtherNode {
+                node: node_idx,
+                edge: edge_idx,
+            } => {
+                node(*node_idx)?;
+                edge(*edge_idx)?;
+                after.nodes += 1;
+            }
+
+            PopNode => after.nodes = pop(before.nodes)?,
+            PopEdge => after.edges = pop(before.edges)?,
+            PopRow => after.rows = pop(before.rows)?,
+
+            CheckIsOrigin {
+                jump,
+                node: node_idx,
+                edge: edge_idx,
+            }
+            | CheckIsTarget {
+                jump,
+                node: node_idx,
+                edge: edge_idx,
+            } => {
+                node(*node_idx)?;
+                edge(*edge_idx)?;
+                jumped = Some((*jump, before));
+            }
+            CheckNodeLabel {
+                jump,
+                node: idx,
+                label: value,
+            }
+            | CheckNodeId {
+                jump,
+                node: idx,
+                id: value,
+            } => {
+                node(*idx)?;
+                access(*value)?;
+                jumped = Some((*jump, before));
+            }
+            CheckEdgeLabel {
+                jump,
+                edge: idx,
+                label: value,
+            }
+            | CheckEdgeId {
+                jump,
+                edge: idx,
+                id: value,
+            } => {
+                edge(*idx)?;
+                access(*value)?;
+                jumped = Some((*jump, before));
+            }
+            CheckTrue { jump, value } => {
+                access(*value)?;
+                jumped = Some((*jump, before));
+            }
+            CheckEq { jump, lhs, rhs }
+            | CheckLt { jump, lhs, rhs }
+            | CheckLe { jump, lhs, rhs }
+            | CheckGt { jump, lhs, rhs }
+            | CheckGe { jump, lhs, rhs } => {
+                access(*lhs)?;
+                access(*rhs)?;
+                jumped = Some((*jump, before));
+            }
+
+            CreateNode { label, properties } => {
+                access(*label)?;
+                properties
+                    .iter()
+                    .try_for_each(|(_, value)| access(*value))?;
+                after.nodes += 1;
+            }
+            CreateEdge {
+                label,
+                origin,
+                target,
+                properties,
+            } => {
+                access(*label)?;
+                node(*origin)?;
+                node(*target)?;
+                properties
+                    .iter()
+                    .try_for_each(|(_, value)| access(*value))?;
+                after.edges += 1;
+            }
+            SetNodeProperty {
+                node: idx,
+                key,
+                value,
+            } => {
+                node(*idx)?;
+                access(*key)?;
+                access(*value)?;
+            }
+            SetEdgeProperty {
+                edge: idx,
+                key,
+                value,
+            } => {
+                edge(*idx)?;
+                access(*key)?;
+                access(*value)?;
+            }
+            DeleteNode { node: idx } => node(*idx)?,
+            DeleteEdge { edge: idx } => edge(*idx)?,
+            Foreach {
+                subquery: idx,
+                list,
+                arguments,
+            } => {
+                subquery(*idx)?;
+                access(*list)?;
+                accesses(arguments)?;
+            }
+        }
+        Ok(jumped.into_iter().chain(Some((inst + 1, after))).collect())
+    }
+}
+
+/// Check that `access` only refers to values on the stacks.
+fn check_access(access: &Access, depths: &Depths) -> Result<(), Error> {
+    let nodes = |nodes: &[usize]| check(nodes.iter().all(|&node| node < depths.nodes));
+    let edges = |edges: &[usize]| check(edges.iter().all(|&edge| edge < depths.edges));
+    match access {
+        Access::Constant(_) | Access::Parameter(_) | Access::Argument(_) => Ok(()),
+        Access::NodeId(node) | Access::NodeLabel(node) | Access::Node(node) => nodes(&[*node]),
+        Access::EdgeId(edge) | Access::EdgeLabel(edge) | Access::Edge(edge) => edges(&[*edge]),
+        Access::NodeProperty(node, key) => {
+            nodes(&[*node])?;
+            check_access(key, depths)
+        }
+        Access::EdgeProperty(edge, key) => {
+            edges(&[*edge])?;
+            check_access(key, depths)
+        }
+        Access::Path(path_nodes, path_edges) => {
+            nodes(path_nodes)?;
+            edges(path_edges)
+        }
+        Access::NodeIds(ids) => nodes(ids),
+        Access::EdgeIds(ids) => edges(ids),
+        // missing columns are null
+        Access::Column(row, _) => check(*row < depths.rows),
+        Access::Function(_, arguments) => arguments
+            .iter()
+            .try_for_each(|argument| check_access(argument, depths)),
+    }
+}
+
+fn check(valid: bool) -> Result<(), Error> {
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Internal)
+    }
+}
+
+fn pop(depth: usize) -> Result<usize, Error> {
+    depth.checked_sub(1).ok_or(Error::Internal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::PropOwned;
+    use Instruction::*;
+
+    fn program(instructions: Vec<Instruction>, accesses: Vec<Access>) -> Program {
+        Program {
+            instructions,
+            accesses,
+            returns: Vec::new(),
+            procedures: Vec::new(),
+            unions: Vec::new(),
+            distinct: false,
+            profile: None,
+            subqueries: Vec::new(),
+            conditions: Vec::new(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// `MATCH (a) WHERE a.name = 'Ada' RETURN a`
+    fn valid() -> Program {
+        let mut program = program(
+            vec![
+                IterNodes,
+                LoadNextNode { jump: 6 },
+                CheckEq {
+                    jump: 4,
+                    lhs: 0,
+                    rhs: 1,
+                },
+                Yield,
+                PopNode,
+                Jump { jump: 1 },
+                Halt,
+            ],
+            vec![
+                Access::NodeProperty(
+                    0,
+                    Box::new(Access::Constant(PropOwned::Text("name".into()))),
+                ),
+                Access::Constant(PropOwned::Text("Ada".into())),
+            ],
+        );
+        program.returns = vec![Access::Node(0)];
+        program
+    }
+
+    #[test]
+    fn accept_valid_programs() {
+        valid().verify().unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_jumps() {
+        let mut program = valid();
+        program.instructions[1] = LoadNextNode { jump: 7 };
+        assert!(program.verify().is_err());
+
+        // falls through the end of the program
+        let mut program = valid();
+        program.instructions.pop();
+        assert!(program.verify().is_err());
+        assert!(self::program(Vec::new(), Vec::new()).verify().is_err());
+    }
+
+    #[test]
+    fn reject_inconsistent_stacks() {
+        // reaches the check with and without a node
+        let mut program = valid();
+        program.instructions[2] = CheckEq {
+            jump: 5,
+            lhs: 0,
+            rhs: 1,
+        };
+        assert!(program.verify().is_err());
+
+        // halts with an iterator left
+        let mut program = valid();
+        program.instructions[1] = LoadNextNode { jump: 5 };
+        program.instructions[5] = Halt;
+        assert!(program.verify().is_err());
+
+        let mut program = valid();
+        program.instructions[4] = PopEdge;
+        assert!(program.verify().is_err());
+    }
+
+    #[test]
+    fn reject_invalid_accesses() {
+        let mut program = valid();
+        program.returns = vec![Access::Node(1)];
+        assert!(program.verify().is_err());
+
+        let mut program = valid();
+        program.instructions[2] = CheckEq {
+            jump: 4,
+            lhs: 0,
+            rhs: 2,
+        };
+        assert!(program.verify().is_err());
+
+        let mut program = valid();
+        program.accesses[1] = Access::Column(0, 0);
+        assert!(program.verify().is_err());
+
+        // nested programs are verified
+        let mut program = valid();
+        program
+            .subqueries
+            .push(self::program(vec![PopRow, Halt], Vec::new()));
+        assert!(program.verify().is_err());
+    }
+
+    #[test]
+    fn reject_invalid_variants() {
+        let condition = self::program(vec![Halt], Vec::new());
+        let mut program = valid();
+        program.conditions = vec![condition.clone(); 2];
+        program.variants = vec![valid(); 4];
+        program.verify().unwrap();
+
+        // one variant for each outcome of the conditions
+        program.variants.pop();
+        assert!(program.verify().is_err());
+
+        let mut program = valid();
+        program.conditions = vec![condition; MAX_CONDITIONS + 1];
+        program.variants = vec![valid(); 1 << (MAX_CONDITIONS + 1)];
+        assert!(program.verify().is_err());
+    }
+}
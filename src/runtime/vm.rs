@@ -1,8 +1,19 @@
 use super::Program;
-use crate::store::{Edge, EdgeIter, Node, NodeIter, PropOwned, PropRef, StoreTxn, Update};
-use crate::Error;
+use crate::function::Function;
+use crate::procedure::Procedure;
+use crate::store::types::Path;
+use crate::store::{
+    Edge, EdgeIter, Index, IndexKind, LazyEdge, Node, NodeIter, PropOwned, PropRef, Region, Rule,
+    StoreTxn, Update,
+};
+use crate::{Error, Property};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::iter::once;
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+use std::vec;
 
 /// Runtime to execute a compiled query program. Note that the
 /// transaction takes an immutable borrow, but expects to be the
@@ -14,19 +25,49 @@ pub(crate) struct VirtualMachine<'env, 'txn, 'prog> {
     instructions: &'prog [Instruction],
     accesses: &'prog [Access],
     returns: &'prog [Access],
+    procedures: &'prog [Procedure],
+    subqueries: &'prog [Program],
     parameters: HashMap<String, PropOwned>,
+    arguments: Vec<PropOwned>,
     current_inst: usize,
 
+    unions: &'prog [Program],
+    next_union: usize,
+    seen: Option<HashSet<Vec<u8>>>,
+
+    profiling: bool,
+    profile: Option<Profile>,
+    report: Option<&'prog Program>,
+
     node_stack: Vec<Node>,
-    edge_stack: Vec<Edge>,
+    edge_stack: Vec<LazyEdge>,
     node_iters: Vec<NodeIter<'txn>>,
     edge_iters: Vec<EdgeIter<'txn>>,
+    row_stack: Vec<Vec<PropOwned>>,
+    row_iters: Vec<vec::IntoIter<Vec<PropOwned>>>,
+    /// Hash tables built by `IterJoin`, by subquery.
+    joins: HashMap<usize, JoinTable>,
+}
+
+/// The rows returned by the joined plan, by their keys.
+type JoinTable = HashMap<Vec<Vec<u8>>, Vec<Vec<PropOwned>>>;
+
+/// Execution statistics collected while
+/// running a program using `PROFILE`.
+#[derive(Debug)]
+struct Profile {
+    /// Execution count and total time of each instruction,
+    /// for the program and each of its unions.
+    instructions: Vec<Vec<(u64, Duration)>>,
+    rows: u64,
+    nodes: u64,
+    edges: u64,
 }
 
 /// TODO: Consider to do a Cranelift JIT
 /// instead? (let's see how slow this ends
 /// up being ...)
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum Instruction {
     /// Do nothing.
     NoOp,
@@ -42,10 +83,27 @@ pub(crate) enum Instruction {
 
     /// Create an iterator over all nodes.
     IterNodes,
-    /// Create an iterator over nodes with an
-    /// exact label.
+    /// Create an iterator over nodes with the
+    /// exact label `access[label]`.
     IterLabeledNodes {
-        label: String,
+        label: usize,
+    },
+    /// Create an iterator over nodes with the label
+    /// `access[label]`, where property `access[key]`
+    /// lies between the accesses `lower` and `upper`.
+    IterIndexedNodes {
+        label: usize,
+        key: usize,
+        lower: Bound<usize>,
+        upper: Bound<usize>,
+    },
+    /// Iterate nodes with the exact label `access[label]`,
+    /// where the property `access[key]` may be a point
+    /// within the region.
+    IterSpatialNodes {
+        label: usize,
+        key: usize,
+        region: Region<usize>,
     },
 
     /// Iterate edges originating from `node`.
@@ -60,6 +118,62 @@ pub(crate) enum Instruction {
     IterBothEdges {
         node: usize,
     },
+    /// Iterate edges with the exact label
+    /// `access[label]`.
+    IterLabeledEdges {
+        label: usize,
+    },
+    /// Iterate edges originating from `node`
+    /// with the exact label `access[label]`.
+    IterLabeledOriginEdges {
+        node: usize,
+        label: usize,
+    },
+    /// Iterate edges terminating at `node`
+    /// with the exact label `access[label]`.
+    IterLabeledTargetEdges {
+        node: usize,
+        label: usize,
+    },
+    /// Iterate all edges connected to `node`
+    /// with the exact label `access[label]`.
+    IterLabeledBothEdges {
+        node: usize,
+        label: usize,
+    },
+    /// Call `procedures[procedure]` with the given
+    /// argument accesses and iterate the returned rows.
+    IterProcedure {
+        procedure: usize,
+        arguments: Vec<usize>,
+    },
+    /// Iterate rows describing the statistics which
+    /// were collected while running the profiled program.
+    IterProfile,
+    /// Run `subqueries[subquery]` with the given argument
+    /// accesses and iterate the returned rows.
+    IterSubquery {
+        subquery: usize,
+        arguments: Vec<usize>,
+    },
+
+    /// Run `subqueries[subquery]` once and hash the returned rows
+    /// by their first `keys.len()` columns. Iterate the rows where
+    /// these columns equal the `keys` accesses.
+    IterJoin {
+        subquery: usize,
+        keys: Vec<usize>,
+    },
+
+    /// Run `subqueries[subquery]` once for every item of the
+    /// `list` access (passing the item followed by the given
+    /// argument accesses), or once if there is no list. Push
+    /// a row with the list of all returned values.
+    Collect {
+        subquery: usize,
+        list: Option<usize>,
+        arguments: Vec<usize>,
+    },
 
     /// Load the next node from the top iterator or pop
     /// the iterator and jump.
@@ -71,12 +185,22 @@ pub(crate) enum Instruction {
     LoadNextEdge {
         jump: usize,
     },
+    /// Load the next row form the top iterator or pop
+    /// the iterator and jump.
+    LoadNextRow {
+        jump: usize,
+    },
 
     /// Load the node with `id = access[id]` of jump.
     LoadExactNode {
         jump: usize,
         id: usize,
     },
+    /// Load the edge with `id = access[id]` of jump.
+    LoadExactEdge {
+        jump: usize,
+        id: usize,
+    },
 
     /// Load the node from which `edge` originates.
     LoadOriginNode {
@@ -95,6 +219,7 @@ pub(crate) enum Instruction {
 
     PopNode,
     PopEdge,
+    PopRow,
 
     /// Perform a conditional jump if `node` is not
     /// the origin of `edge`.
@@ -112,18 +237,18 @@ pub(crate) enum Instruction {
     },
 
     /// Perform a conditional jump if the label of
-    /// `node` is different from `label`.
+    /// `node` is different from `access[label]`.
     CheckNodeLabel {
         jump: usize,
         node: usize,
-        label: String,
+        label: usize,
     },
     /// Perform a conditional jump if the label of
-    /// `edge` is different from `label`.
+    /// `edge` is different from `access[label]`.
     CheckEdgeLabel {
         jump: usize,
         edge: usize,
-        label: String,
+        label: usize,
     },
 
     /// Perform a conditional jump if the id of
@@ -160,48 +285,60 @@ pub(crate) enum Instruction {
         lhs: usize,
         rhs: usize,
     },
+    /// Perform a conditional jump if not `lhs <= rhs`.
+    CheckLe {
+        jump: usize,
+        lhs: usize,
+        rhs: usize,
+    },
     /// Perform a conditional jump if not `lhs > rhs`.
     CheckGt {
         jump: usize,
         lhs: usize,
         rhs: usize,
     },
+    /// Perform a conditional jump if not `lhs >= rhs`.
+    CheckGe {
+        jump: usize,
+        lhs: usize,
+        rhs: usize,
+    },
 
     /// Queue an update that creates a new node with
-    /// the given label and the set of properties
+    /// the label `access[label]` and the set of properties
     /// assembled using the `Vec` of accesses.
     ///
     /// The created node is also pushed to the node
     /// stack.
     CreateNode {
-        label: String,
+        label: usize,
         properties: Vec<(String, usize)>,
     },
     /// Queue an update that creates a new edge with
-    /// the given label, origin, target, and the set
-    /// of properties assembled using the `Vec` of
-    /// accesses.
+    /// the label `access[label]`, origin, target, and
+    /// the set of properties assembled using the `Vec`
+    /// of accesses.
     ///
     /// The created node is also pushed to the node
     /// stack.
     CreateEdge {
-        label: String,
+        label: usize,
         origin: usize,
         target: usize,
         properties: Vec<(String, usize)>,
     },
-    /// Queue an update that sets property `key` of
-    /// `node` to `access[value]`.
+    /// Queue an update that sets property `access[key]`
+    /// of `node` to `access[value]`.
     SetNodeProperty {
         node: usize,
-        key: String,
+        key: usize,
         value: usize,
     },
-    /// Queue an update that sets property `key` of
-    /// `edge` to `access[value]`.
+    /// Queue an update that sets property `access[key]`
+    /// of `edge` to `access[value]`.
     SetEdgeProperty {
         edge: usize,
-        key: String,
+        key: usize,
         value: usize,
     },
     /// Queue an update that deletes the given `node`.
@@ -212,18 +349,54 @@ pub(crate) enum Instruction {
     DeleteEdge {
         edge: usize,
     },
+    /// Queue an update that creates `index`, which
+    /// enforces the constraint given by `kind`.
+    CreateIndex {
+        index: Index,
+        kind: IndexKind,
+    },
+    /// Queue an update that drops `index`, if it
+    /// has the given `kind`.
+    DropIndex {
+        index: Index,
+        kind: IndexKind,
+    },
+    /// Queue an update that stores `rule`.
+    CreateRule {
+        rule: Rule,
+    },
+    /// Queue an update that removes `rule`.
+    DropRule {
+        rule: Rule,
+    },
+    /// Run `subqueries[subquery]` once for every item of the
+    /// `list` access, passing the item followed by the given
+    /// argument accesses.
+    Foreach {
+        subquery: usize,
+        list: usize,
+        arguments: Vec<usize>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) enum Access {
     Constant(PropOwned),
     NodeId(usize),
     EdgeId(usize),
     NodeLabel(usize),
     EdgeLabel(usize),
-    NodeProperty(usize, String),
-    EdgeProperty(usize, String),
+    NodeProperty(usize, Box<Access>),
+    EdgeProperty(usize, Box<Access>),
     Parameter(String),
+    Path(Vec<usize>, Vec<usize>),
+    NodeIds(Vec<usize>),
+    EdgeIds(Vec<usize>),
+    Column(usize, usize),
+    Node(usize),
+    Edge(usize),
+    Argument(usize),
+    Function(Function, Vec<Access>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -233,56 +406,187 @@ pub(crate) enum Status {
 }
 
 impl<'env, 'txn, 'prog> VirtualMachine<'env, 'txn, 'prog> {
+    /// Create a virtual machine which runs the variant of `program`
+    /// that was optimized for the bound `parameters`.
     pub fn new(
         txn: &'txn mut StoreTxn<'env>,
         program: &'prog Program,
         parameters: HashMap<String, PropOwned>,
+    ) -> Result<Self, Error> {
+        let mut vm = Self::with_txn(txn, program, parameters);
+        // the profile reports the instructions of the
+        // program as compiled, so it is never re-bound
+        if !vm.profiling {
+            let bound = Self::bind(vm.txn, program, &vm.parameters)?;
+            vm.load_program(bound);
+        }
+        Ok(vm)
+    }
+
+    /// Create a virtual machine which runs `program` as compiled.
+    /// Used for nested programs, which run once for every match.
+    fn with_txn(
+        txn: &'txn StoreTxn<'env>,
+        program: &'prog Program,
+        parameters: HashMap<String, PropOwned>,
     ) -> Self {
+        // profiled programs run before the program
+        // which reports the profile
+        let (report, program) = match &program.profile {
+            Some(profiled) => (Some(program), profiled.as_ref()),
+            None => (None, program),
+        };
         Self {
             txn,
             instructions: &program.instructions,
             accesses: &program.accesses,
             returns: &program.returns,
+            procedures: &program.procedures,
+            subqueries: &program.subqueries,
             current_inst: 0,
 
             parameters,
+            arguments: Vec::new(),
+
+            unions: &program.unions,
+            next_union: 0,
+            seen: if program.distinct {
+                Some(HashSet::new())
+            } else {
+                None
+            },
+
+            profiling: report.is_some(),
+            profile: report.map(|_| Profile::new(program)),
+            report,
 
             node_stack: Vec::new(),
             edge_stack: Vec::new(),
             node_iters: Vec::new(),
             edge_iters: Vec::new(),
+            row_stack: Vec::new(),
+            row_iters: Vec::new(),
+            joins: HashMap::new(),
         }
     }
 
+    /// Choose the variant of `program` which was optimized for the
+    /// outcomes of its conditions under the bound `parameters`. Uses
+    /// `program` itself if it has no variants.
+    fn bind(
+        txn: &'txn StoreTxn<'env>,
+        program: &'prog Program,
+        parameters: &HashMap<String, PropOwned>,
+    ) -> Result<&'prog Program, Error> {
+        if program.variants.is_empty() {
+            return Ok(program);
+        }
+        let mut outcomes = 0;
+        for (idx, condition) in program.conditions.iter().enumerate() {
+            let mut vm = VirtualMachine::with_txn(txn, condition, parameters.clone());
+            if vm.run()? == Status::Yield {
+                outcomes |= 1 << idx;
+            }
+        }
+        Ok(&program.variants[outcomes])
+    }
+
     fn access_property(&self, access: usize) -> Result<PropRef, Error> {
-        match &self.accesses[access] {
+        self.access_value(&self.accesses[access])
+    }
+
+    fn access_bound(&self, bound: Bound<usize>) -> Result<Bound<PropOwned>, Error> {
+        Ok(match bound {
+            Bound::Included(access) => Bound::Included(self.access_property(access)?.to_owned()),
+            Bound::Excluded(access) => Bound::Excluded(self.access_property(access)?.to_owned()),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+
+    /// A label or property key, which must be text.
+    fn access_symbol<'a>(&'a self, access: &'a Access) -> Result<&'a str, Error> {
+        match self.access_value(access)? {
+            PropRef::Text(text) => Ok(text),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    fn access_value<'a>(&'a self, access: &'a Access) -> Result<PropRef<'a>, Error> {
+        match access {
             Access::Constant(val) => Ok(val.to_ref()),
             Access::NodeId(node) => Ok(PropRef::Id(self.node_stack[*node].id())),
-            Access::EdgeId(edge) => Ok(PropRef::Id(self.edge_stack[*edge].id())),
+            Access::EdgeId(edge) => Ok(PropRef::Id(self.edge_stack[*edge].id)),
             Access::NodeLabel(node) => Ok(PropRef::Text(self.node_stack[*node].label())),
-            Access::EdgeLabel(edge) => Ok(PropRef::Text(self.edge_stack[*edge].label())),
-            Access::NodeProperty(node, key) => Ok(self.node_stack[*node].property(key).to_ref()),
-            Access::EdgeProperty(edge, key) => Ok(self.edge_stack[*edge].property(key).to_ref()),
+            Access::EdgeLabel(edge) => Ok(PropRef::Text(&self.edge_stack[*edge].label)),
+            Access::NodeProperty(node, key) => {
+                let key = self.access_symbol(key)?;
+                Ok(self.node_stack[*node].property(key).to_ref())
+            }
+            Access::EdgeProperty(edge, key) => {
+                let key = self.access_symbol(key)?;
+                let edge = self.edge_stack[*edge].record(self.txn)?;
+                Ok(edge.property(key).to_ref())
+            }
             Access::Parameter(name) => Ok(self
                 .parameters
                 .get(name)
                 .map(PropOwned::to_ref)
                 .unwrap_or(PropRef::Null)),
+            Access::Path(nodes, edges) => Ok(PropRef::Path(self.build_path(nodes, edges))),
+            Access::NodeIds(nodes) => Ok(PropRef::List(
+                nodes
+                    .iter()
+                    .map(|node| PropRef::Id(self.node_stack[*node].id()))
+                    .collect(),
+            )),
+            Access::EdgeIds(edges) => Ok(PropRef::List(
+                edges
+                    .iter()
+                    .map(|edge| PropRef::Id(self.edge_stack[*edge].id))
+                    .collect(),
+            )),
+            Access::Column(row, column) => Ok(self.row_stack[*row]
+                .get(*column)
+                .map(PropOwned::to_ref)
+                .unwrap_or(PropRef::Null)),
+            Access::Node(node) => Ok(PropRef::Node(self.node_stack[*node].clone())),
+            Access::Edge(edge) => Ok(PropRef::Edge(
+                self.edge_stack[*edge].record(self.txn)?.clone(),
+            )),
+            Access::Argument(index) => Ok(self
+                .arguments
+                .get(*index)
+                .map(PropOwned::to_ref)
+                .unwrap_or(PropRef::Null)),
+            Access::Function(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|arg| self.access_value(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(&arguments)
+            }
         }
     }
 
     pub fn access_return(&self, access: usize) -> Result<PropOwned, Error> {
-        match self.returns.get(access).ok_or(Error::IndexOutOfBounds)? {
+        self.access_owned(self.returns.get(access).ok_or(Error::IndexOutOfBounds)?)
+    }
+
+    /// Like `access_value`, but includes changes made by
+    /// updates of the current match.
+    fn access_owned(&self, access: &Access) -> Result<PropOwned, Error> {
+        match access {
             Access::Constant(val) => Ok(val.clone()),
             Access::NodeId(node) => Ok(PropOwned::Id(self.node_stack[*node].id())),
-            Access::EdgeId(edge) => Ok(PropOwned::Id(self.edge_stack[*edge].id())),
+            Access::EdgeId(edge) => Ok(PropOwned::Id(self.edge_stack[*edge].id)),
             Access::NodeLabel(node) => {
                 Ok(PropOwned::Text(self.node_stack[*node].label().to_string()))
             }
             Access::EdgeLabel(edge) => {
-                Ok(PropOwned::Text(self.edge_stack[*edge].label().to_string()))
+                Ok(PropOwned::Text(self.edge_stack[*edge].label.clone()))
             }
             Access::NodeProperty(node, key) => {
+                let key = self.access_symbol(key)?;
                 let node = &self.node_stack[*node];
                 Ok(self
                     .txn
@@ -290,17 +594,85 @@ impl<'env, 'txn, 'prog> VirtualMachine<'env, 'txn, 'prog> {
                     .unwrap_or_else(|| node.property(key).clone()))
             }
             Access::EdgeProperty(edge, key) => {
+                let key = self.access_symbol(key)?;
                 let edge = &self.edge_stack[*edge];
-                Ok(self
-                    .txn
-                    .get_updated_property(edge.id(), key)?
-                    .unwrap_or_else(|| edge.property(key).clone()))
+                match self.txn.get_updated_property(edge.id, key)? {
+                    Some(value) => Ok(value),
+                    None => Ok(edge.record(self.txn)?.property(key).clone()),
+                }
             }
             Access::Parameter(name) => Ok(self
                 .parameters
                 .get(name)
                 .map(Clone::clone)
                 .unwrap_or(PropOwned::Null)),
+            path @ (Access::Path(..) | Access::NodeIds(_) | Access::EdgeIds(_)) => {
+                self.access_path(path)
+            }
+            Access::Column(row, column) => Ok(self.row_stack[*row]
+                .get(*column)
+                .cloned()
+                .unwrap_or(PropOwned::Null)),
+            Access::Node(node) => Ok(PropOwned::Node(
+                self.txn.get_updated_node(&self.node_stack[*node])?,
+            )),
+            Access::Edge(edge) => Ok(PropOwned::Edge(
+                self.txn
+                    .get_updated_edge(self.edge_stack[*edge].record(self.txn)?)?,
+            )),
+            Access::Argument(index) => Ok(self
+                .arguments
+                .get(*index)
+                .cloned()
+                .unwrap_or(PropOwned::Null)),
+            Access::Function(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|arg| self.access_owned(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let arguments: Vec<_> = arguments.iter().map(PropOwned::to_ref).collect();
+                Ok(function.call(&arguments)?.to_owned())
+            }
+        }
+    }
+
+    fn access_path(&self, access: &Access) -> Result<PropOwned, Error> {
+        match access {
+            Access::Path(nodes, edges) => Ok(PropOwned::Path(self.build_path(nodes, edges))),
+            Access::NodeIds(nodes) => Ok(PropOwned::List(
+                nodes
+                    .iter()
+                    .map(|node| PropOwned::Id(self.node_stack[*node].id()))
+                    .collect(),
+            )),
+            Access::EdgeIds(edges) => Ok(PropOwned::List(
+                edges
+                    .iter()
+                    .map(|edge| PropOwned::Id(self.edge_stack[*edge].id))
+                    .collect(),
+            )),
+            _ => Err(Error::Internal),
+        }
+    }
+
+    /// Check if the current match returns values which were
+    /// not yielded before. Always true if the program does not
+    /// require distinct matches.
+    fn is_distinct(&mut self) -> Result<bool, Error> {
+        if self.seen.is_none() {
+            return Ok(true);
+        }
+        let row = (0..self.returns.len())
+            .map(|idx| self.access_return(idx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = bincode::serialize(&row)?;
+        Ok(self.seen.as_mut().unwrap().insert(key))
+    }
+
+    fn build_path(&self, nodes: &[usize], edges: &[usize]) -> Path {
+        Path {
+            nodes: nodes.iter().map(|node| self.node_stack[*node].id()).collect(),
+            edges: edges.iter().map(|edge| self.edge_stack[*edge].id).collect(),
         }
     }
 
@@ -310,273 +682,671 @@ impl<'env, 'txn, 'prog> VirtualMachine<'env, 'txn, 'prog> {
     /// Indices in instructions are not checked and
     /// may panic. Instructions for consuming iterators
     /// do not check if iterators exist and may panic.
+    /// Programs which pass [`Program::verify`] do
+    /// neither.
     pub fn run(&mut self) -> Result<Status, Error> {
+        if self.profiling {
+            return self.run_profiled();
+        }
         loop {
-            match &self.instructions[self.current_inst] {
-                Instruction::NoOp => self.current_inst += 1,
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
 
-                Instruction::Jump { jump } => self.current_inst = *jump,
-                Instruction::Yield => {
-                    self.current_inst += 1;
-                    return Ok(Status::Yield);
+    /// Run the profiled program to completion, recording
+    /// statistics for each instruction. Once the profiled
+    /// program halts, continues with the program reporting
+    /// the collected statistics.
+    fn run_profiled(&mut self) -> Result<Status, Error> {
+        while self.profiling {
+            let part = self.next_union;
+            let inst = self.current_inst;
+            let nodes = self.node_stack.len();
+            let edges = self.edge_stack.len();
+
+            let start = Instant::now();
+            let status = self.step()?;
+            let elapsed = start.elapsed();
+
+            let creates = matches!(
+                self.instructions[inst],
+                Instruction::CreateNode { .. } | Instruction::CreateEdge { .. }
+            );
+            let profile = self.profile.as_mut().ok_or(Error::Internal)?;
+            let (count, time) = &mut profile.instructions[part][inst];
+            *count += 1;
+            *time += elapsed;
+            if !creates && self.node_stack.len() > nodes {
+                profile.nodes += 1;
+            }
+            if !creates && self.edge_stack.len() > edges {
+                profile.edges += 1;
+            }
+            match status {
+                Some(Status::Yield) => profile.rows += 1,
+                Some(Status::Halt) => {
+                    self.profiling = false;
+                    self.load_program(self.report.ok_or(Error::Internal)?);
+                    self.unions = &[];
+                    self.seen = None;
                 }
-                Instruction::Halt => return Ok(Status::Halt),
+                None => (),
+            }
+        }
+        self.run()
+    }
 
-                Instruction::IterNodes => {
-                    self.node_iters.push(NodeIter::all(self.txn)?);
-                    self.current_inst += 1;
+    fn load_program(&mut self, program: &'prog Program) {
+        self.instructions = &program.instructions;
+        self.accesses = &program.accesses;
+        self.returns = &program.returns;
+        self.procedures = &program.procedures;
+        self.subqueries = &program.subqueries;
+        self.current_inst = 0;
+        // tables are keyed by the subqueries of this program
+        self.joins.clear();
+    }
+
+    fn access_arguments(&self, arguments: &[usize]) -> Result<Vec<PropOwned>, Error> {
+        arguments
+            .iter()
+            .map(|arg| Ok(self.access_property(*arg)?.to_owned()))
+            .collect()
+    }
+
+    /// Run `subqueries[subquery]` to completion and return the
+    /// values of every match. The nested program shares the
+    /// transaction, such that updates are queued together with
+    /// the updates of this program.
+    fn run_subquery(
+        &self,
+        subquery: usize,
+        arguments: Vec<PropOwned>,
+    ) -> Result<Vec<Vec<PropOwned>>, Error> {
+        let mut vm = VirtualMachine::with_txn(
+            self.txn,
+            &self.subqueries[subquery],
+            self.parameters.clone(),
+        );
+        vm.arguments = arguments;
+        let mut rows = Vec::new();
+        while vm.run()? == Status::Yield {
+            rows.push(
+                (0..vm.returns.len())
+                    .map(|idx| vm.access_return(idx))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(rows)
+    }
+
+    /// Execute a single instruction. Returns a `Status`
+    /// if the program yielded or halted.
+    #[inline(always)]
+    fn step(&mut self) -> Result<Option<Status>, Error> {
+        match &self.instructions[self.current_inst] {
+            Instruction::NoOp => self.current_inst += 1,
+
+            Instruction::Jump { jump } => self.current_inst = *jump,
+            Instruction::Yield => {
+                self.current_inst += 1;
+                if self.is_distinct()? {
+                    return Ok(Some(Status::Yield));
                 }
-                Instruction::IterLabeledNodes { label } => {
-                    self.node_iters
-                        .push(NodeIter::with_label(self.txn, label.clone())?);
-                    self.current_inst += 1;
+            }
+            Instruction::Halt => {
+                if let Some(program) = self.unions.get(self.next_union) {
+                    if !self.profiling {
+                        self.load_program(Self::bind(self.txn, program, &self.parameters)?);
+                    } else {
+                        self.load_program(program);
+                    }
+                    self.next_union += 1;
+                } else {
+                    return Ok(Some(Status::Halt));
                 }
+            }
 
-                Instruction::IterOriginEdges { node } => {
-                    let node = &self.node_stack[*node];
-                    self.edge_iters.push(EdgeIter::origins(self.txn, node.id)?);
-                    self.current_inst += 1;
-                }
-                Instruction::IterTargetEdges { node } => {
-                    let node = &self.node_stack[*node];
-                    self.edge_iters.push(EdgeIter::targets(self.txn, node.id)?);
+            Instruction::IterNodes => {
+                self.node_iters.push(NodeIter::all(self.txn)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterLabeledNodes { label } => {
+                let label = self.access_symbol(&self.accesses[*label])?.to_string();
+                self.node_iters
+                    .push(NodeIter::with_label(self.txn, label)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterIndexedNodes {
+                label,
+                key,
+                lower,
+                upper,
+            } => {
+                let label = self.access_symbol(&self.accesses[*label])?.to_string();
+                let key = self.access_symbol(&self.accesses[*key])?.to_string();
+                let lower = self.access_bound(*lower)?;
+                let upper = self.access_bound(*upper)?;
+                self.node_iters.push(NodeIter::with_property(
+                    self.txn, label, key, lower, upper,
+                )?);
+                self.current_inst += 1;
+            }
+            Instruction::IterSpatialNodes { label, key, region } => {
+                let label = self.access_symbol(&self.accesses[*label])?.to_string();
+                let key = self.access_symbol(&self.accesses[*key])?.to_string();
+                let boxes = region.try_map(|&access| self.access_property(access))?.boxes();
+                self.node_iters.push(NodeIter::within(self.txn, label, key, boxes)?);
+                self.current_inst += 1;
+            }
+
+            Instruction::IterOriginEdges { node } => {
+                let node = &self.node_stack[*node];
+                self.edge_iters.push(EdgeIter::origins(self.txn, node.id, None)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterTargetEdges { node } => {
+                let node = &self.node_stack[*node];
+                self.edge_iters.push(EdgeIter::targets(self.txn, node.id, None)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterBothEdges { node } => {
+                let node = &self.node_stack[*node];
+                self.edge_iters.push(EdgeIter::both(self.txn, node.id, None)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterLabeledEdges { label } => {
+                let label = self.access_symbol(&self.accesses[*label])?.to_string();
+                self.edge_iters
+                    .push(EdgeIter::with_label(self.txn, label)?);
+                self.current_inst += 1;
+            }
+            Instruction::IterLabeledOriginEdges { node, label } => {
+                let node = &self.node_stack[*node];
+                let label = self.access_symbol(&self.accesses[*label])?;
+                self.edge_iters
+                    .push(EdgeIter::origins(self.txn, node.id, Some(label))?);
+                self.current_inst += 1;
+            }
+            Instruction::IterLabeledTargetEdges { node, label } => {
+                let node = &self.node_stack[*node];
+                let label = self.access_symbol(&self.accesses[*label])?;
+                self.edge_iters
+                    .push(EdgeIter::targets(self.txn, node.id, Some(label))?);
+                self.current_inst += 1;
+            }
+            Instruction::IterLabeledBothEdges { node, label } => {
+                let node = &self.node_stack[*node];
+                let label = self.access_symbol(&self.accesses[*label])?;
+                self.edge_iters
+                    .push(EdgeIter::both(self.txn, node.id, Some(label))?);
+                self.current_inst += 1;
+            }
+
+            Instruction::LoadNextNode { jump } => {
+                let iter = self.node_iters.last_mut().unwrap();
+                if let Some(entry) = iter.next() {
+                    self.node_stack.push(entry?);
                     self.current_inst += 1;
+                } else {
+                    self.node_iters.pop();
+                    self.current_inst = *jump;
                 }
-                Instruction::IterBothEdges { node } => {
-                    let node = &self.node_stack[*node];
-                    self.edge_iters.push(EdgeIter::both(self.txn, node.id)?);
+            }
+            Instruction::LoadNextEdge { jump } => {
+                let iter = self.edge_iters.last_mut().unwrap();
+                if let Some(edge) = iter.next() {
+                    self.edge_stack.push(edge?);
                     self.current_inst += 1;
+                } else {
+                    self.edge_iters.pop();
+                    self.current_inst = *jump;
                 }
+            }
 
-                Instruction::LoadNextNode { jump } => {
-                    let iter = self.node_iters.last_mut().unwrap();
-                    if let Some(entry) = iter.next() {
-                        self.node_stack.push(entry?);
-                        self.current_inst += 1;
-                    } else {
-                        self.node_iters.pop();
-                        self.current_inst = *jump;
-                    }
+            Instruction::IterProcedure {
+                procedure,
+                arguments,
+            } => {
+                let arguments = self
+                    .access_arguments(arguments)?
+                    .into_iter()
+                    .map(PropOwned::to_external)
+                    .collect::<Vec<_>>();
+                let rows = self.procedures[*procedure].call(self.txn, &arguments)?;
+                self.row_iters.push(
+                    rows.into_iter()
+                        .map(|row| row.into_iter().map(Property::to_internal).collect())
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                );
+                self.current_inst += 1;
+            }
+            Instruction::IterProfile => {
+                let profiled = self
+                    .report
+                    .and_then(|report| report.profile.as_deref())
+                    .ok_or(Error::Internal)?;
+                let profile = self.profile.take().ok_or(Error::Internal)?;
+                self.row_iters.push(profile.into_rows(profiled).into_iter());
+                self.current_inst += 1;
+            }
+            Instruction::IterSubquery {
+                subquery,
+                arguments,
+            } => {
+                let arguments = self.access_arguments(arguments)?;
+                let mut rows = self.run_subquery(*subquery, arguments)?;
+                // subqueries without `RETURN` keep the outer match
+                if self.subqueries[*subquery].returns.is_empty() {
+                    rows = vec![Vec::new()];
                 }
-                Instruction::LoadNextEdge { jump } => {
-                    let iter = self.edge_iters.last_mut().unwrap();
-                    if let Some(edge_id) = iter.next() {
-                        self.edge_stack
-                            .push(self.txn.load_edge(edge_id?)?.ok_or(Error::MissingEdge)?);
-                        self.current_inst += 1;
-                    } else {
-                        self.edge_iters.pop();
-                        self.current_inst = *jump;
+                self.row_iters.push(rows.into_iter());
+                self.current_inst += 1;
+            }
+            Instruction::IterJoin { subquery, keys } => {
+                if !self.joins.contains_key(subquery) {
+                    // the joined plan does not depend on the match
+                    let mut table = JoinTable::new();
+                    for row in self.run_subquery(*subquery, self.arguments.clone())? {
+                        let key = row[..keys.len()]
+                            .iter()
+                            .map(|value| join_key(&value.to_ref()))
+                            .collect::<Result<_, _>>()?;
+                        table.entry(key).or_default().push(row);
                     }
+                    self.joins.insert(*subquery, table);
                 }
-
-                Instruction::LoadExactNode { jump, id } => {
-                    let id = self.access_property(*id)?.cast_to_id().ok();
-                    if let Some(node) = id
-                        .map(|id| self.txn.load_node(id).transpose())
-                        .flatten()
-                        .transpose()?
-                    {
-                        self.node_stack.push(node);
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
+                let key = keys
+                    .iter()
+                    .map(|key| join_key(&self.access_property(*key)?))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let rows = self.joins[subquery].get(&key).cloned().unwrap_or_default();
+                self.row_iters.push(rows.into_iter());
+                self.current_inst += 1;
+            }
+            Instruction::Collect {
+                subquery,
+                list,
+                arguments,
+            } => {
+                let arguments = self.access_arguments(arguments)?;
+                let value = match list.map(|list| self.access_property(list)).transpose()? {
+                    Some(PropRef::List(items)) => {
+                        let mut values = Vec::new();
+                        for item in items {
+                            let arguments =
+                                once(item.to_owned()).chain(arguments.iter().cloned()).collect();
+                            let rows = self.run_subquery(*subquery, arguments)?;
+                            values.extend(rows.into_iter().flat_map(|row| row.into_iter().next()));
+                        }
+                        PropOwned::List(values)
                     }
-                }
-
-                Instruction::LoadOriginNode { edge } => {
-                    let edge = &self.edge_stack[*edge];
-                    let node = self.txn.load_node(edge.origin)?.ok_or(Error::MissingNode)?;
-                    self.node_stack.push(node);
+                    Some(PropRef::Null) => PropOwned::Null,
+                    Some(_) => return Err(Error::TypeMismatch),
+                    None => PropOwned::List(
+                        self.run_subquery(*subquery, arguments)?
+                            .into_iter()
+                            .flat_map(|row| row.into_iter().next())
+                            .collect(),
+                    ),
+                };
+                self.row_stack.push(vec![value]);
+                self.current_inst += 1;
+            }
+            Instruction::LoadNextRow { jump } => {
+                let iter = self.row_iters.last_mut().unwrap();
+                if let Some(row) = iter.next() {
+                    self.row_stack.push(row);
                     self.current_inst += 1;
+                } else {
+                    self.row_iters.pop();
+                    self.current_inst = *jump;
                 }
-                Instruction::LoadTargetNode { edge } => {
-                    let edge = &self.edge_stack[*edge];
-                    let node = self.txn.load_node(edge.target)?.ok_or(Error::MissingNode)?;
+            }
+
+            Instruction::LoadExactNode { jump, id } => {
+                let node = match self.access_property(*id)? {
+                    // nodes passed as arguments to subqueries
+                    PropRef::Node(node) => Some(node),
+                    id => match id.cast_to_id() {
+                        Ok(id) => self.txn.load_node(id)?,
+                        Err(_) => None,
+                    },
+                };
+                if let Some(node) = node {
                     self.node_stack.push(node);
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::LoadOtherNode { node, edge } => {
-                    let node = &self.node_stack[*node];
-                    let edge = &self.edge_stack[*edge];
-                    let other = if edge.target == node.id {
-                        self.txn.load_node(edge.origin)?.ok_or(Error::MissingNode)?
-                    } else {
-                        self.txn.load_node(edge.target)?.ok_or(Error::MissingNode)?
-                    };
-                    self.node_stack.push(other);
+            }
+            Instruction::LoadExactEdge { jump, id } => {
+                let edge = match self.access_property(*id)? {
+                    // edges passed as arguments to subqueries
+                    PropRef::Edge(edge) => Some(edge),
+                    id => match id.cast_to_id() {
+                        Ok(id) => self.txn.load_edge(id)?,
+                        Err(_) => None,
+                    },
+                };
+                if let Some(edge) = edge {
+                    self.edge_stack.push(edge.into());
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
+            }
 
-                Instruction::PopNode => {
-                    self.node_stack.pop();
-                    self.current_inst += 1;
-                }
-                Instruction::PopEdge => {
-                    self.edge_stack.pop();
-                    self.current_inst += 1;
-                }
+            Instruction::LoadOriginNode { edge } => {
+                let edge = &self.edge_stack[*edge];
+                let node = self.txn.load_node(edge.origin)?.ok_or(Error::MissingNode)?;
+                self.node_stack.push(node);
+                self.current_inst += 1;
+            }
+            Instruction::LoadTargetNode { edge } => {
+                let edge = &self.edge_stack[*edge];
+                let node = self.txn.load_node(edge.target)?.ok_or(Error::MissingNode)?;
+                self.node_stack.push(node);
+                self.current_inst += 1;
+            }
+            Instruction::LoadOtherNode { node, edge } => {
+                let node = &self.node_stack[*node];
+                let edge = &self.edge_stack[*edge];
+                let other = if edge.target == node.id {
+                    edge.origin
+                } else {
+                    edge.target
+                };
+                let other = self.txn.load_node(other)?.ok_or(Error::MissingNode)?;
+                self.node_stack.push(other);
+                self.current_inst += 1;
+            }
 
-                Instruction::CheckIsOrigin { jump, node, edge } => {
-                    let node = &self.node_stack[*node];
-                    let edge = &self.edge_stack[*edge];
-                    if node.id == edge.origin {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
-                }
-                Instruction::CheckIsTarget { jump, node, edge } => {
-                    let node = &self.node_stack[*node];
-                    let edge = &self.edge_stack[*edge];
-                    if node.id == edge.target {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
-                }
+            Instruction::PopNode => {
+                self.node_stack.pop();
+                self.current_inst += 1;
+            }
+            Instruction::PopEdge => {
+                self.edge_stack.pop();
+                self.current_inst += 1;
+            }
+            Instruction::PopRow => {
+                self.row_stack.pop();
+                self.current_inst += 1;
+            }
 
-                Instruction::CheckNodeLabel { jump, node, label } => {
-                    let node = &self.node_stack[*node];
-                    if node.label() == label.as_str() {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
+            Instruction::CheckIsOrigin { jump, node, edge } => {
+                let node = &self.node_stack[*node];
+                let edge = &self.edge_stack[*edge];
+                if node.id == edge.origin {
+                    self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::CheckEdgeLabel { jump, edge, label } => {
-                    let edge = &self.edge_stack[*edge];
-                    if edge.label() == label.as_str() {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
+            }
+            Instruction::CheckIsTarget { jump, node, edge } => {
+                let node = &self.node_stack[*node];
+                let edge = &self.edge_stack[*edge];
+                if node.id == edge.target {
+                    self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
+            }
 
-                Instruction::CheckNodeId { jump, node, id } => {
-                    let node = &self.node_stack[*node];
-                    match self.access_property(*id)?.cast_to_id() {
-                        Ok(id) if id == node.id => self.current_inst += 1,
-                        Ok(_) | Err(_) => self.current_inst = *jump,
-                    }
+            Instruction::CheckNodeLabel { jump, node, label } => {
+                let node = &self.node_stack[*node];
+                if node.label() == self.access_symbol(&self.accesses[*label])? {
+                    self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::CheckEdgeId { jump, edge, id } => {
-                    let edge = &self.edge_stack[*edge];
-                    match self.access_property(*id)?.cast_to_id() {
-                        Ok(id) if id == edge.id => self.current_inst += 1,
-                        Ok(_) | Err(_) => self.current_inst = *jump,
-                    }
+            }
+            Instruction::CheckEdgeLabel { jump, edge, label } => {
+                let edge = &self.edge_stack[*edge];
+                if edge.label == self.access_symbol(&self.accesses[*label])? {
+                    self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
+            }
 
-                Instruction::CheckTrue { jump, value } => {
-                    let value = self.access_property(*value)?;
-                    if value.is_truthy() {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
-                }
-                Instruction::CheckEq { jump, lhs, rhs } => {
-                    let lhs = self.access_property(*lhs)?;
-                    let rhs = self.access_property(*rhs)?;
-                    if lhs.loosely_equals(&rhs) {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
-                }
-                Instruction::CheckLt { jump, lhs, rhs } => {
-                    let lhs = self.access_property(*lhs)?;
-                    let rhs = self.access_property(*rhs)?;
-                    if let Some(Ordering::Less) = lhs.loosely_compare(&rhs) {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
+            Instruction::CheckNodeId { jump, node, id } => {
+                let node = &self.node_stack[*node];
+                match self.access_property(*id)?.cast_to_id() {
+                    Ok(id) if id == node.id => self.current_inst += 1,
+                    Ok(_) | Err(_) => self.current_inst = *jump,
                 }
-                Instruction::CheckGt { jump, lhs, rhs } => {
-                    let lhs = self.access_property(*lhs)?;
-                    let rhs = self.access_property(*rhs)?;
-                    if let Some(Ordering::Greater) = lhs.loosely_compare(&rhs) {
-                        self.current_inst += 1;
-                    } else {
-                        self.current_inst = *jump;
-                    }
+            }
+            Instruction::CheckEdgeId { jump, edge, id } => {
+                let edge = &self.edge_stack[*edge];
+                match self.access_property(*id)?.cast_to_id() {
+                    Ok(id) if id == edge.id => self.current_inst += 1,
+                    Ok(_) | Err(_) => self.current_inst = *jump,
                 }
+            }
 
-                Instruction::CreateNode { label, properties } => {
-                    let node = Node {
-                        id: self.txn.id_seq(),
-                        label: label.clone(),
-                        properties: properties
-                            .iter()
-                            .map(|(key, access)| -> Result<_, Error> {
-                                Ok((key.clone(), self.access_property(*access)?.to_owned()))
-                            })
-                            .filter(|prop| !matches!(prop, Ok((_, PropOwned::Null))))
-                            .collect::<Result<_, Error>>()?,
-                    };
-                    self.txn.queue_update(Update::CreateNode(node.clone()))?;
-                    self.node_stack.push(node);
+            Instruction::CheckTrue { jump, value } => {
+                let value = self.access_property(*value)?;
+                if value.is_truthy() {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::CreateEdge {
-                    label,
-                    origin,
-                    target,
-                    properties,
-                } => {
-                    let origin = self.node_stack[*origin].id();
-                    let target = self.node_stack[*target].id();
-                    let edge = Edge {
-                        id: self.txn.id_seq(),
-                        label: label.clone(),
-                        origin,
-                        target,
-                        properties: properties
-                            .iter()
-                            .map(|(key, access)| -> Result<_, Error> {
-                                Ok((key.clone(), self.access_property(*access)?.to_owned()))
-                            })
-                            .filter(|prop| !matches!(prop, Ok((_, PropOwned::Null))))
-                            .collect::<Result<_, Error>>()?,
-                    };
-                    self.txn.queue_update(Update::CreateEdge(edge.clone()))?;
-                    self.edge_stack.push(edge);
+            }
+            Instruction::CheckEq { jump, lhs, rhs } => {
+                let lhs = self.access_property(*lhs)?;
+                let rhs = self.access_property(*rhs)?;
+                if lhs.loosely_equals(&rhs) {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::SetNodeProperty { node, key, value } => {
-                    let node = &self.node_stack[*node];
-                    let value = self.access_property(*value)?.to_owned();
-                    self.txn.queue_update(Update::SetNodeProperty(
-                        node.id,
-                        key.to_string(),
-                        value,
-                    ))?;
+            }
+            Instruction::CheckLt { jump, lhs, rhs } => {
+                let lhs = self.access_property(*lhs)?;
+                let rhs = self.access_property(*rhs)?;
+                if let Some(Ordering::Less) = lhs.loosely_compare(&rhs) {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::SetEdgeProperty { edge, key, value } => {
-                    let edge = &self.node_stack[*edge];
-                    let value = self.access_property(*value)?.to_owned();
-                    self.txn.queue_update(Update::SetEdgeProperty(
-                        edge.id,
-                        key.to_string(),
-                        value,
-                    ))?;
+            }
+            Instruction::CheckLe { jump, lhs, rhs } => {
+                let lhs = self.access_property(*lhs)?;
+                let rhs = self.access_property(*rhs)?;
+                if lhs.loosely_equals(&rhs) || lhs.loosely_compare(&rhs) == Some(Ordering::Less) {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::DeleteNode { node } => {
-                    let node = &self.node_stack[*node];
-                    self.txn.queue_update(Update::DeleteNode(node.id))?;
+            }
+            Instruction::CheckGt { jump, lhs, rhs } => {
+                let lhs = self.access_property(*lhs)?;
+                let rhs = self.access_property(*rhs)?;
+                if let Some(Ordering::Greater) = lhs.loosely_compare(&rhs) {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
                 }
-                Instruction::DeleteEdge { edge } => {
-                    let edge = &self.edge_stack[*edge];
-                    self.txn.queue_update(Update::DeleteEdge(edge.id))?;
+            }
+            Instruction::CheckGe { jump, lhs, rhs } => {
+                let lhs = self.access_property(*lhs)?;
+                let rhs = self.access_property(*rhs)?;
+                if lhs.loosely_equals(&rhs) || lhs.loosely_compare(&rhs) == Some(Ordering::Greater)
+                {
                     self.current_inst += 1;
+                } else {
+                    self.current_inst = *jump;
+                }
+            }
+
+            Instruction::CreateNode { label, properties } => {
+                let node = Node {
+                    id: self.txn.id_seq(),
+                    label: self.access_symbol(&self.accesses[*label])?.to_string(),
+                    properties: properties
+                        .iter()
+                        .map(|(key, access)| -> Result<_, Error> {
+                            Ok((key.clone(), self.access_property(*access)?.to_owned()))
+                        })
+                        .filter(|prop| !matches!(prop, Ok((_, PropOwned::Null))))
+                        .collect::<Result<_, Error>>()?,
+                };
+                self.txn.queue_update(Update::CreateNode(node.clone()))?;
+                self.node_stack.push(node);
+                self.current_inst += 1;
+            }
+            Instruction::CreateEdge {
+                label,
+                origin,
+                target,
+                properties,
+            } => {
+                let origin = self.node_stack[*origin].id();
+                let target = self.node_stack[*target].id();
+                let edge = Edge {
+                    id: self.txn.id_seq(),
+                    label: self.access_symbol(&self.accesses[*label])?.to_string(),
+                    origin,
+                    target,
+                    properties: properties
+                        .iter()
+                        .map(|(key, access)| -> Result<_, Error> {
+                            Ok((key.clone(), self.access_property(*access)?.to_owned()))
+                        })
+                        .filter(|prop| !matches!(prop, Ok((_, PropOwned::Null))))
+                        .collect::<Result<_, Error>>()?,
+                };
+                self.txn.queue_update(Update::CreateEdge(edge.clone()))?;
+                self.edge_stack.push(edge.into());
+                self.current_inst += 1;
+            }
+            Instruction::SetNodeProperty { node, key, value } => {
+                let node = &self.node_stack[*node];
+                let value = self.access_property(*value)?.to_owned();
+                let key = self.access_symbol(&self.accesses[*key])?.to_string();
+                self.txn
+                    .queue_update(Update::SetNodeProperty(node.id, key, value))?;
+                self.current_inst += 1;
+            }
+            Instruction::SetEdgeProperty { edge, key, value } => {
+                let edge = &self.edge_stack[*edge];
+                let value = self.access_property(*value)?.to_owned();
+                let key = self.access_symbol(&self.accesses[*key])?.to_string();
+                self.txn
+                    .queue_update(Update::SetEdgeProperty(edge.id, key, value))?;
+                self.current_inst += 1;
+            }
+            Instruction::DeleteNode { node } => {
+                let node = &self.node_stack[*node];
+                self.txn.queue_update(Update::DeleteNode(node.id))?;
+                self.current_inst += 1;
+            }
+            Instruction::DeleteEdge { edge } => {
+                let edge = &self.edge_stack[*edge];
+                self.txn.queue_update(Update::DeleteEdge(edge.id))?;
+                self.current_inst += 1;
+            }
+            Instruction::CreateIndex { index, kind } => {
+                self.txn
+                    .queue_update(Update::CreateIndex(index.clone(), *kind))?;
+                self.current_inst += 1;
+            }
+            Instruction::DropIndex { index, kind } => {
+                self.txn
+                    .queue_update(Update::DropIndex(index.clone(), *kind))?;
+                self.current_inst += 1;
+            }
+            Instruction::CreateRule { rule } => {
+                self.txn.queue_update(Update::CreateRule(rule.clone()))?;
+                self.current_inst += 1;
+            }
+            Instruction::DropRule { rule } => {
+                self.txn.queue_update(Update::DropRule(rule.clone()))?;
+                self.current_inst += 1;
+            }
+            Instruction::Foreach {
+                subquery,
+                list,
+                arguments,
+            } => {
+                let items = match self.access_property(*list)?.to_owned() {
+                    PropOwned::List(items) => items,
+                    PropOwned::Null => Vec::new(),
+                    _ => return Err(Error::TypeMismatch),
+                };
+                let arguments = self.access_arguments(arguments)?;
+                for item in items {
+                    let arguments = once(item).chain(arguments.iter().cloned()).collect();
+                    self.run_subquery(*subquery, arguments)?;
                 }
+                self.current_inst += 1;
             }
         }
+        Ok(None)
+    }
+}
+
+impl Profile {
+    fn new(program: &Program) -> Self {
+        Self {
+            instructions: once(program)
+                .chain(&program.unions)
+                .map(|part| vec![(0, Duration::ZERO); part.instructions.len()])
+                .collect(),
+            rows: 0,
+            nodes: 0,
+            edges: 0,
+        }
+    }
+
+    /// Report the profile as rows of `(query, kind, index,
+    /// detail, count, time)`, where `time` is in nanoseconds.
+    fn into_rows(self, program: &Program) -> Vec<Vec<PropOwned>> {
+        let mut rows = Vec::new();
+        let mut total = Duration::ZERO;
+        for (part, (program, stats)) in once(program)
+            .chain(&program.unions)
+            .zip(self.instructions)
+            .enumerate()
+        {
+            let stats = program.instructions.iter().zip(stats);
+            for (idx, (inst, (count, time))) in stats.enumerate() {
+                total += time;
+                rows.push(vec![
+                    PropOwned::Integer(part as i64),
+                    PropOwned::Text("instruction".to_string()),
+                    PropOwned::Integer(idx as i64),
+                    PropOwned::Text(format!("{:?}", inst)),
+                    PropOwned::Integer(count as i64),
+                    PropOwned::Integer(time.as_nanos() as i64),
+                ]);
+            }
+        }
+        let counts = [("rows", self.rows), ("nodes", self.nodes), ("edges", self.edges)];
+        for (kind, count) in counts {
+            rows.push(vec![
+                PropOwned::Null,
+                PropOwned::Text(kind.to_string()),
+                PropOwned::Null,
+                PropOwned::Null,
+                PropOwned::Integer(count as i64),
+                PropOwned::Null,
+            ]);
+        }
+        rows.push(vec![
+            PropOwned::Null,
+            PropOwned::Text("time".to_string()),
+            PropOwned::Null,
+            PropOwned::Null,
+            PropOwned::Null,
+            PropOwned::Integer(total.as_nanos() as i64),
+        ]);
+        rows
     }
 }
 
@@ -592,3 +1362,25 @@ impl<'env, 'txn, 'prog> std::fmt::Debug for VirtualMachine<'env, 'txn, 'prog> {
             .finish()
     }
 }
+
+/// The hash table key of a value joined by `IterJoin`. Values which
+/// are loosely equal have the same key, but values with the same key
+/// are not necessarily equal, which is checked after the join.
+fn join_key(value: &PropRef) -> Result<Vec<u8>, Error> {
+    let key = match value {
+        // -0.0 is equal to 0.0
+        PropRef::Integer(num) => bincode::serialize(&PropRef::Real(*num as f64 + 0.0))?,
+        PropRef::Real(num) => bincode::serialize(&PropRef::Real(num + 0.0))?,
+        PropRef::Node(node) => bincode::serialize(&PropRef::Id(node.id))?,
+        PropRef::Edge(edge) => bincode::serialize(&PropRef::Id(edge.id))?,
+        // nodes in lists do not serialize their properties
+        // in a stable order, so items are keyed one by one
+        PropRef::List(items) => {
+            let keys = items.iter().map(join_key).collect::<Result<Vec<_>, _>>()?;
+            let items = keys.iter().map(|key| PropRef::Blob(key)).collect();
+            bincode::serialize(&PropRef::List(items))?
+        }
+        value => bincode::serialize(value)?,
+    };
+    Ok(key)
+}
@@ -0,0 +1,97 @@
+use super::schema::{push_text, read_text};
+use super::Edge;
+use crate::Error;
+use std::convert::TryInto;
+
+/// Which end of an edge a node of an adjacency entry is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Direction {
+    /// The node is the origin of the edge.
+    Outgoing,
+    /// The node is the target of the edge.
+    Incoming,
+}
+
+/// An entry of the adjacency index, which is keyed by the node, the
+/// direction, the label of the edge and the edge id, and maps to the
+/// node on the other end of the edge. Entries are ordered, such that
+/// the edges of a node with a given direction and label are adjacent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Adjacency<'a> {
+    pub node: u64,
+    pub direction: Direction,
+    pub label: &'a str,
+    pub edge: u64,
+}
+
+impl Direction {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Outgoing => 0,
+            Self::Incoming => 1,
+        }
+    }
+
+    fn from_u8(direction: u8) -> Result<Self, Error> {
+        match direction {
+            0 => Ok(Self::Outgoing),
+            1 => Ok(Self::Incoming),
+            _ => Err(Error::Corruption),
+        }
+    }
+}
+
+impl<'a> Adjacency<'a> {
+    /// The entries for the origin and target of `edge`,
+    /// together with the node on their other end.
+    pub fn of_edge(edge: &'a Edge) -> [(Self, u64); 2] {
+        let entry = |node, direction| Self {
+            node,
+            direction,
+            label: &edge.label,
+            edge: edge.id,
+        };
+        [
+            (entry(edge.origin, Direction::Outgoing), edge.target),
+            (entry(edge.target, Direction::Incoming), edge.origin),
+        ]
+    }
+
+    /// The prefix shared by the entries of `node`, which are further
+    /// narrowed down to a direction and a label of the edges. A label
+    /// is only used if a direction is given.
+    pub fn prefix(node: u64, direction: Option<Direction>, label: Option<&str>) -> Vec<u8> {
+        let mut bytes = node.to_be_bytes().to_vec();
+        if let Some(direction) = direction {
+            bytes.push(direction.to_u8());
+            if let Some(label) = label {
+                push_text(&mut bytes, label);
+            }
+        }
+        bytes
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Self::prefix(self.node, Some(self.direction), Some(self.label));
+        bytes.extend_from_slice(&self.edge.to_be_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, Error> {
+        let read_id = |bytes: &[u8]| {
+            bytes
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| Error::Corruption)
+        };
+        let node = read_id(bytes.get(..8).ok_or(Error::Corruption)?)?;
+        let direction = Direction::from_u8(*bytes.get(8).ok_or(Error::Corruption)?)?;
+        let (label, rest) = read_text(&bytes[9..])?;
+        Ok(Self {
+            node,
+            direction,
+            label,
+            edge: read_id(rest)?,
+        })
+    }
+}
@@ -1,10 +1,15 @@
-use super::{DynTxn, Node, StoreTxn};
+use super::schema::read_z_order;
+use super::spatial::{self, Point};
+use super::{Adjacency, Direction, DynTxn, Edge, Index, Node, PropOwned, PropRef, StoreTxn};
 use crate::Error;
 use crate::Vault;
 
 use sanakirja::{btree, Env, UnsizedStorable};
 use serde::Deserialize;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
 use std::marker::PhantomData;
+use std::ops::Bound;
 use std::sync::Arc;
 
 type BytesCursor<K, V> = btree::Cursor<K, V, btree::page_unsized::Page<K, V>>;
@@ -12,13 +17,23 @@ type BytesCursor<K, V> = btree::Cursor<K, V, btree::page_unsized::Page<K, V>>;
 type BytesIter<'txn, K, V> =
     btree::Iter<'txn, DynTxn<&'txn Env>, K, V, btree::page_unsized::Page<K, V>>;
 
-pub(crate) type IndexIter<'txn> =
-    btree::Iter<'txn, DynTxn<&'txn Env>, u64, u64, btree::page::Page<u64, u64>>;
-
 pub(crate) enum EdgeIter<'txn> {
-    Directed(u64, IndexIter<'txn>),
-    // TODO(dyedgreen): Fix this; its a bit iffy ...
-    Undirected(u64, Option<IndexIter<'txn>>, IndexIter<'txn>),
+    Adjacent(Vec<u8>, &'txn StoreTxn<'txn>, BytesCursor<[u8], u64>),
+    WithLabel(String, &'txn StoreTxn<'txn>, BytesCursor<[u8], u64>),
+    Chained(Box<EdgeIter<'txn>>, Box<EdgeIter<'txn>>),
+}
+
+/// An edge returned by an [`EdgeIter`]. The label and ends of edges
+/// found through the adjacency index are read from the index, such
+/// that the record of the edge is only loaded once its properties
+/// are accessed.
+#[derive(Debug, Clone)]
+pub(crate) struct LazyEdge {
+    pub id: u64,
+    pub label: String,
+    pub origin: u64,
+    pub target: u64,
+    record: OnceCell<Edge>,
 }
 
 pub(crate) struct DeserializeIter<'txn, K, I>
@@ -33,53 +48,129 @@ where
 pub(crate) enum NodeIter<'txn> {
     All(VaultNodeDeserializeIter<'txn, u64>),
     WithLabel(String, &'txn StoreTxn<'txn>, BytesCursor<[u8], u64>),
+    /// Index entries from the cursor up to and including
+    /// all entries which start with the given end.
+    Indexed(Vec<u8>, &'txn StoreTxn<'txn>, BytesCursor<[u8], u64>),
+    /// Index entries of points within boxes, given by the prefix of
+    /// their entries and the positions of their corners on the Z-order
+    /// curve. The cursor is within the last box, earlier boxes remain.
+    Spatial(Vec<(Vec<u8>, u64, u64)>, &'txn StoreTxn<'txn>, BytesCursor<[u8], u64>),
+    Filtered(Box<NodeIter<'txn>>, String, Bound<PropOwned>, Bound<PropOwned>),
 }
 
 impl<'txn> EdgeIter<'txn> {
-    pub fn origins(txn: &'txn StoreTxn<'txn>, node: u64) -> Result<Self, Error> {
-        let iter = btree::iter(&txn.txn, &txn.origins, Some((&node, None)))?;
-        Ok(Self::Directed(node, iter))
+    /// Edges going out of `node`, which have `label` if given.
+    pub fn origins(
+        txn: &'txn StoreTxn<'txn>,
+        node: u64,
+        label: Option<&str>,
+    ) -> Result<Self, Error> {
+        Self::adjacent(txn, Adjacency::prefix(node, Some(Direction::Outgoing), label))
+    }
+
+    /// Edges coming into `node`, which have `label` if given.
+    pub fn targets(
+        txn: &'txn StoreTxn<'txn>,
+        node: u64,
+        label: Option<&str>,
+    ) -> Result<Self, Error> {
+        Self::adjacent(txn, Adjacency::prefix(node, Some(Direction::Incoming), label))
     }
 
-    pub fn targets(txn: &'txn StoreTxn<'txn>, node: u64) -> Result<Self, Error> {
-        let iter = btree::iter(&txn.txn, &txn.targets, Some((&node, None)))?;
-        Ok(Self::Directed(node, iter))
+    /// Edges going out of and then coming into `node`, which have
+    /// `label` if given. Edges from `node` to itself are returned
+    /// twice, once for each direction.
+    pub fn both(
+        txn: &'txn StoreTxn<'txn>,
+        node: u64,
+        label: Option<&str>,
+    ) -> Result<Self, Error> {
+        match label {
+            None => Self::adjacent(txn, Adjacency::prefix(node, None, None)),
+            Some(label) => {
+                let origins = Self::origins(txn, node, Some(label))?;
+                let targets = Self::targets(txn, node, Some(label))?;
+                Ok(Self::Chained(Box::new(origins), Box::new(targets)))
+            }
+        }
     }
 
-    pub fn both(txn: &'txn StoreTxn<'txn>, node: u64) -> Result<Self, Error> {
-        let iter_orig = btree::iter(&txn.txn, &txn.origins, Some((&node, None)))?;
-        let iter_targ = btree::iter(&txn.txn, &txn.targets, Some((&node, None)))?;
-        Ok(Self::Undirected(node, Some(iter_orig), iter_targ))
+    fn adjacent(txn: &'txn StoreTxn<'txn>, prefix: Vec<u8>) -> Result<Self, Error> {
+        let mut cursor = BytesCursor::new(&txn.txn, &txn.adjacency)?;
+        cursor.set(&txn.txn, prefix.as_slice(), None)?;
+        Ok(Self::Adjacent(prefix, txn, cursor))
+    }
+
+    pub fn with_label(txn: &'txn StoreTxn<'txn>, label: String) -> Result<Self, Error> {
+        let mut cursor = BytesCursor::new(&txn.txn, &txn.edge_labels)?;
+        cursor.set(&txn.txn, label.as_bytes(), None)?;
+        Ok(Self::WithLabel(label, txn, cursor))
     }
 }
 
 impl<'txn> Iterator for EdgeIter<'txn> {
-    type Item = Result<u64, Error>;
+    type Item = Result<LazyEdge, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let filter = |id: u64| {
-            move |entry: Result<(&u64, &u64), Error>| {
-                entry
-                    .map(|(&k, &v)| if k == id { Some(v) } else { None })
-                    .transpose()
-            }
-        };
         match self {
-            Self::Directed(id, iter) | Self::Undirected(id, None, iter) => {
-                iter.next().and_then(filter(*id))
-            }
-            // It might also be worth, to combine the indices with a key like
-            // (u64, u8) / (node, type) / orig_type = 0, target_type = 1, which is
-            // ordered; so that pages can be loaded continuously ...
-            Self::Undirected(id, iter_opt, iter_target) => iter_opt
-                .as_mut()
-                .unwrap() // None case matched above, but need ref to opt to assign
-                .next()
-                .and_then(filter(*id))
-                .or_else(|| {
-                    *iter_opt = None;
-                    iter_target.next().and_then(filter(*id))
-                }),
+            Self::Adjacent(prefix, txn, cursor) => match cursor.next(&txn.txn).transpose()? {
+                Ok((key, &other)) if key.starts_with(prefix) => {
+                    Some(Adjacency::decode(key).map(|entry| LazyEdge::adjacent(&entry, other)))
+                }
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            },
+            Self::Chained(first, second) => first.next().or_else(|| second.next()),
+            // the label index does not store the ends of edges
+            Self::WithLabel(label, txn, cursor) => match cursor.next(&txn.txn).transpose()? {
+                Ok((key, &id)) if key == label.as_bytes() => Some(
+                    txn.load_edge(id)
+                        .and_then(|edge| edge.ok_or(Error::MissingEdge))
+                        .map(LazyEdge::from),
+                ),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            },
+        }
+    }
+}
+
+impl LazyEdge {
+    /// The edge of an adjacency `entry`, which has
+    /// the node `other` on its other end.
+    fn adjacent(entry: &Adjacency, other: u64) -> Self {
+        let (origin, target) = match entry.direction {
+            Direction::Outgoing => (entry.node, other),
+            Direction::Incoming => (other, entry.node),
+        };
+        Self {
+            id: entry.edge,
+            label: entry.label.to_string(),
+            origin,
+            target,
+            record: OnceCell::new(),
+        }
+    }
+
+    /// The record of the edge, which is loaded
+    /// from `txn` when it is first accessed.
+    pub fn record(&self, txn: &StoreTxn) -> Result<&Edge, Error> {
+        if let Some(edge) = self.record.get() {
+            return Ok(edge);
+        }
+        let edge = txn.load_edge(self.id)?.ok_or(Error::MissingEdge)?;
+        Ok(self.record.get_or_init(|| edge))
+    }
+}
+
+impl From<Edge> for LazyEdge {
+    fn from(edge: Edge) -> Self {
+        Self {
+            id: edge.id,
+            label: edge.label.clone(),
+            origin: edge.origin,
+            target: edge.target,
+            record: OnceCell::from(edge),
         }
     }
 }
@@ -130,6 +221,64 @@ impl<'txn> NodeIter<'txn> {
         cursor.set(&txn.txn, label.as_bytes(), None)?;
         Ok(Self::WithLabel(label, txn, cursor))
     }
+
+    /// Nodes with `label`, where the property `key` lies between the
+    /// `lower` and `upper` bound. Uses an index if one exists, which
+    /// returns the nodes ordered by the property, and falls back to
+    /// checking all nodes with the label otherwise.
+    pub(crate) fn with_property(
+        txn: &'txn StoreTxn<'txn>,
+        label: String,
+        key: String,
+        lower: Bound<PropOwned>,
+        upper: Bound<PropOwned>,
+    ) -> Result<Self, Error> {
+        let index = Index::new(&label, &key);
+        // a bound on one side only limits the scan on the other
+        // side to the values which are comparable with it
+        let limit = |bound: &Bound<PropOwned>, other: &Bound<PropOwned>| match (bound, other) {
+            (Bound::Included(value) | Bound::Excluded(value), _) => index.entry(&[value.to_ref()]),
+            (Bound::Unbounded, Bound::Included(value) | Bound::Excluded(value)) => {
+                index.comparable_prefix(&value.to_ref())
+            }
+            (Bound::Unbounded, Bound::Unbounded) => Ok(Some(index.prefix())),
+        };
+        let range = (limit(&lower, &upper)?, limit(&upper, &lower)?);
+        let inner = match range {
+            (Some(start), Some(end)) if txn.has_index(&index)? => {
+                let mut cursor = BytesCursor::new(&txn.txn, &txn.index_entries)?;
+                cursor.set(&txn.txn, start.as_slice(), None)?;
+                Self::Indexed(end, txn, cursor)
+            }
+            _ => Self::with_label(txn, label)?,
+        };
+        Ok(Self::Filtered(Box::new(inner), key, lower, upper))
+    }
+
+    /// Nodes with `label`, where the property `key` may be a point
+    /// within one of `boxes`. Uses an index if one exists, which only
+    /// returns the nodes with a point close to one of the boxes, and
+    /// falls back to all nodes with the label otherwise.
+    pub(crate) fn within(
+        txn: &'txn StoreTxn<'txn>,
+        label: String,
+        key: String,
+        boxes: Vec<(Point, Point)>,
+    ) -> Result<Self, Error> {
+        let index = Index::new(&label, &key);
+        if !txn.has_index(&index)? {
+            return Self::with_label(txn, label);
+        }
+        let ranges: Vec<_> = boxes
+            .iter()
+            .map(|(lower_left, upper_right)| index.point_range(lower_left, upper_right))
+            .collect();
+        let mut cursor = BytesCursor::new(&txn.txn, &txn.index_entries)?;
+        if let Some((prefix, min, _)) = ranges.last() {
+            cursor.set(&txn.txn, &[prefix.as_slice(), &min.to_be_bytes()].concat(), None)?;
+        }
+        Ok(Self::Spatial(ranges, txn, cursor))
+    }
 }
 
 impl<'txn> Iterator for NodeIter<'txn> {
@@ -153,7 +302,68 @@ impl<'txn> Iterator for NodeIter<'txn> {
                     .and_then(|result| result.and_then(|&id| txn.load_node(id)).transpose()),
                 None => None,
             },
+            Self::Indexed(end, txn, cursor) => match cursor.next(&txn.txn).transpose()? {
+                Ok((key, &id)) if key <= end.as_slice() || key.starts_with(end) => {
+                    txn.load_node(id).transpose()
+                }
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            },
+            Self::Spatial(ranges, txn, cursor) => loop {
+                let (prefix, min, max) = ranges.last()?;
+                let next = match cursor.next(&txn.txn).transpose()? {
+                    Ok((entry, &id)) if entry.starts_with(prefix) => {
+                        let z = match read_z_order(&entry[prefix.len()..]) {
+                            Ok(z) => z,
+                            Err(err) => return Some(Err(err)),
+                        };
+                        if spatial::z_order_contains(z, *min, *max) {
+                            return txn.load_node(id).transpose();
+                        }
+                        spatial::z_order_next(z, *min, *max)
+                    }
+                    Ok(_) => None,
+                    Err(err) => return Some(Err(err)),
+                };
+                // skip to the next position within the box,
+                // or to the start of the next box
+                let seek = match next {
+                    Some(next) => [prefix.as_slice(), &next.to_be_bytes()].concat(),
+                    None => {
+                        ranges.pop();
+                        match ranges.last() {
+                            Some((prefix, min, _)) => {
+                                [prefix.as_slice(), &min.to_be_bytes()].concat()
+                            }
+                            None => return None,
+                        }
+                    }
+                };
+                if let Err(err) = cursor.set(&txn.txn, &seek, None) {
+                    return Some(Err(err));
+                }
+            },
+            Self::Filtered(iter, key, lower, upper) => iter.find(|node| match node {
+                Ok(node) => {
+                    let value = node.property(key).to_ref();
+                    within(&value, lower, Ordering::Greater)
+                        && within(&value, upper, Ordering::Less)
+                }
+                Err(_) => true,
+            }),
+        }
+    }
+}
+
+/// Whether `value` is on the side of `bound` given by `ordering`.
+fn within(value: &PropRef, bound: &Bound<PropOwned>, ordering: Ordering) -> bool {
+    match bound {
+        Bound::Included(bound) => {
+            let bound = bound.to_ref();
+            value.loosely_equals(&bound) || value.loosely_compare(&bound) == Some(ordering)
         }
+        Bound::Excluded(bound) => value.loosely_compare(&bound.to_ref()) == Some(ordering),
+        Bound::Unbounded => true,
     }
 }
 
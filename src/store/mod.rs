@@ -1,6 +1,8 @@
+use crate::cache::Definitions;
 use crate::Error;
-use sanakirja::btree::{Db, UDb};
+use sanakirja::btree::UDb;
 use sanakirja::{btree, Env, MutTxn, RootDb, Storable, UnsizedStorable};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
@@ -8,7 +10,12 @@ use std::sync::Arc;
 
 use txn::DynTxn;
 
+mod adjacency;
 mod iter;
+pub(crate) mod schema;
+pub(crate) mod spatial;
+mod statistics;
+pub(crate) mod temporal;
 mod txn;
 pub(crate) mod types;
 pub(crate) mod vault;
@@ -16,7 +23,11 @@ pub(crate) mod vault;
 #[cfg(test)]
 mod tests;
 
-pub(crate) use iter::{EdgeIter, NodeIter};
+pub(crate) use adjacency::{Adjacency, Direction};
+pub(crate) use iter::{EdgeIter, LazyEdge, NodeIter};
+pub(crate) use schema::{Index, IndexKind, PropertyType, Rule, Schema};
+pub(crate) use spatial::Region;
+pub(crate) use statistics::{Counter, Statistics};
 pub use types::{Edge, Node, PropOwned, PropRef};
 
 pub use vault::Vault;
@@ -24,9 +35,17 @@ pub use vault::Vault;
 const ID_SQUENCE: usize = 0;
 const DB_NODES: usize = 1;
 const DB_EDGES: usize = 2;
-const DB_ORIGINS: usize = 3;
-const DB_TARGETS: usize = 4;
+// held the origins and targets of edges, before
+// they were replaced by the adjacency index
+const DB_RETIRED_ORIGINS: usize = 3;
+const DB_RETIRED_TARGETS: usize = 4;
 const DB_LABELS: usize = 5;
+const DB_INDEXES: usize = 6;
+const DB_INDEX_ENTRIES: usize = 7;
+const DB_RULES: usize = 8;
+const DB_STATISTICS: usize = 9;
+const DB_EDGE_LABELS: usize = 10;
+const DB_ADJACENCY: usize = 11;
 
 pub(crate) struct Store {
     pub env: Env,
@@ -41,10 +60,24 @@ pub(crate) struct StoreTxn<'env> {
     pub nodes: UDb<u64, [u8]>,
     pub edges: UDb<u64, [u8]>,
 
-    pub origins: Db<u64, u64>,
-    pub targets: Db<u64, u64>,
+    pub adjacency: UDb<[u8], u64>,
 
     pub labels: UDb<[u8], u64>,
+    pub edge_labels: UDb<[u8], u64>,
+
+    pub indexes: UDb<[u8], u64>,
+    pub index_entries: UDb<[u8], u64>,
+    pub rules: UDb<[u8], u64>,
+    pub statistics: UDb<[u8], u64>,
+
+    /// Changes to the statistics, which are
+    /// applied when the transaction is committed.
+    counters: BTreeMap<Counter, i64>,
+
+    /// Nodes and edges which were changed since
+    /// constraints were last checked.
+    unchecked_nodes: HashSet<u64>,
+    unchecked_edges: HashSet<u64>,
 
     pub(crate) vault: Option<Arc<dyn Vault<Error=crate::error::Error>>>
 }
@@ -57,6 +90,10 @@ pub(crate) enum Update {
     SetEdgeProperty(u64, String, PropOwned),
     DeleteNode(u64),
     DeleteEdge(u64),
+    CreateIndex(Index, IndexKind),
+    DropIndex(Index, IndexKind),
+    CreateRule(Rule),
+    DropRule(Rule),
 }
 
 impl Store {
@@ -87,54 +124,106 @@ impl Store {
         let id_seq = AtomicU64::new(txn.root(ID_SQUENCE));
         let nodes = txn.root_db(DB_NODES).ok_or(Error::Corruption)?;
         let edges = txn.root_db(DB_EDGES).ok_or(Error::Corruption)?;
-        let origins = txn.root_db(DB_ORIGINS).ok_or(Error::Corruption)?;
-        let targets = txn.root_db(DB_TARGETS).ok_or(Error::Corruption)?;
+        let adjacency = txn.root_db(DB_ADJACENCY).ok_or(Error::Corruption)?;
         let labels = txn.root_db(DB_LABELS).ok_or(Error::Corruption)?;
+        let edge_labels = txn.root_db(DB_EDGE_LABELS).ok_or(Error::Corruption)?;
+        let indexes = txn.root_db(DB_INDEXES).ok_or(Error::Corruption)?;
+        let index_entries = txn.root_db(DB_INDEX_ENTRIES).ok_or(Error::Corruption)?;
+        let rules = txn.root_db(DB_RULES).ok_or(Error::Corruption)?;
+        let statistics = txn.root_db(DB_STATISTICS).ok_or(Error::Corruption)?;
         Ok(StoreTxn {
             txn: DynTxn::Txn(txn),
             id_seq,
             updates: RwLock::new(Vec::new()),
             nodes,
             edges,
-            origins,
-            targets,
+            adjacency,
             labels,
+            edge_labels,
+            indexes,
+            index_entries,
+            rules,
+            statistics,
+            counters: BTreeMap::new(),
+            unchecked_nodes: HashSet::new(),
+            unchecked_edges: HashSet::new(),
             vault: self.vault.clone()
         })
     }
 
     pub fn mut_txn(&self) -> Result<StoreTxn, Error> {
         let mut txn = Env::mut_txn_begin(&self.env)?;
+        // graphs written by earlier versions may still
+        // have databases which are no longer used
+        let origins: Option<btree::Db<u64, u64>> = txn.root_db(DB_RETIRED_ORIGINS);
+        let targets: Option<btree::Db<u64, u64>> = txn.root_db(DB_RETIRED_TARGETS);
+        Self::drop_db(&mut txn, DB_RETIRED_ORIGINS, origins)?;
+        Self::drop_db(&mut txn, DB_RETIRED_TARGETS, targets)?;
         let id_seq = AtomicU64::new(txn.root(ID_SQUENCE).unwrap_or(0));
         let nodes = Self::get_buffer_db(&mut txn, DB_NODES)?;
         let edges = Self::get_buffer_db(&mut txn, DB_EDGES)?;
-        let origins = Self::get_db(&mut txn, DB_ORIGINS)?;
-        let targets = Self::get_db(&mut txn, DB_TARGETS)?;
+        // graphs written before the adjacency index existed
+        // need to have their existing edges indexed
+        let index_adjacency = txn.root(DB_ADJACENCY).is_none();
+        let adjacency = Self::get_buffer_db(&mut txn, DB_ADJACENCY)?;
         let labels = Self::get_buffer_db(&mut txn, DB_LABELS)?;
-        Ok(StoreTxn {
+        // graphs written before edge labels were indexed
+        // need to have their existing edges indexed
+        let index_edge_labels = txn.root(DB_EDGE_LABELS).is_none();
+        let edge_labels = Self::get_buffer_db(&mut txn, DB_EDGE_LABELS)?;
+        let indexes = Self::get_buffer_db(&mut txn, DB_INDEXES)?;
+        let index_entries = Self::get_buffer_db(&mut txn, DB_INDEX_ENTRIES)?;
+        let rules = Self::get_buffer_db(&mut txn, DB_RULES)?;
+        // graphs written before statistics were kept
+        // need to have their existing data counted
+        let index_statistics = txn.root(DB_STATISTICS).is_none();
+        let statistics = Self::get_buffer_db(&mut txn, DB_STATISTICS)?;
+        let mut store_txn = StoreTxn {
             txn: DynTxn::MutTxn(txn),
             id_seq,
             updates: RwLock::new(Vec::new()),
             nodes,
             edges,
-            origins,
-            targets,
+            adjacency,
             labels,
+            edge_labels,
+            indexes,
+            index_entries,
+            rules,
+            statistics,
+            counters: BTreeMap::new(),
+            unchecked_nodes: HashSet::new(),
+            unchecked_edges: HashSet::new(),
             vault: self.vault.clone()
-        })
+        };
+        if index_edge_labels {
+            store_txn.index_edge_labels()?;
+        }
+        if index_adjacency {
+            store_txn.index_adjacency()?;
+        }
+        if index_statistics {
+            store_txn.index_statistics()?;
+        }
+        Ok(store_txn)
     }
 
-    fn get_db<K, V>(txn: &mut MutTxn<&Env, ()>, n: usize) -> Result<btree::Db<K, V>, Error>
+    /// Free the pages of `db`, if it exists, and clear the root `n`.
+    fn drop_db<K, V, P>(
+        txn: &mut MutTxn<&Env, ()>,
+        n: usize,
+        db: Option<btree::Db_<K, V, P>>,
+    ) -> Result<(), Error>
     where
-        K: Storable,
-        V: Storable,
+        K: Storable + ?Sized,
+        V: Storable + ?Sized,
+        P: btree::BTreePage<K, V>,
     {
-        if let Some(db) = txn.root_db::<K, V, _>(n) {
-            Ok(db)
-        } else {
-            let db = btree::create_db(txn)?;
-            Ok(db)
+        if let Some(db) = db {
+            btree::drop(txn, db)?;
+            txn.set_root(n, 0);
         }
+        Ok(())
     }
 
     fn get_buffer_db<K, V>(txn: &mut MutTxn<&Env, ()>, n: usize) -> Result<btree::UDb<K, V>, Error>
@@ -196,8 +285,363 @@ impl<'e> StoreTxn<'e> {
         }
     }
 
-    pub fn unchecked_create_node(&mut self, node: Node) -> Result<u64, Error> {
+    /// The distinct labels of all nodes.
+    pub fn node_labels(&self) -> Result<BTreeSet<String>, Error> {
+        let mut labels = BTreeSet::new();
+        for entry in btree::iter(&self.txn, &self.labels, None)? {
+            let (label, _) = entry?;
+            let label = std::str::from_utf8(label).map_err(|_| Error::Corruption)?;
+            labels.insert(label.to_string());
+        }
+        Ok(labels)
+    }
+
+    /// Add all existing edges to the edge label index.
+    fn index_edge_labels(&mut self) -> Result<(), Error> {
+        for id in self.edge_ids()? {
+            let edge = self.load_edge(id)?.ok_or(Error::MissingEdge)?;
+            btree::put(&mut self.txn, &mut self.edge_labels, edge.label.as_bytes(), &id)?;
+        }
+        Ok(())
+    }
+
+    /// Add all existing edges to the adjacency index.
+    fn index_adjacency(&mut self) -> Result<(), Error> {
+        for id in self.edge_ids()? {
+            let edge = self.load_edge(id)?.ok_or(Error::MissingEdge)?;
+            for (entry, other) in Adjacency::of_edge(&edge).iter() {
+                btree::put(&mut self.txn, &mut self.adjacency, &entry.encode(), other)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count all existing nodes and edges.
+    fn index_statistics(&mut self) -> Result<(), Error> {
+        let labels = NodeIter::all(self)?
+            .map(|node| node.map(|node| node.label))
+            .collect::<Result<Vec<_>, _>>()?;
+        for label in labels {
+            self.count(Counter::Nodes(label), 1);
+        }
+        for id in self.edge_ids()? {
+            let edge = self.load_edge(id)?.ok_or(Error::MissingEdge)?;
+            self.count_edge(&edge, 1)?;
+        }
+        self.commit_counters()
+    }
+
+    /// The IDs of all edges.
+    pub fn edge_ids(&self) -> Result<Vec<u64>, Error> {
+        btree::iter(&self.txn, &self.edges, None)?
+            .map(|entry| Ok(*entry?.0))
+            .collect()
+    }
+
+    /// The schema of the graph, which is
+    /// used when planning queries.
+    pub fn schema(&self) -> Result<Schema, Error> {
+        Ok(Schema {
+            indexes: self.index_definitions()?,
+            rules: self.load_rules()?.into_iter().collect(),
+            statistics: self.statistics()?,
+        })
+    }
+
+    /// The index and rule definitions, which is the
+    /// schema without the statistics.
+    pub fn definitions(&self) -> Result<Definitions, Error> {
+        Ok(Definitions {
+            indexes: self.index_definitions()?,
+            rules: self.load_rules()?.into_iter().collect(),
+        })
+    }
+
+    fn index_definitions(&self) -> Result<BTreeMap<Index, IndexKind>, Error> {
+        let mut indexes = BTreeMap::new();
+        for entry in btree::iter(&self.txn, &self.indexes, None)? {
+            let (index, &kind) = entry?;
+            indexes.insert(Index::decode(index)?, IndexKind::from_u64(kind)?);
+        }
+        Ok(indexes)
+    }
+
+    /// The statistics as of the last commit.
+    pub fn statistics(&self) -> Result<Statistics, Error> {
+        let mut statistics = Statistics::default();
+        for entry in btree::iter(&self.txn, &self.statistics, None)? {
+            let (counter, &count) = entry?;
+            statistics.insert(Counter::decode(counter)?, count);
+        }
+        Ok(statistics)
+    }
+
+    fn count(&mut self, counter: Counter, delta: i64) {
+        *self.counters.entry(counter).or_insert(0) += delta;
+    }
+
+    /// Count an edge and its endpoints, if they exist.
+    fn count_edge(&mut self, edge: &Edge, delta: i64) -> Result<(), Error> {
+        self.count(Counter::Edges(edge.label.clone()), delta);
+        if let Some(origin) = self.load_node(edge.origin)? {
+            self.count(Counter::Origins(edge.label.clone(), origin.label), delta);
+        }
+        if let Some(target) = self.load_node(edge.target)? {
+            self.count(Counter::Targets(edge.label.clone(), target.label), delta);
+        }
+        Ok(())
+    }
+
+    /// Apply the changes to the statistics made by this transaction.
+    fn commit_counters(&mut self) -> Result<(), Error> {
+        for (counter, delta) in std::mem::take(&mut self.counters) {
+            if delta == 0 {
+                continue;
+            }
+            let key = counter.encode();
+            let count = btree::get(&self.txn, &self.statistics, key.as_slice(), None)?
+                .filter(|(other, _)| *other == key.as_slice())
+                .map(|(_, &count)| count)
+                .unwrap_or(0);
+            let count = (count as i64 + delta).max(0) as u64;
+            btree::del(&mut self.txn, &mut self.statistics, key.as_slice(), None)?;
+            if count > 0 {
+                btree::put(&mut self.txn, &mut self.statistics, key.as_slice(), &count)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_rules(&self) -> Result<Vec<Rule>, Error> {
+        let mut rules = Vec::new();
+        for entry in btree::iter(&self.txn, &self.rules, None)? {
+            let (rule, _) = entry?;
+            rules.push(bincode::deserialize(rule)?);
+        }
+        Ok(rules)
+    }
+
+    pub fn create_rule(&mut self, rule: &Rule) -> Result<(), Error> {
+        let rule = bincode::serialize(rule)?;
+        btree::del(&mut self.txn, &mut self.rules, rule.as_slice(), None)?;
+        btree::put(&mut self.txn, &mut self.rules, rule.as_slice(), &0)?;
+        Ok(())
+    }
+
+    pub fn drop_rule(&mut self, rule: &Rule) -> Result<(), Error> {
+        let rule = bincode::serialize(rule)?;
+        btree::del(&mut self.txn, &mut self.rules, rule.as_slice(), None)?;
+        Ok(())
+    }
+
+    /// The kind of `index`, if the index exists.
+    pub fn index_kind(&self, index: &Index) -> Result<Option<IndexKind>, Error> {
+        let prefix = index.prefix();
+        btree::get(&self.txn, &self.indexes, prefix.as_slice(), None)?
+            .filter(|(key, _)| *key == prefix.as_slice())
+            .map(|(_, &kind)| IndexKind::from_u64(kind))
+            .transpose()
+    }
+
+    pub fn has_index(&self, index: &Index) -> Result<bool, Error> {
+        Ok(self.index_kind(index)?.is_some())
+    }
+
+    /// All indexes over nodes with `label`.
+    fn indexes_of(&self, label: &str) -> Result<Vec<(Index, IndexKind)>, Error> {
+        let prefix = Index::label_prefix(label);
+        let mut indexes = Vec::new();
+        for entry in btree::iter(&self.txn, &self.indexes, Some((prefix.as_slice(), None)))? {
+            let (index, &kind) = entry?;
+            if !index.starts_with(&prefix) {
+                break;
+            }
+            indexes.push((Index::decode(index)?, IndexKind::from_u64(kind)?));
+        }
+        Ok(indexes)
+    }
+
+    /// The index entries of `node`, optionally only
+    /// for the indexes which include property `key`.
+    fn node_entries(&self, node: &Node, key: Option<&str>) -> Result<Vec<Vec<u8>>, Error> {
+        let mut entries = Vec::new();
+        for (index, _) in self.indexes_of(&node.label)? {
+            if key.map(|key| index.keys.iter().any(|k| k == key)).unwrap_or(true) {
+                entries.extend(index.entry(&index.values(node))?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Ids of all nodes with `label`.
+    fn labeled_nodes(&self, label: &str) -> Result<Vec<u64>, Error> {
+        let mut ids = Vec::new();
+        for entry in btree::iter(&self.txn, &self.labels, Some((label.as_bytes(), None)))? {
+            let (key, &id) = entry?;
+            if key != label.as_bytes() {
+                break;
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// Create an index and add entries for all existing nodes. If
+    /// the index already exists, it keeps the stricter kind. Nodes
+    /// are checked against new constraints on the next flush.
+    pub fn create_index(&mut self, index: Index, kind: IndexKind) -> Result<(), Error> {
+        let existing = self.index_kind(&index)?;
+        if existing.map(|existing| existing >= kind).unwrap_or(false) {
+            return Ok(());
+        }
+        let ids = self.labeled_nodes(&index.label)?;
+        let prefix = index.prefix();
+        btree::del(&mut self.txn, &mut self.indexes, prefix.as_slice(), None)?;
+        btree::put(&mut self.txn, &mut self.indexes, prefix.as_slice(), &kind.to_u64())?;
+        if kind != IndexKind::Plain {
+            self.unchecked_nodes.extend(ids.iter().copied());
+        }
+        if existing.is_some() {
+            return Ok(());
+        }
+        for id in ids {
+            let node = self.load_node(id)?.ok_or(Error::MissingNode)?;
+            if let Some(entry) = index.entry(&index.values(&node))? {
+                btree::put(&mut self.txn, &mut self.index_entries, entry.as_slice(), &id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove an index of the given kind and all of its entries. Does
+    /// nothing if the index does not exist or is of a different kind,
+    /// such that dropping an index keeps constraints in place.
+    pub fn drop_index(&mut self, index: &Index, kind: IndexKind) -> Result<(), Error> {
+        if self.index_kind(index)? != Some(kind) {
+            return Ok(());
+        }
+        let prefix = index.prefix();
+        btree::del(&mut self.txn, &mut self.indexes, prefix.as_slice(), None)?;
+        let mut entries = Vec::new();
+        let origin = Some((prefix.as_slice(), None));
+        for entry in btree::iter(&self.txn, &self.index_entries, origin)? {
+            let (entry, &id) = entry?;
+            if !entry.starts_with(&prefix) {
+                break;
+            }
+            entries.push((entry.to_vec(), id));
+        }
+        for (entry, id) in entries {
+            btree::del(&mut self.txn, &mut self.index_entries, entry.as_slice(), Some(&id))?;
+        }
+        Ok(())
+    }
+
+    /// Check `node` against the unique and node key constraints
+    /// and the property rules on its label.
+    fn check_node(&self, node: &Node, rules: &[Rule]) -> Result<(), Error> {
+        for rule in rules.iter().filter(|rule| rule.label() == node.label) {
+            rule.check_properties(false, &node.label, &node.properties)?;
+        }
+        for (index, kind) in self.indexes_of(&node.label)? {
+            if kind == IndexKind::Plain {
+                continue;
+            }
+            let values = index.values(node);
+            let entry = match index.entry(&values)? {
+                Some(entry) => entry,
+                None if kind == IndexKind::NodeKey => return Err(index.violation(&values)),
+                None => continue,
+            };
+            let origin = Some((entry.as_slice(), None));
+            for other in btree::iter(&self.txn, &self.index_entries, origin)? {
+                let (other, &other_id) = other?;
+                if !other.starts_with(&entry) {
+                    break;
+                }
+                if other_id == node.id {
+                    continue;
+                }
+                let other = self.load_node(other_id)?.ok_or(Error::MissingNode)?;
+                let equal = values
+                    .iter()
+                    .zip(index.values(&other))
+                    .all(|(value, other)| value.loosely_equals(&other));
+                if equal {
+                    return Err(index.violation(&values));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `edge` against the property and endpoint rules on its label.
+    fn check_edge(&self, edge: &Edge, rules: &[Rule]) -> Result<(), Error> {
+        let rules: Vec<_> = rules
+            .iter()
+            .filter(|rule| rule.label() == edge.label)
+            .collect();
+        for rule in &rules {
+            rule.check_properties(true, &edge.label, &edge.properties)?;
+        }
+        if rules.iter().any(|rule| matches!(rule, Rule::Endpoints { .. })) {
+            let origin = self.load_node(edge.origin)?.ok_or(Error::MissingNode)?;
+            let target = self.load_node(edge.target)?.ok_or(Error::MissingNode)?;
+            if !rules
+                .iter()
+                .any(|rule| rule.allows_endpoints(edge, &origin.label, &target.label))
+            {
+                return Err(Error::ConstraintViolation {
+                    label: edge.label.clone(),
+                    key: "endpoints".to_string(),
+                    value: format!("(:{}) -[:{}]-> (:{})", origin.label, edge.label, target.label),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the nodes and edges changed since the last successful check
+    /// against the constraints on their label. They remain unchecked if a
+    /// constraint is violated, such that the transaction can not be
+    /// committed until the violation is resolved.
+    fn check_constraints(&mut self) -> Result<(), Error> {
+        if self.unchecked_nodes.is_empty() && self.unchecked_edges.is_empty() {
+            return Ok(());
+        }
+        let rules = self.load_rules()?;
+        for &id in &self.unchecked_nodes {
+            if let Some(node) = self.load_node(id)? {
+                self.check_node(&node, &rules)?;
+            }
+        }
+        for &id in &self.unchecked_edges {
+            if let Some(edge) = self.load_edge(id)? {
+                self.check_edge(&edge, &rules)?;
+            }
+        }
+        self.unchecked_nodes.clear();
+        self.unchecked_edges.clear();
+        Ok(())
+    }
+
+    /// Check all nodes and edges against the constraints
+    /// on their label, including rules which were added
+    /// after the data was written.
+    pub fn validate(&self) -> Result<(), Error> {
+        let rules = self.load_rules()?;
+        for node in NodeIter::all(self)? {
+            self.check_node(&node?, &rules)?;
+        }
+        for id in self.edge_ids()? {
+            let edge = self.load_edge(id)?.ok_or(Error::MissingEdge)?;
+            self.check_edge(&edge, &rules)?;
+        }
+        Ok(())
+    }
 
+    pub fn unchecked_create_node(&mut self, node: Node) -> Result<u64, Error> {
+        self.count(Counter::Nodes(node.label.clone()), 1);
 
         btree::put(
             &mut self.txn,
@@ -206,6 +650,10 @@ impl<'e> StoreTxn<'e> {
             &node.id,
         )?;
 
+        for entry in self.node_entries(&node, None)? {
+            btree::put(&mut self.txn, &mut self.index_entries, entry.as_slice(), &node.id)?;
+        }
+
         let node_id = node.id;
 
         let bytes = if let Some(vault) = &self.vault {
@@ -222,11 +670,17 @@ impl<'e> StoreTxn<'e> {
 
     pub fn update_node(&mut self, node: u64, key: &str, value: PropOwned) -> Result<(), Error> {
         let mut node = self.load_node(node)?.ok_or(Error::MissingNode)?;
+        for entry in self.node_entries(&node, Some(key))? {
+            btree::del(&mut self.txn, &mut self.index_entries, entry.as_slice(), Some(&node.id))?;
+        }
         if value == PropOwned::Null {
             node.properties.remove(key);
         } else {
             node.properties.insert(key.to_string(), value);
         }
+        for entry in self.node_entries(&node, Some(key))? {
+            btree::put(&mut self.txn, &mut self.index_entries, entry.as_slice(), &node.id)?;
+        }
 
         let node_id = node.id;
 
@@ -242,23 +696,26 @@ impl<'e> StoreTxn<'e> {
     }
 
     pub fn delete_node(&mut self, node: u64) -> Result<(), Error> {
-        let has_origin = btree::get(&self.txn, &self.origins, &node, None)?
-            .map(|(k, _)| *k == node)
+        let prefix = Adjacency::prefix(node, None, None);
+        let is_connected = btree::get(&self.txn, &self.adjacency, prefix.as_slice(), None)?
+            .map(|(key, _)| key.starts_with(&prefix))
             .unwrap_or(false);
-        let has_target = btree::get(&self.txn, &self.targets, &node, None)?
-            .map(|(k, _)| *k == node)
-            .unwrap_or(false);
-        if has_origin || has_target {
+        if is_connected {
             Err(Error::DeleteConnected)
         } else {
             self.load_node(node)?
                 .map(|node| {
+                    self.count(Counter::Nodes(node.label.clone()), -1);
                     btree::del(
                         &mut self.txn,
                         &mut self.labels,
                         node.label.as_bytes(),
                         Some(&node.id),
                     )?;
+                    for entry in self.node_entries(&node, None)? {
+                        let entries = &mut self.index_entries;
+                        btree::del(&mut self.txn, entries, entry.as_slice(), Some(&node.id))?;
+                    }
                     btree::del(&mut self.txn, &mut self.nodes, &node.id, None)
                 })
                 .transpose()?;
@@ -267,10 +724,17 @@ impl<'e> StoreTxn<'e> {
     }
 
     pub fn unchecked_create_edge(&mut self, edge: Edge) -> Result<u64, Error> {
+        self.count_edge(&edge, 1)?;
 
-        
-        btree::put(&mut self.txn, &mut self.origins, &edge.origin, &edge.id)?;
-        btree::put(&mut self.txn, &mut self.targets, &edge.target, &edge.id)?;
+        for (entry, other) in Adjacency::of_edge(&edge).iter() {
+            btree::put(&mut self.txn, &mut self.adjacency, &entry.encode(), other)?;
+        }
+        btree::put(
+            &mut self.txn,
+            &mut self.edge_labels,
+            edge.label.as_bytes(),
+            &edge.id,
+        )?;
 
         let edge_id = edge.id;
 
@@ -308,16 +772,14 @@ impl<'e> StoreTxn<'e> {
 
     pub fn delete_edge(&mut self, edge: u64) -> Result<(), Error> {
         if let Some(edge) = self.load_edge(edge)? {
+            self.count_edge(&edge, -1)?;
+            for (entry, other) in Adjacency::of_edge(&edge).iter() {
+                btree::del(&mut self.txn, &mut self.adjacency, &entry.encode(), Some(other))?;
+            }
             btree::del(
                 &mut self.txn,
-                &mut self.origins,
-                &edge.origin,
-                Some(&edge.id),
-            )?;
-            btree::del(
-                &mut self.txn,
-                &mut self.targets,
-                &edge.target,
+                &mut self.edge_labels,
+                edge.label.as_bytes(),
                 Some(&edge.id),
             )?;
             btree::del(&mut self.txn, &mut self.edges, &edge.id, None)?;
@@ -375,32 +837,95 @@ impl<'e> StoreTxn<'e> {
                 }
                 Update::DeleteNode(_) => None,
                 Update::DeleteEdge(_) => None,
+                Update::CreateIndex(..) | Update::DropIndex(..) => None,
+                Update::CreateRule(_) | Update::DropRule(_) => None,
             }))
     }
 
+    /// Returns a copy of `node` with all queued
+    /// property updates applied.
+    pub fn get_updated_node(&self, node: &Node) -> Result<Node, Error> {
+        let mut node = node.clone();
+        for update in self.updates.try_read()?.iter() {
+            match update {
+                Update::CreateNode(created) if created.id == node.id => node = created.clone(),
+                Update::SetNodeProperty(id, key, value) if *id == node.id => {
+                    if *value == PropOwned::Null {
+                        node.properties.remove(key);
+                    } else {
+                        node.properties.insert(key.clone(), value.clone());
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(node)
+    }
+
+    /// Returns a copy of `edge` with all queued
+    /// property updates applied.
+    pub fn get_updated_edge(&self, edge: &Edge) -> Result<Edge, Error> {
+        let mut edge = edge.clone();
+        for update in self.updates.try_read()?.iter() {
+            match update {
+                Update::CreateEdge(created) if created.id == edge.id => edge = created.clone(),
+                Update::SetEdgeProperty(id, key, value) if *id == edge.id => {
+                    if *value == PropOwned::Null {
+                        edge.properties.remove(key);
+                    } else {
+                        edge.properties.insert(key.clone(), value.clone());
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(edge)
+    }
+
     pub fn flush(&mut self) -> Result<(), Error> {
         let updates = std::mem::take(&mut *self.updates.try_write()?);
         for update in updates {
             match update {
-                Update::CreateNode(node) => self.unchecked_create_node(node).map(|_| ())?,
-                Update::CreateEdge(edge) => self.unchecked_create_edge(edge).map(|_| ())?,
-                Update::SetNodeProperty(node, key, value) => self.update_node(node, &key, value)?,
-                Update::SetEdgeProperty(edge, key, value) => self.update_edge(edge, &key, value)?,
+                Update::CreateNode(node) => {
+                    self.unchecked_nodes.insert(node.id);
+                    self.unchecked_create_node(node).map(|_| ())?
+                }
+                Update::CreateEdge(edge) => {
+                    self.unchecked_edges.insert(edge.id);
+                    self.unchecked_create_edge(edge).map(|_| ())?
+                }
+                Update::SetNodeProperty(node, key, value) => {
+                    self.unchecked_nodes.insert(node);
+                    self.update_node(node, &key, value)?
+                }
+                Update::SetEdgeProperty(edge, key, value) => {
+                    self.unchecked_edges.insert(edge);
+                    self.update_edge(edge, &key, value)?
+                }
                 Update::DeleteNode(node) => self.delete_node(node)?,
                 Update::DeleteEdge(edge) => self.delete_edge(edge)?,
+                Update::CreateIndex(index, kind) => self.create_index(index, kind)?,
+                Update::DropIndex(index, kind) => self.drop_index(&index, kind)?,
+                Update::CreateRule(rule) => self.create_rule(&rule)?,
+                Update::DropRule(rule) => self.drop_rule(&rule)?,
             }
         }
-        Ok(())
+        self.check_constraints()
     }
 
     pub fn commit(mut self) -> Result<(), Error> {
         self.flush()?;
+        self.commit_counters()?;
         self.txn.set_root(ID_SQUENCE, self.id_seq.into_inner())?;
         self.txn.set_root(DB_NODES, self.nodes.db)?;
         self.txn.set_root(DB_EDGES, self.edges.db)?;
-        self.txn.set_root(DB_ORIGINS, self.origins.db)?;
-        self.txn.set_root(DB_TARGETS, self.targets.db)?;
         self.txn.set_root(DB_LABELS, self.labels.db)?;
+        self.txn.set_root(DB_INDEXES, self.indexes.db)?;
+        self.txn.set_root(DB_INDEX_ENTRIES, self.index_entries.db)?;
+        self.txn.set_root(DB_RULES, self.rules.db)?;
+        self.txn.set_root(DB_STATISTICS, self.statistics.db)?;
+        self.txn.set_root(DB_EDGE_LABELS, self.edge_labels.db)?;
+        self.txn.set_root(DB_ADJACENCY, self.adjacency.db)?;
         self.txn.commit()
     }
 }
@@ -0,0 +1,425 @@
+use super::spatial::{self, Crs, Point};
+use super::{Edge, Node, PropOwned, PropRef, Statistics};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+
+/// Index entries keep at most this many bytes of the encoded
+/// values, such that keys stay small enough for the B-tree. Nodes
+/// loaded through an index are always checked against the looked
+/// up value, which tells apart values with the same prefix.
+const MAX_ENTRY_VALUE: usize = 256;
+
+// Encoded values start with a tag, such that values which can be
+// compared with each other are kept together in the index.
+const TAG_NUMBER: u8 = 1;
+const TAG_TEXT: u8 = 2;
+const TAG_DATE: u8 = 3;
+const TAG_LOCAL_DATE_TIME: u8 = 4;
+const TAG_DATE_TIME: u8 = 5;
+const TAG_POINT: u8 = 6;
+const TAG_OTHER: u8 = 0xff;
+
+/// A property index over the nodes with `label`, keyed
+/// by the values of `keys`. Indexes over more than one
+/// key back composite constraints.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) struct Index {
+    pub label: String,
+    pub keys: Vec<String>,
+}
+
+/// The constraint enforced by an index, which is
+/// stored as the value of the index definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) enum IndexKind {
+    /// Only used to speed up queries.
+    Plain,
+    /// No two nodes have the same values for all keys,
+    /// ignoring nodes where any of the keys is missing.
+    Unique,
+    /// Every node has all keys and no two nodes have
+    /// the same values for them.
+    NodeKey,
+}
+
+/// The type of a property, as named in `IS :: <type>` constraints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PropertyType {
+    Boolean,
+    String,
+    Integer,
+    Float,
+    Date,
+    LocalDateTime,
+    DateTime,
+    Duration,
+    Point,
+    Blob,
+    List,
+}
+
+/// A rule which all nodes or edges with `label` follow. Rules are
+/// checked when nodes or edges change, existing data is only checked
+/// when validating the whole graph.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) enum Rule {
+    /// Property `key` is set.
+    Exists { edge: bool, label: String, key: String },
+    /// Property `key` is missing or of the given type.
+    Type { edge: bool, label: String, key: String, kind: PropertyType },
+    /// No properties other than `keys` are set.
+    Keys { edge: bool, label: String, keys: BTreeSet<String> },
+    /// Edges go from a node with label `origin` to a node with
+    /// label `target`. Edges with rules for more than one pair
+    /// of labels may connect any of the pairs.
+    Endpoints { label: String, origin: String, target: String },
+}
+
+/// Definitions and statistics stored alongside the graph,
+/// which the planner uses to decide how to load nodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Schema {
+    pub indexes: BTreeMap<Index, IndexKind>,
+    pub rules: BTreeSet<Rule>,
+    pub statistics: Statistics,
+}
+
+impl Index {
+    pub fn new(label: &str, key: &str) -> Self {
+        Self::composite(label, &[key])
+    }
+
+    pub fn composite(label: &str, keys: &[&str]) -> Self {
+        Self {
+            label: label.to_string(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        }
+    }
+
+    /// The prefix shared by all indexes over
+    /// nodes with `label` and their entries.
+    pub fn label_prefix(label: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        push_text(&mut bytes, label);
+        bytes
+    }
+
+    /// The encoded definition, which is also the
+    /// prefix of all entries of the index.
+    pub fn prefix(&self) -> Vec<u8> {
+        let mut bytes = Self::label_prefix(&self.label);
+        bytes.extend_from_slice(&(self.keys.len() as u32).to_be_bytes());
+        for key in &self.keys {
+            push_text(&mut bytes, key);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (label, rest) = read_text(bytes)?;
+        let (count, mut rest) = read_len(rest)?;
+        let mut keys = Vec::with_capacity(count.min(rest.len()));
+        for _ in 0..count {
+            let (key, tail) = read_text(rest)?;
+            keys.push(key.to_string());
+            rest = tail;
+        }
+        Ok(Self {
+            label: label.to_string(),
+            keys,
+        })
+    }
+
+    /// The entry for nodes with the keys set to `values`, or
+    /// `None` if the values are not indexed because one of
+    /// them is missing.
+    pub fn entry(&self, values: &[PropRef]) -> Result<Option<Vec<u8>>, Error> {
+        let mut encoded = Vec::new();
+        for value in values {
+            if !push_value(&mut encoded, value)? {
+                return Ok(None);
+            }
+        }
+        let mut bytes = self.prefix();
+        bytes.extend_from_slice(&encoded[..encoded.len().min(MAX_ENTRY_VALUE)]);
+        Ok(Some(bytes))
+    }
+
+    /// The prefix shared by the entries of this single key index for
+    /// all values which are comparable with `value`, or `None` if
+    /// `value` is missing.
+    pub fn comparable_prefix(&self, value: &PropRef) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.entry(std::slice::from_ref(value))?.map(|mut entry| {
+            entry.truncate(self.prefix().len() + 1);
+            entry
+        }))
+    }
+
+    /// The prefix shared by the entries of this single key index for
+    /// all points in the system of the box spanned by `lower_left` and
+    /// `upper_right`, and the positions of the cells of both corners
+    /// on the Z-order curve.
+    pub fn point_range(&self, lower_left: &Point, upper_right: &Point) -> (Vec<u8>, u64, u64) {
+        let mut prefix = self.prefix();
+        push_crs(&mut prefix, lower_left.crs());
+        (prefix, z_order(lower_left), z_order(upper_right))
+    }
+
+    /// The values of the keys of `node`.
+    pub fn values<'a>(&self, node: &'a Node) -> Vec<PropRef<'a>> {
+        self.keys
+            .iter()
+            .map(|key| node.property(key).to_ref())
+            .collect()
+    }
+
+    /// The error reported if `values` violate the
+    /// constraint enforced by this index.
+    pub fn violation(&self, values: &[PropRef]) -> Error {
+        let value = match values {
+            [value] => describe(value),
+            values => {
+                let values: Vec<_> = values.iter().map(describe).collect();
+                format!("({})", values.join(", "))
+            }
+        };
+        Error::ConstraintViolation {
+            label: self.label.clone(),
+            key: self.keys.join(", "),
+            value,
+        }
+    }
+}
+
+impl IndexKind {
+    pub fn to_u64(self) -> u64 {
+        match self {
+            Self::Plain => 0,
+            Self::Unique => 1,
+            Self::NodeKey => 2,
+        }
+    }
+
+    pub fn from_u64(kind: u64) -> Result<Self, Error> {
+        match kind {
+            0 => Ok(Self::Plain),
+            1 => Ok(Self::Unique),
+            2 => Ok(Self::NodeKey),
+            _ => Err(Error::Corruption),
+        }
+    }
+}
+
+impl PropertyType {
+    pub fn matches(&self, value: &PropRef) -> bool {
+        matches!(
+            (self, value),
+            (Self::Boolean, PropRef::Boolean(_))
+                | (Self::String, PropRef::Text(_))
+                | (Self::Integer, PropRef::Integer(_))
+                | (Self::Float, PropRef::Real(_))
+                | (Self::Date, PropRef::Date(_))
+                | (Self::LocalDateTime, PropRef::LocalDateTime(_))
+                | (Self::DateTime, PropRef::DateTime(_))
+                | (Self::Duration, PropRef::Duration(_))
+                | (Self::Point, PropRef::Point(_))
+                | (Self::Blob, PropRef::Blob(_))
+                | (Self::List, PropRef::List(_))
+        )
+    }
+}
+
+impl Rule {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Exists { label, .. }
+            | Self::Type { label, .. }
+            | Self::Keys { label, .. }
+            | Self::Endpoints { label, .. } => label,
+        }
+    }
+
+    /// Check the properties of a node, or of an edge if `edge`
+    /// is set. Endpoint rules are checked separately, since an
+    /// edge only needs to match one of them.
+    pub fn check_properties(
+        &self,
+        edge: bool,
+        label: &str,
+        properties: &BTreeMap<String, PropOwned>,
+    ) -> Result<(), Error> {
+        let violation = |key: &str, value: &PropRef| Error::ConstraintViolation {
+            label: label.to_string(),
+            key: key.to_string(),
+            value: describe(value),
+        };
+        match self {
+            Self::Exists { edge: e, key, .. } if *e == edge => match properties.get(key) {
+                Some(_) => Ok(()),
+                None => Err(violation(key, &PropRef::Null)),
+            },
+            Self::Type { edge: e, key, kind, .. } if *e == edge => match properties.get(key) {
+                Some(value) if !kind.matches(&value.to_ref()) => {
+                    Err(violation(key, &value.to_ref()))
+                }
+                _ => Ok(()),
+            },
+            Self::Keys { edge: e, keys, .. } if *e == edge => {
+                match properties.iter().find(|(key, _)| !keys.contains(*key)) {
+                    Some((key, value)) => Err(violation(key, &value.to_ref())),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check if `edge`, which goes from a node with label `origin`
+    /// to a node with label `target`, matches this endpoint rule.
+    pub fn allows_endpoints(&self, edge: &Edge, origin: &str, target: &str) -> bool {
+        match self {
+            Self::Endpoints {
+                label,
+                origin: o,
+                target: t,
+            } => label == &edge.label && o == origin && t == target,
+            _ => false,
+        }
+    }
+}
+
+/// Format a value the way it would be written in a query.
+fn describe(value: &PropRef) -> String {
+    match value {
+        PropRef::Null => "null".to_string(),
+        PropRef::Integer(num) => num.to_string(),
+        PropRef::Real(num) => format!("{:?}", num),
+        PropRef::Boolean(val) => val.to_string(),
+        PropRef::Text(text) => format!("'{}'", text),
+        PropRef::Date(date) => date.to_string(),
+        PropRef::LocalDateTime(date_time) => date_time.to_string(),
+        PropRef::DateTime(date_time) => date_time.to_string(),
+        PropRef::Duration(duration) => duration.to_string(),
+        PropRef::Point(point) => point.to_string(),
+        value => format!("{:?}", value),
+    }
+}
+
+/// Append the encoding of a `value` which is part of an index entry,
+/// or return `false` if the value is missing. Encoded values compare
+/// like the values: numbers by their value regardless of whether they
+/// are integers or reals, text by its bytes and temporal values by
+/// time. Values of other types are only kept apart by equality.
+fn push_value(bytes: &mut Vec<u8>, value: &PropRef) -> Result<bool, Error> {
+    match value {
+        PropRef::Null => return Ok(false),
+        PropRef::Integer(num) => push_number(bytes, *num as f64),
+        PropRef::Real(num) => push_number(bytes, *num),
+        PropRef::Text(text) => {
+            bytes.push(TAG_TEXT);
+            // escaped and terminated, such that shorter text sorts
+            // first and entries of composite indexes stay ordered
+            for &byte in text.as_bytes() {
+                bytes.push(byte);
+                if byte == 0 {
+                    bytes.push(0xff);
+                }
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        PropRef::Date(date) => {
+            bytes.push(TAG_DATE);
+            bytes.extend_from_slice(&date.to_bytes());
+        }
+        PropRef::LocalDateTime(local) => {
+            bytes.push(TAG_LOCAL_DATE_TIME);
+            bytes.extend_from_slice(&local.to_bytes());
+        }
+        PropRef::DateTime(time) => {
+            bytes.push(TAG_DATE_TIME);
+            bytes.extend_from_slice(&time.to_bytes());
+        }
+        PropRef::Point(point) => {
+            // points are grouped by system and ordered along the
+            // Z-order curve, such that a spatial scan can skip
+            // the cells which are outside of a box
+            push_crs(bytes, point.crs());
+            bytes.extend_from_slice(&z_order(point).to_be_bytes());
+            for coordinate in [point.x(), point.y(), point.z().unwrap_or(0.0)] {
+                bytes.extend_from_slice(&sortable(coordinate).to_be_bytes());
+            }
+        }
+        value => {
+            bytes.push(TAG_OTHER);
+            bytes.extend(bincode::serialize(&value.to_owned())?);
+        }
+    }
+    Ok(true)
+}
+
+/// Integers are encoded as reals, since they are loosely equal to
+/// reals of the same value. Integers which can not be represented
+/// exactly share their entry with the nearest real.
+fn push_number(bytes: &mut Vec<u8>, num: f64) {
+    bytes.push(TAG_NUMBER);
+    bytes.extend_from_slice(&sortable(num).to_be_bytes());
+}
+
+/// The bits of `num`, such that they sort by its value.
+fn sortable(num: f64) -> u64 {
+    // negative zero is equal to zero
+    let bits = (num + 0.0).to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    }
+}
+
+fn push_crs(bytes: &mut Vec<u8>, crs: Crs) {
+    bytes.push(TAG_POINT);
+    bytes.extend_from_slice(&(crs.srid() as u16).to_be_bytes());
+}
+
+/// The position of `point` on the Z-order curve through a grid over
+/// the `x` and `y` coordinates. The cell of a coordinate is given by
+/// the leading bits of its sortable encoding, such that cells grow
+/// with the distance from the origin and points within a box are in
+/// the cells between those of its corners.
+fn z_order(point: &Point) -> u64 {
+    let cell = |coordinate: f64| (sortable(coordinate) >> 32) as u32;
+    spatial::z_order(cell(point.x()), cell(point.y()))
+}
+
+pub(super) fn push_text(bytes: &mut Vec<u8>, text: &str) {
+    bytes.extend_from_slice(&(text.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+pub(super) fn read_len(bytes: &[u8]) -> Result<(usize, &[u8]), Error> {
+    let len = bytes
+        .get(..4)
+        .and_then(|len| len.try_into().ok())
+        .map(u32::from_be_bytes)
+        .ok_or(Error::Corruption)?;
+    Ok((len as usize, &bytes[4..]))
+}
+
+/// Read the position on the Z-order curve
+/// at the start of an encoded point.
+pub(super) fn read_z_order(bytes: &[u8]) -> Result<u64, Error> {
+    bytes
+        .get(..8)
+        .and_then(|z| z.try_into().ok())
+        .map(u64::from_be_bytes)
+        .ok_or(Error::Corruption)
+}
+
+pub(super) fn read_text(bytes: &[u8]) -> Result<(&str, &[u8]), Error> {
+    let (len, bytes) = read_len(bytes)?;
+    let text = bytes.get(..len).ok_or(Error::Corruption)?;
+    let text = std::str::from_utf8(text).map_err(|_| Error::Corruption)?;
+    Ok((text, &bytes[len..]))
+}
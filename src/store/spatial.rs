@@ -0,0 +1,539 @@
+use super::PropRef;
+use minicbor::{Decode, Encode};
+use nanoserde::ToJSON;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Mean radius of the earth in meters, which is used
+/// to compute distances between WGS-84 points.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The bits of a position on the Z-order curve which
+/// come from the `x` coordinate.
+const Z_ORDER_X: u64 = 0x5555_5555_5555_5555;
+
+/// The coordinate reference system of a point.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToJSON, Encode, Decode,
+)]
+pub enum Crs {
+    #[n(0)]
+    Cartesian,
+    #[n(1)]
+    Cartesian3D,
+    /// Longitude and latitude in degrees.
+    #[n(2)]
+    Wgs84,
+    /// Longitude and latitude in degrees and
+    /// the height in meters.
+    #[n(3)]
+    Wgs843D,
+}
+
+/// A point in 2D or 3D space, either using Cartesian
+/// coordinates or WGS-84 geographic coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToJSON, Encode, Decode)]
+pub struct Point {
+    #[n(0)]
+    crs: Crs,
+    #[n(1)]
+    x: f64,
+    #[n(2)]
+    y: f64,
+    /// Zero for 2D points.
+    #[n(3)]
+    z: f64,
+}
+
+/// A region of space which is searched for points
+/// through a spatial index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Region<T> {
+    /// The points at most `radius` away from `center`.
+    Circle { center: T, radius: T },
+    /// The points within the box spanned by the two corners.
+    Box { lower_left: T, upper_right: T },
+}
+
+impl Crs {
+    /// The name used in queries, e.g. `'wgs-84'`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cartesian => "cartesian",
+            Self::Cartesian3D => "cartesian-3d",
+            Self::Wgs84 => "wgs-84",
+            Self::Wgs843D => "wgs-84-3d",
+        }
+    }
+
+    /// The spatial reference identifier of the system.
+    pub fn srid(&self) -> i64 {
+        match self {
+            Self::Cartesian => 7203,
+            Self::Cartesian3D => 9157,
+            Self::Wgs84 => 4326,
+            Self::Wgs843D => 4979,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Cartesian,
+            Self::Cartesian3D,
+            Self::Wgs84,
+            Self::Wgs843D,
+        ]
+        .iter()
+        .find(|crs| crs.name().eq_ignore_ascii_case(name))
+        .copied()
+    }
+
+    pub fn from_srid(srid: i64) -> Option<Self> {
+        [
+            Self::Cartesian,
+            Self::Cartesian3D,
+            Self::Wgs84,
+            Self::Wgs843D,
+        ]
+        .iter()
+        .find(|crs| crs.srid() == srid)
+        .copied()
+    }
+
+    pub fn is_3d(&self) -> bool {
+        matches!(self, Self::Cartesian3D | Self::Wgs843D)
+    }
+
+    pub fn is_geographic(&self) -> bool {
+        matches!(self, Self::Wgs84 | Self::Wgs843D)
+    }
+}
+
+impl Point {
+    /// A point in the given system, if the coordinates are valid. The
+    /// `z` coordinate is required for 3D systems and ignored otherwise.
+    /// Geographic points take the longitude as `x`, which must be
+    /// within `-180` and `180`, and the latitude as `y`, which must
+    /// be within `-90` and `90`.
+    pub fn new(crs: Crs, x: f64, y: f64, z: Option<f64>) -> Option<Self> {
+        let z = match (crs.is_3d(), z) {
+            (true, Some(z)) => z,
+            (false, None) => 0.0,
+            _ => return None,
+        };
+        if !x.is_finite() || !y.is_finite() || !z.is_finite() {
+            return None;
+        }
+        if crs.is_geographic() && (!(-180.0..=180.0).contains(&x) || !(-90.0..=90.0).contains(&y)) {
+            return None;
+        }
+        Some(Self { crs, x, y, z })
+    }
+
+    pub fn crs(&self) -> Crs {
+        self.crs
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    /// The third coordinate of 3D points.
+    pub fn z(&self) -> Option<f64> {
+        if self.crs.is_3d() {
+            Some(self.z)
+        } else {
+            None
+        }
+    }
+
+    /// The distance to `other`, if both points use the same system. Distances
+    /// between geographic points are in meters, along the surface of the earth
+    /// and include the difference in height for 3D points.
+    pub fn distance(&self, other: &Self) -> Option<f64> {
+        if self.crs != other.crs {
+            return None;
+        }
+        let (dx, dy, dz) = (other.x - self.x, other.y - self.y, other.z - self.z);
+        if self.crs.is_geographic() {
+            // haversine formula
+            let (lat1, lat2) = (self.y.to_radians(), other.y.to_radians());
+            let a = (dy.to_radians() / 2.0).sin().powi(2)
+                + lat1.cos() * lat2.cos() * (dx.to_radians() / 2.0).sin().powi(2);
+            let surface = 2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin();
+            Some((surface * surface + dz * dz).sqrt())
+        } else {
+            Some((dx * dx + dy * dy + dz * dz).sqrt())
+        }
+    }
+
+    /// Check if the point lies within the box spanned by `lower_left`
+    /// and `upper_right`, if all points use the same system. For
+    /// geographic points, boxes where the left side is east of the
+    /// right side cross the 180th meridian. The height of 3D points
+    /// must also be within the box.
+    pub fn within_bbox(&self, lower_left: &Self, upper_right: &Self) -> Option<bool> {
+        if self.crs != lower_left.crs || self.crs != upper_right.crs {
+            return None;
+        }
+        let x = if self.crs.is_geographic() && lower_left.x > upper_right.x {
+            self.x >= lower_left.x || self.x <= upper_right.x
+        } else {
+            (lower_left.x..=upper_right.x).contains(&self.x)
+        };
+        let y = (lower_left.y..=upper_right.y).contains(&self.y);
+        let z = (lower_left.z..=upper_right.z).contains(&self.z);
+        Some(x && y && z)
+    }
+}
+
+impl<T> Region<T> {
+    /// The values which define the region.
+    pub fn values(&self) -> [&T; 2] {
+        match self {
+            Self::Circle { center, radius } => [center, radius],
+            Self::Box {
+                lower_left,
+                upper_right,
+            } => [lower_left, upper_right],
+        }
+    }
+
+    pub fn try_map<U, E>(&self, mut f: impl FnMut(&T) -> Result<U, E>) -> Result<Region<U>, E> {
+        Ok(match self {
+            Self::Circle { center, radius } => Region::Circle {
+                center: f(center)?,
+                radius: f(radius)?,
+            },
+            Self::Box {
+                lower_left,
+                upper_right,
+            } => Region::Box {
+                lower_left: f(lower_left)?,
+                upper_right: f(upper_right)?,
+            },
+        })
+    }
+}
+
+impl Region<PropRef<'_>> {
+    /// Boxes which together cover the `x` and `y` coordinates of all
+    /// points within the region, given by their lower left and upper
+    /// right corner. Regions which contain no points, e.g. because the
+    /// corners use different systems, are not covered by any box.
+    pub fn boxes(&self) -> Vec<(Point, Point)> {
+        match self {
+            Self::Circle {
+                center: PropRef::Point(center),
+                radius: PropRef::Integer(radius),
+            } => center.around(*radius as f64),
+            Self::Circle {
+                center: PropRef::Point(center),
+                radius: PropRef::Real(radius),
+            } => center.around(*radius),
+            Self::Box {
+                lower_left: PropRef::Point(lower_left),
+                upper_right: PropRef::Point(upper_right),
+            } if lower_left.crs == upper_right.crs => {
+                let (lower_left, upper_right) = (*lower_left, *upper_right);
+                if lower_left.crs.is_geographic() && lower_left.x > upper_right.x {
+                    // the box crosses the 180th meridian
+                    vec![
+                        (
+                            lower_left,
+                            Point {
+                                x: 180.0,
+                                ..upper_right
+                            },
+                        ),
+                        (
+                            Point {
+                                x: -180.0,
+                                ..lower_left
+                            },
+                            upper_right,
+                        ),
+                    ]
+                } else {
+                    vec![(lower_left, upper_right)]
+                }
+            }
+            _ => Vec::new(),
+        }
+        .into_iter()
+        .filter(|(lower_left, upper_right)| {
+            lower_left.x <= upper_right.x && lower_left.y <= upper_right.y
+        })
+        .collect()
+    }
+}
+
+impl Point {
+    /// Boxes which cover all points at most `radius` away.
+    fn around(&self, radius: f64) -> Vec<(Point, Point)> {
+        let corner = |x: f64, y: f64| Point { x, y, ..*self };
+        if !self.crs.is_geographic() {
+            let lower_left = corner(self.x - radius, self.y - radius);
+            let upper_right = corner(self.x + radius, self.y + radius);
+            return vec![(lower_left, upper_right)];
+        }
+        // the difference in height only adds to the distance,
+        // such that the distance along the surface is bounded
+        let angle = radius / EARTH_RADIUS;
+        let latitudes = (self.y - angle.to_degrees(), self.y + angle.to_degrees());
+        if latitudes.0 <= -90.0 || latitudes.1 >= 90.0 {
+            // the circle contains a pole
+            let lower_left = corner(-180.0, latitudes.0.max(-90.0));
+            let upper_right = corner(180.0, latitudes.1.min(90.0));
+            return vec![(lower_left, upper_right)];
+        }
+        let longitude = (angle.sin() / self.y.to_radians().cos())
+            .min(1.0)
+            .asin()
+            .to_degrees();
+        let (west, east) = (self.x - longitude, self.x + longitude);
+        if east - west >= 360.0 {
+            vec![(corner(-180.0, latitudes.0), corner(180.0, latitudes.1))]
+        } else if west < -180.0 {
+            vec![
+                (
+                    corner(west + 360.0, latitudes.0),
+                    corner(180.0, latitudes.1),
+                ),
+                (corner(-180.0, latitudes.0), corner(east, latitudes.1)),
+            ]
+        } else if east > 180.0 {
+            vec![
+                (corner(west, latitudes.0), corner(180.0, latitudes.1)),
+                (
+                    corner(-180.0, latitudes.0),
+                    corner(east - 360.0, latitudes.1),
+                ),
+            ]
+        } else {
+            vec![(corner(west, latitudes.0), corner(east, latitudes.1))]
+        }
+    }
+}
+
+/// The position of the cell `(x, y)` on the Z-order curve through a
+/// grid, which interleaves the bits of both coordinates. Cells which
+/// are close to each other are mostly close on the curve, such that
+/// the cells within a box are covered by few ranges of the curve.
+pub(crate) fn z_order(x: u32, y: u32) -> u64 {
+    spread(x) | spread(y) << 1
+}
+
+/// Whether position `z` on the Z-order curve lies within
+/// the box between the cells at `min` and `max`.
+pub(crate) fn z_order_contains(z: u64, min: u64, max: u64) -> bool {
+    let cell = |z: u64| (compact(z), compact(z >> 1));
+    let ((x, y), (min_x, min_y), (max_x, max_y)) = (cell(z), cell(min), cell(max));
+    (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+}
+
+/// The first position after `z` on the Z-order curve which lies within
+/// the box between the cells at `min` and `max`, computed as described
+/// by Tropf and Herzog in "Multidimensional Range Search in Dynamically
+/// Balanced Trees". There is none if `z` is beyond the box.
+pub(crate) fn z_order_next(z: u64, mut min: u64, mut max: u64) -> Option<u64> {
+    let mut next = None;
+    for bit in (0..64).rev() {
+        let mask = 1 << bit;
+        // the lower bits which come from the same coordinate
+        let lower = (Z_ORDER_X << (bit & 1)) & (mask - 1);
+        // the lowest position whose bit is set and the highest
+        // position whose bit is cleared, keeping the higher bits
+        let first = |z: u64| (z | mask) & !lower;
+        let last = |z: u64| (z & !mask) | lower;
+        match (z & mask != 0, min & mask != 0, max & mask != 0) {
+            (false, false, false) | (true, true, true) => (),
+            (false, false, true) => {
+                next = Some(first(min));
+                max = last(max);
+            }
+            (false, true, true) => return Some(min),
+            (true, false, false) => return next,
+            (true, false, true) => min = first(min),
+            // the box is empty
+            (_, true, false) => return None,
+        }
+    }
+    next
+}
+
+fn spread(value: u32) -> u64 {
+    let mut value = value as u64;
+    value = (value | value << 16) & 0x0000_ffff_0000_ffff;
+    value = (value | value << 8) & 0x00ff_00ff_00ff_00ff;
+    value = (value | value << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    value = (value | value << 2) & 0x3333_3333_3333_3333;
+    (value | value << 1) & Z_ORDER_X
+}
+
+fn compact(value: u64) -> u32 {
+    let mut value = value & Z_ORDER_X;
+    value = (value | value >> 1) & 0x3333_3333_3333_3333;
+    value = (value | value >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    value = (value | value >> 4) & 0x00ff_00ff_00ff_00ff;
+    value = (value | value >> 8) & 0x0000_ffff_0000_ffff;
+    (value | value >> 16) as u32
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x, y, z) = if self.crs.is_geographic() {
+            ("longitude", "latitude", "height")
+        } else {
+            ("x", "y", "z")
+        };
+        write!(f, "point({{{}: {:?}, {}: {:?}", x, self.x, y, self.y)?;
+        if self.crs.is_3d() {
+            write!(f, ", {}: {:?}", z, self.z)?;
+        }
+        write!(f, ", crs: '{}'}})", self.crs.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cartesian(x: f64, y: f64) -> Point {
+        Point::new(Crs::Cartesian, x, y, None).unwrap()
+    }
+
+    fn wgs84(longitude: f64, latitude: f64) -> Point {
+        Point::new(Crs::Wgs84, longitude, latitude, None).unwrap()
+    }
+
+    #[test]
+    fn distances() {
+        let a = cartesian(0.0, 0.0);
+        let b = cartesian(3.0, 4.0);
+        assert_eq!(a.distance(&b), Some(5.0));
+        assert_eq!(a.distance(&wgs84(0.0, 0.0)), None);
+
+        let a = Point::new(Crs::Cartesian3D, 1.0, 2.0, Some(3.0)).unwrap();
+        let b = Point::new(Crs::Cartesian3D, 3.0, 5.0, Some(9.0)).unwrap();
+        assert_eq!(a.distance(&b), Some(7.0));
+
+        // London to Paris is about 344 km
+        let london = wgs84(-0.1276, 51.5072);
+        let paris = wgs84(2.3522, 48.8566);
+        let distance = london.distance(&paris).unwrap();
+        assert!((distance - 343_900.0).abs() < 1_000.0, "{}", distance);
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let point = cartesian(1.0, 1.0);
+        let lower_left = cartesian(0.0, 0.0);
+        let upper_right = cartesian(2.0, 2.0);
+        assert_eq!(point.within_bbox(&lower_left, &upper_right), Some(true));
+        assert_eq!(point.within_bbox(&upper_right, &lower_left), Some(false));
+
+        // box across the 180th meridian
+        let fiji = wgs84(178.0, -17.7);
+        let lower_left = wgs84(170.0, -20.0);
+        let upper_right = wgs84(-170.0, -10.0);
+        assert_eq!(fiji.within_bbox(&lower_left, &upper_right), Some(true));
+        assert_eq!(
+            wgs84(0.0, -15.0).within_bbox(&lower_left, &upper_right),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn z_order_within_boxes() {
+        let boxes = [
+            ((0, 0), (15, 15)),
+            ((3, 5), (10, 6)),
+            ((7, 2), (7, 13)),
+            ((1, 9), (4, 12)),
+        ];
+        for ((min_x, min_y), (max_x, max_y)) in boxes {
+            let (min, max) = (z_order(min_x, min_y), z_order(max_x, max_y));
+            let within: Vec<u64> = (0..256)
+                .filter(|&z| {
+                    let (x, y) = (compact(z), compact(z >> 1));
+                    (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+                })
+                .collect();
+            for z in 0..256 {
+                assert_eq!(z_order_contains(z, min, max), within.contains(&z));
+                if !within.contains(&z) {
+                    let next = within.iter().copied().find(|&other| other > z);
+                    assert_eq!(z_order_next(z, min, max), next, "{} in {:?}", z, (min, max));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn boxes_around_points() {
+        let boxes = |center: Point, radius: f64| {
+            Region::Circle {
+                center: PropRef::Point(center),
+                radius: PropRef::Real(radius),
+            }
+            .boxes()
+        };
+        let corners = |boxes: Vec<(Point, Point)>| -> Vec<_> {
+            boxes
+                .iter()
+                .map(|(lower_left, upper_right)| (lower_left.x, upper_right.x))
+                .collect()
+        };
+
+        assert_eq!(
+            boxes(cartesian(1.0, 2.0), 1.0),
+            vec![(cartesian(0.0, 1.0), cartesian(2.0, 3.0))]
+        );
+        assert!(boxes(cartesian(1.0, 2.0), -1.0).is_empty());
+
+        // the circle crosses the 180th meridian
+        let suva = wgs84(178.4419, -18.1416);
+        let split = corners(boxes(suva, 500_000.0));
+        assert_eq!(split.len(), 2);
+        assert_eq!((split[0].1, split[1].0), (180.0, -180.0));
+        assert!(split[0].0 < 178.0 && split[1].1 > -180.0);
+        // the circle contains the north pole
+        let alert = wgs84(-62.3481, 82.5018);
+        assert_eq!(corners(boxes(alert, 1_000_000.0)), vec![(-180.0, 180.0)]);
+
+        // the box crosses the 180th meridian
+        let region = Region::Box {
+            lower_left: PropRef::Point(wgs84(170.0, -30.0)),
+            upper_right: PropRef::Point(wgs84(-170.0, 0.0)),
+        };
+        assert_eq!(
+            corners(region.boxes()),
+            vec![(170.0, 180.0), (-180.0, -170.0)]
+        );
+        let region = Region::Box {
+            lower_left: PropRef::Point(wgs84(0.0, 0.0)),
+            upper_right: PropRef::Point(cartesian(1.0, 1.0)),
+        };
+        assert!(region.boxes().is_empty());
+    }
+
+    #[test]
+    fn invalid_points() {
+        assert!(Point::new(Crs::Wgs84, 0.0, 91.0, None).is_none());
+        assert!(Point::new(Crs::Wgs84, 181.0, 0.0, None).is_none());
+        assert!(Point::new(Crs::Cartesian3D, 0.0, 0.0, None).is_none());
+        assert!(Point::new(Crs::Cartesian, 0.0, 0.0, Some(1.0)).is_none());
+        assert!(Point::new(Crs::Cartesian, f64::NAN, 0.0, None).is_none());
+        assert_eq!(
+            Point::new(Crs::Wgs843D, 12.5, 41.9, Some(21.0))
+                .unwrap()
+                .to_string(),
+            "point({longitude: 12.5, latitude: 41.9, height: 21.0, crs: 'wgs-84-3d'})"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use super::schema::{push_text, read_text};
+use crate::Error;
+use std::collections::BTreeMap;
+
+/// A count of nodes or edges, which is kept up to
+/// date when transactions are committed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Counter {
+    /// Nodes with the given label.
+    Nodes(String),
+    /// Edges with the given label.
+    Edges(String),
+    /// Edges with the given label, which start at a
+    /// node with the second label.
+    Origins(String, String),
+    /// Edges with the given label, which end at a
+    /// node with the second label.
+    Targets(String, String),
+}
+
+/// Counts of nodes and edges by label, which the planner
+/// uses to estimate how many matches a step produces.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Statistics {
+    pub nodes: BTreeMap<String, u64>,
+    pub edges: BTreeMap<String, u64>,
+    /// Edges keyed by their label and the label of their origin.
+    pub origins: BTreeMap<(String, String), u64>,
+    /// Edges keyed by their label and the label of their target.
+    pub targets: BTreeMap<(String, String), u64>,
+}
+
+impl Counter {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Nodes(label) => {
+                bytes.push(0);
+                push_text(&mut bytes, label);
+            }
+            Self::Edges(label) => {
+                bytes.push(1);
+                push_text(&mut bytes, label);
+            }
+            Self::Origins(edge, node) => {
+                bytes.push(2);
+                push_text(&mut bytes, edge);
+                push_text(&mut bytes, node);
+            }
+            Self::Targets(edge, node) => {
+                bytes.push(3);
+                push_text(&mut bytes, edge);
+                push_text(&mut bytes, node);
+            }
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (&kind, rest) = bytes.split_first().ok_or(Error::Corruption)?;
+        let (first, rest) = read_text(rest)?;
+        let first = first.to_string();
+        match kind {
+            0 => Ok(Self::Nodes(first)),
+            1 => Ok(Self::Edges(first)),
+            2 | 3 => {
+                let (second, _) = read_text(rest)?;
+                let second = second.to_string();
+                if kind == 2 {
+                    Ok(Self::Origins(first, second))
+                } else {
+                    Ok(Self::Targets(first, second))
+                }
+            }
+            _ => Err(Error::Corruption),
+        }
+    }
+}
+
+impl Statistics {
+    pub fn insert(&mut self, counter: Counter, count: u64) {
+        match counter {
+            Counter::Nodes(label) => self.nodes.insert(label, count),
+            Counter::Edges(label) => self.edges.insert(label, count),
+            Counter::Origins(edge, node) => self.origins.insert((edge, node), count),
+            Counter::Targets(edge, node) => self.targets.insert((edge, node), count),
+        };
+    }
+
+    /// The number of nodes with `label`, or of all nodes.
+    pub fn nodes(&self, label: Option<&str>) -> f64 {
+        count(&self.nodes, |other| {
+            label.map(|l| l == *other).unwrap_or(true)
+        })
+    }
+
+    /// The number of edges with `label`, or of all edges.
+    pub fn edges(&self, label: Option<&str>) -> f64 {
+        count(&self.edges, |other| {
+            label.map(|l| l == *other).unwrap_or(true)
+        })
+    }
+
+    /// The number of edges with label `edge` which start
+    /// at a node with label `node`.
+    pub fn origins(&self, edge: Option<&str>, node: Option<&str>) -> f64 {
+        count(&self.origins, |key| matches_pair(key, edge, node))
+    }
+
+    /// The number of edges with label `edge` which end
+    /// at a node with label `node`.
+    pub fn targets(&self, edge: Option<&str>, node: Option<&str>) -> f64 {
+        count(&self.targets, |key| matches_pair(key, edge, node))
+    }
+
+    /// The average number of edges with label `edge` which
+    /// start at a node with label `node`.
+    pub fn out_degree(&self, edge: Option<&str>, node: Option<&str>) -> f64 {
+        self.origins(edge, node) / self.nodes(node).max(1.0)
+    }
+
+    /// The average number of edges with label `edge` which
+    /// end at a node with label `node`.
+    pub fn in_degree(&self, edge: Option<&str>, node: Option<&str>) -> f64 {
+        self.targets(edge, node) / self.nodes(node).max(1.0)
+    }
+}
+
+fn count<K>(counts: &BTreeMap<K, u64>, filter: impl Fn(&K) -> bool) -> f64 {
+    counts
+        .iter()
+        .filter(|(key, _)| filter(key))
+        .map(|(_, &count)| count as f64)
+        .sum()
+}
+
+fn matches_pair(key: &(String, String), edge: Option<&str>, node: Option<&str>) -> bool {
+    edge.map(|edge| edge == key.0).unwrap_or(true) && node.map(|node| node == key.1).unwrap_or(true)
+}
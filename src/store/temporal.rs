@@ -0,0 +1,868 @@
+use crate::Error;
+use minicbor::{Decode, Encode};
+use nanoserde::ToJSON;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Temporal values are stored as fixed size big endian integers, where
+// the sign bit of signed integers is flipped. This keeps the encoding
+// compact and means that comparing the encoded bytes orders values
+// chronologically.
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+const MIN_YEAR: i64 = -999_999;
+const MAX_YEAR: i64 = 999_999;
+
+/// A calendar date, e.g. `2024-01-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToJSON, Encode, Decode)]
+pub struct Date {
+    /// Days since `1970-01-01`.
+    #[n(0)]
+    days: i64,
+}
+
+/// A date and time without a time zone, e.g. `2024-01-01T12:30:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToJSON, Encode, Decode)]
+pub struct LocalDateTime {
+    /// Seconds since `1970-01-01T00:00:00`.
+    #[n(0)]
+    seconds: i64,
+    #[n(1)]
+    nanos: u32,
+}
+
+/// A date and time with an offset from UTC, e.g. `2024-01-01T12:30:00+02:00`.
+///
+/// Date times are ordered by the instant they refer to, and
+/// by their offset if they refer to the same instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToJSON, Encode, Decode)]
+pub struct DateTime {
+    /// Seconds since `1970-01-01T00:00:00Z`.
+    #[n(0)]
+    seconds: i64,
+    #[n(1)]
+    nanos: u32,
+    /// Offset from UTC in seconds.
+    #[n(2)]
+    offset: i32,
+}
+
+/// An amount of time, e.g. `P1Y2M3DT4H5M6S`. Months, days and
+/// seconds are kept apart, since their length in seconds depends
+/// on the point in time they are added to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ToJSON, Encode, Decode)]
+pub struct Duration {
+    #[n(0)]
+    months: i64,
+    #[n(1)]
+    days: i64,
+    #[n(2)]
+    seconds: i64,
+    #[n(3)]
+    nanos: u32,
+}
+
+impl Date {
+    /// The date with the given year, month (`1` to `12`) and
+    /// day of the month (starting at `1`), if it exists.
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Self> {
+        if !(MIN_YEAR..=MAX_YEAR).contains(&year)
+            || !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+        {
+            return None;
+        }
+        Some(Self {
+            days: days_from_civil(year, month, day),
+        })
+    }
+
+    /// The current date in UTC.
+    pub fn today() -> Self {
+        LocalDateTime::now().date()
+    }
+
+    pub fn year(&self) -> i64 {
+        civil_from_days(self.days).0
+    }
+
+    pub fn month(&self) -> u32 {
+        civil_from_days(self.days).1
+    }
+
+    pub fn day(&self) -> u32 {
+        civil_from_days(self.days).2
+    }
+
+    /// The day of the week, from `1` (Monday) to `7` (Sunday).
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday
+        ((self.days + 3).rem_euclid(7) + 1) as u32
+    }
+
+    /// The day of the year, starting at `1`.
+    pub fn ordinal(&self) -> u32 {
+        (self.days - days_from_civil(self.year(), 1, 1) + 1) as u32
+    }
+
+    /// Add a duration. Components smaller than
+    /// a day are ignored.
+    pub fn checked_add(&self, duration: &Duration) -> Option<Self> {
+        let (year, month, day) = civil_from_days(self.days);
+        let date = add_months(year, month, day, duration.months)?;
+        let days = duration
+            .days
+            .checked_add(duration.seconds / SECONDS_PER_DAY)?;
+        Self::from_days(date.days.checked_add(days)?)
+    }
+
+    fn from_days(days: i64) -> Option<Self> {
+        let year = civil_from_days(days).0;
+        if (MIN_YEAR..=MAX_YEAR).contains(&year) {
+            Some(Self { days })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 8] {
+        encode_i64(self.days)
+    }
+
+    fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self {
+            days: decode_i64(&bytes),
+        }
+    }
+}
+
+impl LocalDateTime {
+    /// The given time on `date`, if it is valid.
+    pub fn new(date: Date, hour: u32, minute: u32, second: u32, nanosecond: u32) -> Option<Self> {
+        if hour > 23 || minute > 59 || second > 59 || nanosecond as i64 >= NANOS_PER_SECOND {
+            return None;
+        }
+        Some(Self {
+            seconds: date.days * SECONDS_PER_DAY + (hour * 3600 + minute * 60 + second) as i64,
+            nanos: nanosecond,
+        })
+    }
+
+    /// The current date and time in UTC.
+    pub fn now() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            seconds: now.as_secs() as i64,
+            nanos: now.subsec_nanos(),
+        }
+    }
+
+    pub fn date(&self) -> Date {
+        Date {
+            days: self.seconds.div_euclid(SECONDS_PER_DAY),
+        }
+    }
+
+    pub fn hour(&self) -> u32 {
+        (self.seconds.rem_euclid(SECONDS_PER_DAY) / 3600) as u32
+    }
+
+    pub fn minute(&self) -> u32 {
+        (self.seconds.rem_euclid(3600) / 60) as u32
+    }
+
+    pub fn second(&self) -> u32 {
+        self.seconds.rem_euclid(60) as u32
+    }
+
+    pub fn nanosecond(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Add a duration, where months are added first,
+    /// followed by days and then the remaining time.
+    pub fn checked_add(&self, duration: &Duration) -> Option<Self> {
+        let date = self.date();
+        let date = add_months(date.year(), date.month(), date.day(), duration.months)?;
+        let nanos = (date.days as i128 + duration.days as i128)
+            * (SECONDS_PER_DAY as i128)
+            * (NANOS_PER_SECOND as i128)
+            + self.seconds.rem_euclid(SECONDS_PER_DAY) as i128 * NANOS_PER_SECOND as i128
+            + self.nanos as i128
+            + duration.total_nanos();
+        Self::from_nanos(nanos)
+    }
+
+    fn from_nanos(nanos: i128) -> Option<Self> {
+        let seconds: i64 = nanos.div_euclid(NANOS_PER_SECOND as i128).try_into().ok()?;
+        Date::from_days(seconds.div_euclid(SECONDS_PER_DAY))?;
+        Some(Self {
+            seconds,
+            nanos: nanos.rem_euclid(NANOS_PER_SECOND as i128) as u32,
+        })
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[..8].copy_from_slice(&encode_i64(self.seconds));
+        bytes[8..].copy_from_slice(&self.nanos.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 12]) -> Self {
+        Self {
+            seconds: decode_i64(&bytes[..8]),
+            nanos: u32::from_be_bytes(bytes[8..].try_into().unwrap()),
+        }
+    }
+}
+
+impl DateTime {
+    /// The given local date and time, at `offset` seconds from UTC. The
+    /// offset must be less than 18 hours.
+    pub fn new(local: LocalDateTime, offset: i32) -> Option<Self> {
+        if offset.abs() >= 18 * 3600 {
+            return None;
+        }
+        Some(Self {
+            seconds: local.seconds - offset as i64,
+            nanos: local.nanos,
+            offset,
+        })
+    }
+
+    /// The current date and time in UTC.
+    pub fn now() -> Self {
+        Self::new(LocalDateTime::now(), 0).unwrap()
+    }
+
+    /// The local date and time at the offset.
+    pub fn local(&self) -> LocalDateTime {
+        LocalDateTime {
+            seconds: self.seconds + self.offset as i64,
+            nanos: self.nanos,
+        }
+    }
+
+    /// Offset from UTC in seconds.
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Seconds since `1970-01-01T00:00:00Z`.
+    pub fn timestamp(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Add a duration to the local date and time,
+    /// keeping the offset.
+    pub fn checked_add(&self, duration: &Duration) -> Option<Self> {
+        Self::new(self.local().checked_add(duration)?, self.offset)
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&encode_i64(self.seconds));
+        bytes[8..12].copy_from_slice(&self.nanos.to_be_bytes());
+        bytes[12..].copy_from_slice(&encode_i32(self.offset));
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self {
+            seconds: decode_i64(&bytes[..8]),
+            nanos: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+            offset: decode_i32(&bytes[12..]),
+        }
+    }
+}
+
+impl Duration {
+    /// A duration of the given number of months, days, seconds and
+    /// nanoseconds. Nanoseconds which make up whole seconds are
+    /// moved to the seconds.
+    pub fn new(months: i64, days: i64, seconds: i64, nanoseconds: i64) -> Self {
+        Self {
+            months,
+            days,
+            seconds: seconds + nanoseconds.div_euclid(NANOS_PER_SECOND),
+            nanos: nanoseconds.rem_euclid(NANOS_PER_SECOND) as u32,
+        }
+    }
+
+    pub fn months(&self) -> i64 {
+        self.months
+    }
+
+    pub fn days(&self) -> i64 {
+        self.days
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// The nanoseconds in addition to the seconds,
+    /// which are always positive.
+    pub fn nanoseconds(&self) -> u32 {
+        self.nanos
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        Self::from_parts(
+            self.months.checked_add(other.months)?,
+            self.days.checked_add(other.days)?,
+            self.total_nanos() + other.total_nanos(),
+        )
+    }
+
+    pub fn checked_neg(&self) -> Option<Self> {
+        Self::from_parts(
+            self.months.checked_neg()?,
+            self.days.checked_neg()?,
+            -self.total_nanos(),
+        )
+    }
+
+    fn total_nanos(&self) -> i128 {
+        self.seconds as i128 * NANOS_PER_SECOND as i128 + self.nanos as i128
+    }
+
+    fn from_parts(months: i64, days: i64, nanos: i128) -> Option<Self> {
+        Some(Self {
+            months,
+            days,
+            seconds: nanos.div_euclid(NANOS_PER_SECOND as i128).try_into().ok()?,
+            nanos: nanos.rem_euclid(NANOS_PER_SECOND as i128) as u32,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; 28] {
+        let mut bytes = [0; 28];
+        bytes[..8].copy_from_slice(&encode_i64(self.months));
+        bytes[8..16].copy_from_slice(&encode_i64(self.days));
+        bytes[16..24].copy_from_slice(&encode_i64(self.seconds));
+        bytes[24..].copy_from_slice(&self.nanos.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 28]) -> Self {
+        Self {
+            months: decode_i64(&bytes[..8]),
+            days: decode_i64(&bytes[8..16]),
+            seconds: decode_i64(&bytes[16..24]),
+            nanos: u32::from_be_bytes(bytes[24..].try_into().unwrap()),
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    /// Parse an ISO 8601 date, e.g. `2024-01-01`.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        parse_date(text).ok_or_else(|| Error::InvalidTemporal(text.to_string()))
+    }
+}
+
+impl FromStr for LocalDateTime {
+    type Err = Error;
+
+    /// Parse an ISO 8601 date and time, e.g. `2024-01-01T12:30:00.5`. The
+    /// time defaults to midnight if it is not given.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        parse_local_date_time(text).ok_or_else(|| Error::InvalidTemporal(text.to_string()))
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = Error;
+
+    /// Parse an ISO 8601 date and time with an offset, e.g.
+    /// `2024-01-01T12:30:00+02:00`. The offset defaults to UTC
+    /// if it is not given.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let (local, offset) = match text.find('T') {
+            Some(time) => match text[time..].rfind(['Z', '+', '-']) {
+                Some(offset) => text.split_at(time + offset),
+                None => (text, "Z"),
+            },
+            None => (text, "Z"),
+        };
+        parse_local_date_time(local)
+            .zip(parse_offset(offset))
+            .and_then(|(local, offset)| Self::new(local, offset))
+            .ok_or_else(|| Error::InvalidTemporal(text.to_string()))
+    }
+}
+
+impl FromStr for Duration {
+    type Err = Error;
+
+    /// Parse an ISO 8601 duration, e.g. `P1Y2M3DT4H5M6.5S` or `P2W`.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        parse_duration(text).ok_or_else(|| Error::InvalidTemporal(text.to_string()))
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.days);
+        if (0..=9999).contains(&year) {
+            write!(f, "{:04}-{:02}-{:02}", year, month, day)
+        } else {
+            write!(f, "{:+05}-{:02}-{:02}", year, month, day)
+        }
+    }
+}
+
+impl fmt::Display for LocalDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}",
+            self.date(),
+            self.hour(),
+            self.minute(),
+            self.second()
+        )?;
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.local())?;
+        if self.offset == 0 {
+            write!(f, "Z")
+        } else {
+            let sign = if self.offset < 0 { '-' } else { '+' };
+            let offset = self.offset.abs();
+            write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset % 3600 / 60)?;
+            if offset % 60 != 0 {
+                write!(f, ":{:02}", offset % 60)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "P")?;
+        let (years, months) = (self.months / 12, self.months % 12);
+        for (value, unit) in [(years, 'Y'), (months, 'M'), (self.days, 'D')] {
+            if value != 0 {
+                write!(f, "{}{}", value, unit)?;
+            }
+        }
+
+        let nanos = self.total_nanos();
+        if nanos == 0 && (self.months != 0 || self.days != 0) {
+            return Ok(());
+        }
+        write!(f, "T")?;
+        let sign = if nanos < 0 { "-" } else { "" };
+        let nanos = nanos.abs();
+        let seconds = nanos / NANOS_PER_SECOND as i128;
+        let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+        let (seconds, fraction) = (seconds % 60, nanos % NANOS_PER_SECOND as i128);
+        if hours != 0 {
+            write!(f, "{}{}H", sign, hours)?;
+        }
+        if minutes != 0 {
+            write!(f, "{}{}M", sign, minutes)?;
+        }
+        if seconds != 0 || fraction != 0 || (hours == 0 && minutes == 0) {
+            write!(f, "{}{}", sign, seconds)?;
+            if fraction != 0 {
+                let fraction = format!("{:09}", fraction);
+                write!(f, ".{}", fraction.trim_end_matches('0'))?;
+            }
+            write!(f, "S")?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! sortable_bytes {
+    ($type:ty, $len:expr) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.to_bytes().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <[u8; $len]>::deserialize(deserializer).map(Self::from_bytes)
+            }
+        }
+    };
+}
+
+sortable_bytes!(Date, 8);
+sortable_bytes!(LocalDateTime, 12);
+sortable_bytes!(DateTime, 16);
+sortable_bytes!(Duration, 28);
+
+fn encode_i64(value: i64) -> [u8; 8] {
+    ((value as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+fn decode_i64(bytes: &[u8]) -> i64 {
+    (u64::from_be_bytes(bytes.try_into().unwrap()) ^ (1 << 63)) as i64
+}
+
+fn encode_i32(value: i32) -> [u8; 4] {
+    ((value as u32) ^ (1 << 31)).to_be_bytes()
+}
+
+fn decode_i32(bytes: &[u8]) -> i32 {
+    (u32::from_be_bytes(bytes.try_into().unwrap()) ^ (1 << 31)) as i32
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since `1970-01-01` of the given date in the proleptic
+/// Gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of the given days since `1970-01-01`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// Add months to a date, where the day is clamped
+/// to the last day of the resulting month.
+fn add_months(year: i64, month: u32, day: u32, months: i64) -> Option<Date> {
+    let total = year
+        .checked_mul(12)?
+        .checked_add(month as i64 - 1)?
+        .checked_add(months)?;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+        return None;
+    }
+    Date::from_ymd(year, month, day.min(days_in_month(year, month)))
+}
+
+fn parse_digits(text: &str, len: usize) -> Option<u32> {
+    if text.len() == len && text.bytes().all(|b| b.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut parts = unsigned.splitn(3, '-');
+    let year = parts.next()?;
+    if year.len() < 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year = sign * year.parse::<i64>().ok()?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    Date::from_ymd(year, month, day)
+}
+
+fn parse_local_date_time(text: &str) -> Option<LocalDateTime> {
+    let (date, time) = match text.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+    let date = parse_date(date)?;
+    let time = match time {
+        None => return LocalDateTime::new(date, 0, 0, 0, 0),
+        Some(time) => time,
+    };
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut parts = time.split(':');
+    let hour = parse_digits(parts.next()?, 2)?;
+    let minute = parse_digits(parts.next()?, 2)?;
+    let second = parts
+        .next()
+        .map(|s| parse_digits(s, 2))
+        .unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let nanosecond = match fraction {
+        Some(fraction) => parse_fraction(fraction)?,
+        None => 0,
+    };
+    LocalDateTime::new(date, hour, minute, second, nanosecond)
+}
+
+/// Parse the digits after the decimal point as nanoseconds.
+fn parse_fraction(fraction: &str) -> Option<u32> {
+    if fraction.is_empty() || fraction.len() > 9 {
+        return None;
+    }
+    let digits = parse_digits(fraction, fraction.len())?;
+    Some(digits * 10u32.pow(9 - fraction.len() as u32))
+}
+
+fn parse_offset(text: &str) -> Option<i32> {
+    if text == "Z" {
+        return Some(0);
+    } else if !text.is_ascii() {
+        return None;
+    }
+    let sign = match text.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let text = &text[1..];
+    let (hours, minutes) = match text.len() {
+        2 => (text, "00"),
+        4 => text.split_at(2),
+        5 if text.as_bytes()[2] == b':' => (&text[..2], &text[3..]),
+        _ => return None,
+    };
+    let hours = parse_digits(hours, 2)? as i32;
+    let minutes = parse_digits(minutes, 2)? as i32;
+    if minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let text = text.strip_prefix('P')?;
+    let (date, time) = match text.split_once('T') {
+        Some((date, time)) if !time.is_empty() => (date, Some(time)),
+        Some(_) => return None,
+        None => (text, None),
+    };
+    if date.is_empty() && time.is_none() {
+        return None;
+    }
+
+    let (mut months, mut days) = (0i64, 0i64);
+    for (value, unit) in duration_components(date, &['Y', 'M', 'W', 'D'])? {
+        let value: i64 = value.parse().ok()?;
+        match unit {
+            'Y' => months = months.checked_add(value.checked_mul(12)?)?,
+            'M' => months = months.checked_add(value)?,
+            'W' => days = days.checked_add(value.checked_mul(7)?)?,
+            _ => days = days.checked_add(value)?,
+        }
+    }
+
+    let mut nanos = 0i128;
+    for (value, unit) in duration_components(time.unwrap_or(""), &['H', 'M', 'S'])? {
+        let (whole, fraction) = match value.split_once('.') {
+            Some((whole, fraction)) if unit == 'S' => (whole, parse_fraction(fraction)?),
+            Some(_) => return None,
+            None => (value, 0),
+        };
+        let whole: i64 = whole.parse().ok()?;
+        let seconds = match unit {
+            'H' => whole as i128 * 3600,
+            'M' => whole as i128 * 60,
+            _ => whole as i128,
+        };
+        let fraction = if whole < 0 || whole == 0 && value.starts_with('-') {
+            -(fraction as i128)
+        } else {
+            fraction as i128
+        };
+        nanos += seconds * NANOS_PER_SECOND as i128 + fraction;
+    }
+
+    let duration = Duration::from_parts(months, days, nanos)?;
+    if negative {
+        duration.checked_neg()
+    } else {
+        Some(duration)
+    }
+}
+
+/// Split `text` into signed numbers followed by one of
+/// the given units, which must appear in order.
+fn duration_components<'a>(mut text: &'a str, units: &[char]) -> Option<Vec<(&'a str, char)>> {
+    let mut components = Vec::new();
+    let mut units = units.iter();
+    while !text.is_empty() {
+        let end = text.find(|c: char| c.is_ascii_alphabetic())?;
+        let unit = text[end..].chars().next()?;
+        units.find(|&&u| u == unit)?;
+        let value = &text[..end];
+        let digits = value.strip_prefix('-').unwrap_or(value);
+        if digits.is_empty() || digits.starts_with('.') {
+            return None;
+        }
+        components.push((value, unit));
+        text = &text[end + 1..];
+    }
+    Some(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_round_trip() {
+        for text in [
+            "1970-01-01",
+            "2024-02-29",
+            "1600-03-01",
+            "0001-01-01",
+            "-0044-03-15",
+        ] {
+            let date: Date = text.parse().unwrap();
+            assert_eq!(date.to_string(), text);
+        }
+        let date = Date::from_ymd(2024, 2, 29).unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2024, 2, 29));
+        assert_eq!(date.weekday(), 4);
+        assert_eq!(date.ordinal(), 60);
+
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2023-1-01".parse::<Date>().is_err());
+        assert!("23-01-01".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn date_time_round_trip() {
+        for text in ["2024-01-01T12:30:00", "1969-12-31T23:59:59.5"] {
+            let local: LocalDateTime = text.parse().unwrap();
+            assert_eq!(local.to_string(), text);
+        }
+        for text in [
+            "2024-01-01T12:30:00Z",
+            "2024-01-01T12:30:00.123+02:00",
+            "2024-01-01T00:00:00-05:30",
+        ] {
+            let date_time: DateTime = text.parse().unwrap();
+            assert_eq!(date_time.to_string(), text);
+        }
+
+        let date_time: DateTime = "2024-01-01T01:00:00+02:00".parse().unwrap();
+        assert_eq!(date_time.timestamp(), 1704063600);
+        assert_eq!(date_time.local().hour(), 1);
+        assert!("2024-01-01T24:00:00Z".parse::<DateTime>().is_err());
+        assert!("2024-01-01T12:00:00+18:00".parse::<DateTime>().is_err());
+    }
+
+    #[test]
+    fn duration_round_trip() {
+        for text in ["P1Y2M3DT4H5M6.5S", "PT0S", "P14D", "PT-1.5S", "P-1MT-2H"] {
+            let duration: Duration = text.parse().unwrap();
+            assert_eq!(duration.to_string(), text);
+        }
+        assert_eq!(
+            "P2W".parse::<Duration>().unwrap(),
+            Duration::new(0, 14, 0, 0)
+        );
+        assert_eq!(
+            "-PT1S".parse::<Duration>().unwrap(),
+            Duration::new(0, 0, -1, 0)
+        );
+        assert!("P".parse::<Duration>().is_err());
+        assert!("PT".parse::<Duration>().is_err());
+        assert!("P1D2Y".parse::<Duration>().is_err());
+        assert!("P1.5D".parse::<Duration>().is_err());
+    }
+
+    #[test]
+    fn add_durations() {
+        let date: Date = "2024-01-31".parse().unwrap();
+        let month: Duration = "P1M".parse().unwrap();
+        assert_eq!(date.checked_add(&month).unwrap().to_string(), "2024-02-29");
+
+        let local: LocalDateTime = "2024-12-31T23:00:00".parse().unwrap();
+        let hours: Duration = "PT2H".parse().unwrap();
+        assert_eq!(
+            local.checked_add(&hours).unwrap().to_string(),
+            "2025-01-01T01:00:00"
+        );
+        let back = local.checked_add(&hours.checked_neg().unwrap()).unwrap();
+        assert_eq!(back.to_string(), "2024-12-31T21:00:00");
+
+        let max: Date = "+999999-12-31".parse().unwrap();
+        assert!(max.checked_add(&"P1D".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn encoding_is_chronological() {
+        let dates = ["-0001-12-31", "1969-12-31", "1970-01-01", "2024-01-01"];
+        let encoded: Vec<_> = dates
+            .iter()
+            .map(|d| bincode::serialize(&d.parse::<Date>().unwrap()).unwrap())
+            .collect();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(encoded[0].len(), 8);
+
+        let times = [
+            "1970-01-01T01:00:00+02:00",
+            "1969-12-31T23:59:59.999Z",
+            "1970-01-01T00:00:00Z",
+        ];
+        let encoded: Vec<_> = times
+            .iter()
+            .map(|t| bincode::serialize(&t.parse::<DateTime>().unwrap()).unwrap())
+            .collect();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+
+        let decoded: DateTime = bincode::deserialize(&encoded[0]).unwrap();
+        assert_eq!(decoded.to_string(), "1970-01-01T01:00:00+02:00");
+    }
+}
@@ -127,3 +127,346 @@ fn delete_nodes_and_edges() {
     assert!(txn.load_node(node_id).unwrap().is_none());
     assert!(txn.load_edge(edge_id).unwrap().is_none());
 }
+
+#[test]
+fn maintain_index_entries() {
+    let store = Store::open_anon().unwrap();
+    let index = Index::new("PERSON", "name");
+    let mut txn = store.mut_txn().unwrap();
+    let node_id = txn
+        .unchecked_create_node(Node {
+            id: txn.id_seq(),
+            label: "PERSON".to_string(),
+            properties: vec![("name".to_string(), PropOwned::Text("Peter".into()))]
+                .into_iter()
+                .collect(),
+        })
+        .unwrap();
+    txn.create_index(index.clone(), IndexKind::Plain).unwrap();
+    txn.commit().unwrap();
+
+    let entries = |txn: &StoreTxn| {
+        let prefix = index.prefix();
+        btree::iter(&txn.txn, &txn.index_entries, Some((prefix.as_slice(), None)))
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .take_while(|(entry, _)| entry.starts_with(&prefix))
+            .map(|(entry, &id)| (entry.to_vec(), id))
+            .collect::<Vec<_>>()
+    };
+    let entry = |name: &str| index.entry(&[PropRef::Text(name)]).unwrap().unwrap();
+
+    let txn = store.txn().unwrap();
+    assert_eq!(txn.schema().unwrap().indexes.len(), 1);
+    assert_eq!(entries(&txn), vec![(entry("Peter"), node_id)]);
+
+    let mut txn = store.mut_txn().unwrap();
+    txn.update_node(node_id, "name", PropOwned::Text("Paul".into()))
+        .unwrap();
+    txn.update_node(node_id, "age", PropOwned::Integer(42))
+        .unwrap();
+    assert_eq!(entries(&txn), vec![(entry("Paul"), node_id)]);
+
+    txn.update_node(node_id, "name", PropOwned::Null).unwrap();
+    assert_eq!(entries(&txn), vec![]);
+
+    txn.update_node(node_id, "name", PropOwned::Text("Mary".into()))
+        .unwrap();
+    txn.delete_node(node_id).unwrap();
+    assert_eq!(entries(&txn), vec![]);
+    txn.commit().unwrap();
+
+    let mut txn = store.mut_txn().unwrap();
+    txn.drop_index(&index, IndexKind::Plain).unwrap();
+    assert!(!txn.has_index(&index).unwrap());
+    assert_eq!(txn.schema().unwrap(), Schema::default());
+}
+
+#[test]
+fn order_index_entries() {
+    let index = Index::new("PERSON", "age");
+    let values = [
+        PropRef::Real(f64::NEG_INFINITY),
+        PropRef::Integer(i64::MIN),
+        PropRef::Integer(-3),
+        PropRef::Real(-2.5),
+        PropRef::Integer(0),
+        PropRef::Real(0.5),
+        PropRef::Integer(10),
+        PropRef::Real(1e20),
+        PropRef::Text(""),
+        PropRef::Text("a"),
+        PropRef::Text("a\0"),
+        PropRef::Text("ab"),
+        PropRef::Text("b"),
+    ];
+    let entries: Vec<_> = values
+        .iter()
+        .map(|value| index.entry(std::slice::from_ref(value)).unwrap().unwrap())
+        .collect();
+    let mut sorted = entries.clone();
+    sorted.sort();
+    assert_eq!(entries, sorted);
+
+    // loosely equal values share their entry
+    let entry = |value| index.entry(&[value]).unwrap().unwrap();
+    assert_eq!(entry(PropRef::Integer(3)), entry(PropRef::Real(3.0)));
+    assert_eq!(entry(PropRef::Real(0.0)), entry(PropRef::Real(-0.0)));
+    assert_eq!(index.entry(&[PropRef::Null]).unwrap(), None);
+}
+
+#[test]
+fn enforce_unique_constraints() {
+    let store = Store::open_anon().unwrap();
+    let index = Index::new("USER", "email");
+    let user = |txn: &StoreTxn, email: &str| Node {
+        id: txn.id_seq(),
+        label: "USER".to_string(),
+        properties: vec![("email".to_string(), PropOwned::Text(email.into()))]
+            .into_iter()
+            .collect(),
+    };
+
+    let txn = store.mut_txn().unwrap();
+    txn.queue_update(Update::CreateIndex(index.clone(), IndexKind::Unique))
+        .unwrap();
+    let ada = user(&txn, "ada@example.com");
+    txn.queue_update(Update::CreateNode(ada.clone())).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = store.mut_txn().unwrap();
+    let copy = user(&txn, "ada@example.com");
+    txn.queue_update(Update::CreateNode(copy.clone())).unwrap();
+    assert!(matches!(
+        txn.flush(),
+        Err(Error::ConstraintViolation { .. })
+    ));
+    // the violation is reported until it is resolved
+    assert!(txn.flush().is_err());
+    txn.queue_update(Update::SetNodeProperty(
+        copy.id,
+        "email".to_string(),
+        PropOwned::Text("grace@example.com".into()),
+    ))
+    .unwrap();
+    txn.commit().unwrap();
+
+    let txn = store.txn().unwrap();
+    assert_eq!(
+        txn.schema().unwrap().indexes.get(&index),
+        Some(&IndexKind::Unique)
+    );
+}
+
+#[test]
+fn maintain_statistics() {
+    let store = Store::open_anon().unwrap();
+    let node = |txn: &StoreTxn, label: &str| Node {
+        id: txn.id_seq(),
+        label: label.to_string(),
+        properties: Default::default(),
+    };
+
+    let mut txn = store.mut_txn().unwrap();
+    let ada = txn.unchecked_create_node(node(&txn, "PERSON")).unwrap();
+    let grace = txn.unchecked_create_node(node(&txn, "PERSON")).unwrap();
+    let robot = txn.unchecked_create_node(node(&txn, "ROBOT")).unwrap();
+    let edge = |txn: &StoreTxn, origin: u64, target: u64| Edge {
+        id: txn.id_seq(),
+        label: "KNOWS".to_string(),
+        origin,
+        target,
+        properties: Default::default(),
+    };
+    txn.unchecked_create_edge(edge(&txn, ada, grace)).unwrap();
+    let knows_robot = txn.unchecked_create_edge(edge(&txn, ada, robot)).unwrap();
+    // statistics are updated on commit
+    assert_eq!(txn.statistics().unwrap(), Statistics::default());
+    txn.commit().unwrap();
+
+    let statistics = store.txn().unwrap().statistics().unwrap();
+    assert_eq!(statistics.nodes(Some("PERSON")), 2.0);
+    assert_eq!(statistics.nodes(None), 3.0);
+    assert_eq!(statistics.edges(Some("KNOWS")), 2.0);
+    assert_eq!(statistics.origins(Some("KNOWS"), Some("PERSON")), 2.0);
+    assert_eq!(statistics.targets(Some("KNOWS"), Some("ROBOT")), 1.0);
+    assert_eq!(statistics.out_degree(Some("KNOWS"), Some("PERSON")), 1.0);
+    assert_eq!(statistics.in_degree(None, Some("ROBOT")), 1.0);
+
+    let mut txn = store.mut_txn().unwrap();
+    txn.delete_edge(knows_robot).unwrap();
+    txn.delete_node(robot).unwrap();
+    txn.commit().unwrap();
+
+    let statistics = store.txn().unwrap().statistics().unwrap();
+    assert_eq!(statistics.nodes(Some("ROBOT")), 0.0);
+    assert_eq!(statistics.targets(None, Some("ROBOT")), 0.0);
+    assert_eq!(statistics.targets(None, None), 1.0);
+    assert!(!statistics.nodes.contains_key("ROBOT"));
+}
+
+#[test]
+fn index_statistics() {
+    let store = Store::open_anon().unwrap();
+    let mut txn = store.mut_txn().unwrap();
+    let node = |txn: &mut StoreTxn, label: &str| {
+        txn.unchecked_create_node(Node {
+            id: txn.id_seq(),
+            label: label.to_string(),
+            properties: Default::default(),
+        })
+        .unwrap()
+    };
+    let (ada, robot) = (node(&mut txn, "PERSON"), node(&mut txn, "ROBOT"));
+    node(&mut txn, "PERSON");
+    let edge = Edge {
+        id: txn.id_seq(),
+        label: "KNOWS".to_string(),
+        origin: ada,
+        target: robot,
+        properties: Default::default(),
+    };
+    txn.unchecked_create_edge(edge).unwrap();
+    txn.commit().unwrap();
+    let statistics = store.txn().unwrap().statistics().unwrap();
+
+    // graphs written before statistics were kept
+    let mut txn = Env::mut_txn_begin(&store.env).unwrap();
+    txn.set_root(DB_STATISTICS, 0);
+    sanakirja::Commit::commit(txn).unwrap();
+
+    store.mut_txn().unwrap().commit().unwrap();
+    let counted = store.txn().unwrap().statistics().unwrap();
+    assert_eq!(counted, statistics);
+    assert_eq!(counted.nodes(Some("PERSON")), 2.0);
+    assert_eq!(counted.targets(Some("KNOWS"), Some("ROBOT")), 1.0);
+}
+
+#[test]
+fn drop_retired_databases() {
+    let store = Store::open_anon().unwrap();
+    let mut txn = Env::mut_txn_begin(&store.env).unwrap();
+    let origins: btree::Db<u64, u64> = btree::create_db(&mut txn).unwrap();
+    let targets: btree::Db<u64, u64> = btree::create_db(&mut txn).unwrap();
+    txn.set_root(DB_RETIRED_ORIGINS, origins.db);
+    txn.set_root(DB_RETIRED_TARGETS, targets.db);
+    sanakirja::Commit::commit(txn).unwrap();
+
+    store.mut_txn().unwrap().commit().unwrap();
+    let txn = Env::mut_txn_begin(&store.env).unwrap();
+    for n in [DB_RETIRED_ORIGINS, DB_RETIRED_TARGETS] {
+        assert_eq!(txn.root(n), None);
+    }
+}
+
+#[test]
+fn index_edge_labels() {
+    let store = Store::open_anon().unwrap();
+    let mut txn = store.mut_txn().unwrap();
+    let node = txn
+        .unchecked_create_node(Node {
+            id: txn.id_seq(),
+            label: "PERSON".to_string(),
+            properties: Default::default(),
+        })
+        .unwrap();
+    let edge = |txn: &StoreTxn, label: &str| Edge {
+        id: txn.id_seq(),
+        label: label.to_string(),
+        origin: node,
+        target: node,
+        properties: Default::default(),
+    };
+    let knows = txn.unchecked_create_edge(edge(&txn, "KNOWS")).unwrap();
+    let likes = txn.unchecked_create_edge(edge(&txn, "LIKES")).unwrap();
+    let knows_too = txn.unchecked_create_edge(edge(&txn, "KNOWS")).unwrap();
+    txn.commit().unwrap();
+
+    let labeled = |txn: &StoreTxn, label: &str| {
+        EdgeIter::with_label(txn, label.to_string())
+            .unwrap()
+            .map(|edge| edge.map(|edge| edge.id))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+    let txn = store.txn().unwrap();
+    assert_eq!(labeled(&txn, "KNOWS"), vec![knows, knows_too]);
+    assert_eq!(labeled(&txn, "LIKES"), vec![likes]);
+    assert_eq!(labeled(&txn, "KNOW"), Vec::<u64>::new());
+    drop(txn);
+
+    let mut txn = store.mut_txn().unwrap();
+    txn.delete_edge(knows).unwrap();
+    txn.commit().unwrap();
+    let txn = store.txn().unwrap();
+    assert_eq!(labeled(&txn, "KNOWS"), vec![knows_too]);
+}
+
+#[test]
+fn index_adjacency() {
+    let store = Store::open_anon().unwrap();
+    let mut txn = store.mut_txn().unwrap();
+    let node = |txn: &mut StoreTxn| {
+        txn.unchecked_create_node(Node {
+            id: txn.id_seq(),
+            label: "PERSON".to_string(),
+            properties: Default::default(),
+        })
+        .unwrap()
+    };
+    let (alice, bob) = (node(&mut txn), node(&mut txn));
+    let edge = |txn: &StoreTxn, label: &str, origin: u64, target: u64| Edge {
+        id: txn.id_seq(),
+        label: label.to_string(),
+        origin,
+        target,
+        properties: Default::default(),
+    };
+    let knows = txn
+        .unchecked_create_edge(edge(&txn, "KNOWS", alice, bob))
+        .unwrap();
+    let likes = txn
+        .unchecked_create_edge(edge(&txn, "LIKES", bob, alice))
+        .unwrap();
+    let itself = txn
+        .unchecked_create_edge(edge(&txn, "KNOWS", alice, alice))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let ids = |iter: Result<EdgeIter, Error>| {
+        iter.unwrap()
+            .map(|edge| edge.map(|edge| edge.id))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+    let txn = store.txn().unwrap();
+    assert_eq!(ids(EdgeIter::origins(&txn, alice, None)), vec![knows, itself]);
+    assert_eq!(ids(EdgeIter::targets(&txn, alice, None)), vec![itself, likes]);
+    assert_eq!(ids(EdgeIter::both(&txn, bob, None)), vec![likes, knows]);
+    assert_eq!(ids(EdgeIter::origins(&txn, alice, Some("KNOWS"))), vec![knows, itself]);
+    assert_eq!(ids(EdgeIter::origins(&txn, alice, Some("LIKES"))), vec![]);
+    assert_eq!(ids(EdgeIter::targets(&txn, alice, Some("LIKES"))), vec![likes]);
+    assert_eq!(
+        ids(EdgeIter::both(&txn, alice, Some("KNOWS"))),
+        vec![knows, itself, itself]
+    );
+    // the ends of edges are read from the index
+    for edge in EdgeIter::both(&txn, bob, None).unwrap() {
+        let edge = edge.unwrap();
+        let record = edge.record(&txn).unwrap();
+        assert_eq!(
+            (edge.label.as_str(), edge.origin, edge.target),
+            (record.label(), record.origin(), record.target())
+        );
+    }
+    drop(txn);
+
+    let mut txn = store.mut_txn().unwrap();
+    assert!(matches!(txn.delete_node(bob), Err(Error::DeleteConnected)));
+    txn.delete_edge(knows).unwrap();
+    txn.delete_edge(likes).unwrap();
+    txn.delete_node(bob).unwrap();
+    txn.commit().unwrap();
+    let txn = store.txn().unwrap();
+    assert_eq!(ids(EdgeIter::both(&txn, alice, None)), vec![itself, itself]);
+}
@@ -1,4 +1,6 @@
-use crate::Error;
+use super::spatial::Point;
+use super::temporal::{Date, DateTime, Duration, LocalDateTime};
+use crate::{Error, Property};
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
 use std::{cmp::Ordering};
@@ -51,7 +53,16 @@ pub enum PropRef<'a> {
     Boolean(bool),
     Text(&'a str),
     Blob(&'a [u8]),
-    Null
+    Null,
+    List(Vec<PropRef<'a>>),
+    Path(Path),
+    Node(Node),
+    Edge(Edge),
+    Date(Date),
+    LocalDateTime(LocalDateTime),
+    DateTime(DateTime),
+    Duration(Duration),
+    Point(Point),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize,ToJSON,Encode,Decode)]
@@ -63,6 +74,15 @@ pub enum PropOwned {
     #[n(4)] Text( #[n(0)] String),
     #[n(5)] Blob( #[n(0)] Vec<u8>),
     #[n(6)] Null,
+    #[n(7)] List( #[n(0)] Vec<PropOwned>),
+    #[n(8)] Path( #[n(0)] Path),
+    #[n(9)] Node( #[n(0)] Node),
+    #[n(10)] Edge( #[n(0)] Edge),
+    #[n(11)] Date( #[n(0)] Date),
+    #[n(12)] LocalDateTime( #[n(0)] LocalDateTime),
+    #[n(13)] DateTime( #[n(0)] DateTime),
+    #[n(14)] Duration( #[n(0)] Duration),
+    #[n(15)] Point( #[n(0)] Point),
 }
 
 #[derive(Default,Debug, Clone, PartialEq, Serialize, Deserialize,ToJSON,Encode,Decode)]
@@ -81,6 +101,12 @@ pub struct Edge {
     #[n(4)] pub(crate) target: u64,
 }
 
+#[derive(Default,Debug, Clone, PartialEq, Serialize, Deserialize,ToJSON,Encode,Decode)]
+pub struct Path {
+    #[n(0)] pub(crate) nodes: Vec<u64>,
+    #[n(1)] pub(crate) edges: Vec<u64>,
+}
+
 impl Node {
     pub fn id(&self) -> u64 {
         self.id
@@ -93,6 +119,13 @@ impl Node {
     pub fn property(&self, key: &str) -> &PropOwned {
         self.properties.get(key).unwrap_or(&PropOwned::Null)
     }
+
+    /// All properties of the node, ordered by key.
+    pub fn properties(&self) -> impl Iterator<Item = (&str, Property)> + '_ {
+        self.properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone().to_external()))
+    }
 }
 
 impl Edge {
@@ -104,9 +137,48 @@ impl Edge {
         self.label.as_str()
     }
 
+    /// The ID of the node the edge starts from.
+    pub fn origin(&self) -> u64 {
+        self.origin
+    }
+
+    /// The ID of the node the edge points to.
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
     pub fn property(&self, key: &str) -> &PropOwned {
         self.properties.get(key).unwrap_or(&PropOwned::Null)
     }
+
+    /// All properties of the edge, ordered by key.
+    pub fn properties(&self) -> impl Iterator<Item = (&str, Property)> + '_ {
+        self.properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone().to_external()))
+    }
+}
+
+impl Path {
+    /// The IDs of the nodes along the path, starting
+    /// with the first node of the pattern.
+    pub fn nodes(&self) -> &[u64] {
+        self.nodes.as_slice()
+    }
+
+    /// The IDs of the edges along the path, in order.
+    pub fn edges(&self) -> &[u64] {
+        self.edges.as_slice()
+    }
+
+    /// The number of edges in the path.
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
 }
 
 impl PropOwned {
@@ -119,6 +191,15 @@ impl PropOwned {
             Self::Text(text) => PropRef::Text(text.as_str()),
             Self::Blob(bytes) => PropRef::Blob(bytes.as_slice()),
             Self::Null => PropRef::Null,
+            Self::List(items) => PropRef::List(items.iter().map(Self::to_ref).collect()),
+            Self::Path(path) => PropRef::Path(path.clone()),
+            Self::Node(node) => PropRef::Node(node.clone()),
+            Self::Edge(edge) => PropRef::Edge(edge.clone()),
+            Self::Date(date) => PropRef::Date(*date),
+            Self::LocalDateTime(date_time) => PropRef::LocalDateTime(*date_time),
+            Self::DateTime(date_time) => PropRef::DateTime(*date_time),
+            Self::Duration(duration) => PropRef::Duration(*duration),
+            Self::Point(point) => PropRef::Point(*point),
         }
     }
 }
@@ -133,6 +214,15 @@ impl<'a> PropRef<'a> {
             Self::Text(text) => PropOwned::Text(text.to_string()),
             Self::Blob(bytes) => PropOwned::Blob(bytes.to_vec()),
             Self::Null => PropOwned::Null,
+            Self::List(items) => PropOwned::List(items.iter().map(Self::to_owned).collect()),
+            Self::Path(path) => PropOwned::Path(path.clone()),
+            Self::Node(node) => PropOwned::Node(node.clone()),
+            Self::Edge(edge) => PropOwned::Edge(edge.clone()),
+            Self::Date(date) => PropOwned::Date(*date),
+            Self::LocalDateTime(date_time) => PropOwned::LocalDateTime(*date_time),
+            Self::DateTime(date_time) => PropOwned::DateTime(*date_time),
+            Self::Duration(duration) => PropOwned::Duration(*duration),
+            Self::Point(point) => PropOwned::Point(*point),
         }
     }
 
@@ -153,6 +243,9 @@ impl<'a> PropRef<'a> {
             (Self::Real(lhs), Self::Integer(rhs)) => lhs.partial_cmp(&(*rhs as f64)),
             (Self::Integer(lhs), Self::Real(rhs)) => (*lhs as f64).partial_cmp(rhs),
             (Self::Text(lhs), Self::Text(rhs)) => Some(lhs.cmp(rhs)),
+            (Self::Date(lhs), Self::Date(rhs)) => Some(lhs.cmp(rhs)),
+            (Self::LocalDateTime(lhs), Self::LocalDateTime(rhs)) => Some(lhs.cmp(rhs)),
+            (Self::DateTime(lhs), Self::DateTime(rhs)) => Some(lhs.cmp(rhs)),
             _ => None,
         }
     }
@@ -166,6 +259,13 @@ impl<'a> PropRef<'a> {
             Self::Text(_) => true,
             Self::Blob(_) => true,
             Self::Null => false,
+            Self::List(items) => !items.is_empty(),
+            Self::Path(_) => true,
+            Self::Node(_) => true,
+            Self::Edge(_) => true,
+            Self::Date(_) | Self::LocalDateTime(_) | Self::DateTime(_) => true,
+            Self::Duration(_) => true,
+            Self::Point(_) => true,
         }
     }
 
@@ -0,0 +1,144 @@
+use cqlite::{Error, Graph};
+
+#[macro_use]
+mod common;
+
+#[test]
+fn run_batch() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE (:PERSON { name: 'Ada; Countess of Lovelace' });
+            CREATE (:PERSON { name: 'Charles' });
+            MATCH (a:PERSON) MATCH (b:PERSON { name: 'Charles' })
+            WHERE ID(a) <> ID(b)
+            CREATE (a) -[:WORKS_WITH]-> (b);
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+
+    let names: Vec<(String, String)> = graph
+        .prepare("MATCH (a) -[:WORKS_WITH]-> (b) RETURN a.name, b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        names,
+        vec![(
+            "Ada; Countess of Lovelace".to_string(),
+            "Charles".to_string()
+        )]
+    );
+}
+
+#[test]
+fn run_empty_batch() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph.execute_batch(&mut txn, "").unwrap();
+    graph.execute_batch(&mut txn, " \n ").unwrap();
+}
+
+#[test]
+fn run_batch_with_quotes_and_comments() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            r#"
+            // people; one per statement
+            CREATE (:PERSON { name: 'it\'s; here' });;
+            CREATE (:PERSON { name: "say \"hi\"; bye" }) /* ; */;
+            ;
+            "#,
+        )
+        .unwrap();
+    txn.commit().unwrap();
+
+    let mut names: Vec<String> = graph
+        .prepare("MATCH (a:PERSON) RETURN a.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["it's; here", "say \"hi\"; bye"]);
+}
+
+#[test]
+fn batch_reports_failing_statement() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let err = graph
+        .execute_batch(
+            &mut txn,
+            "CREATE (:PERSON);\nCREATE (:PERSON);\n  MATCH (a) RETURN b.name",
+        )
+        .unwrap_err();
+    match err {
+        Error::Batch {
+            index,
+            line,
+            column,
+            source,
+        } => {
+            assert_eq!((index, line, column), (2, 3, 3));
+            assert!(matches!(*source, Error::UnknownIdentifier(_)));
+        }
+        err => panic!("Unexpected {}", err),
+    }
+}
+
+#[test]
+fn batch_reports_syntax_position() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let err = graph
+        .execute_batch(&mut txn, "CREATE (:PERSON);\n  MATCH (a) RETURN ?")
+        .unwrap_err();
+    match err {
+        Error::Batch {
+            index,
+            line,
+            column,
+            source,
+        } => {
+            assert_eq!((index, line, column), (1, 2, 3));
+            match *source {
+                Error::Syntax { line, column, .. } => assert_eq!((line, column), (2, 20)),
+                err => panic!("Unexpected {}", err),
+            }
+        }
+        err => panic!("Unexpected {}", err),
+    }
+}
+
+#[test]
+fn batch_stops_at_error() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    assert_err!(
+        graph.execute_batch(
+            &mut txn,
+            "CREATE (:PERSON); RETURN unknown.name; CREATE (:PERSON)"
+        ),
+        Error::Batch { index: 1, .. }
+    );
+    txn.commit().unwrap();
+
+    let count = graph
+        .prepare("MATCH (a:PERSON) RETURN ID(a)")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<u64, _>(0))
+        .unwrap()
+        .count();
+    assert_eq!(count, 1);
+}
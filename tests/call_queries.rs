@@ -0,0 +1,150 @@
+use cqlite::{Error, Graph, Property};
+
+#[macro_use]
+mod common;
+
+fn create_graph(graph: &Graph) {
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Peter', age: 42 })
+            CREATE (b:PERSON { name: 'Clark' })
+            CREATE (c:CITY { title: 'Metropolis' })
+            CREATE (a) -[:KNOWS { since: 2001 }]-> (b)
+            CREATE (b) -[:LIVES_IN]-> (c)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+}
+
+fn column(graph: &Graph, query: &str) -> Vec<String> {
+    graph
+        .prepare(query)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn call_builtin_procedures() {
+    let graph = Graph::open_anon().unwrap();
+    create_graph(&graph);
+
+    assert_eq!(column(&graph, "CALL db.labels()"), vec!["CITY", "PERSON"]);
+    assert_eq!(
+        column(&graph, "CALL db.relationshipTypes()"),
+        vec!["KNOWS", "LIVES_IN"]
+    );
+    assert_eq!(
+        column(&graph, "CALL db.propertyKeys() YIELD propertyKey RETURN propertyKey"),
+        vec!["age", "name", "since", "title"]
+    );
+    assert_eq!(
+        column(
+            &graph,
+            "CALL db.labels() YIELD label WHERE label = 'CITY' RETURN label"
+        ),
+        vec!["CITY"]
+    );
+}
+
+#[test]
+fn call_registered_procedure() {
+    let mut graph = Graph::open_anon().unwrap();
+    create_graph(&graph);
+    graph.register_procedure("text.repeat", &["text", "count"], |_, args| {
+        let (text, count) = match args {
+            [Property::Text(text), Property::Integer(count)] => (text, *count),
+            _ => return Err(Error::TypeMismatch),
+        };
+        Ok((1..=count)
+            .map(|i| vec![Property::Text(text.repeat(i as usize)), Property::Integer(i)])
+            .collect())
+    });
+
+    let mut rows: Vec<(String, String, i64)> = graph
+        .prepare(
+            "
+            MATCH (p:PERSON)
+            CALL text.repeat(p.name, 2) YIELD text, count
+            RETURN p.name, text, count
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    rows.sort();
+    assert_eq!(
+        rows,
+        vec![
+            ("Clark".into(), "Clark".into(), 1),
+            ("Clark".into(), "ClarkClark".into(), 2),
+            ("Peter".into(), "Peter".into(), 1),
+            ("Peter".into(), "PeterPeter".into(), 2),
+        ]
+    );
+
+    let stmt = graph
+        .prepare("CALL text.repeat($text, 1) YIELD text RETURN text")
+        .unwrap();
+    assert_err!(
+        stmt.query_map(&mut graph.txn().unwrap(), ("text", 42), |m| {
+            m.get::<String, _>(0)
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>(),
+        Error::TypeMismatch
+    );
+}
+
+#[test]
+fn call_reads_transaction() {
+    let mut graph = Graph::open_anon().unwrap();
+    create_graph(&graph);
+    graph.register_procedure("node.label", &["label"], |txn, args| {
+        let id = match args {
+            [Property::Id(id)] => *id,
+            _ => return Err(Error::TypeMismatch),
+        };
+        let node = txn.load_node(id)?.ok_or(Error::MissingNode)?;
+        Ok(vec![vec![node.label().into()]])
+    });
+    assert_eq!(
+        column(
+            &graph,
+            "MATCH (p { age: 42 }) CALL node.label(ID(p)) YIELD label RETURN label"
+        ),
+        vec!["PERSON"]
+    );
+}
+
+#[test]
+fn call_errors() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("CALL db.missing()"),
+        Error::UnknownProcedure(_)
+    );
+    assert_err!(
+        graph.prepare("CALL db.labels() YIELD name RETURN name"),
+        Error::UnknownIdentifier(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH (label) CALL db.labels() YIELD label RETURN label"),
+        Error::IdentifierExists(_)
+    );
+    assert_err!(
+        graph.prepare("CALL db.labels() YIELD label RETURN ID(label)"),
+        Error::IdentifierIsNotNode(_)
+    );
+}
@@ -0,0 +1,185 @@
+use cqlite::{Error, Graph, Property};
+use std::convert::TryInto;
+
+#[macro_use]
+mod common;
+
+fn people() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Ada', age: 36 })
+            CREATE (b:PERSON { name: 'Grace', age: 85 })
+            CREATE (c:PERSON { name: 'Alan', age: 41 })
+            CREATE (a) -[:KNOWS]-> (b)
+            CREATE (a) -[:KNOWS]-> (c)
+            CREATE (b) -[:KNOWS]-> (c)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn texts(list: Vec<Property>) -> Vec<String> {
+    let mut texts: Vec<String> = list
+        .into_iter()
+        .map(|prop| prop.try_into().unwrap())
+        .collect();
+    texts.sort();
+    texts
+}
+
+#[test]
+fn list_comprehension() {
+    let graph = Graph::open_anon().unwrap();
+    let list = vec![
+        Property::Integer(1),
+        Property::Integer(3),
+        Property::Integer(5),
+        Property::Null,
+    ];
+    let values: Vec<Property> = graph
+        .prepare("RETURN [x IN $list WHERE x > 2]")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), ("list", list), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(values, vec![Property::Integer(3), Property::Integer(5)]);
+
+    let value: Property = graph
+        .prepare("RETURN [x IN $list | x]")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), ("list", Property::Null), |m| {
+            m.get(0)
+        })
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(value, Property::Null);
+}
+
+#[test]
+fn list_comprehension_over_path() {
+    let graph = people();
+    let names: Vec<Property> = graph
+        .prepare(
+            "
+            MATCH p = (a:PERSON) -[:KNOWS]-> (:PERSON) -[:KNOWS]-> (:PERSON)
+            RETURN [n IN nodes(p) WHERE n.age > 40 | n.name]
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        names,
+        vec![Property::from("Grace"), Property::from("Alan")]
+    );
+}
+
+#[test]
+fn pattern_comprehension() {
+    let graph = people();
+    let mut rows: Vec<(String, Vec<String>)> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON)
+            RETURN a.name, [(a) -[:KNOWS]-> (b:PERSON) | b.name]
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, texts(m.get(1)?)))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    rows.sort();
+    assert_eq!(
+        rows,
+        vec![
+            ("Ada".to_string(), vec!["Alan".to_string(), "Grace".to_string()]),
+            ("Alan".to_string(), vec![]),
+            ("Grace".to_string(), vec!["Alan".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn comprehension_in_where() {
+    let graph = people();
+    let names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON)
+            WHERE [(a) -[:KNOWS]-> (b) WHERE b.age > 80 | b]
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["Ada"]);
+}
+
+#[test]
+fn comprehension_in_updates() {
+    let graph = people();
+    let mut txn = graph.mut_txn().unwrap();
+    let friends: Vec<Property> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON { name: 'Ada' })
+            CREATE (s:SUMMARY { friends: [(a) -[:KNOWS]-> (b) | b.name] })
+            RETURN [(a) -> (b) | b.age]
+            ",
+        )
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(friends.len(), 2);
+    txn.commit().unwrap();
+
+    let friends: Vec<Property> = graph
+        .prepare("MATCH (s:SUMMARY) RETURN s.friends")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(texts(friends), vec!["Alan", "Grace"]);
+}
+
+#[test]
+fn comprehension_errors() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("MATCH (a) RETURN [a IN $list | a]"),
+        Error::IdentifierExists(_)
+    );
+    assert_err!(
+        graph.prepare("RETURN [x IN $list | y]"),
+        Error::UnknownIdentifier(_)
+    );
+    assert_err!(
+        graph.prepare("RETURN [(a) -> (b) | c.name]"),
+        Error::UnknownIdentifier(_)
+    );
+}
@@ -0,0 +1,173 @@
+use cqlite::{Error, Graph};
+
+#[macro_use]
+mod common;
+
+fn create_test_graph() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE CONSTRAINT ON (u:USER) ASSERT u.email IS UNIQUE;
+            CREATE CONSTRAINT ON (c:CITY) ASSERT (c.name, c.country) IS NODE KEY;
+            CREATE (:USER { email: 'ada@example.com' });
+            CREATE (:USER { name: 'anonymous' });
+            CREATE (:CITY { name: 'Paris', country: 'FR' });
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn execute(graph: &Graph, query: &str) -> Result<(), Error> {
+    let mut txn = graph.mut_txn()?;
+    graph.prepare(query)?.execute(&mut txn, ())?;
+    txn.commit()
+}
+
+#[test]
+fn unique_constraints() {
+    let graph = create_test_graph();
+    let error = execute(&graph, "CREATE (:USER { email: 'ada@example.com' })");
+    assert_err!(error, Error::ConstraintViolation { .. });
+    match error {
+        Err(Error::ConstraintViolation { label, key, value }) => {
+            assert_eq!(label, "USER");
+            assert_eq!(key, "email");
+            assert_eq!(value, "'ada@example.com'");
+        }
+        _ => unreachable!(),
+    }
+
+    let error = execute(
+        &graph,
+        "MATCH (u:USER) WHERE u.name = 'anonymous' SET u.email = 'ada@example.com'",
+    );
+    assert_err!(error, Error::ConstraintViolation { .. });
+
+    // missing values and other labels are not constrained
+    execute(&graph, "CREATE (:USER { name: 'nobody' })").unwrap();
+    execute(&graph, "CREATE (:ADMIN { email: 'ada@example.com' })").unwrap();
+    execute(&graph, "CREATE (:USER { email: 'grace@example.com' })").unwrap();
+}
+
+#[test]
+fn unique_values_can_be_swapped() {
+    let graph = create_test_graph();
+    execute(&graph, "CREATE (:USER { email: 'grace@example.com' })").unwrap();
+    execute(
+        &graph,
+        "
+        MATCH (a:USER) MATCH (b:USER)
+        WHERE a.email = 'ada@example.com' AND b.email = 'grace@example.com'
+        SET a.email = b.email
+        SET b.email = 'ada@example.com'
+        ",
+    )
+    .unwrap();
+}
+
+#[test]
+fn node_key_constraints() {
+    let graph = create_test_graph();
+    assert_err!(
+        execute(&graph, "CREATE (:CITY { name: 'Paris', country: 'FR' })"),
+        Error::ConstraintViolation { .. }
+    );
+    assert_err!(
+        execute(&graph, "CREATE (:CITY { name: 'Lyon' })"),
+        Error::ConstraintViolation { .. }
+    );
+    assert_err!(
+        execute(&graph, "MATCH (c:CITY) SET c.country = null"),
+        Error::ConstraintViolation { .. }
+    );
+    execute(&graph, "CREATE (:CITY { name: 'Paris', country: 'US' })").unwrap();
+}
+
+#[test]
+fn violations_prevent_commit() {
+    let graph = create_test_graph();
+    let mut txn = graph.mut_txn().unwrap();
+    let error = graph
+        .prepare("CREATE (:USER { email: 'ada@example.com' })")
+        .unwrap()
+        .execute(&mut txn, ());
+    assert_err!(error, Error::ConstraintViolation { .. });
+    let error = txn.commit();
+    assert_err!(error, Error::ConstraintViolation { .. });
+
+    let users: Vec<u64> = graph
+        .prepare("MATCH (u:USER) RETURN ID(u)")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(users.len(), 2);
+}
+
+#[test]
+fn constraints_on_existing_data() {
+    let graph = create_test_graph();
+    execute(
+        &graph,
+        "CREATE CONSTRAINT ON (u:USER) ASSERT u.name IS UNIQUE",
+    )
+    .unwrap();
+    execute(
+        &graph,
+        "DROP CONSTRAINT ON (u:USER) ASSERT u.name IS UNIQUE",
+    )
+    .unwrap();
+
+    execute(&graph, "CREATE (:USER { name: 'anonymous' })").unwrap();
+    assert_err!(
+        execute(
+            &graph,
+            "CREATE CONSTRAINT ON (u:USER) ASSERT u.name IS UNIQUE"
+        ),
+        Error::ConstraintViolation { .. }
+    );
+    assert_err!(
+        execute(
+            &graph,
+            "CREATE CONSTRAINT ON (u:USER) ASSERT u.email IS NODE KEY"
+        ),
+        Error::ConstraintViolation { .. }
+    );
+}
+
+#[test]
+fn drop_constraints() {
+    let graph = create_test_graph();
+    // dropping the index keeps the constraint
+    execute(&graph, "DROP INDEX ON :USER(email)").unwrap();
+    assert_err!(
+        execute(&graph, "CREATE (:USER { email: 'ada@example.com' })"),
+        Error::ConstraintViolation { .. }
+    );
+
+    execute(
+        &graph,
+        "DROP CONSTRAINT ON (u:USER) ASSERT u.email IS UNIQUE",
+    )
+    .unwrap();
+    execute(&graph, "CREATE (:USER { email: 'ada@example.com' })").unwrap();
+}
+
+#[test]
+fn constraints_back_indexes() {
+    let graph = create_test_graph();
+    let steps: Vec<String> = graph
+        .prepare("EXPLAIN MATCH (u:USER) WHERE u.email = 'ada@example.com' RETURN u")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(3))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(steps[0].starts_with("LoadIndexedNode"), "{:?}", steps);
+}
@@ -0,0 +1,124 @@
+use cqlite::{Error, Graph};
+
+#[macro_use]
+mod common;
+
+#[test]
+fn create_with_dynamic_labels() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:$($label) { name: 'Ada' })
+            CREATE (b:$($label) { name: 'Grace' })
+            CREATE (a) -[:$($type)]-> (b)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, (("label", "PERSON"), ("type", "KNOWS")))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let names: Vec<(String, String, String)> = graph
+        .prepare("MATCH (a:PERSON) -[e:KNOWS]-> (b) RETURN a.name, LABEL(e), b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        names,
+        vec![("Ada".into(), "KNOWS".into(), "Grace".into())]
+    );
+}
+
+#[test]
+fn match_dynamic_labels() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Ada' })
+            CREATE (b:ROBOT { name: 'Marvin' })
+            CREATE (a) -[:KNOWS]-> (b)
+            CREATE (b) -[:IGNORES]-> (a)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let stmt = graph
+        .prepare("MATCH (a:$($label)) -[:$($type)]-> (b) RETURN a.name, b.name")
+        .unwrap();
+    let mut txn = graph.txn().unwrap();
+    for (label, kind, expected) in [
+        ("PERSON", "KNOWS", vec![("Ada", "Marvin")]),
+        ("ROBOT", "IGNORES", vec![("Marvin", "Ada")]),
+        ("ROBOT", "KNOWS", vec![]),
+    ] {
+        let names: Vec<(String, String)> = stmt
+            .query_map(&mut txn, (("label", label), ("type", kind)), |m| {
+                Ok((m.get(0)?, m.get(1)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        assert_eq!(names, expected);
+    }
+}
+
+#[test]
+fn dynamic_property_keys() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("CREATE (a:PERSON { name: 'Ada' }) SET a[$key] = $value")
+        .unwrap()
+        .execute(&mut txn, (("key", "born"), ("value", 1815)))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let stmt = graph
+        .prepare("MATCH (a:PERSON) WHERE a[$key] = $value RETURN a[$key], a['name']")
+        .unwrap();
+    let values: Vec<(i64, String)> = stmt
+        .query_map(
+            &mut graph.txn().unwrap(),
+            (("key", "born"), ("value", 1815)),
+            |m| Ok((m.get(0)?, m.get(1)?)),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(values, vec![(1815, "Ada".to_string())]);
+}
+
+#[test]
+fn dynamic_symbols_must_be_text() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    assert_err!(
+        graph
+            .prepare("CREATE (a:$($label))")
+            .unwrap()
+            .execute(&mut txn, ("label", 42)),
+        Error::TypeMismatch
+    );
+    assert_err!(
+        graph
+            .prepare("CREATE (a:PERSON) SET a[$key] = 42")
+            .unwrap()
+            .execute(&mut txn, ()),
+        Error::TypeMismatch
+    );
+}
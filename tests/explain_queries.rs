@@ -0,0 +1,230 @@
+use cqlite::{Graph, Property};
+
+fn chain() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Ada' })
+            CREATE (b:PERSON { name: 'Grace' })
+            CREATE (c:PERSON { name: 'Alan' })
+            CREATE (a) -[:KNOWS]-> (b)
+            CREATE (b) -[:KNOWS]-> (c)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn rows(graph: &Graph, query: &str) -> Vec<Vec<Property>> {
+    graph
+        .prepare(query)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            (0..6)
+                .map(|idx| m.get(idx))
+                .collect::<Result<Vec<Property>, _>>()
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn count(rows: &[Vec<Property>], kind: &str) -> i64 {
+    rows.iter()
+        .find(|row| row[1] == Property::Text(kind.into()))
+        .and_then(|row| match row[4] {
+            Property::Integer(count) => Some(count),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn explain_returns_plan() {
+    let graph = chain();
+    let rows: Vec<(i64, String, i64, String)> = graph
+        .prepare("EXPLAIN MATCH (a:PERSON) -[:KNOWS]-> (b) RETURN b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert!(rows.iter().all(|row| row.0 == 0));
+    assert!(rows.iter().any(|row| row.1 == "step"));
+    assert!(rows.iter().any(|row| row.1 == "return"));
+    // there are fewer KNOWS edges than PERSON nodes
+    assert!(rows
+        .iter()
+        .any(|row| row.1 == "instruction" && row.3.starts_with("IterLabeledEdges")));
+}
+
+#[test]
+fn explain_binds_parameters() {
+    let graph = chain();
+    let stmt = graph
+        .prepare("EXPLAIN MATCH (a:PERSON) WHERE $p = 1 OR a.age > 30 RETURN a.name")
+        .unwrap();
+    let steps = |p: i64| -> Vec<String> {
+        stmt.query_map(&mut graph.txn().unwrap(), ("p", p), |m| {
+            Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+        })
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|(kind, _)| kind == "step")
+        .map(|(_, detail)| detail)
+        .collect()
+    };
+
+    // the condition holds, so the age is never compared
+    assert!(steps(1).iter().all(|step| !step.contains("Gt")));
+    assert!(steps(2).iter().any(|step| step.contains("Gt")));
+}
+
+#[test]
+fn explain_reports_unbound_conditions() {
+    let graph = chain();
+    let kinds = |query: &str| -> Vec<(i64, String)> {
+        graph
+            .prepare(query)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (("p", 1), ("q", 2)), |m| {
+                Ok((m.get(0)?, m.get(1)?))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|(_, kind): &(i64, String)| kind == "note")
+            .collect()
+    };
+
+    let query = "
+        EXPLAIN MATCH (a:PERSON)
+        WHERE ($p = 1 OR a.age > 1) AND ($p = 2 OR a.age > 2)
+            AND ($q = 1 OR a.age > 3) AND ($q = 2 OR a.age > 4)
+        RETURN a.name
+        ";
+    assert_eq!(kinds(query), vec![(0, "note".to_string())]);
+
+    // the queries of a union are bound one by one
+    let query = "
+        EXPLAIN MATCH (a:PERSON) WHERE ($p = 1 OR a.age > 1) AND ($q = 1 OR a.age > 2)
+        RETURN a.name
+        UNION
+        MATCH (a:PERSON) WHERE ($p = 2 OR a.age > 3) AND ($q = 2 OR a.age > 4)
+        RETURN a.name
+        ";
+    assert_eq!(kinds(query), Vec::new());
+}
+
+#[test]
+fn explain_does_not_execute() {
+    let graph = chain();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("EXPLAIN MATCH (a:PERSON) DELETE a")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let people = graph
+        .prepare("MATCH (a:PERSON) RETURN ID(a)")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<u64, _>(0))
+        .unwrap()
+        .count();
+    assert_eq!(people, 3);
+}
+
+#[test]
+fn explain_empty_plan_halts() {
+    let graph = chain();
+    let rows: Vec<(i64, String, i64, String)> = graph
+        .prepare("EXPLAIN MATCH (a:PERSON) WHERE 1 = 2 RETURN a.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let instructions: Vec<_> = rows.iter().filter(|row| row.1 == "instruction").collect();
+    assert_eq!(instructions.len(), 1);
+    assert_eq!(instructions[0].3, "Halt");
+    assert!(rows.iter().any(|row| row.1 == "return"));
+
+    let names = graph
+        .prepare("MATCH (a:PERSON) WHERE 1 = 2 RETURN a.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<String, _>(0))
+        .unwrap()
+        .count();
+    assert_eq!(names, 0);
+}
+
+#[test]
+fn explain_disconnected_join() {
+    let graph = chain();
+    let rows: Vec<(i64, String, i64, String)> = graph
+        .prepare("EXPLAIN MATCH (a:PERSON) MATCH (b:PERSON) WHERE a.name = b.name RETURN b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert!(rows
+        .iter()
+        .any(|row| row.1 == "instruction" && row.3.starts_with("IterJoin")));
+
+    let names = graph
+        .prepare("MATCH (a:PERSON) MATCH (b:PERSON) WHERE a.name = b.name RETURN b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<String, _>(0))
+        .unwrap()
+        .count();
+    assert_eq!(names, 3);
+}
+
+#[test]
+fn profile_counts_rows() {
+    let graph = chain();
+    let rows = rows(
+        &graph,
+        "PROFILE MATCH (a:PERSON) -[:KNOWS]-> (b:PERSON) RETURN b.name",
+    );
+
+    // both KNOWS edges are scanned, loading the nodes at their ends
+    assert_eq!(count(&rows, "rows"), 2);
+    assert_eq!(count(&rows, "nodes"), 4);
+    assert_eq!(count(&rows, "edges"), 2);
+    assert!(rows
+        .iter()
+        .any(|row| row[1] == Property::Text("instruction".into())
+            && row[4] != Property::Integer(0)));
+}
+
+#[test]
+fn profile_covers_unions() {
+    let graph = chain();
+    let rows = rows(
+        &graph,
+        "
+        PROFILE MATCH (a:PERSON) RETURN a.name
+        UNION ALL MATCH (a:PERSON) RETURN a.name
+        ",
+    );
+
+    assert_eq!(count(&rows, "rows"), 6);
+    assert!(rows.iter().any(|row| row[0] == Property::Integer(1)));
+}
@@ -0,0 +1,263 @@
+use cqlite::{Date, Graph, Property};
+
+fn create_test_graph() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE (:PERSON { name: 'Ada', age: 36 });
+            CREATE (:PERSON { name: 'Grace', age: 85 });
+            CREATE (:PERSON { name: 'Alan', age: 41.0 });
+            CREATE (:PERSON { age: 12 });
+            CREATE (:ROBOT { name: 'Ada' });
+            CREATE INDEX ON :PERSON(name);
+            CREATE INDEX ON :PERSON(age);
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn names(graph: &Graph, query: &str, params: &[(&str, Property)]) -> Vec<String> {
+    let stmt = graph.prepare(query).unwrap();
+    let mut txn = graph.txn().unwrap();
+    let mut names: Vec<String> = stmt
+        .query_map(&mut txn, params, |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    names
+}
+
+fn plan(graph: &Graph, query: &str) -> Vec<String> {
+    let stmt = graph.prepare(&format!("EXPLAIN {}", query)).unwrap();
+    let mut txn = graph.txn().unwrap();
+    stmt.query_map(&mut txn, (), |m| {
+        Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+    })
+    .unwrap()
+    .map(|row| row.unwrap())
+    .filter(|(kind, _)| kind == "step")
+    .map(|(_, detail)| detail)
+    .collect()
+}
+
+#[test]
+fn planner_uses_indexes() {
+    let graph = create_test_graph();
+    let steps = plan(
+        &graph,
+        "MATCH (p:PERSON) WHERE p.name = 'Ada' RETURN p.name",
+    );
+    assert_eq!(steps.len(), 1);
+    assert!(steps[0].starts_with("LoadIndexedNode"), "{:?}", steps);
+
+    let steps = plan(&graph, "MATCH (p:PERSON) WHERE $min < p.age RETURN p.name");
+    assert_eq!(steps.len(), 1);
+    assert!(steps[0].contains("lower: Excluded"), "{:?}", steps);
+
+    let steps = plan(&graph, "MATCH (r:ROBOT) WHERE r.name = 'Ada' RETURN r.name");
+    assert!(steps[0].starts_with("LoadLabeledNode"), "{:?}", steps);
+}
+
+#[test]
+fn indexed_lookups() {
+    let graph = create_test_graph();
+    let query = "MATCH (p:PERSON) WHERE p.name = $name RETURN p.name";
+    assert_eq!(
+        names(&graph, query, &[("name", "Ada".into())]),
+        vec!["Ada".to_string()]
+    );
+    assert!(names(&graph, query, &[("name", "Bob".into())]).is_empty());
+
+    // integers and reals are loosely equal
+    let query = "MATCH (p:PERSON) WHERE p.age = $age RETURN p.name";
+    assert_eq!(
+        names(&graph, query, &[("age", Property::Real(36.0))]),
+        vec!["Ada".to_string()]
+    );
+    assert_eq!(
+        names(&graph, query, &[("age", Property::Integer(41))]),
+        vec!["Alan".to_string()]
+    );
+}
+
+#[test]
+fn indexed_range_lookups() {
+    let graph = create_test_graph();
+    assert_eq!(
+        names(
+            &graph,
+            "MATCH (p:PERSON) WHERE p.age > 40 RETURN p.name",
+            &[]
+        ),
+        vec!["Alan".to_string(), "Grace".to_string()]
+    );
+    assert_eq!(
+        names(
+            &graph,
+            "MATCH (p:PERSON) WHERE 40 > p.age AND p.age > 20 RETURN p.name",
+            &[]
+        ),
+        vec!["Ada".to_string()]
+    );
+}
+
+#[test]
+fn indexes_are_maintained() {
+    let graph = create_test_graph();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            MATCH (p:PERSON) WHERE p.name = 'Ada' SET p.name = 'Ada Lovelace';
+            MATCH (p:PERSON) WHERE p.name = 'Alan' DELETE p;
+            CREATE (:PERSON { name: 'Alan', age: 7 });
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+
+    let query = "MATCH (p:PERSON) WHERE p.name = $name RETURN p.age";
+    let ages = |name: &str| -> Vec<i64> {
+        graph
+            .prepare(query)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), ("name", name), |m| m.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    assert!(ages("Ada").is_empty());
+    assert_eq!(ages("Ada Lovelace"), vec![36]);
+    assert_eq!(ages("Alan"), vec![7]);
+}
+
+#[test]
+fn dropped_indexes_fall_back_to_labels() {
+    let graph = create_test_graph();
+    let query = "MATCH (p:PERSON) WHERE p.name = 'Grace' RETURN p.name";
+    let stmt = graph.prepare(query).unwrap();
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("DROP INDEX ON :PERSON(name)")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    // statements prepared before the index was dropped still work
+    let names: Vec<String> = stmt
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["Grace".to_string()]);
+    assert!(plan(&graph, query)[0].starts_with("LoadLabeledNode"));
+}
+
+#[test]
+fn indexes_in_the_same_transaction() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE INDEX ON :PERSON(name);
+            CREATE (:PERSON { name: 'Ada' });
+            CREATE INDEX ON :PERSON(name);
+            ",
+        )
+        .unwrap();
+    let names: Vec<String> = graph
+        .prepare("MATCH (p:PERSON) WHERE p.name = 'Ada' RETURN p.name")
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["Ada".to_string()]);
+}
+
+#[test]
+fn indexed_bounded_ranges() {
+    let graph = create_test_graph();
+    let query = "MATCH (p:PERSON) WHERE p.age >= $min AND p.age <= $max RETURN p.name";
+    let steps = plan(&graph, query);
+    assert_eq!(steps.len(), 1);
+    assert!(steps[0].contains("lower: Included"), "{:?}", steps);
+    assert!(steps[0].contains("upper: Included"), "{:?}", steps);
+
+    let params = [("min", Property::Integer(36)), ("max", Property::Real(41.0))];
+    assert_eq!(
+        names(&graph, query, &params),
+        vec!["Ada".to_string(), "Alan".to_string()]
+    );
+    let params = [("min", Property::Real(36.5)), ("max", Property::Integer(85))];
+    assert_eq!(
+        names(&graph, query, &params),
+        vec!["Alan".to_string(), "Grace".to_string()]
+    );
+    let params = [("min", Property::Integer(36)), ("max", Property::Text("Z".into()))];
+    assert!(names(&graph, query, &params).is_empty());
+
+    let query = "MATCH (p:PERSON) WHERE p.age BETWEEN $min AND $max RETURN p.name";
+    let steps = plan(&graph, query);
+    assert_eq!(steps.len(), 1);
+    assert!(steps[0].contains("lower: Included"), "{:?}", steps);
+    assert!(steps[0].contains("upper: Included"), "{:?}", steps);
+    let params = [("min", Property::Integer(36)), ("max", Property::Integer(41))];
+    assert_eq!(
+        names(&graph, query, &params),
+        vec!["Ada".to_string(), "Alan".to_string()]
+    );
+}
+
+#[test]
+fn indexed_ranges_are_ordered() {
+    let graph = create_test_graph();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE (:EVENT { name: 'launch', on: date('2024-03-01') });
+            CREATE (:EVENT { name: 'draft', on: date('1999-12-31') });
+            CREATE (:EVENT { name: 'review', on: date('2024-02-29') });
+            CREATE (:EVENT { name: 'undated' });
+            CREATE INDEX ON :EVENT(on);
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+
+    let unsorted = |query: &str| -> Vec<String> {
+        let date = Date::from_ymd(2000, 1, 1).unwrap();
+        graph
+            .prepare(query)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), ("date", date), |m| m.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    assert_eq!(
+        unsorted("MATCH (p:PERSON) WHERE p.name >= 'A' RETURN p.name"),
+        vec!["Ada".to_string(), "Alan".to_string(), "Grace".to_string()]
+    );
+    assert_eq!(
+        unsorted("MATCH (e:EVENT) WHERE e.on > $date RETURN e.name"),
+        vec!["review".to_string(), "launch".to_string()]
+    );
+    assert_eq!(
+        unsorted("MATCH (e:EVENT) WHERE e.on <= $date RETURN e.name"),
+        vec!["draft".to_string()]
+    );
+}
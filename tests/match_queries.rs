@@ -191,9 +191,75 @@ fn match_long_path() {
         .collect::<Result<_, _>>()
         .unwrap();
     nodes.sort_unstable();
+    assert_eq!(nodes, vec![(2, 0, 1, 3)]);
+}
+
+#[test]
+fn match_long_path_with_repeated_edges() {
+    let graph = create_test_graph();
+
+    let mut nodes: Vec<(u64, u64, u64, u64)> = graph
+        .prepare(
+            "
+            MATCH REPEATABLE ELEMENTS (s) <- (p) -> (c) -> (j)
+            RETURN ID(s), ID(p), ID(c), ID(j)
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    nodes.sort_unstable();
     assert_eq!(nodes, vec![(1, 0, 1, 3), (2, 0, 1, 3)]);
 }
 
+#[test]
+fn match_undirected_path_with_different_edges() {
+    let graph = create_test_graph();
+
+    let stmt = graph
+        .prepare("MATCH (a) -[e1]- (b) -[e2]- (c) WHERE ID(a) = 0 RETURN ID(e1), ID(e2)")
+        .unwrap();
+    let edges: Vec<(u64, u64)> = stmt
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(!edges.is_empty());
+    assert!(edges.iter().all(|(e1, e2)| e1 != e2));
+
+    let stmt = graph
+        .prepare(
+            "
+            MATCH DIFFERENT RELATIONSHIPS (a) -[e]- (b) -[e]- (c)
+            RETURN ID(a)
+            ",
+        )
+        .unwrap();
+    let mut txn = graph.txn().unwrap();
+    let mut query = stmt.query(&mut txn, ()).unwrap();
+    assert!(query.step().unwrap().is_none());
+
+    let walks: Vec<(u64, u64)> = graph
+        .prepare(
+            "
+            MATCH REPEATABLE ELEMENTS (a) -[e]- (b) -[e]- (c)
+            WHERE ID(a) = 0
+            RETURN ID(b), ID(c)
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(!walks.is_empty());
+    assert!(walks.iter().all(|&(_, c)| c == 0));
+}
+
 #[test]
 fn match_labeled_nodes() {
     let graph = create_test_graph();
@@ -318,3 +384,40 @@ fn match_nodes_with_label() {
     nodes.sort_unstable();
     assert_eq!(nodes, [0, 1]);
 }
+
+#[test]
+fn match_edges_with_label() {
+    let graph = create_test_graph();
+
+    let neighbours = |query: &str| {
+        let mut nodes: Vec<u64> = graph
+            .prepare(query)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+            .unwrap()
+            .collect::<Result<Vec<u64>, _>>()
+            .unwrap();
+        nodes.sort_unstable();
+        nodes
+    };
+    assert_eq!(
+        neighbours("MATCH (a) -[:KNOWS]-> (b) WHERE ID(a) = 0 RETURN ID(b)"),
+        [1]
+    );
+    assert_eq!(
+        neighbours("MATCH (a) <-[:KNOWS]- (b) WHERE ID(a) = 1 RETURN ID(b)"),
+        [0]
+    );
+    assert_eq!(
+        neighbours("MATCH (a) -[:IS_A]- (b) WHERE ID(a) = 0 RETURN ID(b)"),
+        [2]
+    );
+    assert_eq!(
+        neighbours("MATCH (a) <-[:IS_A]- (b) WHERE ID(a) = 0 RETURN ID(b)"),
+        Vec::<u64>::new()
+    );
+    assert_eq!(
+        neighbours("MATCH (a:PERSON) -[:IS_A]-> (b) RETURN ID(b)"),
+        [2, 3]
+    );
+}
@@ -1,4 +1,4 @@
-use cqlite::Graph;
+use cqlite::{Graph, Property};
 
 fn create_test_graph() -> Graph {
     let graph = Graph::open_anon().unwrap();
@@ -330,7 +330,8 @@ fn match_long_path_with_id_constraint() {
         .collect::<Result<_, _>>()
         .unwrap();
     paths.sort_unstable();
-    assert_eq!(paths, [(0, 3, 2), (2, 0, 2), (2, 3, 2)]);
+    // walking back along the same edge is not a match
+    assert_eq!(paths, [(0, 3, 2)]);
 
     let paths: Vec<(u64, u64, u64)> = graph
         .prepare(
@@ -372,3 +373,160 @@ fn match_short_path_with_id_constraint() {
     paths.sort_unstable();
     assert_eq!(paths, [(0, 2), (3, 2)]);
 }
+
+#[test]
+fn match_where_split_conditions() {
+    let graph = create_test_graph();
+
+    let mut names = graph
+        .prepare(
+            "
+            MATCH (a:PERSON) -[:IS_A]-> (b)
+            WHERE NOT (a.fictional = FALSE OR b.permanent = TRUE)
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Peter Parker"]);
+
+    let mut names = graph
+        .prepare(
+            "
+            MATCH (a:PERSON) -[:IS_A]-> (b)
+            WHERE (a.age > 30 AND b.salary > 0) OR a.fictional = FALSE
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Clark Kent", "Stacey"]);
+}
+
+#[test]
+fn match_where_constant_conditions() {
+    let graph = create_test_graph();
+    let count = |query: &str, params: Vec<(&str, i64)>| {
+        let mut txn = graph.txn().unwrap();
+        let stmt = graph.prepare(query).unwrap();
+        let mut matches = match params.as_slice() {
+            [] => stmt.query(&mut txn, ()).unwrap(),
+            [param] => stmt.query(&mut txn, *param).unwrap(),
+            _ => unreachable!(),
+        };
+        let mut count = 0;
+        while matches.step().unwrap().is_some() {
+            count += 1;
+        }
+        count
+    };
+
+    assert_eq!(count("MATCH (a:PERSON) WHERE 1 = 1 RETURN a", vec![]), 3);
+    assert_eq!(count("MATCH (a:PERSON) WHERE 1 = 2 RETURN a", vec![]), 0);
+    assert_eq!(count("MATCH (a:PERSON) WHERE 1 < 2 OR a.age > 30 RETURN a", vec![]), 3);
+    assert_eq!(count("MATCH (a) WHERE ID(a) = 0 AND ID(a) = 1 RETURN a", vec![]), 0);
+    assert_eq!(count("MATCH (a) WHERE ID(a) = 0 AND ID(a) = 0 RETURN a", vec![]), 1);
+
+    let query = "MATCH (a:PERSON) WHERE $p = $p RETURN a";
+    assert_eq!(count(query, vec![("p", 1)]), 3);
+    let query = "MATCH (a:PERSON) WHERE $p = 1 OR a.age > 30 RETURN a";
+    assert_eq!(count(query, vec![("p", 1)]), 3);
+    assert_eq!(count(query, vec![("p", 2)]), 1);
+    let query = "MATCH (a:PERSON) WHERE NOT $p = 1 RETURN a.name";
+    assert_eq!(count(query, vec![("p", 1)]), 0);
+    assert_eq!(count(query, vec![("p", 2)]), 3);
+}
+
+#[test]
+fn match_where_disconnected_equal() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (:USER { org: 1, login: 'ada' })
+            CREATE (:USER { org: 1, login: 'bob' })
+            CREATE (:USER { org: 2, login: 'cyd' })
+            CREATE (:TICKET { owner: 'ada', priority: 1 })
+            CREATE (:TICKET { owner: 'ada', priority: 2.0 })
+            CREATE (:TICKET { owner: 'cyd', priority: 1.0 })
+            CREATE (:TICKET { priority: 3 })
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let pairs = |query: &str| {
+        let mut pairs: Vec<(String, u64)> = graph
+            .prepare(query)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), ("o", 1), |m| {
+                Ok((m.get(0)?, m.get(1)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        pairs.sort();
+        pairs
+    };
+    assert_eq!(
+        pairs(
+            "
+            MATCH (a:USER {org: $o}) MATCH (b:TICKET) WHERE b.owner = a.login
+            RETURN a.login, ID(b)
+            "
+        ),
+        vec![("ada".to_string(), 3), ("ada".to_string(), 4)],
+    );
+    assert_eq!(
+        pairs(
+            "
+            MATCH (a:TICKET) MATCH (b:TICKET) WHERE a.priority = b.priority AND a.owner <> b.owner
+            RETURN a.owner, ID(b)
+            "
+        ),
+        vec![("ada".to_string(), 5), ("cyd".to_string(), 3)],
+    );
+}
+
+#[test]
+fn match_where_disconnected_equal_lists() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let create = graph.prepare("CREATE (:USER { login: $login, tags: $tags })").unwrap();
+    let tags = |tags: Vec<Property>| Property::List(tags);
+    create
+        .execute(&mut txn, (("login", "ada"), ("tags", tags(vec![1.into(), "x".into()]))))
+        .unwrap();
+    create
+        .execute(&mut txn, (("login", "bob"), ("tags", tags(vec![2.into(), "x".into()]))))
+        .unwrap();
+    let create = graph.prepare("CREATE (:TICKET { tags: $tags })").unwrap();
+    for ticket in [
+        vec![1.into(), "x".into()],
+        vec![2.into(), "x".into(), 3.into()],
+        vec![],
+    ] {
+        create.execute(&mut txn, ("tags", tags(ticket))).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let pairs: Vec<(String, u64)> = graph
+        .prepare("MATCH (a:USER) MATCH (b:TICKET) WHERE b.tags = a.tags RETURN a.login, ID(b)")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(pairs, vec![("ada".to_string(), 2)]);
+}
@@ -81,3 +81,33 @@ fn create_undirected_edge() {
         Error::Syntax { .. }
     );
 }
+
+#[test]
+fn use_node_as_path() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("MATCH (a) -> (b) RETURN length(a)"),
+        Error::IdentifierIsNotPath(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH p = (a) -> (b) RETURN ID(p)"),
+        Error::IdentifierIsNotNode(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH p = (a) MATCH p = (b)"),
+        Error::IdentifierExists(_)
+    );
+}
+
+#[test]
+fn store_node_as_property() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("MATCH (a) MATCH (b) SET a.friend = b"),
+        Error::TypeMismatch
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) -[e]-> (b) CREATE (c:COPY { edge: e })"),
+        Error::TypeMismatch
+    );
+}
@@ -1,4 +1,4 @@
-use cqlite::{Error, Graph, Property};
+use cqlite::{Edge, Error, Graph, Node, Path, Property};
 
 #[macro_use]
 mod common;
@@ -201,3 +201,156 @@ fn return_out_of_bounds() {
     assert_eq!(m.get::<i64, _>(1).unwrap(), 42);
     assert_err!(m.get::<Property, _>(2), Error::IndexOutOfBounds);
 }
+
+#[test]
+fn return_named_path() {
+    let graph = Graph::open_anon().unwrap();
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Alice' })
+            CREATE (b:PERSON { name: 'Bob' })
+            CREATE (c:CITY { name: 'Paris' })
+            CREATE (a) -[:KNOWS]-> (b)
+            CREATE (b) -[:LIVES_IN]-> (c)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let stmt = graph
+        .prepare(
+            "
+            MATCH p = (a:PERSON) -[:KNOWS]-> (b:PERSON) -[e:LIVES_IN]-> (c:CITY)
+            RETURN p, length(p), nodes(p), relationships(p), ID(a), ID(b), ID(c), ID(e)
+            ",
+        )
+        .unwrap();
+    let mut txn = graph.txn().unwrap();
+    let mut query = stmt.query(&mut txn, ()).unwrap();
+    let m = query.step().unwrap().unwrap();
+
+    let path: Path = m.get(0).unwrap();
+    let nodes: Vec<u64> = vec![m.get(4).unwrap(), m.get(5).unwrap(), m.get(6).unwrap()];
+    assert_eq!(path.nodes(), nodes.as_slice());
+    assert_eq!(path.edges().len(), 2);
+    assert_eq!(path.edges()[1], m.get::<u64, _>(7).unwrap());
+    assert_eq!(m.get::<i64, _>(1).unwrap(), 2);
+    assert_eq!(
+        m.get::<Vec<Property>, _>(2).unwrap(),
+        nodes.into_iter().map(Property::Id).collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        m.get::<Vec<Property>, _>(3).unwrap(),
+        path.edges().iter().copied().map(Property::Id).collect::<Vec<_>>(),
+    );
+    assert!(query.step().unwrap().is_none());
+}
+
+#[test]
+fn return_nodes_and_edges() {
+    let graph = Graph::open_anon().unwrap();
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Alice', age: 42 })
+            CREATE (b:PERSON { name: 'Bob' })
+            CREATE (a) -[:KNOWS { since: 2001 }]-> (b)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let stmt = graph
+        .prepare("MATCH (a:PERSON) -[e:KNOWS]-> (b) RETURN a, e, ID(a), ID(b)")
+        .unwrap();
+    let mut txn = graph.txn().unwrap();
+    let mut query = stmt.query(&mut txn, ()).unwrap();
+    let m = query.step().unwrap().unwrap();
+
+    let node: Node = m.get(0).unwrap();
+    assert_eq!(node.id(), m.get::<u64, _>(2).unwrap());
+    assert_eq!(node.label(), "PERSON");
+    assert_eq!(
+        node.properties().collect::<Vec<_>>(),
+        vec![
+            ("age", Property::Integer(42)),
+            ("name", Property::Text("Alice".into()))
+        ]
+    );
+
+    let edge: Edge = m.get(1).unwrap();
+    assert_eq!(edge.label(), "KNOWS");
+    assert_eq!(edge.origin(), node.id());
+    assert_eq!(edge.target(), m.get::<u64, _>(3).unwrap());
+    assert_eq!(
+        edge.properties().collect::<Vec<_>>(),
+        vec![("since", Property::Integer(2001))]
+    );
+    assert!(query.step().unwrap().is_none());
+}
+
+#[test]
+fn return_updated_node() {
+    let graph = Graph::open_anon().unwrap();
+
+    let mut txn = graph.mut_txn().unwrap();
+    let node: Node = graph
+        .prepare("CREATE (a:PERSON { name: 'Alice' }) SET a.age = 42 RETURN a")
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        node.properties().collect::<Vec<_>>(),
+        vec![
+            ("age", Property::Integer(42)),
+            ("name", Property::Text("Alice".into()))
+        ]
+    );
+}
+
+#[test]
+fn return_all_variables() {
+    let graph = Graph::open_anon().unwrap();
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Alice' })
+            CREATE (b:PERSON { name: 'Bob' })
+            CREATE (a) -[:KNOWS]-> (b)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let stmt = graph
+        .prepare("MATCH p = (b:PERSON) -[a]-> (:PERSON) RETURN *, b.name")
+        .unwrap();
+    let mut txn = graph.txn().unwrap();
+    let mut query = stmt.query(&mut txn, ()).unwrap();
+    let m = query.step().unwrap().unwrap();
+
+    let edge: Edge = m.get(0).unwrap();
+    let node: Node = m.get(1).unwrap();
+    let path: Path = m.get(2).unwrap();
+    assert_eq!(edge.label(), "KNOWS");
+    assert_eq!(node.properties().count(), 1);
+    assert_eq!(path.edges(), &[edge.id()]);
+    assert_eq!(m.get::<String, _>(3).unwrap(), "Alice");
+    assert_err!(m.get::<Property, _>(4), Error::IndexOutOfBounds);
+}
@@ -0,0 +1,159 @@
+use cqlite::{Error, Graph};
+
+#[macro_use]
+mod common;
+
+fn create_test_graph() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE CONSTRAINT ON (p:PERSON) ASSERT exists(p.name);
+            CREATE CONSTRAINT ON (p:PERSON) ASSERT p.age IS :: INTEGER;
+            CREATE CONSTRAINT ON (c:COMPANY) ASSERT keys(c) IN ['name', 'founded'];
+            CREATE CONSTRAINT ON () -[e:WORKS_AT]-> () ASSERT exists(e.since);
+            CREATE CONSTRAINT ON (:PERSON) -[:WORKS_AT]-> (:COMPANY);
+            CREATE (:PERSON { name: 'Ada', age: 36 });
+            CREATE (:COMPANY { name: 'Analytical Engines' });
+            ",
+        )
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn execute(graph: &Graph, query: &str) -> Result<(), Error> {
+    let mut txn = graph.mut_txn()?;
+    graph.prepare(query)?.execute(&mut txn, ())?;
+    txn.commit()
+}
+
+#[test]
+fn property_existence() {
+    let graph = create_test_graph();
+    assert_err!(
+        execute(&graph, "CREATE (:PERSON { age: 42 })"),
+        Error::ConstraintViolation { .. }
+    );
+    assert_err!(
+        execute(&graph, "MATCH (p:PERSON) SET p.name = NULL"),
+        Error::ConstraintViolation { .. }
+    );
+    // properties may be set later in the same statement
+    execute(&graph, "CREATE (p:PERSON) SET p.name = 'Grace'").unwrap();
+    execute(&graph, "CREATE (:ROBOT { age: 42 })").unwrap();
+}
+
+#[test]
+fn property_types() {
+    let graph = create_test_graph();
+    let error = execute(&graph, "CREATE (:PERSON { name: 'Alan', age: 41.5 })");
+    match error {
+        Err(Error::ConstraintViolation { label, key, value }) => {
+            assert_eq!(label, "PERSON");
+            assert_eq!(key, "age");
+            assert_eq!(value, "41.5");
+        }
+        _ => panic!("unexpected {:?}", error),
+    }
+    assert_err!(
+        execute(&graph, "MATCH (p:PERSON) SET p.age = '36'"),
+        Error::ConstraintViolation { .. }
+    );
+    execute(&graph, "CREATE (:PERSON { name: 'Alan' })").unwrap();
+    execute(&graph, "CREATE (:PERSON { name: 'Grace', age: 85 })").unwrap();
+}
+
+#[test]
+fn allowed_properties() {
+    let graph = create_test_graph();
+    assert_err!(
+        execute(&graph, "CREATE (:COMPANY { name: 'Acme', ceo: 'Wile' })"),
+        Error::ConstraintViolation { .. }
+    );
+    execute(&graph, "CREATE (:COMPANY { name: 'Acme', founded: 1949 })").unwrap();
+    execute(&graph, "CREATE (:COMPANY)").unwrap();
+}
+
+#[test]
+fn edge_rules() {
+    let graph = create_test_graph();
+    execute(
+        &graph,
+        "
+        MATCH (p:PERSON) MATCH (c:COMPANY)
+        CREATE (p) -[:WORKS_AT { since: 1843 }]-> (c)
+        ",
+    )
+    .unwrap();
+    assert_err!(
+        execute(
+            &graph,
+            "MATCH (p:PERSON) MATCH (c:COMPANY) CREATE (p) -[:WORKS_AT]-> (c)",
+        ),
+        Error::ConstraintViolation { .. }
+    );
+    let error = execute(
+        &graph,
+        "MATCH (p:PERSON) MATCH (c:COMPANY) CREATE (c) -[:WORKS_AT { since: 1843 }]-> (p)",
+    );
+    match error {
+        Err(Error::ConstraintViolation { label, value, .. }) => {
+            assert_eq!(label, "WORKS_AT");
+            assert_eq!(value, "(:COMPANY) -[:WORKS_AT]-> (:PERSON)");
+        }
+        _ => panic!("unexpected {:?}", error),
+    }
+
+    // more endpoint rules allow more pairs of labels
+    execute(
+        &graph,
+        "CREATE CONSTRAINT ON (:COMPANY) -[:WORKS_AT]-> (:PERSON)",
+    )
+    .unwrap();
+    execute(
+        &graph,
+        "MATCH (p:PERSON) MATCH (c:COMPANY) CREATE (c) -[:WORKS_AT { since: 1843 }]-> (p)",
+    )
+    .unwrap();
+}
+
+#[test]
+fn validate_existing_data() {
+    let graph = create_test_graph();
+    graph.validate().unwrap();
+
+    execute(&graph, "CREATE (:ROBOT { name: 'R2' })").unwrap();
+    execute(&graph, "CREATE (:ROBOT)").unwrap();
+    execute(
+        &graph,
+        "CREATE CONSTRAINT ON (r:ROBOT) ASSERT exists(r.name)",
+    )
+    .unwrap();
+    assert_err!(graph.validate(), Error::ConstraintViolation { .. });
+
+    execute(&graph, "MATCH (r:ROBOT) SET r.name = 'unnamed'").unwrap();
+    graph.validate().unwrap();
+
+    // unique constraints check existing data when they are created
+    let error = execute(
+        &graph,
+        "CREATE CONSTRAINT ON (r:ROBOT) ASSERT r.name IS UNIQUE",
+    );
+    assert_err!(error, Error::ConstraintViolation { .. });
+    graph.validate().unwrap();
+}
+
+#[test]
+fn drop_rules() {
+    let graph = create_test_graph();
+    execute(
+        &graph,
+        "DROP CONSTRAINT ON (p:PERSON) ASSERT exists(p.name)",
+    )
+    .unwrap();
+    execute(&graph, "CREATE (:PERSON { age: 42 })").unwrap();
+    graph.validate().unwrap();
+}
@@ -0,0 +1,376 @@
+use cqlite::{Crs, Error, Graph, Point, Property};
+
+#[macro_use]
+mod common;
+
+fn cartesian(x: f64, y: f64) -> Point {
+    Point::new(Crs::Cartesian, x, y, None).unwrap()
+}
+
+fn wgs84(longitude: f64, latitude: f64) -> Point {
+    Point::new(Crs::Wgs84, longitude, latitude, None).unwrap()
+}
+
+fn create_test_graph() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (:SITE { name: 'london', at: point({longitude: -0.1276, latitude: 51.5072}) })
+            CREATE (:SITE { name: 'paris', at: point({longitude: 2.3522, latitude: 48.8566}) })
+            CREATE (:SITE { name: 'suva', at: point({longitude: 178.4419, latitude: -18.1416}) })
+            CREATE (:SITE { name: 'desk', at: point({x: 3, y: 4}) })
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+#[test]
+fn create_and_return_points() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let points: Vec<(Point, Point, Point)> = graph
+        .prepare(
+            "
+            CREATE (s:SITE { flat: point({x: 1, y: 2.5}) })
+            SET s.tall = point({x: 1, y: 2, z: 3, crs: 'cartesian-3d'})
+            SET s.globe = point({longitude: 12.5, latitude: 41.9, height: 21, srid: 4979})
+            RETURN s.flat, s.tall, s.globe
+            ",
+        )
+        .unwrap()
+        .query_map(&mut txn, (), |m| Ok((m.get(0)?, m.get(1)?, m.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    txn.commit().unwrap();
+
+    let (flat, tall, globe) = points[0];
+    assert_eq!(flat, cartesian(1.0, 2.5));
+    assert_eq!(
+        tall,
+        Point::new(Crs::Cartesian3D, 1.0, 2.0, Some(3.0)).unwrap()
+    );
+    assert_eq!(globe.crs(), Crs::Wgs843D);
+    assert_eq!(globe.z(), Some(21.0));
+
+    // values are read back from disk unchanged
+    let stored: Vec<Point> = graph
+        .prepare("MATCH (s:SITE) RETURN s.globe")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(stored, vec![globe]);
+}
+
+#[test]
+fn point_components() {
+    let graph = create_test_graph();
+
+    let components: Vec<(f64, f64, String, i64, Option<f64>)> = graph
+        .prepare(
+            "
+            MATCH (s:SITE { name: 'paris' })
+            RETURN s.at.longitude, s.at.y, s.at.crs, s.at.srid, s.at.height
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?, m.get(4)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        components,
+        vec![(2.3522, 48.8566, "wgs-84".to_string(), 4326, None)]
+    );
+
+    let components: Vec<(f64, Option<f64>)> = graph
+        .prepare("MATCH (s:SITE { name: 'desk' }) RETURN s.at.x, s.at.longitude")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(components, vec![(3.0, None)]);
+}
+
+#[test]
+fn distance_between_points() {
+    let graph = create_test_graph();
+
+    let distances: Vec<(Option<f64>, Option<f64>)> = graph
+        .prepare(
+            "
+            MATCH (a:SITE { name: 'london' })
+            MATCH (b:SITE { name: 'paris' })
+            MATCH (c:SITE { name: 'desk' })
+            RETURN distance(a.at, b.at), distance(a.at, c.at)
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let (london_paris, mixed) = distances[0];
+    assert!((london_paris.unwrap() - 343_900.0).abs() < 1_000.0);
+    assert_eq!(mixed, None);
+
+    let mut names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (s:SITE)
+            WHERE point.distance(s.at, $center) < 500000.0
+            RETURN s.name
+            ",
+        )
+        .unwrap()
+        .query_map(
+            &mut graph.txn().unwrap(),
+            ("center", wgs84(1.0, 50.0)),
+            |m| m.get(0),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["london", "paris"]);
+
+    let distance: f64 = graph
+        .prepare("RETURN distance(point({x: 0, y: 0, z: 0}), point({x: 2, y: 3, z: 6}))")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(distance, 7.0);
+}
+
+#[test]
+fn points_within_bounding_box() {
+    let graph = create_test_graph();
+    let query = graph
+        .prepare("MATCH (s:SITE) WHERE point.withinBBox(s.at, $ll, $ur) RETURN s.name")
+        .unwrap();
+
+    let mut names: Vec<String> = query
+        .query_map(
+            &mut graph.txn().unwrap(),
+            (
+                ("ll", wgs84(-10.0, 45.0)),
+                ("ur", wgs84(10.0, 55.0)),
+            ),
+            |m| m.get(0),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["london", "paris"]);
+
+    // the box crosses the 180th meridian
+    let names: Vec<String> = query
+        .query_map(
+            &mut graph.txn().unwrap(),
+            (
+                ("ll", wgs84(170.0, -30.0)),
+                ("ur", wgs84(-170.0, 0.0)),
+            ),
+            |m| m.get(0),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["suva"]);
+
+    let names: Vec<String> = query
+        .query_map(
+            &mut graph.txn().unwrap(),
+            (
+                ("ll", cartesian(0.0, 0.0)),
+                ("ur", cartesian(5.0, 5.0)),
+            ),
+            |m| m.get(0),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["desk"]);
+}
+
+#[test]
+fn spatial_index() {
+    let graph = create_test_graph();
+    let mut txn = graph.mut_txn().unwrap();
+    let create = graph
+        .prepare("CREATE (:SITE { name: 'grid', at: $at })")
+        .unwrap();
+    for x in -20..=20 {
+        for y in -20..=20 {
+            let at = cartesian(x as f64 / 2.0, y as f64 / 2.0);
+            create.execute(&mut txn, ("at", at)).unwrap();
+        }
+    }
+    graph
+        .prepare("CREATE INDEX ON :SITE(at)")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    let query = |query: &str, params: &[(&str, Property)]| -> (Vec<String>, Vec<String>) {
+        let stmt = graph.prepare(query).unwrap();
+        let mut txn = graph.txn().unwrap();
+        let mut names: Vec<String> = stmt
+            .query_map(&mut txn, params, |m| m.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        names.sort();
+        let explain = graph.prepare(&format!("EXPLAIN {}", query)).unwrap();
+        let steps = explain
+            .query_map(&mut txn, params, |m| {
+                Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|(kind, _)| kind == "step")
+            .map(|(_, detail)| detail)
+            .collect();
+        (names, steps)
+    };
+    let near = "MATCH (s:SITE) WHERE distance(s.at, $center) <= $radius RETURN s.name";
+    let within = "MATCH (s:SITE) WHERE point.withinBBox(s.at, $ll, $ur) RETURN s.name";
+
+    let (names, steps) = query(
+        near,
+        &[
+            ("center", Property::Point(cartesian(3.0, 3.0))),
+            ("radius", Property::Real(1.0)),
+        ],
+    );
+    assert!(steps[0].starts_with("LoadSpatialNode"), "{:?}", steps);
+    // 13 grid points and the desk are at most 1 away
+    assert_eq!(names.len(), 14);
+    assert_eq!(names.iter().filter(|name| *name == "desk").count(), 1);
+    // the index skips most of the 1685 sites
+    let loaded: i64 = graph
+        .prepare(&format!("PROFILE {}", near))
+        .unwrap()
+        .query_map(
+            &mut graph.txn().unwrap(),
+            (("center", cartesian(3.0, 3.0)), ("radius", 1.0)),
+            |m| Ok((m.get::<String, _>(1)?, m.get::<Property, _>(4)?)),
+        )
+        .unwrap()
+        .map(Result::unwrap)
+        .find_map(|(kind, count)| match (kind.as_str(), count) {
+            ("nodes", Property::Integer(count)) => Some(count),
+            _ => None,
+        })
+        .unwrap();
+    assert!(loaded < 100, "{}", loaded);
+
+    let (names, steps) = query(
+        within,
+        &[
+            ("ll", Property::Point(cartesian(-10.0, -0.2))),
+            ("ur", Property::Point(cartesian(-9.5, 0.2))),
+        ],
+    );
+    assert!(steps[0].starts_with("LoadSpatialNode"), "{:?}", steps);
+    assert_eq!(names, vec!["grid", "grid"]);
+
+    // geographic regions which cross the 180th meridian
+    let (names, _) = query(
+        near,
+        &[
+            ("center", Property::Point(wgs84(-179.5, -18.0))),
+            ("radius", Property::Integer(300_000)),
+        ],
+    );
+    assert_eq!(names, vec!["suva"]);
+    let (names, _) = query(
+        within,
+        &[
+            ("ll", Property::Point(wgs84(170.0, -30.0))),
+            ("ur", Property::Point(wgs84(-170.0, 0.0))),
+        ],
+    );
+    assert_eq!(names, vec!["suva"]);
+    let (names, _) = query(
+        near,
+        &[
+            ("center", Property::Point(wgs84(-0.1276, 51.5072))),
+            ("radius", Property::Real(400_000.0)),
+        ],
+    );
+    assert_eq!(names, vec!["london", "paris"]);
+
+    // regions in other systems contain no points
+    let (names, _) = query(
+        within,
+        &[
+            ("ll", Property::Point(wgs84(-1.0, -1.0))),
+            ("ur", Property::Point(cartesian(1.0, 1.0))),
+        ],
+    );
+    assert!(names.is_empty());
+}
+
+#[test]
+fn spatial_errors() {
+    let graph = Graph::open_anon().unwrap();
+    let run = |query: &str| -> Result<Property, Error> {
+        graph
+            .prepare(query)?
+            .query_map(&mut graph.txn()?, (), |m| m.get(0))?
+            .next()
+            .unwrap()
+    };
+
+    assert_err!(
+        graph.prepare("RETURN point({x: 1, w: 2})"),
+        Error::InvalidArguments(_)
+    );
+    assert_err!(graph.prepare("RETURN {x: 1}"), Error::TypeMismatch);
+    assert_err!(run("RETURN point({x: 1})"), Error::InvalidArguments(_));
+    assert_err!(
+        run("RETURN point({x: 1, latitude: 2})"),
+        Error::InvalidArguments(_)
+    );
+    assert_err!(
+        run("RETURN point({longitude: 1, latitude: 91})"),
+        Error::InvalidArguments(_)
+    );
+    assert_err!(
+        run("RETURN point({longitude: 181, latitude: 1})"),
+        Error::InvalidArguments(_)
+    );
+    assert_err!(
+        run("RETURN point({x: 1, y: 2, crs: 'mars'})"),
+        Error::InvalidArguments(_)
+    );
+    assert_err!(run("RETURN point({x: 'a', y: 2})"), Error::TypeMismatch);
+    assert_eq!(
+        run("RETURN point({x: 1, y: $missing})").unwrap(),
+        Property::Null
+    );
+    assert_eq!(
+        run("RETURN distance(point({x: 1, y: 2}), NULL)").unwrap(),
+        Property::Null
+    );
+}
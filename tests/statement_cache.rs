@@ -0,0 +1,99 @@
+use cqlite::{Graph, Property};
+
+fn steps(graph: &Graph, query: &str) -> Vec<String> {
+    graph
+        .prepare_cached(query)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+        })
+        .unwrap()
+        .filter_map(|row| match row.unwrap() {
+            (kind, detail) if kind == "step" => Some(detail),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn reuse_cached_statements() {
+    let graph = Graph::open_anon().unwrap().with_statement_cache(1);
+    let mut txn = graph.mut_txn().unwrap();
+    for num in 0..3 {
+        graph
+            .prepare_cached("CREATE (:NUMBER { num: $num })")
+            .unwrap()
+            .execute(&mut txn, ("num", num))
+            .unwrap();
+        // evicts the other statement
+        graph
+            .prepare_cached("CREATE (:OTHER)")
+            .unwrap()
+            .execute(&mut txn, ())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let mut nums: Vec<i64> = graph
+        .prepare_cached("MATCH (n:NUMBER) RETURN n.num")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    nums.sort_unstable();
+    assert_eq!(nums, vec![0, 1, 2]);
+
+    assert!(graph.prepare_cached("MATCH (n RETURN n").is_err());
+    graph.clear_statement_cache().unwrap();
+}
+
+#[test]
+fn replan_cached_statements_after_schema_changes() {
+    let graph = Graph::open_anon().unwrap();
+    let query = "EXPLAIN MATCH (p:PERSON) WHERE p.name = 'Ada' RETURN p";
+    assert!(steps(&graph, query)[0].starts_with("LoadLabeledNode"));
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("CREATE INDEX ON :PERSON(name)")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    // not visible before the index is committed
+    assert!(steps(&graph, query)[0].starts_with("LoadLabeledNode"));
+    txn.commit().unwrap();
+    assert!(steps(&graph, query)[0].starts_with("LoadIndexedNode"));
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("DROP INDEX ON :PERSON(name)")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    assert!(steps(&graph, query)[0].starts_with("LoadLabeledNode"));
+}
+
+#[test]
+fn replan_cached_statements_after_registering_procedures() {
+    let mut graph = Graph::open_anon().unwrap();
+    let call = |graph: &Graph| -> i64 {
+        graph
+            .prepare_cached("CALL my.value() YIELD value RETURN value")
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+    };
+    graph.register_procedure("my.value", &["value"], |_, _| {
+        Ok(vec![vec![Property::Integer(1)]])
+    });
+    assert_eq!(call(&graph), 1);
+    graph.register_procedure("my.value", &["value"], |_, _| {
+        Ok(vec![vec![Property::Integer(2)]])
+    });
+    assert_eq!(call(&graph), 2);
+}
@@ -0,0 +1,138 @@
+use cqlite::Graph;
+
+fn robot_owners() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let create = graph.prepare("CREATE (:PERSON { name: $name })").unwrap();
+    for idx in 0..20 {
+        create
+            .execute(&mut txn, ("name", format!("person-{}", idx)))
+            .unwrap();
+    }
+    graph
+        .prepare(
+            "
+            MATCH (a:PERSON) MATCH (b:PERSON)
+            WHERE a.name = 'person-0' AND b.name = 'person-1'
+            CREATE (r:ROBOT { name: 'R2' })
+            CREATE (s:ROBOT { name: 'C3' })
+            CREATE (a) -[:OWNS]-> (r)
+            CREATE (b) -[:OWNS]-> (s)
+            CREATE (a) -[:KNOWS]-> (b)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn pairs(graph: &Graph, query: &str) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = graph
+        .prepare(query)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    pairs.sort();
+    pairs
+}
+
+#[test]
+fn start_from_smaller_label() {
+    let graph = robot_owners();
+    let steps: Vec<String> = graph
+        .prepare("EXPLAIN MATCH (p:PERSON) -[:OWNS]-> (r:ROBOT) RETURN p.name, r.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+        })
+        .unwrap()
+        .filter_map(|row| match row.unwrap() {
+            (kind, detail) if kind == "step" => Some(detail),
+            _ => None,
+        })
+        .collect();
+
+    assert!(steps[0].starts_with("LoadLabeledNode"));
+    assert!(steps[0].contains("ROBOT"));
+    assert!(steps[1].starts_with("LoadLabeledTargetEdge"));
+    assert!(steps[1].contains("OWNS"));
+}
+
+#[test]
+fn reordered_patterns_match_the_same() {
+    let graph = robot_owners();
+    let owners = vec![
+        ("person-0".to_string(), "R2".to_string()),
+        ("person-1".to_string(), "C3".to_string()),
+    ];
+
+    assert_eq!(
+        pairs(
+            &graph,
+            "MATCH (p:PERSON) -[:OWNS]-> (r:ROBOT) RETURN p.name, r.name"
+        ),
+        owners
+    );
+    assert_eq!(
+        pairs(
+            &graph,
+            "MATCH (r:ROBOT) <-[:OWNS]- (p:PERSON) RETURN p.name, r.name"
+        ),
+        owners
+    );
+    assert_eq!(
+        pairs(
+            &graph,
+            "MATCH (p:PERSON) -[:OWNS]- (r:ROBOT) RETURN p.name, r.name"
+        ),
+        owners
+    );
+    assert_eq!(
+        pairs(
+            &graph,
+            "
+            MATCH (a:PERSON) -[:KNOWS]-> (b:PERSON) -[:OWNS]-> (r:ROBOT)
+            WHERE a.name = 'person-0'
+            RETURN b.name, r.name
+            "
+        ),
+        vec![("person-1".to_string(), "C3".to_string())]
+    );
+}
+
+#[test]
+fn start_from_edge_label() {
+    let graph = robot_owners();
+    let steps: Vec<String> = graph
+        .prepare("EXPLAIN MATCH (a) -[e:KNOWS]-> (b) RETURN a.name, b.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get::<String, _>(1)?, m.get::<String, _>(3)?))
+        })
+        .unwrap()
+        .filter_map(|row| match row.unwrap() {
+            (kind, detail) if kind == "step" => Some(detail),
+            _ => None,
+        })
+        .collect();
+
+    assert!(steps[0].starts_with("LoadLabeledEdge"));
+    assert!(steps[0].contains("KNOWS"));
+    assert!(steps.iter().all(|step| !step.contains("EdgeHasLabel")));
+
+    let knows = vec![("person-0".to_string(), "person-1".to_string())];
+    assert_eq!(
+        pairs(&graph, "MATCH (a) -[e:KNOWS]-> (b) RETURN a.name, b.name"),
+        knows
+    );
+    assert_eq!(
+        pairs(&graph, "MATCH (b) <-[e:KNOWS]- (a) RETURN a.name, b.name"),
+        knows
+    );
+}
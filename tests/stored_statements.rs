@@ -0,0 +1,191 @@
+use cqlite::{Error, Graph, Property};
+
+fn create_graph(indexed: bool) -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .execute_batch(
+            &mut txn,
+            "
+            CREATE (:PERSON { name: 'Ada', age: 36 });
+            CREATE (:PERSON { name: 'Grace', age: 85 });
+            CREATE (:PERSON { name: 'Alan', age: 41 });
+            ",
+        )
+        .unwrap();
+    if indexed {
+        graph
+            .prepare("CREATE INDEX ON :PERSON(age)")
+            .unwrap()
+            .execute(&mut txn, ())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+    graph
+}
+
+fn names(graph: &Graph, bytes: &[u8], min: i64) -> Result<Vec<String>, Error> {
+    let stmt = graph.load_statement(bytes)?;
+    let mut txn = graph.txn()?;
+    let names = stmt
+        .query_map(&mut txn, ("min", min), |m| m.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(names)
+}
+
+#[test]
+fn load_stored_statements() {
+    let query = "MATCH (p:PERSON) WHERE p.age > $min RETURN p.name";
+    let bytes = create_graph(true)
+        .prepare(query)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let cached = create_graph(true)
+        .prepare_cached(query)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    assert_eq!(bytes, cached);
+
+    let graph = create_graph(true);
+    assert_eq!(
+        names(&graph, &bytes, 40).unwrap(),
+        vec!["Alan".to_string(), "Grace".to_string()]
+    );
+    assert_eq!(names(&graph, &bytes, 100).unwrap(), Vec::<String>::new());
+
+    let graph = create_graph(false);
+    let bytes = graph.prepare(query).unwrap().to_bytes().unwrap();
+    let mut names = names(&graph, &bytes, 40).unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Alan".to_string(), "Grace".to_string()]);
+}
+
+#[test]
+fn reject_statements_for_other_schemas() {
+    let query = "MATCH (p:PERSON) WHERE p.age > $min RETURN p.name";
+    let bytes = create_graph(true)
+        .prepare(query)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let graph = create_graph(false);
+    assert!(matches!(
+        graph.load_statement(&bytes),
+        Err(Error::SchemaMismatch)
+    ));
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("CREATE INDEX ON :PERSON(age)")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    assert!(graph.load_statement(&bytes).is_ok());
+}
+
+#[test]
+fn reject_statements_of_other_versions() {
+    let graph = create_graph(false);
+    let bytes = graph
+        .prepare("MATCH (p:PERSON) RETURN p.name")
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+
+    let mut other = bytes.clone();
+    other[7] += 1;
+    assert!(matches!(
+        graph.load_statement(&other),
+        Err(Error::VersionMismatch)
+    ));
+    assert!(matches!(
+        graph.load_statement(&bytes[..bytes.len() - 1]),
+        Err(Error::Corruption)
+    ));
+    assert!(matches!(
+        graph.load_statement(b"MATCH (p) RETURN p"),
+        Err(Error::Corruption)
+    ));
+}
+
+#[test]
+fn resolve_stored_procedures() {
+    let query = "CALL my.value() YIELD value RETURN value";
+    let mut graph = create_graph(false);
+    graph.register_procedure("my.value", &["value"], |_, _| {
+        Ok(vec![vec![Property::Integer(1)]])
+    });
+    let bytes = graph.prepare(query).unwrap().to_bytes().unwrap();
+
+    let mut other = create_graph(false);
+    assert!(matches!(
+        other.load_statement(&bytes),
+        Err(Error::UnknownProcedure(name)) if name == "my.value"
+    ));
+    other.register_procedure("my.value", &["other"], |_, _| Ok(Vec::new()));
+    assert!(matches!(
+        other.load_statement(&bytes),
+        Err(Error::SchemaMismatch)
+    ));
+    other.register_procedure("my.value", &["value"], |_, _| {
+        Ok(vec![vec![Property::Integer(2)]])
+    });
+    let values: Vec<i64> = other
+        .load_statement(&bytes)
+        .unwrap()
+        .query_map(&mut other.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(values, vec![2]);
+}
+
+#[test]
+fn load_stored_explain_and_profile() {
+    let graph = create_graph(false);
+    let rows = |bytes: &[u8]| -> Vec<(i64, String, i64, String)> {
+        graph
+            .load_statement(bytes)
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| {
+                Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+
+    let stmt = graph
+        .prepare("EXPLAIN MATCH (p:PERSON) RETURN p.name")
+        .unwrap();
+    let explained = stmt
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(!explained.is_empty());
+    assert_eq!(rows(&stmt.to_bytes().unwrap()), explained);
+
+    let bytes = graph
+        .prepare("PROFILE MATCH (p:PERSON) RETURN p.name")
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let profiled: Vec<Vec<Property>> = graph
+        .load_statement(&bytes)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            (0..6).map(|idx| m.get(idx)).collect()
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(profiled
+        .iter()
+        .any(|row| row[1] == Property::Text("rows".into()) && row[4] == Property::Integer(3)));
+}
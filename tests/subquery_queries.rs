@@ -0,0 +1,203 @@
+use cqlite::{Error, Graph, Property};
+
+#[macro_use]
+mod common;
+
+fn chain() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (a:PERSON { name: 'Ada' })
+            CREATE (b:PERSON { name: 'Grace' })
+            CREATE (c:PERSON { name: 'Alan' })
+            CREATE (a) -[:KNOWS]-> (b)
+            CREATE (b) -[:KNOWS]-> (c)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+fn names(graph: &Graph, query: &str) -> Vec<String> {
+    let mut names: Vec<String> = graph
+        .prepare(query)
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    names
+}
+
+#[test]
+fn foreach_over_path_nodes() {
+    let graph = chain();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            MATCH p = (:PERSON { name: 'Ada' }) -[:KNOWS]-> (:PERSON) -[:KNOWS]-> (:PERSON)
+            FOREACH (n IN nodes(p) | SET n.visited = TRUE)
+            FOREACH (e IN relationships(p) | SET e.visited = TRUE)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        names(&graph, "MATCH (a:PERSON) WHERE a.visited RETURN a.name"),
+        vec!["Ada", "Alan", "Grace"],
+    );
+    assert_eq!(
+        names(&graph, "MATCH (a) -[e:KNOWS]-> () WHERE e.visited RETURN a.name"),
+        vec!["Ada", "Grace"],
+    );
+}
+
+#[test]
+fn foreach_over_parameter() {
+    let graph = chain();
+    let mut txn = graph.mut_txn().unwrap();
+    let list = vec![Property::from("Edsger"), Property::from("Barbara")];
+    graph
+        .prepare(
+            "
+            MATCH (a:PERSON { name: 'Ada' })
+            FOREACH (name IN $names |
+                CREATE (b:PERSON { name: name })
+                CREATE (a) -[:KNOWS]-> (b)
+            )
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ("names", list))
+        .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        names(
+            &graph,
+            "MATCH (:PERSON { name: 'Ada' }) -[:KNOWS]-> (b:PERSON) RETURN b.name"
+        ),
+        vec!["Barbara", "Edsger", "Grace"],
+    );
+}
+
+#[test]
+fn correlated_subquery() {
+    let graph = chain();
+    let pairs: Vec<(String, String)> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON)
+            CALL {
+                WITH a
+                MATCH (a) -[:KNOWS]-> (b:PERSON)
+                RETURN b
+            }
+            RETURN a.name, b.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut pairs = pairs;
+    pairs.sort();
+    assert_eq!(
+        pairs,
+        vec![
+            ("Ada".to_string(), "Grace".to_string()),
+            ("Grace".to_string(), "Alan".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn subquery_writes_per_row() {
+    let graph = chain();
+    let mut txn = graph.mut_txn().unwrap();
+    let mut tags: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON)
+            CALL {
+                WITH a
+                CREATE (t:TAG { of: a.name })
+                CREATE (t) -[:TAGS]-> (a)
+                RETURN t
+            }
+            RETURN t.of
+            ",
+        )
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    tags.sort();
+    assert_eq!(tags, vec!["Ada", "Alan", "Grace"]);
+    txn.commit().unwrap();
+
+    assert_eq!(
+        names(&graph, "MATCH (:TAG) -[:TAGS]-> (a:PERSON) RETURN a.name"),
+        vec!["Ada", "Alan", "Grace"],
+    );
+}
+
+#[test]
+fn unit_subquery_keeps_rows() {
+    let graph = chain();
+    let mut txn = graph.mut_txn().unwrap();
+    let count = graph
+        .prepare(
+            "
+            MATCH (a:PERSON)
+            CALL { WITH a CREATE (:LOG { name: a.name }) }
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get::<String, _>(0))
+        .unwrap()
+        .count();
+    assert_eq!(count, 3);
+    txn.commit().unwrap();
+
+    assert_eq!(
+        names(&graph, "MATCH (l:LOG) RETURN l.name"),
+        vec!["Ada", "Alan", "Grace"],
+    );
+}
+
+#[test]
+fn nested_query_errors() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("MATCH p = (a) -> (b) FOREACH (a IN nodes(p) | SET a.x = 1)"),
+        Error::IdentifierExists(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) CALL { WITH b RETURN b }"),
+        Error::UnknownIdentifier(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) CALL { MATCH (b) RETURN b } RETURN a.name, b.name, c"),
+        Error::UnknownIdentifier(_)
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) CALL { WITH a MATCH (a) -> (b) RETURN a }"),
+        Error::IdentifierExists(_)
+    );
+}
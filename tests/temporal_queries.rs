@@ -0,0 +1,252 @@
+use cqlite::{Date, DateTime, Duration, Error, Graph, LocalDateTime, Property};
+
+#[macro_use]
+mod common;
+
+fn create_test_graph() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (:EVENT { name: 'launch', day: date('2024-03-01') })
+            CREATE (:EVENT { name: 'landing', day: date('2024-01-15') })
+            CREATE (:EVENT { name: 'party', day: date('2023-12-31') })
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+#[test]
+fn create_and_return_temporal_values() {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    let values: Vec<(Date, LocalDateTime, DateTime, Duration)> = graph
+        .prepare(
+            "
+            CREATE (e:EVENT { day: date('2024-01-01'), length: duration('PT1H30M') })
+            SET e.local = localdatetime('2024-01-01T12:30:00')
+            SET e.at = datetime('2024-01-01T12:30:00+02:00')
+            RETURN e.day, e.local, e.at, e.length
+            ",
+        )
+        .unwrap()
+        .query_map(&mut txn, (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    txn.commit().unwrap();
+
+    let (day, local, at, length) = values[0];
+    assert_eq!(day, Date::from_ymd(2024, 1, 1).unwrap());
+    assert_eq!(local.to_string(), "2024-01-01T12:30:00");
+    assert_eq!(at.to_string(), "2024-01-01T12:30:00+02:00");
+    assert_eq!(at.offset(), 7200);
+    assert_eq!(length, Duration::new(0, 0, 5400, 0));
+
+    // values are read back from disk unchanged
+    let stored: Vec<(Date, DateTime)> = graph
+        .prepare("MATCH (e:EVENT) RETURN e.day, e.at")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(stored, vec![(day, at)]);
+}
+
+#[test]
+fn compare_temporal_values() {
+    let graph = create_test_graph();
+
+    let mut names: Vec<String> = graph
+        .prepare("MATCH (e:EVENT) WHERE e.day >= date('2024-01-01') RETURN e.name")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["landing", "launch"]);
+
+    let names: Vec<String> = graph
+        .prepare("MATCH (e:EVENT) WHERE e.day = $day RETURN e.name")
+        .unwrap()
+        .query_map(
+            &mut graph.txn().unwrap(),
+            ("day", Date::from_ymd(2023, 12, 31).unwrap()),
+            |m| m.get(0),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["party"]);
+
+    // the same instant at different offsets
+    let later: Vec<bool> = graph
+        .prepare(
+            "
+            MATCH (e:EVENT { name: 'launch' })
+            WHERE datetime('2024-01-01T12:00:00+02:00') < datetime('2024-01-01T11:00:00Z')
+            RETURN TRUE
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(later, vec![true]);
+}
+
+#[test]
+fn temporal_components() {
+    let graph = create_test_graph();
+
+    let components: Vec<(i64, i64, i64, i64)> = graph
+        .prepare(
+            "
+            MATCH (e:EVENT { name: 'launch' })
+            RETURN e.day.year, e.day.month, e.day.day, e.day.dayOfWeek
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(components, vec![(2024, 3, 1, 5)]);
+
+    let components: Vec<(i64, i64, Option<i64>)> = graph
+        .prepare(
+            "
+            MATCH (e:EVENT { name: 'launch' })
+            RETURN datetime($at).hour, duration('PT1H30M').minutes, e.day.unknown
+            ",
+        )
+        .unwrap()
+        .query_map(
+            &mut graph.txn().unwrap(),
+            ("at", "2024-01-01T23:15:00-01:00"),
+            |m| Ok((m.get(0)?, m.get(1)?, m.get(2)?)),
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(components, vec![(23, 90, None)]);
+
+    let days = Property::List(vec![
+        Date::from_ymd(1999, 12, 31).unwrap().into(),
+        Date::from_ymd(2000, 1, 1).unwrap().into(),
+    ]);
+    let years: Vec<Property> = graph
+        .prepare("RETURN [d IN $days | d.year]")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), ("days", days), |m| m.get(0))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(years, vec![Property::Integer(1999), Property::Integer(2000)]);
+}
+
+#[test]
+fn temporal_arithmetic() {
+    let graph = create_test_graph();
+
+    let values: Vec<(Date, Date, LocalDateTime, DateTime)> = graph
+        .prepare(
+            "
+            MATCH (e:EVENT { name: 'landing' })
+            RETURN
+                e.day + duration('P1M'),
+                e.day - duration('P1Y2W'),
+                localdatetime('2024-12-31T23:00:00') + duration('PT2H'),
+                datetime('2024-03-31T01:30:00+01:00') - duration('P1M') + duration('PT30M')
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| {
+            Ok((m.get(0)?, m.get(1)?, m.get(2)?, m.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let (next_month, earlier, new_year, date_time) = values[0];
+    assert_eq!(next_month.to_string(), "2024-02-15");
+    assert_eq!(earlier.to_string(), "2023-01-01");
+    assert_eq!(new_year.to_string(), "2025-01-01T01:00:00");
+    assert_eq!(date_time.to_string(), "2024-02-29T02:00:00+01:00");
+
+    let durations: Vec<(Duration, i64)> = graph
+        .prepare("RETURN duration('P1D') + duration('PT12H'), 40 + 2")
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| Ok((m.get(0)?, m.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(durations, vec![(Duration::new(0, 1, 43200, 0), 42)]);
+
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("MATCH (e:EVENT) SET e.day = e.day + duration('P1D')")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    let mut days: Vec<Date> = graph
+        .prepare("MATCH (e:EVENT) RETURN e.day")
+        .unwrap()
+        .query_map(&mut txn, (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    days.sort();
+    let days: Vec<String> = days.iter().map(Date::to_string).collect();
+    assert_eq!(days, vec!["2024-01-01", "2024-01-16", "2024-03-02"]);
+}
+
+#[test]
+fn temporal_errors() {
+    let graph = Graph::open_anon().unwrap();
+
+    assert_err!(graph.prepare("RETURN unknown(1)"), Error::UnknownFunction(_));
+    assert_err!(graph.prepare("RETURN duration()"), Error::InvalidArguments(_));
+    assert_err!(
+        graph
+            .prepare("RETURN date('2023-02-29')")
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<Date, _>(0))
+            .unwrap()
+            .next()
+            .unwrap(),
+        Error::InvalidTemporal(_)
+    );
+    assert_err!(
+        graph
+            .prepare("RETURN date('2024-01-01') + 1")
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<Date, _>(0))
+            .unwrap()
+            .next()
+            .unwrap(),
+        Error::TypeMismatch
+    );
+    assert_err!(
+        graph
+            .prepare("RETURN date('+999999-12-31') + duration('P1D')")
+            .unwrap()
+            .query_map(&mut graph.txn().unwrap(), (), |m| m.get::<Date, _>(0))
+            .unwrap()
+            .next()
+            .unwrap(),
+        Error::Overflow
+    );
+}
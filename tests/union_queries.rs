@@ -0,0 +1,154 @@
+use cqlite::{Error, Graph};
+
+#[macro_use]
+mod common;
+
+fn org_chart() -> Graph {
+    let graph = Graph::open_anon().unwrap();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare(
+            "
+            CREATE (boss:PERSON { name: 'Ada' })
+            CREATE (lead:PERSON { name: 'Grace' })
+            CREATE (dev:PERSON { name: 'Alan' })
+            CREATE (lead) -[:REPORTS_TO]-> (boss)
+            CREATE (dev) -[:REPORTS_TO]-> (lead)
+            CREATE (dev) -[:MENTORED_BY]-> (boss)
+            ",
+        )
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+    graph
+}
+
+#[test]
+fn union_removes_duplicates() {
+    let graph = org_chart();
+    let mut names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON) -[:REPORTS_TO]-> (:PERSON { name: 'Ada' })
+            RETURN a.name
+            UNION
+            MATCH (a:PERSON) -[:REPORTS_TO]-> (:PERSON) -[:REPORTS_TO]-> (:PERSON { name: 'Ada' })
+            RETURN a.name
+            UNION
+            MATCH (a:PERSON) -[:MENTORED_BY]-> (:PERSON { name: 'Ada' })
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Alan", "Grace"]);
+}
+
+#[test]
+fn union_all_keeps_duplicates() {
+    let graph = org_chart();
+    let mut names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON) -[:REPORTS_TO]-> (:PERSON) -[:REPORTS_TO]-> (:PERSON { name: 'Ada' })
+            RETURN a.name
+            UNION ALL
+            MATCH (a:PERSON) -[:MENTORED_BY]-> (:PERSON { name: 'Ada' })
+            RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Alan", "Alan"]);
+}
+
+#[test]
+fn union_with_parameters() {
+    let graph = org_chart();
+    let names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON { name: $a }) RETURN a.name
+            UNION ALL
+            MATCH (a:PERSON { name: $b }) RETURN a.name
+            UNION ALL
+            MATCH (a:PERSON { name: $a }) RETURN a.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (("a", "Ada"), ("b", "Alan")), |m| {
+            m.get(0)
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["Ada", "Alan", "Ada"]);
+}
+
+#[test]
+fn union_column_mismatch() {
+    let graph = Graph::open_anon().unwrap();
+    assert_err!(
+        graph.prepare("MATCH (a) RETURN ID(a) UNION MATCH (a) RETURN ID(a), LABEL(a)"),
+        Error::UnionMismatch
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) RETURN a.name UNION MATCH (b) RETURN b.name"),
+        Error::UnionMismatch
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) RETURN a, ID(a) UNION MATCH (a) RETURN ID(a), a"),
+        Error::UnionMismatch
+    );
+    assert_err!(
+        graph.prepare("MATCH (a) -[e]-> () RETURN * UNION MATCH (a) -[f]-> () RETURN *"),
+        Error::UnionMismatch
+    );
+    assert!(graph
+        .prepare("MATCH (a:A) -[e]-> () RETURN * UNION MATCH (a:B) <-[e]- () RETURN *")
+        .is_ok());
+    assert_err!(
+        graph.prepare("RETURN 1 UNION RETURN 2 UNION ALL RETURN 3"),
+        Error::Syntax { .. }
+    );
+}
+
+#[test]
+fn union_of_hash_joins() {
+    let graph = org_chart();
+    let mut txn = graph.mut_txn().unwrap();
+    graph
+        .prepare("CREATE (:ROBOT { name: 'T-800' }) CREATE (:ROBOT { name: 'T-1000' })")
+        .unwrap()
+        .execute(&mut txn, ())
+        .unwrap();
+    txn.commit().unwrap();
+
+    // each part builds its own table for the join
+    let mut names: Vec<String> = graph
+        .prepare(
+            "
+            MATCH (a:PERSON) MATCH (b:PERSON) WHERE a.name = b.name
+            RETURN b.name
+            UNION ALL
+            MATCH (a:ROBOT) MATCH (b:ROBOT) WHERE a.name = b.name
+            RETURN b.name
+            ",
+        )
+        .unwrap()
+        .query_map(&mut graph.txn().unwrap(), (), |m| m.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["Ada", "Alan", "Grace", "T-1000", "T-800"]);
+}
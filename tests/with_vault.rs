@@ -103,15 +103,9 @@ impl<C,MerkleStorage,MerkleHasher> AuthenticatedGraph<C,MerkleStorage,MerkleHash
     
         let mut txn = self.graph.mut_txn().unwrap();
     
-        let mut string_properties = properties.iter().map ( |(key,value)| format!(" {}:'{}'",key,value)).collect::<Vec<_>>().join(",");
-
-        if !string_properties.is_empty() {
-            string_properties.insert_str(0,", ");
-        }
-
         let root_string = if root { "TRUE" } else { "FALSE" };
 
-        let query_string = format!("CREATE (n:element {{ type:'element', root: {} , tag:$tag , payload:$payload {} }}) RETURN ID(n)",root_string,string_properties);
+        let query_string = format!("CREATE (n:element {{ type:'element', root: {} , tag:$tag , payload:$payload }}) RETURN ID(n)",root_string);
 
         println!("{}",&query_string);
 
@@ -125,6 +119,12 @@ impl<C,MerkleStorage,MerkleHasher> AuthenticatedGraph<C,MerkleStorage,MerkleHash
             .collect::<Result<Vec<_>, _>>()?;
     
         let element_id = result.get(0).ok_or(crate::Error::Internal)?;
+
+        // property keys are passed as parameters instead of being formatted into the query
+        let set_property = self.graph.prepare("MATCH (n) WHERE ID(n) = $id SET n[$key] = $value")?;
+        for (key,value) in properties {
+            set_property.execute(&mut txn, (("id", *element_id),("key", key.as_str()),("value", value.as_str())))?;
+        }
     
         if !children.is_empty() {
                 //not id in ()